@@ -114,17 +114,16 @@ pub struct BitsReaderResult<'a> { _spi: &'a mut Spi, num_bits: usize }
 impl<'a> Future for BitsReaderResult<'a> {
     type Output = u32;
     fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<u32> {
-            let mut r: u32 = 0;
-            let regs = unsafe{ pa_regs() };
-            for _ in 0..self.num_bits {
-                regs.bsrr.write(|w| w.br5().reset());
-                delay();
-                let bit = if regs.idr.read().idr7().bits() {1} else {0};
+        let regs = unsafe{ pa_regs() };
+        Poll::Ready(st7735_async_low::spi::bitbang_read(
+            self.num_bits,
+            |high| if high {
                 regs.bsrr.write(|w| w.bs5().set());
-                delay();
-                r = r.wrapping_shl(1) | bit;
-            }
-            Poll::Ready(r)
+            } else {
+                regs.bsrr.write(|w| w.br5().reset());
+            },
+            || regs.idr.read().idr7().bits(),
+            delay, delay))
     }
 }
 
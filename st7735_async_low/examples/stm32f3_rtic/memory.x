@@ -0,0 +1,8 @@
+MEMORY
+{ 
+  FLASH : ORIGIN = 0x08000000, LENGTH = 64K
+  RAM : ORIGIN = 0x20000000, LENGTH = 12K
+  CCRAM : ORIGIN = 0x10000000, LENGTH = 4K
+}
+
+_stack_start = ORIGIN(CCRAM) + LENGTH(CCRAM);
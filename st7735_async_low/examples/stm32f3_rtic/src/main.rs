@@ -0,0 +1,104 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Drives the LCD from an RTIC async task, woken by the TXE interrupt of
+//! SPI1 through a hardware task, instead of polling as
+//! `examples/stm32f3348_disco` does.
+//!
+//! ST7735S on the STM32F3348-DISCO: CSX on PA0, RESX on PA1, SCK on PA5,
+//! DCX on PA6 and SDA on PA7.
+
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+mod spi;
+
+#[rtic::app(device = stm32f3::stm32f3x4, peripherals = true,
+            dispatchers = [EXTI0])]
+mod app {
+    use rtic_monotonics::systick::prelude::*;
+    use st7735_async_low::{Colmod, Commands};
+    use st7735_async_low::adapters::{AdapterRepeat, AdapterU8s};
+    use st7735_async_low::color::Rgb565;
+    use st7735_async_low::panel::Rect;
+
+    use crate::spi::{self, IrqSpi};
+
+    systick_monotonic!(Mono, 1_000);
+
+    type Lcd = Commands<AdapterRepeat<AdapterU8s<IrqSpi>>>;
+
+    #[shared]
+    struct Shared {}
+
+    #[local]
+    struct Local {
+        lcd: Lcd,
+        gpioa: stm32f3::stm32f3x4::GPIOA,
+    }
+
+    #[init]
+    fn init(cx: init::Context) -> (Shared, Local) {
+        let dp = cx.device;
+        // The 8MHz HSI, i.e., no PLL.
+        dp.RCC.ahbenr.modify(|_, w| w.iopaen().enabled());
+        dp.RCC.apb2enr.modify(|_, w| w.spi1en().enabled());
+        Mono::start(cx.core.SYST, 8_000_000);
+
+        // CSX low for good, RESX high.
+        dp.GPIOA.moder.modify(|_, w| w.moder0().output().moder1().output());
+        dp.GPIOA.bsrr.write(|w| w.br0().reset().bs1().set());
+        // Safety: `IrqSpi` only touches PA5 to PA7 and `draw` only PA1, both
+        // through BSRR, whose writes are atomic.
+        let gpioa = unsafe { stm32f3::stm32f3x4::Peripherals::steal() }.GPIOA;
+        let spi = IrqSpi::new(dp.SPI1, dp.GPIOA);
+        let lcd = Commands::new(AdapterRepeat::new(AdapterU8s::new(spi)));
+
+        draw::spawn().ok();
+        (Shared{}, Local{lcd, gpioa})
+    }
+
+    /// Masks TXE and wakes `draw`.
+    #[task(binds = SPI1, priority = 2)]
+    fn spi1(_: spi1::Context) {
+        spi::on_txe();
+    }
+
+    #[task(local = [lcd, gpioa], priority = 1)]
+    async fn draw(cx: draw::Context) {
+        let lcd = cx.local.lcd;
+        let gpioa = cx.local.gpioa;
+        gpioa.bsrr.write(|w| w.br1().reset());
+        Mono::delay(1.millis()).await;
+        gpioa.bsrr.write(|w| w.bs1().set());
+        Mono::delay(120.millis()).await;
+
+        lcd.slpout().await;
+        Mono::delay(120.millis()).await;
+        lcd.mark_awake();
+        lcd.colmod(Colmod::R5G6B5).await;
+        lcd.dispon().await;
+
+        let screen = Rect::new(0, 0, 128, 160);
+        let mut raw = 0u16;
+        loop {
+            // The CPU sleeps between bytes; other tasks run meanwhile.
+            lcd.fill_repeated(screen, Rgb565::from_raw(raw)).await.unwrap();
+            raw = raw.wrapping_add(0x0841);
+            Mono::delay(500.millis()).await;
+        }
+    }
+}
@@ -0,0 +1,135 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SPI1 on PA5 (SCK) and PA7 (SDA), with PA6 as DCX. Each byte is written
+//! when the TXE interrupt fires; the RTIC task bound to it calls [on_txe()],
+//! which wakes the task awaiting the transfer.
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use cortex_m::interrupt::{Mutex, free as interrupt_free};
+use st7735_async_low::spi::{DcxPin, WriteU8s};
+use stm32f3::stm32f3x4 as pac;
+
+/// The waker of the pending [Transfer], woken by [on_txe()].
+static TXE_WAKER: Mutex<RefCell<Option<Waker>>> =
+    Mutex::new(RefCell::new(None));
+
+/// To be called by the handler of the SPI1 interrupt. Masks TXE, so that
+/// the interrupt doesn't fire again before the next byte is wanted, and
+/// wakes the task awaiting the transfer.
+pub fn on_txe() {
+    // Safety: Only TXEIE is modified, atomically w.r.t. the task, which
+    // modifies CR2 only while the interrupt is masked.
+    unsafe { spi1() }.cr2.modify(|_, w| w.txeie().masked());
+    interrupt_free(|cs| {
+        if let Some(waker) = TXE_WAKER.borrow(cs).borrow_mut().take() {
+            waker.wake();
+        }
+    });
+}
+
+pub struct IrqSpi {
+    _spi: pac::SPI1,
+    gpioa: pac::GPIOA,
+}
+
+impl IrqSpi {
+    /// Configures the pins and SPI1. Expects the clocks of GPIOA and SPI1 to
+    /// be enabled.
+    pub fn new(spi: pac::SPI1, gpioa: pac::GPIOA) -> Self {
+        gpioa.afrl.modify(|_, w| w.afrl5().af5().afrl7().af5());
+        gpioa.moder.modify(|_, w| w.moder5().alternate()
+                                   .moder6().output()
+                                   .moder7().alternate());
+        // Reference manual RM0364 30.5.7.
+        spi.cr1.write(|w| w
+            // PCLK2 / 32, i.e., 250kHz with the 8MHz HSI.
+            .br().div32()
+            .cpol().idle_high()
+            .cpha().second_edge()
+            // Transmit-only.
+            .bidimode().bidirectional()
+            .bidioe().output_enabled()
+            .lsbfirst().msbfirst()
+            // No physical NSS pin.
+            .ssm().enabled()
+            .ssi().slave_not_selected()
+            .mstr().master());
+        spi.cr2.write(|w| w.ds().eight_bit());
+        spi.cr1.modify(|_, w| w.spe().enabled());
+        Self{_spi: spi, gpioa}
+    }
+}
+
+impl DcxPin for IrqSpi {
+    fn set_dcx_command_mode(&mut self) {
+        self.gpioa.bsrr.write(|w| w.br6().reset());
+    }
+    fn set_dcx_data_mode(&mut self) {
+        self.gpioa.bsrr.write(|w| w.bs6().set());
+    }
+}
+
+impl<'a> WriteU8s<'a> for IrqSpi {
+    type WriteU8sDone = Transfer<'a>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        Transfer{_spi: self, data}
+    }
+}
+
+/// Writes the remaining `data` into DR while TXE is set, then waits for the
+/// next TXE interrupt.
+pub struct Transfer<'a> {
+    // Borrowed so that no other transfer starts meanwhile.
+    _spi: &'a mut IrqSpi,
+    data: &'a [u8],
+}
+
+impl<'a> Future for Transfer<'a> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: `self._spi` owns SPI1.
+        let spi = unsafe { spi1() };
+        while let Some((&byte, rest)) = self.data.split_first() {
+            if spi.sr.read().txe().is_not_empty() {
+                interrupt_free(|cs| {
+                    TXE_WAKER.borrow(cs).replace(Some(cx.waker().clone()));
+                });
+                spi.cr2.modify(|_, w| w.txeie().not_masked());
+                return Poll::Pending;
+            }
+            // An 8-bit access, or the 16-bit one would send two bytes.
+            let dr = &spi.dr as *const _ as *mut u8;
+            // Safety: DR is writable, TXE is set.
+            unsafe { core::ptr::write_volatile(dr, byte) };
+            self.data = rest;
+        }
+        // The last byte takes a few microseconds; DCX mustn't change before.
+        while spi.sr.read().bsy().is_busy() {}
+        Poll::Ready(())
+    }
+}
+
+/// # Safety
+///
+/// The caller must be the only one modifying the registers it uses.
+unsafe fn spi1() -> &'static pac::spi1::RegisterBlock {
+    &*pac::SPI1::ptr()
+}
@@ -0,0 +1,88 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! An embedded-graphics [DrawTarget] in RAM, sent to the LCD in a single
+//! write, i.e., a single DMA transfer with [crate::spi::DmaSpi].
+
+use core::convert::Infallible;
+
+use embedded_graphics::Pixel;
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::pixelcolor::raw::{RawData, RawU16};
+use embedded_graphics::prelude::{DrawTarget, OriginDimensions, Size};
+
+/// The largest panel, i.e., 128x160, in either orientation.
+pub const MAX_PIXELS: usize = 128 * 160;
+
+/// The pixels of the whole screen in the wire format of
+/// [Colmod::R5G6B5](st7735_async_low::Colmod::R5G6B5), i.e., big-endian.
+pub struct Frame {
+    bytes: &'static mut [u8; MAX_PIXELS * 2],
+    width: u16,
+    height: u16,
+}
+
+impl Frame {
+    pub fn new(bytes: &'static mut [u8; MAX_PIXELS * 2]) -> Self {
+        Self{bytes, width: 0, height: 0}
+    }
+
+    /// Follows the size of the effective panel, e.g., after a change of
+    /// orientation exchanging rows and columns.
+    pub fn resize(&mut self, width: u16, height: u16) {
+        assert!(width as usize * height as usize <= MAX_PIXELS);
+        self.width = width;
+        self.height = height;
+    }
+
+    pub fn width(&self) -> u16 { self.width }
+    pub fn height(&self) -> u16 { self.height }
+
+    /// The bytes to write after RAMWR, row by row.
+    pub fn bytes(&self) -> &[u8] {
+        &self.bytes[..self.width as usize * self.height as usize * 2]
+    }
+}
+
+impl OriginDimensions for Frame {
+    fn size(&self) -> Size {
+        Size::new(self.width as u32, self.height as u32)
+    }
+}
+
+impl DrawTarget for Frame {
+    type Color = Rgb565;
+    type Error = Infallible;
+
+    fn draw_iter<I>(&mut self, pixels: I) -> Result<(), Infallible>
+            where I: IntoIterator<Item = Pixel<Rgb565>> {
+        let (w, h) = (self.width as i32, self.height as i32);
+        for Pixel(p, color) in pixels {
+            if p.x < 0 || p.y < 0 || p.x >= w || p.y >= h { continue; }
+            let i = (p.y * w + p.x) as usize * 2;
+            let raw = RawU16::from(color).into_inner();
+            self.bytes[i..i + 2].copy_from_slice(&raw.to_be_bytes());
+        }
+        Ok(())
+    }
+
+    fn clear(&mut self, color: Rgb565) -> Result<(), Infallible> {
+        let raw = RawU16::from(color).into_inner().to_be_bytes();
+        let len = self.bytes().len();
+        for pixel in self.bytes[..len].chunks_exact_mut(2) {
+            pixel.copy_from_slice(&raw);
+        }
+        Ok(())
+    }
+}
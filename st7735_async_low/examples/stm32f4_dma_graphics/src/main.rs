@@ -0,0 +1,193 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A graphics demo drawing with embedded-graphics into a frame in RAM, then
+//! sending each frame with a single DMA transfer right after the LCD's
+//! vertical blanking (TE), so that the animation doesn't tear. The
+//! orientation changes every few seconds.
+//!
+//! Wiring of a 1.8" red tab module to an STM32F411 "Black Pill":
+//! * SCK: PA5, SDA: PA7, A0 (DCX): PA6, RESET: PA3, CS: GND.
+//! * TE: PA2, where the module exposes it.
+//! * LED: 3.3V.
+
+#![no_std]
+#![no_main]
+
+extern crate panic_halt;
+
+mod frame;
+mod spi;
+
+use core::future::{ready, Future, Ready};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use embedded_graphics::mono_font::{MonoTextStyle, ascii::FONT_6X10};
+use embedded_graphics::pixelcolor::Rgb565;
+use embedded_graphics::prelude::*;
+use embedded_graphics::primitives::{Circle, PrimitiveStyle, Rectangle};
+use embedded_graphics::text::Text;
+use st7735_async_low::{Madctl, TeMode};
+use st7735_async_low::adapters::AdapterU8s;
+use st7735_async_low::delay::DelayMs;
+use st7735_async_low::display::{
+    Display, NoBacklight, PanelVariant, ResetPin};
+use st7735_async_low::panel::Rect;
+use st7735_async_low::spi::WriteU8s as _;
+use st7735_async_low::task::block_on;
+use st7735_async_low::te::TePin;
+use stm32f4::stm32f411 as pac;
+
+/// Cycles per millisecond with the 16MHz HSI, the clock after reset.
+const CYCLES_PER_MS: u32 = 16_000;
+
+/// How many frames before the next orientation.
+const FRAMES_PER_ORIENTATION: u32 = 300;
+
+/// MADCTL of the red tab for the four orientations: portrait, landscape,
+/// upside down and landscape again, all with BGR.
+const ORIENTATIONS: [u8; 4] = [0xC8, 0xA8, 0x08, 0x68];
+
+/// Pins on GPIOA besides the SPI ones, which are shared with [spi::DmaSpi]
+/// through the atomic BSRR and the read-only IDR.
+struct Pins;
+
+impl Pins {
+    fn regs() -> &'static pac::gpioa::RegisterBlock {
+        // Safety: Only BSRR and IDR are accessed, both without a
+        // read-modify-write.
+        unsafe { &*pac::GPIOA::ptr() }
+    }
+}
+
+impl ResetPin for Pins {
+    fn set_reset_low(&mut self) {
+        Self::regs().bsrr.write(|w| w.br3().reset());
+    }
+    fn set_reset_high(&mut self) {
+        Self::regs().bsrr.write(|w| w.bs3().set());
+    }
+}
+
+/// Waits for the rising edge of TE on PA2.
+struct Te;
+
+impl<'a> TePin<'a> for Te {
+    type VblankDone = RisingEdge;
+
+    fn wait_for_vblank(&'a mut self) -> RisingEdge {
+        RisingEdge{seen_low: false}
+    }
+}
+
+struct RisingEdge { seen_low: bool }
+
+impl Future for RisingEdge {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let high = Pins::regs().idr.read().idr2().is_high();
+        if high && self.seen_low { return Poll::Ready(()); }
+        self.seen_low |= !high;
+        // Busy-polling: a real application wakes from the EXTI interrupt.
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// Busy-waits, as nothing else runs meanwhile.
+struct Delay;
+
+impl<'a> DelayMs<'a> for Delay {
+    type DelayDone = Ready<()>;
+
+    fn delay_ms(&'a mut self, ms: u32) -> Ready<()> {
+        cortex_m::asm::delay(ms * CYCLES_PER_MS);
+        ready(())
+    }
+}
+
+fn setup() -> spi::DmaSpi {
+    let dp = pac::Peripherals::take().unwrap();
+    dp.RCC.ahb1enr.modify(|_, w| w.gpioaen().enabled().dma2en().enabled());
+    dp.RCC.apb2enr.modify(|_, w| w.spi1en().enabled());
+    // PA3 as the reset output, high; PA2 as the TE input.
+    dp.GPIOA.bsrr.write(|w| w.bs3().set());
+    dp.GPIOA.moder.modify(|_, w| w.moder3().output().moder2().input());
+    spi::DmaSpi::new(dp.SPI1, dp.DMA2, dp.GPIOA)
+}
+
+/// Draws one frame of the animation, with the ball at step `t`.
+fn draw(frame: &mut frame::Frame, t: u32, orientation: usize) {
+    const BALL: u32 = 24;
+    let (w, h) = (frame.width() as u32, frame.height() as u32);
+    // Bounces between the borders.
+    let bounce = |t: u32, span: u32| {
+        let p = t % (2 * span);
+        if p < span { p } else { 2 * span - p }
+    };
+    let x = bounce(t * 2, w - BALL) as i32;
+    let y = bounce(t * 3, h - BALL) as i32;
+
+    frame.clear(Rgb565::BLACK).unwrap();
+    Rectangle::new(Point::zero(), Size::new(w, h))
+        .into_styled(PrimitiveStyle::with_stroke(Rgb565::BLUE, 1))
+        .draw(frame).unwrap();
+    Circle::new(Point::new(x, y), BALL)
+        .into_styled(PrimitiveStyle::with_fill(Rgb565::YELLOW))
+        .draw(frame).unwrap();
+    let label = ["portrait", "landscape", "upside down", "landscape 2"];
+    Text::new(label[orientation], Point::new(4, 12),
+              MonoTextStyle::new(&FONT_6X10, Rgb565::WHITE))
+        .draw(frame).unwrap();
+}
+
+#[cortex_m_rt::entry]
+fn main() -> ! {
+    static mut FRAME: [u8; frame::MAX_PIXELS * 2] = [0; frame::MAX_PIXELS * 2];
+
+    let spi = setup();
+    let mut display = Display::new(
+        AdapterU8s::new(spi), Pins, Delay, NoBacklight,
+        PanelVariant::RedTab.panel());
+    let mut te = Te;
+    // `cortex_m_rt::entry` turns FRAME into a `&'static mut`.
+    let mut frame = frame::Frame::new(FRAME);
+
+    block_on(async {
+        display.init_variant(PanelVariant::RedTab).await;
+        // TE in mode 0, i.e., on the vertical blanking only.
+        display.commands().teon_mode(TeMode::VBlank).await;
+    });
+    let mut t = 0u32;
+    loop {
+        let orientation = (t / FRAMES_PER_ORIENTATION) as usize % 4;
+        if t % FRAMES_PER_ORIENTATION == 0 {
+            let madctl = Madctl::from(ORIENTATIONS[orientation]);
+            block_on(display.set_orientation(madctl));
+            let panel = display.effective_panel();
+            frame.resize(panel.width(), panel.height());
+        }
+        draw(&mut frame, t, orientation);
+        block_on(async {
+            te.wait_for_vblank().await;
+            let rect = Rect::new(0, 0, frame.width(), frame.height());
+            let mut w = display.window_writer(rect).await.unwrap();
+            // One DMA transfer for the whole frame.
+            w.write_u8s(frame.bytes()).await;
+        });
+        t = t.wrapping_add(1);
+    }
+}
@@ -0,0 +1,142 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! SPI1 on PA5 (SCK) and PA7 (SDA), with PA6 as DCX. Writes go through
+//! DMA2 stream 3, channel 3, so a whole frame is a single transfer.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use st7735_async_low::spi::{DcxPin, WriteU8s};
+use stm32f4::stm32f411 as pac;
+
+/// The most bytes a single DMA transfer can move, as NDTR is 16-bit.
+const MAX_TRANSFER: usize = 0xFFFF;
+
+pub struct DmaSpi {
+    spi: pac::SPI1,
+    dma: pac::DMA2,
+    gpioa: pac::GPIOA,
+}
+
+impl DmaSpi {
+    /// Configures the pins, SPI1 and the DMA stream. Expects the clocks of
+    /// GPIOA, SPI1 and DMA2 to be enabled.
+    pub fn new(spi: pac::SPI1, dma: pac::DMA2, gpioa: pac::GPIOA) -> Self {
+        gpioa.afrl.modify(|_, w| w.afrl5().af5().afrl7().af5());
+        gpioa.ospeedr.modify(|_, w| w.ospeedr5().very_high_speed()
+                                     .ospeedr7().very_high_speed());
+        gpioa.moder.modify(|_, w| w.moder5().alternate()
+                                   .moder6().output()
+                                   .moder7().alternate());
+        spi.cr1.write(|w| w
+            // 8MHz with the 16MHz HSI; the write cycle is at least 66ns.
+            .br().div2()
+            .cpol().idle_high()
+            .cpha().second_edge()
+            // Transmit-only.
+            .bidimode().bidirectional()
+            .bidioe().output_enabled()
+            .dff().eight_bit()
+            .lsbfirst().msbfirst()
+            // No physical NSS pin.
+            .ssm().enabled()
+            .ssi().slave_not_selected()
+            .mstr().master());
+        spi.cr2.write(|w| w.txdmaen().enabled());
+        spi.cr1.modify(|_, w| w.spe().enabled());
+        let dr = &spi.dr as *const _ as u32;
+        dma.st[3].par.write(|w| unsafe { w.pa().bits(dr) });
+        Self{spi, dma, gpioa}
+    }
+
+    fn start(&mut self, data: &[u8]) {
+        let st = &self.dma.st[3];
+        self.dma.lifcr.write(|w| w.ctcif3().clear().chtif3().clear()
+                                  .cteif3().clear().cdmeif3().clear()
+                                  .cfeif3().clear());
+        st.m0ar.write(|w| unsafe { w.m0a().bits(data.as_ptr() as u32) });
+        st.ndtr.write(|w| w.ndt().bits(data.len() as u16));
+        st.cr.write(|w| w
+            .chsel().bits(3)
+            .dir().memory_to_peripheral()
+            .minc().incremented()
+            .pinc().fixed()
+            .msize().bits8()
+            .psize().bits8()
+            .en().enabled());
+    }
+
+    /// Whether the last transfer has left the shift register, so that DCX
+    /// can be toggled or another transfer started.
+    fn is_idle(&self) -> bool {
+        self.dma.st[3].cr.read().en().is_disabled() &&
+            self.spi.sr.read().txe().is_empty() &&
+            self.spi.sr.read().bsy().is_not_busy()
+    }
+}
+
+impl DcxPin for DmaSpi {
+    fn set_dcx_command_mode(&mut self) {
+        self.gpioa.bsrr.write(|w| w.br6().reset());
+    }
+    fn set_dcx_data_mode(&mut self) {
+        self.gpioa.bsrr.write(|w| w.bs6().set());
+    }
+}
+
+impl<'a> WriteU8s<'a> for DmaSpi {
+    type WriteU8sDone = DmaWriting<'a>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        DmaWriting{spi: self, rest: data}
+    }
+}
+
+/// Writes the data, at most [MAX_TRANSFER] bytes per DMA transfer. The
+/// first transfer starts on the first poll.
+///
+/// Dropping it before it completes waits for the current transfer, as the
+/// data may be freed afterwards.
+pub struct DmaWriting<'a> {
+    spi: &'a mut DmaSpi,
+    rest: &'a [u8],
+}
+
+impl<'a> Drop for DmaWriting<'a> {
+    fn drop(&mut self) {
+        while !self.spi.is_idle() {}
+    }
+}
+
+impl<'a> Future for DmaWriting<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: Nothing is pinned.
+        let w = unsafe { self.get_unchecked_mut() };
+        if !w.spi.is_idle() {
+            // Busy-polling: a real application wakes from the DMA interrupt.
+            cx.waker().wake_by_ref();
+            return Poll::Pending;
+        }
+        if w.rest.is_empty() { return Poll::Ready(()); }
+        let (now, rest) = w.rest.split_at(w.rest.len().min(MAX_TRANSFER));
+        w.spi.start(now);
+        w.rest = rest;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
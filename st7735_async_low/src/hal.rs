@@ -0,0 +1,657 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Glue for [embedded-hal](https://crates.io/crates/embedded-hal) (feature
+//! `embedded-hal`).
+
+use core::future::{Future, Ready, ready};
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use embedded_hal::blocking::spi::Write;
+use embedded_hal::digital::v2::{InputPin, OutputPin};
+use embedded_hal::spi::FullDuplex;
+
+use crate::spi::{
+    DcxPin, Read, ReadBits, WriteU8, WriteU8s, bitbang_read};
+
+/// What to do when a pin reports an error.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ErrorPolicy {
+    Panic,
+    Ignore,
+}
+
+impl ErrorPolicy {
+    fn check<E>(self, r: Result<(), E>, message: &str) {
+        if r.is_err() && self == ErrorPolicy::Panic {
+            panic!("{}", message);
+        }
+    }
+}
+
+/// A [DcxPin] driving an [OutputPin]: LOW for the command mode and HIGH for
+/// the data mode.
+#[derive(Debug)]
+pub struct DcxFromOutputPin<P> {
+    pin: P,
+    policy: ErrorPolicy,
+}
+
+impl<P: OutputPin> DcxFromOutputPin<P> {
+    /// Creates an instance panicking on pin errors.
+    pub fn new(pin: P) -> Self {
+        Self::with_policy(pin, ErrorPolicy::Panic)
+    }
+
+    pub fn with_policy(pin: P, policy: ErrorPolicy) -> Self {
+        Self{pin, policy}
+    }
+
+    pub fn into_inner(self) -> P { self.pin }
+
+    fn check<E>(&self, r: Result<(), E>) {
+        self.policy.check(r, "Failed to set the DCX pin.");
+    }
+}
+
+impl<P: OutputPin> DcxPin for DcxFromOutputPin<P> {
+    fn set_dcx_command_mode(&mut self) {
+        let r = self.pin.set_low();
+        self.check(r);
+    }
+    fn set_dcx_data_mode(&mut self) {
+        let r = self.pin.set_high();
+        self.check(r);
+    }
+}
+
+/// [WriteU8] and [WriteU8s] over a blocking SPI [Write], plus a [DcxPin]
+/// (e.g., [DcxFromOutputPin]), to get started on any board before moving to
+/// a truly asynchronous implementation.
+///
+/// The transfer happens when the future is created and the future is
+/// already complete, so the executor is blocked meanwhile.
+#[derive(Debug)]
+pub struct BlockingSpi<SPI, DCX> {
+    spi: SPI,
+    dcx: DCX,
+    policy: ErrorPolicy,
+}
+
+impl<SPI: Write<u8>, DCX: DcxPin> BlockingSpi<SPI, DCX> {
+    /// Creates an instance panicking on SPI errors.
+    pub fn new(spi: SPI, dcx: DCX) -> Self {
+        Self::with_policy(spi, dcx, ErrorPolicy::Panic)
+    }
+
+    pub fn with_policy(spi: SPI, dcx: DCX, policy: ErrorPolicy) -> Self {
+        Self{spi, dcx, policy}
+    }
+
+    pub fn into_inner(self) -> (SPI, DCX) { (self.spi, self.dcx) }
+
+    fn write(&mut self, data: &[u8]) -> Ready<()> {
+        // Not all HALs accept empty writes.
+        if !data.is_empty() {
+            let r = self.spi.write(data);
+            self.policy.check(r, "Failed to write to SPI.");
+        }
+        ready(())
+    }
+}
+
+impl<SPI, DCX: DcxPin> DcxPin for BlockingSpi<SPI, DCX> {
+    fn set_dcx_command_mode(&mut self) { self.dcx.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.dcx.set_dcx_data_mode(); }
+}
+
+impl<'a, SPI: Write<u8>, DCX: DcxPin> WriteU8<'a> for BlockingSpi<SPI, DCX> {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Ready<()> { self.write(&[data]) }
+}
+
+impl<'a, SPI: Write<u8>, DCX: DcxPin> WriteU8s<'a> for BlockingSpi<SPI, DCX> {
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+        self.write(data)
+    }
+}
+
+/// [WriteU8] and [WriteU8s] over a non-blocking SPI [FullDuplex], plus a
+/// [DcxPin], for the HALs predating async.
+///
+/// Unlike [BlockingSpi], the futures are pending while the SPI answers
+/// `WouldBlock`, so other tasks can run. Having no interrupt to wait for,
+/// they wake themselves up right away to be polled again. Each byte sent is
+/// followed by reading the one clocked in meanwhile, as `FullDuplex`
+/// requires.
+#[derive(Debug)]
+pub struct NbSpi<SPI, DCX> {
+    spi: SPI,
+    dcx: DCX,
+    policy: ErrorPolicy,
+}
+
+impl<SPI: FullDuplex<u8>, DCX: DcxPin> NbSpi<SPI, DCX> {
+    /// Creates an instance panicking on SPI errors.
+    pub fn new(spi: SPI, dcx: DCX) -> Self {
+        Self::with_policy(spi, dcx, ErrorPolicy::Panic)
+    }
+
+    /// Creates an instance handling SPI errors as `policy` says. With
+    /// [ErrorPolicy::Ignore], a byte failing to be sent is given up.
+    pub fn with_policy(spi: SPI, dcx: DCX, policy: ErrorPolicy) -> Self {
+        Self{spi, dcx, policy}
+    }
+
+    /// Gives the SPI and the DCX pin back.
+    pub fn into_inner(self) -> (SPI, DCX) { (self.spi, self.dcx) }
+}
+
+impl<SPI, DCX: DcxPin> DcxPin for NbSpi<SPI, DCX> {
+    fn set_dcx_command_mode(&mut self) { self.dcx.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.dcx.set_dcx_data_mode(); }
+}
+
+impl<'a, SPI, DCX> WriteU8<'a> for NbSpi<SPI, DCX>
+        where SPI: 'a + FullDuplex<u8>, DCX: 'a + DcxPin {
+    type WriteU8Done = NbWrite<'a, SPI, DCX>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        NbWrite{spi: self, first: Some(data), rest: &[], sent: false}
+    }
+}
+
+impl<'a, SPI, DCX> WriteU8s<'a> for NbSpi<SPI, DCX>
+        where SPI: 'a + FullDuplex<u8>, DCX: 'a + DcxPin {
+    type WriteU8sDone = NbWrite<'a, SPI, DCX>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        NbWrite{spi: self, first: None, rest: data, sent: false}
+    }
+}
+
+/// The future of the writes of [NbSpi].
+#[derive(Debug)]
+pub struct NbWrite<'a, SPI, DCX> {
+    spi: &'a mut NbSpi<SPI, DCX>,
+    // The bytes left: `first`, if any, then `rest`.
+    first: Option<u8>,
+    rest: &'a [u8],
+    // The next byte was sent; what was clocked in is still to be read.
+    sent: bool,
+}
+
+impl<'a, SPI: FullDuplex<u8>, DCX> NbWrite<'a, SPI, DCX> {
+    fn advance(&mut self) {
+        if self.first.take().is_none() {
+            self.rest = &self.rest[1..];
+        }
+        self.sent = false;
+    }
+}
+
+impl<'a, SPI: FullDuplex<u8>, DCX> Future for NbWrite<'a, SPI, DCX> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        let w = self.get_mut();
+        loop {
+            let byte = match w.first.or_else(|| w.rest.first().copied()) {
+                Some(byte) => byte,
+                None => return Poll::Ready(()),
+            };
+            let policy = w.spi.policy;
+            let r = if w.sent {
+                w.spi.spi.read().map(|_| ())
+            } else {
+                w.spi.spi.send(byte)
+            };
+            match r {
+                Ok(()) if !w.sent => w.sent = true,
+                Ok(()) => w.advance(),
+                Err(nb::Error::WouldBlock) => {
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                },
+                Err(nb::Error::Other(_)) => {
+                    policy.check(Err(()), "Failed to write to SPI.");
+                    w.advance();  // The byte is given up.
+                },
+            }
+        }
+    }
+}
+
+/// [WriteU8], [WriteU8s] and [Read] bit-banged on any three GPIOs, e.g., to
+/// bring a panel up before writing a hardware SPI backend. Blocking, as
+/// [BlockingSpi].
+///
+/// SDA is both written and read, so it should be an open-drain output with
+/// a pull-up which also reads the line: it is released (set HIGH) before
+/// reading. SCK idles HIGH and the LCD samples SDA on the rising edges.
+///
+/// Each half period of SCK is at least `half_period_spins` iterations of
+/// [core::hint::spin_loop()]; see [spins_for()](Self::spins_for) to
+/// respect the timing of the datasheet.
+#[derive(Debug)]
+pub struct BitBangSpi<SCK, SDA, DCX> {
+    sck: SCK,
+    sda: SDA,
+    dcx: DCX,
+    half_period_spins: u32,
+    policy: ErrorPolicy,
+}
+
+impl<SCK, SDA, DCX> BitBangSpi<SCK, SDA, DCX>
+        where SCK: OutputPin, SDA: OutputPin + InputPin, DCX: DcxPin {
+    /// Creates an instance panicking on pin errors. See
+    /// [spins_for()](Self::spins_for) for `half_period_spins`.
+    pub fn new(sck: SCK, sda: SDA, dcx: DCX, half_period_spins: u32) -> Self {
+        Self::with_policy(sck, sda, dcx, half_period_spins, ErrorPolicy::Panic)
+    }
+
+    pub fn with_policy(sck: SCK, sda: SDA, dcx: DCX, half_period_spins: u32,
+                       policy: ErrorPolicy) -> Self {
+        let mut s = Self{sck, sda, dcx, half_period_spins, policy};
+        s.set_sck(true);
+        s
+    }
+
+    /// The `half_period_spins` for a core running at `core_hz`, assuming
+    /// that a spin takes at least a cycle: half of the 150ns cycle of the
+    /// datasheet, which also covers the 66ns (write) and 60ns (read) low and
+    /// high durations.
+    pub const fn spins_for(core_hz: u32) -> u32 {
+        // 75ns, rounded up.
+        (core_hz as u64 * 75).div_ceil(1_000_000_000) as u32
+    }
+
+    pub fn into_inner(self) -> (SCK, SDA, DCX) {
+        (self.sck, self.sda, self.dcx)
+    }
+
+    fn set_sck(&mut self, high: bool) {
+        let r = if high { self.sck.set_high() } else { self.sck.set_low() };
+        self.policy.check(r, "Failed to set the SCK pin.");
+    }
+
+    fn set_sda(&mut self, high: bool) {
+        let r = if high { self.sda.set_high() } else { self.sda.set_low() };
+        self.policy.check(r, "Failed to set the SDA pin.");
+    }
+
+    fn half_period(&self) {
+        for _ in 0..self.half_period_spins { core::hint::spin_loop(); }
+    }
+
+    fn write(&mut self, data: &[u8]) -> Ready<()> {
+        for &byte in data {
+            for i in (0..8).rev() {
+                self.set_sck(false);
+                self.set_sda(byte >> i & 1 != 0);
+                self.half_period();
+                self.set_sck(true);
+                self.half_period();
+            }
+        }
+        ready(())
+    }
+
+    fn read(&mut self, num_bits: usize) -> Ready<u32> {
+        let Self{sck, sda, half_period_spins, policy, ..} = self;
+        let (spins, policy) = (*half_period_spins, *policy);
+        let half_period = || {
+            for _ in 0..spins { core::hint::spin_loop(); }
+        };
+        ready(bitbang_read(
+            num_bits,
+            |high| {
+                let r = if high { sck.set_high() } else { sck.set_low() };
+                policy.check(r, "Failed to set the SCK pin.");
+            },
+            || match sda.is_high() {
+                Ok(high) => high,
+                Err(_) => {
+                    policy.check(Err(()), "Failed to read the SDA pin.");
+                    false
+                },
+            },
+            half_period, half_period))
+    }
+}
+
+impl<SCK, SDA, DCX: DcxPin> DcxPin for BitBangSpi<SCK, SDA, DCX> {
+    fn set_dcx_command_mode(&mut self) { self.dcx.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.dcx.set_dcx_data_mode(); }
+}
+
+impl<'a, SCK, SDA, DCX> WriteU8<'a> for BitBangSpi<SCK, SDA, DCX>
+        where SCK: OutputPin, SDA: OutputPin + InputPin, DCX: DcxPin {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Ready<()> { self.write(&[data]) }
+}
+
+impl<'a, SCK, SDA, DCX> WriteU8s<'a> for BitBangSpi<SCK, SDA, DCX>
+        where SCK: OutputPin, SDA: OutputPin + InputPin, DCX: DcxPin {
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+        self.write(data)
+    }
+}
+
+impl<'a, SCK, SDA, DCX> Read<'a> for BitBangSpi<SCK, SDA, DCX>
+        where SCK: 'a + OutputPin, SDA: 'a + OutputPin + InputPin,
+              DCX: 'a + DcxPin {
+    type ReadBitsType = BitBangReader<'a, SCK, SDA, DCX>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.set_sda(true);  // Released for the LCD to drive.
+        BitBangReader{spi: self}
+    }
+}
+
+/// Returned by [BitBangSpi::start_reading()](Read::start_reading).
+#[derive(Debug)]
+pub struct BitBangReader<'s, SCK, SDA, DCX> {
+    spi: &'s mut BitBangSpi<SCK, SDA, DCX>,
+}
+
+impl<'a, 's, SCK, SDA, DCX> ReadBits<'a> for BitBangReader<'s, SCK, SDA, DCX>
+        where SCK: OutputPin, SDA: OutputPin + InputPin, DCX: DcxPin {
+    type ReadBitsDone = Ready<u32>;
+
+    fn read_bits(&'a mut self, num_bits: usize) -> Ready<u32> {
+        self.spi.read(num_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::task::block_on;
+    use super::*;
+
+    #[derive(Default)]
+    struct FakePin { high: Option<bool>, fail: bool }
+
+    impl OutputPin for FakePin {
+        type Error = ();
+        fn set_low(&mut self) -> Result<(), ()> {
+            self.high = Some(false);
+            if self.fail { Err(()) } else { Ok(()) }
+        }
+        fn set_high(&mut self) -> Result<(), ()> {
+            self.high = Some(true);
+            if self.fail { Err(()) } else { Ok(()) }
+        }
+    }
+
+    #[derive(Default)]
+    struct FakeSpi { written: std::vec::Vec<u8>, fail: bool, calls: usize }
+
+    impl Write<u8> for FakeSpi {
+        type Error = ();
+        fn write(&mut self, words: &[u8]) -> Result<(), ()> {
+            self.calls += 1;
+            self.written.extend_from_slice(words);
+            if self.fail { Err(()) } else { Ok(()) }
+        }
+    }
+
+    #[test]
+    fn blocking_spi() {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let mut spi = BlockingSpi::new(FakeSpi::default(), dcx);
+        spi.set_dcx_data_mode();
+        block_on(spi.write_u8(0x12));
+        block_on(spi.write_u8s(&[0x34, 0x56]));
+        let (spi, dcx) = spi.into_inner();
+        assert_eq!(spi.written, [0x12, 0x34, 0x56]);
+        assert_eq!(dcx.into_inner().high, Some(true));
+    }
+
+    #[test]
+    fn blocking_spi_empty_write() {
+        use crate::conformance::check_write_u8s;
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let fake = FakeSpi{fail: true, ..Default::default()};
+        let mut spi = BlockingSpi::new(fake, dcx);
+        // Nothing observable is left for the check: DCX and the SPI are owned.
+        assert_eq!(check_write_u8s(&mut spi, || 0), Ok(()));
+        let (spi, dcx) = spi.into_inner();
+        assert_eq!(spi.calls, 0);
+        assert_eq!(dcx.into_inner().high, None);
+    }
+
+    #[test]
+    #[should_panic]
+    fn blocking_spi_panics() {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let fake = FakeSpi{fail: true, ..Default::default()};
+        block_on(BlockingSpi::new(fake, dcx).write_u8(0x12));
+    }
+
+    // Answers `WouldBlock` to every other call.
+    #[derive(Default)]
+    struct FakeFullDuplex {
+        sent: std::vec::Vec<u8>,
+        reads: usize,
+        calls: usize,
+        fail: bool,
+    }
+
+    impl FakeFullDuplex {
+        fn ready(&mut self) -> nb::Result<(), ()> {
+            self.calls += 1;
+            if self.calls % 2 == 1 {
+                Err(nb::Error::WouldBlock)
+            } else if self.fail {
+                Err(nb::Error::Other(()))
+            } else {
+                Ok(())
+            }
+        }
+    }
+
+    impl FullDuplex<u8> for FakeFullDuplex {
+        type Error = ();
+        fn read(&mut self) -> nb::Result<u8, ()> {
+            self.ready()?;
+            self.reads += 1;
+            Ok(0xFF)
+        }
+        fn send(&mut self, word: u8) -> nb::Result<(), ()> {
+            self.ready()?;
+            self.sent.push(word);
+            Ok(())
+        }
+    }
+
+    // Polls `f` until ready, returning how many polls were pending.
+    fn count_pending<F: core::future::Future>(f: F) -> usize {
+        use core::task::{Context, Waker};
+        let mut f = core::pin::pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        (0..).find(|_| f.as_mut().poll(&mut cx).is_ready()).unwrap()
+    }
+
+    #[test]
+    fn nb_spi() {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let mut spi = NbSpi::new(FakeFullDuplex::default(), dcx);
+        spi.set_dcx_data_mode();
+        // Pending before each send and each read.
+        assert_eq!(count_pending(spi.write_u8(0x12)), 2);
+        assert_eq!(count_pending(spi.write_u8s(&[0x34, 0x56])), 4);
+        assert_eq!(count_pending(spi.write_u8s(&[])), 0);
+        let (spi, dcx) = spi.into_inner();
+        assert_eq!(spi.sent, [0x12, 0x34, 0x56]);
+        assert_eq!(spi.reads, 3);
+        assert_eq!(dcx.into_inner().high, Some(true));
+    }
+
+    #[test]
+    fn nb_spi_ignores() {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let fake = FakeFullDuplex{fail: true, ..Default::default()};
+        let mut spi = NbSpi::with_policy(fake, dcx, ErrorPolicy::Ignore);
+        assert_eq!(count_pending(spi.write_u8s(&[1, 2])), 2);
+        assert_eq!(spi.into_inner().0.reads, 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn nb_spi_panics() {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let fake = FakeFullDuplex{fail: true, ..Default::default()};
+        count_pending(NbSpi::new(fake, dcx).write_u8(0x12));
+    }
+
+    // The SCK and SDA of a BitBangSpi, decoding what a LCD would sample on
+    // the rising edges of SCK and answering reads with `to_read`.
+    #[derive(Default)]
+    struct Wires {
+        sck: bool,
+        sda: bool,
+        sampled: std::vec::Vec<bool>,
+        to_read: std::vec::Vec<bool>,
+        read_while_low: usize,
+    }
+
+    type SharedWires = std::rc::Rc<core::cell::RefCell<Wires>>;
+
+    struct Sck(SharedWires);
+    struct Sda(SharedWires);
+
+    impl OutputPin for Sck {
+        type Error = ();
+        fn set_low(&mut self) -> Result<(), ()> {
+            self.0.borrow_mut().sck = false;
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), ()> {
+            let mut w = self.0.borrow_mut();
+            if !w.sck {
+                let sda = w.sda;
+                w.sampled.push(sda);
+            }
+            w.sck = true;
+            Ok(())
+        }
+    }
+
+    impl OutputPin for Sda {
+        type Error = ();
+        fn set_low(&mut self) -> Result<(), ()> {
+            self.0.borrow_mut().sda = false;
+            Ok(())
+        }
+        fn set_high(&mut self) -> Result<(), ()> {
+            self.0.borrow_mut().sda = true;
+            Ok(())
+        }
+    }
+
+    impl InputPin for Sda {
+        type Error = ();
+        fn is_high(&self) -> Result<bool, ()> {
+            let mut w = self.0.borrow_mut();
+            if !w.sck { w.read_while_low += 1; }
+            Ok(w.to_read.remove(0))
+        }
+        fn is_low(&self) -> Result<bool, ()> { self.is_high().map(|h| !h) }
+    }
+
+    fn bit_bang(wires: &SharedWires)
+            -> BitBangSpi<Sck, Sda, DcxFromOutputPin<FakePin>> {
+        let dcx = DcxFromOutputPin::new(FakePin::default());
+        let (sck, sda) = (Sck(wires.clone()), Sda(wires.clone()));
+        let spi = BitBangSpi::new(sck, sda, dcx, 1);
+        wires.borrow_mut().sampled.clear();  // Not an edge, just the idling.
+        spi
+    }
+
+    #[test]
+    fn bit_bang_write() {
+        let wires = SharedWires::default();
+        let mut spi = bit_bang(&wires);
+        assert!(wires.borrow().sck);  // Idles HIGH.
+        spi.set_dcx_command_mode();
+        block_on(spi.write_u8(0xA5));
+        block_on(spi.write_u8s(&[0x01, 0x80]));
+        let bytes: std::vec::Vec<u8> = wires.borrow().sampled.chunks(8)
+            .map(|bits| bits.iter().fold(0, |v, &b| v << 1 | b as u8))
+            .collect();
+        assert_eq!(bytes, [0xA5, 0x01, 0x80]);
+        assert!(wires.borrow().sck);
+        let (_, _, dcx) = spi.into_inner();
+        assert_eq!(dcx.into_inner().high, Some(false));
+    }
+
+    #[test]
+    fn bit_bang_read() {
+        let wires = SharedWires::default();
+        let mut spi = bit_bang(&wires);
+        wires.borrow_mut().to_read =
+            [true, false, true, true, false, false, true, false, true]
+            .to_vec();
+        {
+            let mut reader = spi.start_reading();
+            assert!(wires.borrow().sda);  // Released.
+            assert_eq!(block_on(reader.read_bits(1)), 1);
+            assert_eq!(block_on(reader.read_bits(8)), 0b0110_0101);
+        }
+        let w = wires.borrow();
+        assert!(w.to_read.is_empty());
+        assert_eq!(w.read_while_low, 9);
+        assert!(w.sck);
+    }
+
+    #[test]
+    fn spins_for() {
+        type S = BitBangSpi<Sck, Sda, DcxFromOutputPin<FakePin>>;
+        assert_eq!(S::spins_for(8_000_000), 1);
+        assert_eq!(S::spins_for(72_000_000), 6);
+        assert_eq!(S::spins_for(480_000_000), 36);
+    }
+
+    #[test]
+    fn modes() {
+        let mut dcx = DcxFromOutputPin::new(FakePin::default());
+        dcx.set_dcx_data_mode();
+        assert_eq!(dcx.pin.high, Some(true));
+        dcx.set_dcx_command_mode();
+        assert_eq!(dcx.pin.high, Some(false));
+    }
+
+    #[test]
+    #[should_panic]
+    fn panics() {
+        let pin = FakePin{high: None, fail: true};
+        DcxFromOutputPin::new(pin).set_dcx_data_mode();
+    }
+
+    #[test]
+    fn ignores() {
+        let pin = FakePin{high: None, fail: true};
+        let mut dcx = DcxFromOutputPin::with_policy(pin, ErrorPolicy::Ignore);
+        dcx.set_dcx_data_mode();
+        assert_eq!(dcx.into_inner().high, Some(true));
+    }
+}  // mod tests
@@ -19,19 +19,19 @@
 //! wrap it with [Commands](crate::Commands) to use the commands. An example can
 //! be found at the [examples/stm32f3348_disco](https://github.com/jeru/st7735-async-low/tree/main/st7735_async_low/examples/stm32f3348_disco)
 //! directory of the crate.
+//!
+//! Everything not depending on a HAL lives in the `st7735-async-low-core`
+//! crate and is re-exported here under the same paths. Glue for HALs is added
+//! here, each behind its own feature.
+//!
+//! In particular, [crate::spi] is the one set of bus traits: the very items of
+//! `st7735_async_low_core::spi`, so an implementation written against either
+//! crate works with the [Commands](crate::Commands) of both.
 
 #![no_std]
 
 #[cfg(test)] extern crate std;
-#[cfg(test)] extern crate tokio;
-#[cfg(test)] extern crate mockall;
 
-pub mod adapters;
-mod command_structs;
-pub use command_structs::{
-    Colmod, ColorComponentOrder, ColumnOrder, Madctl, RowColumnSwap, RowOrder};
-mod commands;
-pub use commands::{Commands, RamWriter};
-pub mod spi;
+pub use st7735_async_low_core::*;
 
-#[cfg(test)] pub mod testing_device;
+#[cfg(feature = "embedded-hal")] pub mod hal;
@@ -0,0 +1,43 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Feeds arbitrary register values to the parameter structs, as a decoder of
+//! a logic-analyzer capture would.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use st7735_async_low_core::*;
+
+fuzz_target!(|data: &[u8]| {
+    for &raw in data {
+        let read = Madctl::from(raw);
+        assert_eq!(u8::from(read), raw);
+        // Writing back what was read must not change anything.
+        let mut madctl = read;
+        madctl.set_row_address_order(read.row_address_order())
+            .set_column_address_order(read.column_address_order())
+            .set_row_column_swap(read.row_column_swap())
+            .set_vertical_refresh_order(read.vertical_refresh_order())
+            .set_horizontal_refresh_order(read.horizontal_refresh_order())
+            .set_rgb_order(read.rgb_order());
+        assert_eq!(madctl, read);
+
+        let colmod = Colmod::from(raw);
+        if colmod != Colmod::Unknown {
+            assert_eq!(u8::from(colmod), raw);
+        }
+        assert_eq!(Colmod::from(u8::from(colmod)), colmod);
+    }
+});
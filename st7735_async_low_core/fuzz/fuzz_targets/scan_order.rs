@@ -0,0 +1,36 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks that [scan_order] visits each pixel exactly once, for arbitrary
+//! sizes and orientations.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use st7735_async_low_core::Madctl;
+use st7735_async_low_core::scan::scan_order;
+
+fuzz_target!(|data: (u8, u8, u8)| {
+    let (width, height, madctl) = data;
+    let (width, height) = (usize::from(width), usize::from(height));
+    let buffer: Vec<usize> = (0..width * height).collect();
+    let order = scan_order(&buffer, width, height, Madctl::from(madctl));
+    assert_eq!(order.len(), buffer.len());
+    let mut seen = vec![false; buffer.len()];
+    for index in order {
+        assert!(!seen[index]);
+        seen[index] = true;
+    }
+    assert!(seen.iter().all(|&s| s));
+});
@@ -0,0 +1,2069 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helper structs make both [WriteU8] and [WriteU8s] available when only one
+//! is implemented, plus wrappers adding features on top of an implementation,
+//! e.g., [Counting] and [ReadBitOrder].
+
+use core::future::Future;
+use core::iter::Fuse;
+use core::marker::{PhantomData, PhantomPinned};
+use core::pin::Pin;
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll};
+
+use crate::delay::DelayMs;
+use crate::spi;
+use spi::{
+    BusSpeedControl, DcxPin, Read, ReadBits, WriteBarrier, WriteRepeat,
+    WriteU16s, WriteU8, WriteU8s, WriteU8sStatic};
+
+/// A helper to add [WriteU8s] support when [WriteU8] is implemented.
+///
+/// Supposedly **not** very efficient. See the Performance Consideration section
+/// of the module [spi].
+pub struct AdapterU8<W> { w: W }
+
+impl<W> AdapterU8<W> {
+    pub fn new(w: W) -> Self { Self{w} }
+}
+
+impl<W, H> AdapterU8<WithIdleHook<W, H>> {
+    /// Creates an adapter calling `hook` whenever a byte write of `w` is
+    /// pending. See [WithIdleHook].
+    pub fn with_idle_hook(w: W, hook: H) -> Self {
+        Self::new(WithIdleHook::new(w, hook))
+    }
+}
+
+impl<W> AdapterU8<W> where for<'w> W: WriteU8<'w> {
+    /// Writes the bytes produced by `iter`. The next byte is pulled from
+    /// `iter` while the previous one is still being written, so that
+    /// computing it overlaps with the transfer.
+    pub fn write_u8_iter<I>(&mut self, iter: I)
+            -> WriteU8Iter<'_, W, I::IntoIter>
+            where I: IntoIterator<Item=u8> {
+        WriteU8Iter{iter: iter.into_iter().fuse(), next: None, w: &mut self.w,
+                    current_write: None, _w: PhantomData}
+    }
+}
+
+impl<W: DcxPin> DcxPin for AdapterU8<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for AdapterU8<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for AdapterU8<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for AdapterU8<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: 'a> WriteU8s<'a> for AdapterU8<W> where for<'w> W: WriteU8<'w> {
+    type WriteU8sDone = RepeatU8<'a, W>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        RepeatU8{data, w: &mut self.w, current_write: None}
+    }
+}
+
+/// Internal details of [AdapterU8].
+pub struct RepeatU8<'a, W: for<'w> WriteU8<'w>> {
+    data: &'a [u8],
+    // Lifetime is also 'a. `current_write` when not `None` can actually borrow
+    // `*w` in mut.
+    w: *mut W,
+    current_write: Option<<W as WriteU8<'a>>::WriteU8Done>,
+}
+
+impl<'a, W: 'a + for<'w> WriteU8<'w>> Future for RepeatU8<'a, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: Only `Self::current_write` needs pinning. The implementation
+        // below indeed never moves it, only creates and drops.
+        let ru = unsafe {self.get_unchecked_mut()};
+        loop {
+            if ru.current_write.is_none() {
+                if let Some((first, remaining)) = ru.data.split_first() {
+                    // Safety: `current_write` is `None`.
+                    let w: &'a mut W = unsafe {&mut *ru.w};
+                    ru.current_write = Some(w.write_u8(*first));
+                    ru.data = remaining;
+                } else {
+                    return Poll::Ready(());
+                }
+            }
+            if let Some(ref mut done) = &mut ru.current_write {
+                // Safety: Pinning a field of a pinned.
+                let done = unsafe {Pin::new_unchecked(done)};
+                if done.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            } else {
+                unsafe {core::hint::unreachable_unchecked()};
+            }
+            ru.current_write = None;
+        }
+    }
+}
+
+/// Internal details of [AdapterU8::write_u8_iter()].
+pub struct WriteU8Iter<'a, W: for<'w> WriteU8<'w>, I> {
+    iter: Fuse<I>,
+    // Pulled from `iter` while `current_write` was pending.
+    next: Option<u8>,
+    // Same as in `RepeatU8`.
+    w: *mut W,
+    current_write: Option<<W as WriteU8<'a>>::WriteU8Done>,
+    _w: PhantomData<&'a mut W>,
+}
+
+impl<'a, W, I> Future for WriteU8Iter<'a, W, I>
+        where W: 'a + for<'w> WriteU8<'w>, I: Iterator<Item=u8> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: Only `Self::current_write` needs pinning. The implementation
+        // below indeed never moves it, only creates and drops.
+        let wi = unsafe {self.get_unchecked_mut()};
+        loop {
+            if wi.current_write.is_none() {
+                if let Some(data) = wi.next.take().or_else(|| wi.iter.next()) {
+                    // Safety: `current_write` is `None`.
+                    let w: &'a mut W = unsafe {&mut *wi.w};
+                    wi.current_write = Some(w.write_u8(data));
+                } else {
+                    return Poll::Ready(());
+                }
+            }
+            if let Some(ref mut done) = &mut wi.current_write {
+                // Safety: Pinning a field of a pinned.
+                let done = unsafe {Pin::new_unchecked(done)};
+                if done.poll(cx).is_pending() {
+                    if wi.next.is_none() {
+                        wi.next = wi.iter.next();
+                    }
+                    return Poll::Pending;
+                }
+            } else {
+                unsafe {core::hint::unreachable_unchecked()};
+            }
+            wi.current_write = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod adapter_u8_tests {
+    use mockall::Sequence;
+    use mockall::predicate::eq;
+
+    use crate::spi::ReadBits as _;
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[test]
+    fn write_u8_as_is() {
+        let mut a = AdapterU8::new(MockDevice::new());
+        a.set_dcx_command_mode();
+        a.w.mock().expect_write_command()
+            .with(eq(0x34))
+            .times(1);
+        block_on(a.write_u8(0x34));
+    }
+
+    #[test]
+    fn write_u8s() {
+        let mut a = AdapterU8::new(MockDevice::new());
+        a.set_dcx_data_mode();
+        let mut seq = mockall::Sequence::new();
+        a.w.mock().expect_write_data()
+            .with(eq(0x34))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.w.mock().expect_write_data()
+            .with(eq(0x56))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.w.mock().expect_write_data()
+            .with(eq(0x12))
+            .times(1)
+            .in_sequence(&mut seq);
+        block_on(a.write_u8s(&[0x34, 0x56, 0x12]));
+    }
+
+    #[test]
+    fn read_as_is() {
+        let src: u32 = 0b111010;
+        let src_len: usize = 6;
+
+        let mut a = AdapterU8::new(MockDevice::new());
+        let mut seq = Sequence::new();
+        a.w.mock().expect_start_reading().times(1).in_sequence(&mut seq);
+        for i in (0..src_len).rev() {
+            let bit = src >> i & 1 != 0;
+            a.w.mock().expect_read_bit()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || bit);
+        }
+        a.w.mock().expect_finish_reading().times(1).in_sequence(&mut seq);
+
+        let value = block_on(a.start_reading().read_bits(src_len));
+        assert_eq!(value, src);
+    }
+
+    #[test]
+    fn write_u8_iter_pulls_ahead() {
+        use std::{cell::RefCell, rc::Rc, vec::Vec};
+        type Log = Rc<RefCell<Vec<(char, u8)>>>;
+
+        // Each write is pending once before finishing.
+        struct SlowWrite { log: Log, data: u8, yielded: bool }
+        impl Future for SlowWrite {
+            type Output = ();
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                    -> Poll<()> {
+                if !self.yielded {
+                    self.yielded = true;
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                self.log.borrow_mut().push(('w', self.data));
+                Poll::Ready(())
+            }
+        }
+        struct Slow(Log);
+        impl<'a> WriteU8<'a> for Slow {
+            type WriteU8Done = SlowWrite;
+            fn write_u8(&'a mut self, data: u8) -> SlowWrite {
+                SlowWrite{log: self.0.clone(), data, yielded: false}
+            }
+        }
+
+        let log = Log::default();
+        let mut a = AdapterU8::new(Slow(log.clone()));
+        let pulled = log.clone();
+        block_on(a.write_u8_iter(
+            (1..=3).inspect(|&b| pulled.borrow_mut().push(('p', b)))));
+        assert_eq!(*log.borrow(), [
+            ('p', 1), ('p', 2), ('w', 1), ('p', 3), ('w', 2), ('w', 3)]);
+    }
+}  // mod adapter_u8_tests
+
+/// A helper to add [WriteU8] support when [WriteU8s] is implemented.
+///
+/// There is a slightly overhead on using an array to represent an element,
+/// especially when the compiler fails to inline the functions. The user
+/// should decide on their own whether they need to implement [WriteU8] and
+/// [WriteU8s] individually.
+pub struct AdapterU8s<W> { w: W, buf: u8 }
+
+impl<W> AdapterU8s<W> {
+    pub fn new(w: W) -> Self { Self{w, buf: 0} }
+}
+
+impl<W: DcxPin> DcxPin for AdapterU8s<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for AdapterU8s<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for AdapterU8s<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for AdapterU8s<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8<'a> for AdapterU8s<W> {
+    type WriteU8Done = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.buf = data;
+        self.w.write_u8s(core::slice::from_ref(&self.buf))
+    }
+}
+
+#[cfg(test)]
+mod adapter_u8s_tests {
+    use predicates::prelude::*;
+    use mockall::Sequence;
+    use std::{boxed::Box, format, vec::Vec};  // TODO: Remove after mockall 0.9.2+.
+
+    use crate::spi::ReadBits as _;
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[mockall::automock]
+    trait BatchIO {
+        fn write(&mut self, data: &[u8]);
+    }
+
+    #[derive(Default)]
+    struct MockBatchDevice { mock: MockBatchIO }
+
+    impl<'a> WriteU8s<'a> for MockBatchDevice {
+        type WriteU8sDone = Pin<Box<dyn Future<Output=()> + 'a>>;
+
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+            Box::pin(async move { self.mock.write(data); })
+        }
+    }
+
+    fn create_batch_mock() -> AdapterU8s<MockBatchDevice> {
+        AdapterU8s::new(Default::default())
+    }
+
+    #[test]
+    fn write_u8s_as_is() {
+        let mut a = create_batch_mock();
+        let eq = predicate::function(|array| {
+            array == [0x35, 0x46, 0x12, 0xFF]
+        });
+        a.w.mock.expect_write()
+            .with(eq)
+            .times(1);
+        block_on(a.write_u8s(&[0x35, 0x46, 0x12, 0xFF]));
+    }
+
+    #[test]
+    fn write_u8() {
+        let mut a = create_batch_mock();
+        let eq = predicate::function(|array| { array == [0x37] });
+        a.w.mock.expect_write()
+            .with(eq)
+            .times(1);
+        block_on(a.write_u8(0x37));
+    }
+
+    #[test]
+    fn dcx_modes() {
+        let mut a = AdapterU8::new(MockDevice::new());
+        let mut seq = Sequence::new();
+        a.w.mock().expect_write_data()
+            .with(predicate::eq(9))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.w.mock().expect_write_command()
+            .with(predicate::eq(8))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.w.mock().expect_write_data()
+            .with(predicate::eq(7))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.w.mock().expect_write_command()
+            .with(predicate::eq(6))
+            .times(1)
+            .in_sequence(&mut seq);
+        a.set_dcx_data_mode();
+        block_on(a.write_u8(9));
+        a.set_dcx_command_mode();
+        block_on(a.write_u8(8));
+        a.set_dcx_data_mode();
+        block_on(a.write_u8(7));
+        a.set_dcx_command_mode();
+        block_on(a.write_u8(6));
+    }
+
+    #[test]
+    fn read_as_is() {
+        let src: u32 = 0b111010;
+        let src_len: usize = 6;
+
+        let mut a = AdapterU8::new(MockDevice::new());
+        let mut seq = Sequence::new();
+        a.w.mock().expect_start_reading().times(1).in_sequence(&mut seq);
+        for i in (0..src_len).rev() {
+            let bit = src >> i & 1 != 0;
+            a.w.mock().expect_read_bit()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || bit);
+        }
+        a.w.mock().expect_finish_reading().times(1).in_sequence(&mut seq);
+
+        let value = block_on(a.start_reading().read_bits(src_len));
+        assert_eq!(value, src);
+    }
+}  // mod adapter_u8s_tests
+
+/// A helper to add [WriteRepeat] support when [WriteU8s] is implemented, by
+/// writing the sequence again and again.
+pub struct AdapterRepeat<W> { w: W }
+
+impl<W> AdapterRepeat<W> {
+    pub fn new(w: W) -> Self { Self{w} }
+}
+
+impl<W: DcxPin> DcxPin for AdapterRepeat<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for AdapterRepeat<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for AdapterRepeat<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for AdapterRepeat<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for AdapterRepeat<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: 'a> WriteRepeat<'a> for AdapterRepeat<W>
+        where for<'w> W: WriteU8s<'w> {
+    type WriteRepeatDone = RepeatU8s<'a, W>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        RepeatU8s{data, remaining: count, w: &mut self.w, current_write: None}
+    }
+}
+
+/// Internal details of [AdapterRepeat].
+pub struct RepeatU8s<'a, W: for<'w> WriteU8s<'w>> {
+    data: &'a [u8],
+    remaining: usize,
+    // Lifetime is also 'a. `current_write` when not `None` can actually borrow
+    // `*w` in mut.
+    w: *mut W,
+    current_write: Option<<W as WriteU8s<'a>>::WriteU8sDone>,
+}
+
+impl<'a, W: 'a + for<'w> WriteU8s<'w>> Future for RepeatU8s<'a, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: Only `Self::current_write` needs pinning. The implementation
+        // below indeed never moves it, only creates and drops.
+        let ru = unsafe {self.get_unchecked_mut()};
+        loop {
+            if ru.current_write.is_none() {
+                if ru.remaining == 0 {
+                    return Poll::Ready(());
+                }
+                // Safety: `current_write` is `None`.
+                let w: &'a mut W = unsafe {&mut *ru.w};
+                ru.current_write = Some(w.write_u8s(ru.data));
+                ru.remaining -= 1;
+            }
+            if let Some(ref mut done) = &mut ru.current_write {
+                // Safety: Pinning a field of a pinned.
+                let done = unsafe {Pin::new_unchecked(done)};
+                if done.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            } else {
+                unsafe {core::hint::unreachable_unchecked()};
+            }
+            ru.current_write = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod adapter_repeat_tests {
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[test]
+    fn write_repeated() {
+        let mut a = AdapterRepeat::new(MockDevice::new());
+        a.w.expect_standard_write_command(0x2C, &[0x12, 0x34, 0x12, 0x34,
+                                                  0x12, 0x34]);
+        block_on(async {
+            a.write_u8(0x2C).await;
+            a.set_dcx_data_mode();
+            a.write_repeated(&[0x12, 0x34], 3).await;
+        });
+    }
+
+    #[test]
+    fn write_repeated_zero_times() {
+        let mut a = AdapterRepeat::new(MockDevice::new());
+        block_on(a.write_repeated(&[0x12, 0x34], 0));
+    }
+}  // mod adapter_repeat_tests
+
+/// A helper to add [WriteU8sStatic] support when [WriteU8s] is implemented,
+/// by writing the static data like any other.
+pub struct AdapterStatic<W> { w: W }
+
+impl<W> AdapterStatic<W> {
+    pub fn new(w: W) -> Self { Self{w} }
+}
+
+impl<W: DcxPin> DcxPin for AdapterStatic<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for AdapterStatic<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for AdapterStatic<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for AdapterStatic<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for AdapterStatic<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteRepeat<'a>> WriteRepeat<'a> for AdapterStatic<W> {
+    type WriteRepeatDone = <W as WriteRepeat<'a>>::WriteRepeatDone;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        self.w.write_repeated(data, count)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8sStatic<'a> for AdapterStatic<W> {
+    type WriteU8sStaticDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        self.w.write_u8s(data)
+    }
+}
+
+#[cfg(test)]
+mod adapter_static_tests {
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[test]
+    fn write_u8s_static() {
+        static DATA: [u8; 2] = [0x12, 0x34];
+        let mut a = AdapterStatic::new(MockDevice::new());
+        a.w.expect_standard_write_command(0x2C, &DATA);
+        block_on(async {
+            a.write_u8(0x2C).await;
+            a.set_dcx_data_mode();
+            a.write_u8s_static(&DATA).await;
+        });
+    }
+}  // mod adapter_static_tests
+
+/// How many words [AdapterU16] converts per write.
+const U16_CHUNK: usize = 32;
+
+/// A helper to add [WriteU16s] support when [WriteU8s] is implemented, by
+/// writing the words as bytes, 32 words at a time.
+pub struct AdapterU16<W> { w: W }
+
+impl<W> AdapterU16<W> {
+    pub fn new(w: W) -> Self { Self{w} }
+}
+
+impl<W: DcxPin> DcxPin for AdapterU16<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for AdapterU16<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for AdapterU16<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for AdapterU16<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for AdapterU16<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteRepeat<'a>> WriteRepeat<'a> for AdapterU16<W> {
+    type WriteRepeatDone = <W as WriteRepeat<'a>>::WriteRepeatDone;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        self.w.write_repeated(data, count)
+    }
+}
+
+impl<'a, W: 'a> WriteU16s<'a> for AdapterU16<W>
+        where for<'w> W: WriteU8s<'w> {
+    type WriteU16sDone = U16sAsU8s<'a, W>;
+
+    fn write_u16s(&'a mut self, data: &'a [u16]) -> Self::WriteU16sDone {
+        U16sAsU8s{current_write: None, data, w: &mut self.w,
+                  buffer: [0; U16_CHUNK * 2], _pinned: PhantomPinned}
+    }
+}
+
+/// Internal details of [AdapterU16].
+pub struct U16sAsU8s<'a, W: for<'w> WriteU8s<'w>> {
+    // Declared first so that it is dropped first, as it borrows `buffer`.
+    current_write: Option<<W as WriteU8s<'a>>::WriteU8sDone>,
+    // The words not yet in `buffer`.
+    data: &'a [u16],
+    // Lifetime is also 'a. `current_write` when not `None` can actually borrow
+    // `*w` in mut.
+    w: *mut W,
+    buffer: [u8; U16_CHUNK * 2],
+    _pinned: PhantomPinned,
+}
+
+impl<'a, W: 'a + for<'w> WriteU8s<'w>> Future for U16sAsU8s<'a, W> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: `current_write` is never moved, only created and dropped;
+        // `buffer` is never moved either, as `Self` is !Unpin.
+        let uu = unsafe {self.get_unchecked_mut()};
+        loop {
+            if uu.current_write.is_none() {
+                if uu.data.is_empty() {
+                    return Poll::Ready(());
+                }
+                let n = uu.data.len().min(U16_CHUNK);
+                for (i, word) in uu.data[..n].iter().enumerate() {
+                    uu.buffer[2 * i..2 * i + 2]
+                        .copy_from_slice(&word.to_be_bytes());
+                }
+                uu.data = &uu.data[n..];
+                // Safety: `current_write` is `None`, so nothing borrows `*w`
+                // or `buffer`, which stays in place as long as the new one
+                // lives.
+                let w: &'a mut W = unsafe {&mut *uu.w};
+                let bytes: &'a [u8] =
+                    unsafe {&*(&uu.buffer[..2 * n] as *const _)};
+                uu.current_write = Some(w.write_u8s(bytes));
+            }
+            if let Some(ref mut done) = &mut uu.current_write {
+                // Safety: Pinning a field of a pinned.
+                let done = unsafe {Pin::new_unchecked(done)};
+                if done.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+            }
+            uu.current_write = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod adapter_u16_tests {
+    use std::vec::Vec;
+
+    use crate::fake_device::{FakeDevice, Wire::Data};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn write_u16s() {
+        let device = FakeDevice::new();
+        let mut a = AdapterU16::new(device.clone());
+        // More than a chunk.
+        let words: Vec<u16> = (0..40).map(|i| 0x0100 * i + 0x80 + i).collect();
+        a.set_dcx_data_mode();
+        block_on(a.write_u16s(&words));
+        let bytes: Vec<_> = words.iter()
+            .flat_map(|w| w.to_be_bytes())
+            .map(Data)
+            .collect();
+        assert_eq!(device.seq(), bytes);
+        block_on(a.write_u16s(&[]));
+        assert_eq!(device.seq().len(), 80);
+    }
+}  // mod adapter_u16_tests
+
+/// A wrapper counting the traffic going through it, e.g., to measure the bus
+/// utilization or to compare [AdapterU8] against a DMA-based [WriteU8s].
+///
+/// Only writes are counted.
+#[derive(Debug, Default)]
+pub struct Counting<W> {
+    w: W,
+    is_data_mode: bool,
+    bytes: usize,
+    commands: usize,
+    polls: usize,
+}
+
+impl<W> Counting<W> {
+    pub fn new(w: W) -> Self {
+        Self{w, is_data_mode: false, bytes: 0, commands: 0, polls: 0}
+    }
+
+    /// The number of bytes written, including both commands and data.
+    pub fn bytes_written(&self) -> usize { self.bytes }
+    /// The number of bytes written in the command mode.
+    pub fn commands_issued(&self) -> usize { self.commands }
+    /// The number of times the write futures have been polled.
+    pub fn polls(&self) -> usize { self.polls }
+
+    /// Resets all counters to zero.
+    pub fn reset_counts(&mut self) {
+        self.bytes = 0;
+        self.commands = 0;
+        self.polls = 0;
+    }
+
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    pub fn into_inner(self) -> W { self.w }
+
+    fn count(&mut self, num_bytes: usize) -> (&mut W, &mut usize) {
+        self.bytes += num_bytes;
+        if !self.is_data_mode { self.commands += num_bytes; }
+        (&mut self.w, &mut self.polls)
+    }
+}
+
+impl<W: DcxPin> DcxPin for Counting<W> {
+    fn set_dcx_command_mode(&mut self) {
+        self.is_data_mode = false;
+        self.w.set_dcx_command_mode();
+    }
+    fn set_dcx_data_mode(&mut self) {
+        self.is_data_mode = true;
+        self.w.set_dcx_data_mode();
+    }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for Counting<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for Counting<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for Counting<W> {
+    type WriteU8Done = CountPolls<'a, <W as WriteU8<'a>>::WriteU8Done>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        let (w, polls) = self.count(1);
+        CountPolls{done: w.write_u8(data), polls}
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for Counting<W> {
+    type WriteU8sDone = CountPolls<'a, <W as WriteU8s<'a>>::WriteU8sDone>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        let (w, polls) = self.count(data.len());
+        CountPolls{done: w.write_u8s(data), polls}
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+/// Internal details of [Counting].
+pub struct CountPolls<'a, F> {
+    done: F,
+    polls: &'a mut usize,
+}
+
+impl<'a, F: Future> Future for CountPolls<'a, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: Only `Self::done` needs pinning, which is never moved.
+        let cp = unsafe {self.get_unchecked_mut()};
+        *cp.polls += 1;
+        // Safety: Pinning a field of a pinned.
+        unsafe {Pin::new_unchecked(&mut cp.done)}.poll(cx)
+    }
+}
+
+#[cfg(test)]
+mod counting_tests {
+    use mockall::predicate::eq;
+
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[test]
+    fn counts() {
+        let mut c = Counting::new(MockDevice::new());
+        c.inner().expect_standard_write_command(0x2A, &[1, 2, 3, 4]);
+        block_on(async {
+            c.set_dcx_command_mode();
+            c.write_u8(0x2A).await;
+            c.set_dcx_data_mode();
+            c.write_u8s(&[1, 2]).await;
+            c.write_u8s(&[3, 4]).await;
+        });
+        assert_eq!(c.bytes_written(), 5);
+        assert_eq!(c.commands_issued(), 1);
+        assert_eq!(c.polls(), 3);
+    }
+
+    #[test]
+    fn reset_counts() {
+        let mut c = Counting::new(MockDevice::new());
+        c.inner().mock().expect_write_command()
+            .with(eq(0x29))
+            .times(1);
+        block_on(c.write_u8(0x29));
+        assert_eq!(c.commands_issued(), 1);
+        c.reset_counts();
+        assert_eq!(c.bytes_written(), 0);
+        assert_eq!(c.commands_issued(), 0);
+        assert_eq!(c.polls(), 0);
+    }
+}  // mod counting_tests
+
+/// A wrapper computing the CRC-32 of the pixel data written after the last
+/// RAMWR, to detect dropped or duplicated bytes in a [WriteU8s]
+/// implementation: compare [crc()](Self::crc) with [Crc32::of()] the intended
+/// bytes.
+///
+/// The CRC is the common one of Ethernet and zlib.
+#[derive(Debug)]
+pub struct Crc32<W> {
+    w: W,
+    is_data_mode: bool,
+    in_ramwr: bool,
+    /// Inverted, as during the computation.
+    crc: u32,
+    bytes: usize,
+}
+
+impl<W: Default> Default for Crc32<W> {
+    fn default() -> Self { Self::new(W::default()) }
+}
+
+impl<W> Crc32<W> {
+    pub fn new(w: W) -> Self {
+        Self{w, is_data_mode: false, in_ramwr: false, crc: !0, bytes: 0}
+    }
+
+    /// The CRC of the data written after the last RAMWR, even if other
+    /// commands have been written since.
+    pub fn crc(&self) -> u32 { !self.crc }
+    /// The number of data bytes the CRC covers.
+    pub fn bytes(&self) -> usize { self.bytes }
+
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    pub fn into_inner(self) -> W { self.w }
+
+    /// The CRC of `data`.
+    pub fn of(data: &[u8]) -> u32 { !update_crc32(!0, data) }
+
+    fn feed(&mut self, data: &[u8]) -> &mut W {
+        if !self.is_data_mode {
+            if let Some(cmd) = data.last() {
+                self.in_ramwr = *cmd == 0x2C;
+                if self.in_ramwr {
+                    self.crc = !0;
+                    self.bytes = 0;
+                }
+            }
+        } else if self.in_ramwr {
+            self.crc = update_crc32(self.crc, data);
+            self.bytes += data.len();
+        }
+        &mut self.w
+    }
+}
+
+fn update_crc32(mut crc: u32, data: &[u8]) -> u32 {
+    for b in data {
+        crc ^= *b as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xEDB8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    crc
+}
+
+impl<W: DcxPin> DcxPin for Crc32<W> {
+    fn set_dcx_command_mode(&mut self) {
+        self.is_data_mode = false;
+        self.w.set_dcx_command_mode();
+    }
+    fn set_dcx_data_mode(&mut self) {
+        self.is_data_mode = true;
+        self.w.set_dcx_data_mode();
+    }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for Crc32<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for Crc32<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for Crc32<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.feed(&[data]).write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for Crc32<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.feed(data).write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteU8sStatic<'a>> WriteU8sStatic<'a> for Crc32<W> {
+    type WriteU8sStaticDone = <W as WriteU8sStatic<'a>>::WriteU8sStaticDone;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        self.feed(data).write_u8s_static(data)
+    }
+}
+
+impl<'a, W: WriteRepeat<'a>> WriteRepeat<'a> for Crc32<W> {
+    type WriteRepeatDone = <W as WriteRepeat<'a>>::WriteRepeatDone;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        for _ in 0..count { self.feed(data); }
+        self.w.write_repeated(data, count)
+    }
+}
+
+#[cfg(test)]
+mod crc32_tests {
+    use crate::fake_device::FakeDevice;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn of() {
+        assert_eq!(Crc32::<()>::of(b"123456789"), 0xCBF4_3926);
+        assert_eq!(Crc32::<()>::of(&[]), 0);
+    }
+
+    #[test]
+    fn default_is_empty() {
+        let c = Crc32::<FakeDevice>::default();
+        assert_eq!((c.crc(), c.bytes()), (Crc32::<()>::of(&[]), 0));
+    }
+
+    #[test]
+    fn covers_the_last_ramwr() {
+        let mut c = Crc32::new(FakeDevice::new());
+        block_on(async {
+            c.set_dcx_command_mode();
+            c.write_u8(0x2A).await;
+            c.set_dcx_data_mode();
+            c.write_u8s(&[0, 1, 0, 2]).await;
+            c.set_dcx_command_mode();
+            c.write_u8(0x2C).await;
+            c.set_dcx_data_mode();
+            c.write_u8s(&[1, 2]).await;
+            c.write_u8(3).await;
+            c.write_repeated(&[4, 5], 2).await;
+            c.set_dcx_command_mode();
+            c.write_u8(0x29).await;
+        });
+        assert_eq!(c.crc(), Crc32::<()>::of(&[1, 2, 3, 4, 5, 4, 5]));
+        assert_eq!(c.bytes(), 7);
+        assert_eq!(c.inner().seq().len(), 14);
+
+        block_on(c.write_u8(0x2C));
+        assert_eq!(c.crc(), 0);
+        assert_eq!(c.bytes(), 0);
+    }
+}  // mod crc32_tests
+
+/// Called while a write is in progress, see [WithIdleHook].
+pub trait IdleHook {
+    fn on_idle(&mut self);
+}
+
+impl<F: FnMut()> IdleHook for F {
+    fn on_idle(&mut self) { self() }
+}
+
+/// A wrapper calling an [IdleHook] each time a write future of `W` returns
+/// `Pending`, e.g., to pet a watchdog or to blink an activity LED during long
+/// transfers, without instrumenting the SPI implementation.
+///
+/// Wrap the [WriteU8] implementation under an [AdapterU8] (see
+/// [AdapterU8::with_idle_hook()]) to have the hook called for every byte.
+pub struct WithIdleHook<W, H> {
+    w: W,
+    hook: H,
+}
+
+impl<W, H> WithIdleHook<W, H> {
+    /// Calls `hook` whenever a write of `w` is pending.
+    pub fn new(w: W, hook: H) -> Self { Self{w, hook} }
+
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    pub fn into_inner(self) -> (W, H) { (self.w, self.hook) }
+}
+
+impl<W: DcxPin, H> DcxPin for WithIdleHook<W, H> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl, H> BusSpeedControl for WithIdleHook<W, H> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>, H> Read<'a> for WithIdleHook<W, H> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W: WriteU8<'a>, H: IdleHook> WriteU8<'a> for WithIdleHook<W, H> {
+    type WriteU8Done = OnPending<'a, <W as WriteU8<'a>>::WriteU8Done>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        OnPending::new(self.w.write_u8(data), &mut self.hook)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>, H: IdleHook> WriteU8s<'a>
+        for WithIdleHook<W, H> {
+    type WriteU8sDone = OnPending<'a, <W as WriteU8s<'a>>::WriteU8sDone>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        OnPending::new(self.w.write_u8s(data), &mut self.hook)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteRepeat<'a>, H: IdleHook> WriteRepeat<'a>
+        for WithIdleHook<W, H> {
+    type WriteRepeatDone =
+        OnPending<'a, <W as WriteRepeat<'a>>::WriteRepeatDone>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        OnPending::new(self.w.write_repeated(data, count), &mut self.hook)
+    }
+}
+
+/// Internal details of [WithIdleHook].
+///
+/// The hook is type-erased, so that the future doesn't require `H: 'a`,
+/// which couldn't be met under the `for<'a>` bounds on the write traits
+/// unless `H: 'static`.
+pub struct OnPending<'a, F> {
+    done: F,
+    hook: &'a mut dyn IdleHook,
+}
+
+impl<'a, F> OnPending<'a, F> {
+    fn new(done: F, hook: &'a mut dyn IdleHook) -> Self { Self{done, hook} }
+}
+
+impl<'a, F: Future> Future for OnPending<'a, F> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: Only `Self::done` needs pinning, which is never moved.
+        let op = unsafe {self.get_unchecked_mut()};
+        // Safety: Pinning a field of a pinned.
+        let poll = unsafe {Pin::new_unchecked(&mut op.done)}.poll(cx);
+        if poll.is_pending() {
+            op.hook.on_idle();
+        }
+        poll
+    }
+}
+
+#[cfg(test)]
+mod idle_hook_tests {
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    // Each write is pending `pendings` times before finishing.
+    struct Slow { pendings: usize }
+    struct SlowWrite(usize);
+    impl Future for SlowWrite {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+    impl<'a> WriteU8<'a> for Slow {
+        type WriteU8Done = SlowWrite;
+        fn write_u8(&'a mut self, _data: u8) -> SlowWrite {
+            SlowWrite(self.pendings)
+        }
+    }
+
+    #[test]
+    fn called_when_pending() {
+        let mut idles = 0;
+        let mut a =
+            AdapterU8::with_idle_hook(Slow{pendings: 2}, || idles += 1);
+        block_on(a.write_u8s(&[1, 2, 3]));
+        assert_eq!(idles, 6);
+    }
+
+    #[test]
+    fn not_called_when_ready() {
+        let mut idles = 0;
+        let mut h = WithIdleHook::new(MockDevice::new(), || idles += 1);
+        h.inner().expect_standard_write_command(0x2C, &[1, 2]);
+        block_on(async {
+            h.write_u8(0x2C).await;
+            h.set_dcx_data_mode();
+            h.write_u8s(&[1, 2]).await;
+        });
+        drop(h);  // Holds the closure borrowing `idles`.
+        assert_eq!(idles, 0);
+    }
+}  // mod idle_hook_tests
+
+/// The order in which an implementation of [ReadBits] shifts the received
+/// bits into its result.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum BitOrder {
+    /// The first received bit ends up as the highest bit, as expected by
+    /// [Commands](crate::Commands).
+    #[default]
+    MsbFirst,
+    /// The first received bit ends up as the lowest bit.
+    LsbFirst,
+}
+
+/// A helper to use an implementation of [Read] whose [ReadBits] shifts in
+/// the given [BitOrder], by reversing the bits of each read if needed.
+pub struct ReadBitOrder<W> { w: W, order: BitOrder }
+
+impl<W> ReadBitOrder<W> {
+    pub fn new(w: W, order: BitOrder) -> Self { Self{w, order} }
+
+    pub fn into_inner(self) -> W { self.w }
+}
+
+impl<W: DcxPin> DcxPin for ReadBitOrder<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for ReadBitOrder<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: Read<'a>> Read<'a> for ReadBitOrder<W> {
+    type ReadBitsType = OrderedBits<<W as Read<'a>>::ReadBitsType>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        OrderedBits{bits: self.w.start_reading(), order: self.order}
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for ReadBitOrder<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for ReadBitOrder<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+/// Internal details of [ReadBitOrder].
+pub struct OrderedBits<R> { bits: R, order: BitOrder }
+
+impl<'b, R: ReadBits<'b>> ReadBits<'b> for OrderedBits<R> {
+    type ReadBitsDone = OrderedRead<<R as ReadBits<'b>>::ReadBitsDone>;
+
+    fn read_bits(&'b mut self, num_bits: usize) -> Self::ReadBitsDone {
+        OrderedRead{done: self.bits.read_bits(num_bits), num_bits,
+                    order: self.order}
+    }
+}
+
+/// Internal details of [ReadBitOrder].
+pub struct OrderedRead<F> { done: F, num_bits: usize, order: BitOrder }
+
+impl<F: Future<Output=u32>> Future for OrderedRead<F> {
+    type Output = u32;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+        let (num_bits, order) = (self.num_bits, self.order);
+        // Safety: Only `Self::done` needs pinning, which is never moved.
+        let done = unsafe {self.map_unchecked_mut(|or| &mut or.done)};
+        done.poll(cx).map(|v| match order {
+            BitOrder::MsbFirst => v,
+            BitOrder::LsbFirst if num_bits == 0 => 0,
+            BitOrder::LsbFirst => v.reverse_bits() >> (32 - num_bits),
+        })
+    }
+}
+
+#[cfg(test)]
+mod read_bit_order_tests {
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    fn device_reading(bits: &'static str) -> MockDevice {
+        let mut d = MockDevice::new();
+        let mut bits = bits.chars();
+        d.mock().expect_start_reading().return_const(());
+        d.mock().expect_finish_reading().return_const(());
+        d.mock().expect_read_bit()
+            .returning(move || bits.next().unwrap() == '1');
+        d
+    }
+
+    #[test]
+    fn msb_first() {
+        let mut r = ReadBitOrder::new(
+            device_reading("110100"), BitOrder::MsbFirst);
+        assert_eq!(block_on(r.start_reading().read_bits(6)), 0b110100);
+    }
+
+    #[test]
+    fn lsb_first() {
+        let mut r = ReadBitOrder::new(
+            device_reading("110100111"), BitOrder::LsbFirst);
+        let mut bits = r.start_reading();
+        assert_eq!(block_on(bits.read_bits(6)), 0b001011);
+        assert_eq!(block_on(bits.read_bits(0)), 0);
+        assert_eq!(block_on(bits.read_bits(3)), 0b111);
+    }
+}  // mod read_bit_order_tests
+
+/// A helper switching the bus speed of `W` around reads with its
+/// [BusSpeedControl]: `enter_read_speed()` when
+/// [start_reading()](Read::start_reading) is called, i.e., right after the
+/// command byte of a read command, and `enter_write_speed()` when the
+/// reading is dropped.
+pub struct SpeedSwitching<W> { w: W }
+
+impl<W> SpeedSwitching<W> {
+    pub fn new(w: W) -> Self { Self{w} }
+
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    pub fn into_inner(self) -> W { self.w }
+}
+
+impl<W: DcxPin> DcxPin for SpeedSwitching<W> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<'a, W: 'a + BusSpeedControl + Read<'a>> Read<'a> for SpeedSwitching<W> {
+    type ReadBitsType = SwitchedBits<'a, W>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.w.enter_read_speed();
+        let w: *mut W = &mut self.w;
+        // Safety: `w` is borrowed for `'a` by `self`; it is only used again
+        // by `SwitchedBits::drop()`, after the reading borrowing it is gone.
+        let bits = unsafe {&mut *w}.start_reading();
+        SwitchedBits{bits: Some(bits), w, _w: PhantomData}
+    }
+}
+
+impl<'a, W: WriteU8<'a>> WriteU8<'a> for SpeedSwitching<W> {
+    type WriteU8Done = <W as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.w.write_u8(data)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>> WriteU8s<'a> for SpeedSwitching<W> {
+    type WriteU8sDone = <W as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.w.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteU8sStatic<'a>> WriteU8sStatic<'a> for SpeedSwitching<W> {
+    type WriteU8sStaticDone = <W as WriteU8sStatic<'a>>::WriteU8sStaticDone;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        self.w.write_u8s_static(data)
+    }
+}
+
+impl<'a, W: WriteRepeat<'a>> WriteRepeat<'a> for SpeedSwitching<W> {
+    type WriteRepeatDone = <W as WriteRepeat<'a>>::WriteRepeatDone;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        self.w.write_repeated(data, count)
+    }
+}
+
+/// Internal details of [SpeedSwitching].
+pub struct SwitchedBits<'a, W: BusSpeedControl + Read<'a>> {
+    // Only `None` while dropped.
+    bits: Option<<W as Read<'a>>::ReadBitsType>,
+    w: *mut W,
+    _w: PhantomData<&'a mut W>,
+}
+
+impl<'a, 'b, W> ReadBits<'b> for SwitchedBits<'a, W>
+        where W: BusSpeedControl + Read<'a> {
+    type ReadBitsDone =
+        <<W as Read<'a>>::ReadBitsType as ReadBits<'b>>::ReadBitsDone;
+
+    fn read_bits(&'b mut self, num_bits: usize) -> Self::ReadBitsDone {
+        self.bits.as_mut().unwrap().read_bits(num_bits)
+    }
+}
+
+impl<'a, W: BusSpeedControl + Read<'a>> Drop for SwitchedBits<'a, W> {
+    fn drop(&mut self) {
+        self.bits = None;
+        // Safety: The reading, the only other user of `w`, is gone.
+        unsafe {&mut *self.w}.enter_write_speed();
+    }
+}
+
+#[cfg(test)]
+mod speed_switching_tests {
+    use crate::Commands;
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[test]
+    fn around_reads() {
+        let mut d = MockDevice::new();
+        d.mock().expect_write_command().return_const(());
+        d.mock().expect_start_reading().return_const(());
+        d.mock().expect_finish_reading().return_const(());
+        d.mock().expect_read_bit().return_const(true);
+        let mut cmds = Commands::new(SpeedSwitching::new(d));
+        assert_eq!(block_on(cmds.rdid1()), 0xFF);
+        let mut s = cmds.into_inner();
+        assert_eq!(s.inner().speed_switches(), 2);
+        assert!(!s.inner().is_read_speed());
+        {
+            let mut r = s.start_reading();
+            block_on(r.read_bits(1));
+        }
+        assert_eq!(s.inner().speed_switches(), 4);
+        assert!(!s.inner().is_read_speed());
+    }
+
+    #[test]
+    fn read_speed_while_reading() {
+        let mut s = SpeedSwitching::new(MockDevice::new());
+        s.inner().mock().expect_start_reading().return_const(());
+        s.inner().mock().expect_finish_reading().return_const(());
+        let r = s.start_reading();
+        // Safety: Only peeks at the device while the reading is alive.
+        assert!(unsafe {&*r.w}.is_read_speed());
+    }
+}  // mod speed_switching_tests
+
+/// Set by [Timeout] when a write or a read of the wrapped implementation
+/// didn't finish in time, blocking the later ones until cleared by
+/// [check()](Self::check). Typically a `static`, so that it can be checked
+/// while the backend is owned by [Commands](crate::Commands).
+#[derive(Debug, Default)]
+pub struct TimeoutFlag(AtomicBool);
+
+impl TimeoutFlag {
+    pub const fn new() -> Self { Self(AtomicBool::new(false)) }
+
+    /// Returns whether an operation timed out since the last check, and
+    /// clears the flag.
+    pub fn check(&self) -> Result<(), TimedOut> {
+        if self.0.swap(false, Ordering::AcqRel) {
+            Err(TimedOut)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Runs `f`, e.g., a high-level operation on a display over [Timeout],
+    /// returning [TimedOut] instead of its output if an operation timed out
+    /// before or during it. The flag stays set.
+    pub async fn run<F: Future>(&self, f: F) -> Result<F::Output, TimedOut> {
+        if self.is_set() {
+            return Err(TimedOut);
+        }
+        let output = f.await;
+        if self.is_set() { Err(TimedOut) } else { Ok(output) }
+    }
+
+    fn is_set(&self) -> bool { self.0.load(Ordering::Acquire) }
+    fn set(&self) { self.0.store(true, Ordering::Release); }
+}
+
+/// The error of [TimeoutFlag::check()].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct TimedOut;
+
+impl ::core::fmt::Display for TimedOut {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// A wrapper bounding each write and read future of `W` with a delay of `D`,
+/// so that a wedged bus doesn't hang the task forever.
+///
+/// A future not finished after `timeout_ms` is dropped and the [TimeoutFlag]
+/// is set. As the traits can't fail, the operation then completes with a
+/// default output, e.g., reading 0, and so does every later one at once,
+/// without reaching `W`, until the flag is cleared. [TimeoutFlag::run()]
+/// turns that into an error for the caller, who then re-initializes the
+/// display, e.g.:
+///
+/// ```ignore
+/// static BUS_TIMEOUT: TimeoutFlag = TimeoutFlag::new();
+///
+/// let spi = Timeout::new(spi, delay, 50, &BUS_TIMEOUT);
+/// // ...
+/// if BUS_TIMEOUT.run(display.fill(color)).await.is_err() {
+///     BUS_TIMEOUT.check().ok();
+///     // Reset and re-initialize.
+/// }
+/// ```
+///
+/// Reads are only bounded with a `'static` delay, e.g., one owning its timer.
+pub struct Timeout<'f, W, D> {
+    w: W,
+    delay: D,
+    timeout_ms: u32,
+    flag: &'f TimeoutFlag,
+}
+
+impl<'f, W, D> Timeout<'f, W, D> {
+    /// Bounds each operation of `w` to `timeout_ms`, measured with `delay`,
+    /// and sets `flag` when one takes longer.
+    pub fn new(w: W, delay: D, timeout_ms: u32, flag: &'f TimeoutFlag)
+            -> Self {
+        Self{w, delay, timeout_ms, flag}
+    }
+
+    /// The wrapped backend, e.g., to reset it after a timeout.
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    /// Gives the backend and the delay back.
+    pub fn into_inner(self) -> (W, D) { (self.w, self.delay) }
+}
+
+impl<W: DcxPin, D> DcxPin for Timeout<'_, W, D> {
+    fn set_dcx_command_mode(&mut self) { self.w.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.w.set_dcx_data_mode(); }
+}
+
+impl<W: BusSpeedControl, D> BusSpeedControl for Timeout<'_, W, D> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W, D> Read<'a> for Timeout<'_, W, D>
+        where W: Read<'a>, D: 'static + for<'d> DelayMs<'d> {
+    type ReadBitsType = TimeoutReader<'a, <W as Read<'a>>::ReadBitsType, D>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        TimeoutReader{bits: self.w.start_reading(), delay: &mut self.delay,
+                      timeout_ms: self.timeout_ms, flag: self.flag}
+    }
+}
+
+impl<'a, W: WriteU8<'a>, D: DelayMs<'a>> WriteU8<'a> for Timeout<'_, W, D> {
+    type WriteU8Done = WithTimeout<
+        'a, <W as WriteU8<'a>>::WriteU8Done, <D as DelayMs<'a>>::DelayDone>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        let done = if self.flag.is_set() {None}
+                   else {Some(self.w.write_u8(data))};
+        WithTimeout::new(done, self.delay.delay_ms(self.timeout_ms),
+                         self.flag)
+    }
+}
+
+impl<'a, W: WriteU8s<'a>, D: DelayMs<'a>> WriteU8s<'a>
+        for Timeout<'_, W, D> {
+    type WriteU8sDone = WithTimeout<
+        'a, <W as WriteU8s<'a>>::WriteU8sDone, <D as DelayMs<'a>>::DelayDone>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        let done = if self.flag.is_set() {None}
+                   else {Some(self.w.write_u8s(data))};
+        WithTimeout::new(done, self.delay.delay_ms(self.timeout_ms),
+                         self.flag)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W: WriteU8sStatic<'a>, D: DelayMs<'a>> WriteU8sStatic<'a>
+        for Timeout<'_, W, D> {
+    type WriteU8sStaticDone = WithTimeout<
+        'a, <W as WriteU8sStatic<'a>>::WriteU8sStaticDone,
+        <D as DelayMs<'a>>::DelayDone>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        let done = if self.flag.is_set() {None}
+                   else {Some(self.w.write_u8s_static(data))};
+        WithTimeout::new(done, self.delay.delay_ms(self.timeout_ms),
+                         self.flag)
+    }
+}
+
+impl<'a, W: WriteRepeat<'a>, D: DelayMs<'a>> WriteRepeat<'a>
+        for Timeout<'_, W, D> {
+    type WriteRepeatDone = WithTimeout<
+        'a, <W as WriteRepeat<'a>>::WriteRepeatDone,
+        <D as DelayMs<'a>>::DelayDone>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        let done = if self.flag.is_set() {None}
+                   else {Some(self.w.write_repeated(data, count))};
+        WithTimeout::new(done, self.delay.delay_ms(self.timeout_ms),
+                         self.flag)
+    }
+}
+
+/// Internal details of [Timeout].
+pub struct TimeoutReader<'a, R, D> {
+    bits: R,
+    delay: &'a mut D,
+    timeout_ms: u32,
+    flag: &'a TimeoutFlag,
+}
+
+impl<'b, R: ReadBits<'b>, D: DelayMs<'b>> ReadBits<'b>
+        for TimeoutReader<'_, R, D> {
+    type ReadBitsDone = WithTimeout<
+        'b, <R as ReadBits<'b>>::ReadBitsDone, <D as DelayMs<'b>>::DelayDone>;
+
+    fn read_bits(&'b mut self, num_bits: usize) -> Self::ReadBitsDone {
+        let done = if self.flag.is_set() {None}
+                   else {Some(self.bits.read_bits(num_bits))};
+        WithTimeout::new(done, self.delay.delay_ms(self.timeout_ms),
+                         self.flag)
+    }
+}
+
+/// Internal details of [Timeout].
+pub struct WithTimeout<'a, F, G> {
+    done: Option<F>,
+    timer: G,
+    flag: &'a TimeoutFlag,
+}
+
+impl<'a, F, G> WithTimeout<'a, F, G> {
+    // `done` is `None` when blocked by an earlier timeout.
+    fn new(done: Option<F>, timer: G, flag: &'a TimeoutFlag) -> Self {
+        Self{done, timer, flag}
+    }
+}
+
+impl<'a, F, G> Future for WithTimeout<'a, F, G>
+        where F: Future, F::Output: Default, G: Future<Output=()> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: `Self::done` and `Self::timer` are never moved, and `done`
+        // is dropped in place.
+        let wt = unsafe {self.get_unchecked_mut()};
+        // Safety: Pinning a field of a pinned.
+        let mut done = unsafe {Pin::new_unchecked(&mut wt.done)};
+        // Blocked by an earlier timeout.
+        if wt.flag.is_set() {
+            done.set(None);
+            return Poll::Ready(F::Output::default());
+        }
+        if let Some(f) = done.as_mut().as_pin_mut() {
+            if let Poll::Ready(v) = f.poll(cx) {
+                return Poll::Ready(v);
+            }
+        }
+        // Safety: Pinning a field of a pinned.
+        match unsafe {Pin::new_unchecked(&mut wt.timer)}.poll(cx) {
+            Poll::Pending => Poll::Pending,
+            Poll::Ready(()) => {
+                done.set(None);
+                wt.flag.set();
+                Poll::Ready(F::Output::default())
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod timeout_tests {
+    use crate::fake_device::{FakeDevice, Wire::{Command, Data}};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    // Ready after being pending `0` times.
+    struct Pendings(usize);
+    impl Future for Pendings {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            if self.0 == 0 {
+                return Poll::Ready(());
+            }
+            self.0 -= 1;
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    // Each delay is pending `ms` times.
+    struct FakeDelay;
+    impl<'a> DelayMs<'a> for FakeDelay {
+        type DelayDone = Pendings;
+        fn delay_ms(&'a mut self, ms: u32) -> Pendings {
+            Pendings(ms as usize)
+        }
+    }
+
+    // Never finishes any operation.
+    struct Hung;
+    struct HungBits;
+    struct Never;
+    impl Future for Never {
+        type Output = u32;
+        fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<u32> {
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+    impl<'a> WriteU8s<'a> for Hung {
+        type WriteU8sDone = Pendings;
+        fn write_u8s(&'a mut self, _data: &'a [u8]) -> Pendings {
+            Pendings(usize::MAX)
+        }
+    }
+    impl<'a> Read<'a> for Hung {
+        type ReadBitsType = HungBits;
+        fn start_reading(&'a mut self) -> HungBits { HungBits }
+    }
+    impl<'a> ReadBits<'a> for HungBits {
+        type ReadBitsDone = Never;
+        fn read_bits(&'a mut self, _num_bits: usize) -> Never { Never }
+    }
+
+    #[test]
+    fn write_times_out() {
+        let flag = TimeoutFlag::new();
+        let mut t = Timeout::new(Hung, FakeDelay, 3, &flag);
+        assert_eq!(flag.check(), Ok(()));
+        block_on(t.write_u8s(&[1, 2]));
+        assert_eq!(flag.check(), Err(TimedOut));
+        // Cleared by the check.
+        assert_eq!(flag.check(), Ok(()));
+    }
+
+    #[test]
+    fn read_times_out() {
+        let flag = TimeoutFlag::new();
+        let mut t = Timeout::new(Hung, FakeDelay, 3, &flag);
+        assert_eq!(block_on(t.start_reading().read_bits(8)), 0);
+        assert_eq!(flag.check(), Err(TimedOut));
+        let mut reader = t.start_reading();
+        assert_eq!(block_on(flag.run(reader.read_bits(8))), Err(TimedOut));
+    }
+
+    #[test]
+    fn blocks_until_cleared() {
+        let flag = TimeoutFlag::new();
+        let mut t = Timeout::new(Hung, FakeDelay, 3, &flag);
+        assert_eq!(block_on(flag.run(t.write_u8s(&[1]))), Err(TimedOut));
+        let mut t = Timeout::new(FakeDevice::default(), FakeDelay, 0, &flag);
+        assert_eq!(block_on(flag.run(t.write_u8(0x2C))), Err(TimedOut));
+        block_on(t.write_u8(0x2C));
+        assert_eq!(t.inner().seq(), []);
+        // Unblocked by the check.
+        assert_eq!(flag.check(), Err(TimedOut));
+        assert_eq!(block_on(flag.run(t.write_u8(0x2C))), Ok(()));
+        assert_eq!(t.inner().seq(), [Command(0x2C)]);
+    }
+
+    #[test]
+    fn passes_through() {
+        let flag = TimeoutFlag::new();
+        let mut t = Timeout::new(FakeDevice::default(), FakeDelay, 0, &flag);
+        block_on(async {
+            t.set_dcx_command_mode();
+            t.write_u8(0x2C).await;
+            t.set_dcx_data_mode();
+            t.write_u8s(&[1, 2]).await;
+            t.write_repeated(&[3], 2).await;
+        });
+        assert_eq!(flag.check(), Ok(()));
+        assert_eq!(t.inner().seq(),
+                   [Command(0x2C), Data(1), Data(2), Data(3), Data(3)]);
+    }
+}  // mod timeout_tests
+
+/// A wrapper deferring each DCX mode change of `W` to its next write, which
+/// first awaits [WriteBarrier::flush()], so that the bytes still in flight,
+/// e.g., in a TX FIFO, are sent under the previous mode.
+///
+/// Writes without a mode change in between aren't flushed. The pending mode
+/// is applied without flushing by [start_reading()](Read::start_reading),
+/// which can't wait.
+pub struct Barrier<W> {
+    w: W,
+    dcx: DcxState,
+}
+
+impl<W> Barrier<W> {
+    pub fn new(w: W) -> Self { Self{w, dcx: DcxState::default()} }
+
+    pub fn inner(&mut self) -> &mut W { &mut self.w }
+    pub fn into_inner(self) -> W { self.w }
+}
+
+// The DCX modes, `true` being the data mode.
+#[derive(Default)]
+struct DcxState {
+    // The one last set to `W`, if any.
+    applied: Option<bool>,
+    // The one to set before the next write, if different.
+    pending: Option<bool>,
+}
+
+impl DcxState {
+    fn set(&mut self, data_mode: bool) {
+        self.pending = Some(data_mode).filter(|&m| self.applied != Some(m));
+    }
+
+    fn apply<W: DcxPin>(&mut self, w: &mut W) {
+        if let Some(data_mode) = self.pending.take() {
+            if data_mode {
+                w.set_dcx_data_mode();
+            } else {
+                w.set_dcx_command_mode();
+            }
+            self.applied = Some(data_mode);
+        }
+    }
+}
+
+impl<W> DcxPin for Barrier<W> {
+    fn set_dcx_command_mode(&mut self) { self.dcx.set(false); }
+    fn set_dcx_data_mode(&mut self) { self.dcx.set(true); }
+}
+
+impl<W: BusSpeedControl> BusSpeedControl for Barrier<W> {
+    fn enter_read_speed(&mut self) { self.w.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.w.enter_write_speed(); }
+}
+
+impl<'a, W: DcxPin + Read<'a>> Read<'a> for Barrier<W> {
+    type ReadBitsType = <W as Read<'a>>::ReadBitsType;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        self.dcx.apply(&mut self.w);
+        self.w.start_reading()
+    }
+}
+
+impl<'a, W> WriteU8<'a> for Barrier<W>
+        where W: 'a + DcxPin + WriteU8<'a> + WriteBarrier<'a> {
+    type WriteU8Done = Flushed<'a, W, u8, <W as WriteU8<'a>>::WriteU8Done>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        Flushed::new(&mut self.w, &mut self.dcx, |w, d| w.write_u8(d), data)
+    }
+}
+
+impl<'a, W> WriteU8s<'a> for Barrier<W>
+        where W: 'a + DcxPin + WriteU8s<'a> + WriteBarrier<'a> {
+    type WriteU8sDone =
+        Flushed<'a, W, &'a [u8], <W as WriteU8s<'a>>::WriteU8sDone>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        Flushed::new(&mut self.w, &mut self.dcx, |w, d| w.write_u8s(d), data)
+    }
+    fn preferred_chunk(&self) -> usize { self.w.preferred_chunk() }
+}
+
+impl<'a, W> WriteU8sStatic<'a> for Barrier<W>
+        where W: 'a + DcxPin + WriteU8sStatic<'a> + WriteBarrier<'a> {
+    type WriteU8sStaticDone = Flushed<
+        'a, W, &'static [u8], <W as WriteU8sStatic<'a>>::WriteU8sStaticDone>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        Flushed::new(&mut self.w, &mut self.dcx,
+                     |w, d| w.write_u8s_static(d), data)
+    }
+}
+
+impl<'a, W> WriteRepeat<'a> for Barrier<W>
+        where W: 'a + DcxPin + WriteRepeat<'a> + WriteBarrier<'a> {
+    type WriteRepeatDone = Flushed<
+        'a, W, (&'a [u8], usize), <W as WriteRepeat<'a>>::WriteRepeatDone>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        Flushed::new(&mut self.w, &mut self.dcx,
+                     |w, (d, c)| w.write_repeated(d, c), (data, count))
+    }
+}
+
+/// Internal details of [Barrier].
+pub struct Flushed<'a, W: WriteBarrier<'a>, A, F> {
+    // Declared first so that it is dropped first, as it borrows `*w`.
+    state: FlushedState<<W as WriteBarrier<'a>>::FlushDone, F>,
+    // Lifetime is 'a. Only borrowed by one future in `state` at a time.
+    w: *mut W,
+    dcx: &'a mut DcxState,
+    // Starts the write once flushed, with `args`, `None` once started.
+    start: fn(&'a mut W, A) -> F,
+    args: Option<A>,
+}
+
+enum FlushedState<G, F> {
+    Idle,
+    Flush(G),
+    Write(F),
+}
+
+impl<'a, W: 'a + DcxPin + WriteBarrier<'a>, A, F> Flushed<'a, W, A, F> {
+    fn new(w: &'a mut W, dcx: &'a mut DcxState,
+           start: fn(&'a mut W, A) -> F, args: A) -> Self {
+        let w: *mut W = w;
+        let state = if dcx.pending.is_some() {
+            // Safety: Borrowed by `state` only, until it is dropped.
+            FlushedState::Flush(unsafe {&mut *w}.flush())
+        } else {
+            FlushedState::Idle
+        };
+        Self{state, w, dcx, start, args: Some(args)}
+    }
+}
+
+impl<'a, W, A, F> Future for Flushed<'a, W, A, F>
+        where W: 'a + DcxPin + WriteBarrier<'a>, F: Future<Output=()> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: `state` is pinned by never being moved, only replaced after
+        // being dropped.
+        let fl = unsafe {self.get_unchecked_mut()};
+        loop {
+            match &mut fl.state {
+                FlushedState::Idle => match fl.args.take() {
+                    Some(args) => {
+                        // Safety: No future in `state` borrows `*w`.
+                        let w: &'a mut W = unsafe {&mut *fl.w};
+                        fl.dcx.apply(w);
+                        fl.state = FlushedState::Write((fl.start)(w, args));
+                    },
+                    None => return Poll::Ready(()),
+                },
+                FlushedState::Flush(done) => {
+                    // Safety: Pinning a field of a pinned.
+                    if unsafe {Pin::new_unchecked(done)}.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    fl.state = FlushedState::Idle;
+                },
+                FlushedState::Write(done) => {
+                    // Safety: Pinning a field of a pinned.
+                    if unsafe {Pin::new_unchecked(done)}.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    fl.state = FlushedState::Idle;
+                },
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod barrier_tests {
+    use core::future::{Ready, ready};
+    use std::{cell::RefCell, rc::Rc, string::String, vec::Vec};
+
+    use crate::Commands;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    // Logs the DCX changes, the flushes and the bytes written.
+    #[derive(Clone, Default)]
+    struct Fifo { log: Rc<RefCell<Vec<String>>> }
+    impl Fifo {
+        fn push(&self, s: String) { self.log.borrow_mut().push(s); }
+    }
+    impl DcxPin for Fifo {
+        fn set_dcx_command_mode(&mut self) { self.push("C".into()); }
+        fn set_dcx_data_mode(&mut self) { self.push("D".into()); }
+    }
+    impl<'a> WriteBarrier<'a> for Fifo {
+        type FlushDone = Ready<()>;
+        fn flush(&'a mut self) -> Ready<()> {
+            self.push("flush".into());
+            ready(())
+        }
+    }
+    impl<'a> WriteU8<'a> for Fifo {
+        type WriteU8Done = Ready<()>;
+        fn write_u8(&'a mut self, data: u8) -> Ready<()> {
+            self.push(std::format!("{:02X}", data));
+            ready(())
+        }
+    }
+    impl<'a> WriteU8s<'a> for Fifo {
+        type WriteU8sDone = Ready<()>;
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+            self.push(std::format!("{:02X?}", data));
+            ready(())
+        }
+    }
+
+    #[test]
+    fn flushes_before_dcx_changes() {
+        let fifo = Fifo::default();
+        let mut c = Commands::new(Barrier::new(fifo.clone()));
+        block_on(async {
+            c.caset(1, 2).await;
+            c.dispon().await;
+            c.noron().await;
+        });
+        assert_eq!(*fifo.log.borrow(), [
+            "flush", "C", "2A", "flush", "D", "[00, 01, 00, 02]",
+            "flush", "C", "29", "13"]);
+    }
+
+    #[test]
+    fn no_flush_without_change() {
+        let mut b = Barrier::new(Fifo::default());
+        b.set_dcx_data_mode();
+        block_on(b.write_u8(1));
+        b.set_dcx_command_mode();
+        b.set_dcx_data_mode();
+        block_on(b.write_u8s(&[2, 3]));
+        assert_eq!(*b.inner().log.borrow(), ["flush", "D", "01", "[02, 03]"]);
+    }
+}  // mod barrier_tests
@@ -0,0 +1,321 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A frame loop for animations, games and UIs on top of [flush_diff()]:
+//! rendering into a back buffer, waiting for the slot of the frame, then
+//! flushing what changed, with frames skipped when falling behind.
+//!
+//! Frame `i` is due `i` frame periods after [AnimationLoop::run()] starts.
+//! Its slot is waited for by a [Pacer]: [TePacing] writes right after a
+//! vertical blank so that the write doesn't tear, [DelayPacing] just sleeps
+//! when there is no TE pin. When rendering and flushing a frame take longer
+//! than the period, the frames whose slots have passed are skipped, so that
+//! the animation keeps its pace.
+
+use core::future::Future;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::color::Pixel;
+use crate::commands::Commands;
+use crate::delay::DelayMs;
+use crate::diff::{DiffConfig, flush_diff};
+use crate::panel::Panel;
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+use crate::te::TePin;
+
+/// Defines how to read a millisecond counter, wrapping around on overflow.
+pub trait Clock {
+    fn now_ms(&mut self) -> u32;
+}
+
+impl<F: FnMut() -> u32> Clock for F {
+    fn now_ms(&mut self) -> u32 { self() }
+}
+
+/// Defines how [AnimationLoop] waits for the slot of the next frame.
+pub trait Pacer<'a> {
+    type WaitDone : 'a + Future<Output=bool>;
+
+    /// Waits toward the slot, due in `remaining_ms` (`0` if already due).
+    /// Resolves to whether the frame can be written now; otherwise it is
+    /// called again with the time remaining then.
+    fn wait(&'a mut self, remaining_ms: u32) -> Self::WaitDone;
+}
+
+/// A [Pacer] writing each frame right after a vertical blank, with TE
+/// enabled by [teon_mode(TeMode::VBlank)](crate::Commands::teon_mode).
+///
+/// The vertical blank chosen is the one closest to the slot, give or take
+/// half a refresh; a late frame waits for the next one anyway.
+#[derive(Debug)]
+pub struct TePacing<T> {
+    te: T,
+    refresh_ms: u32,
+}
+
+impl<T> TePacing<T> {
+    /// `refresh_ms` is the refresh period of the LCD, e.g., `16` at the
+    /// default [FrameRate](crate::FrameRate) of about 60Hz.
+    pub fn new(te: T, refresh_ms: u32) -> Self { Self{te, refresh_ms} }
+
+    pub fn into_inner(self) -> T { self.te }
+}
+
+impl<'a, T: TePin<'a>> Pacer<'a> for TePacing<T> {
+    type WaitDone = PacerWait<T::VblankDone>;
+
+    fn wait(&'a mut self, remaining_ms: u32) -> Self::WaitDone {
+        // About a refresh passes until the vertical blank.
+        let last = remaining_ms <= self.refresh_ms + self.refresh_ms / 2;
+        PacerWait{wait: Some(self.te.wait_for_vblank()), last}
+    }
+}
+
+/// A [Pacer] for panels without the TE pin wired: it sleeps until the slot,
+/// so the writes may tear.
+#[derive(Debug)]
+pub struct DelayPacing<D>(pub D);
+
+impl<'a, D: DelayMs<'a>> Pacer<'a> for DelayPacing<D> {
+    type WaitDone = PacerWait<D::DelayDone>;
+
+    fn wait(&'a mut self, remaining_ms: u32) -> Self::WaitDone {
+        let wait = if remaining_ms == 0 { None }
+                   else { Some(self.0.delay_ms(remaining_ms)) };
+        PacerWait{wait, last: true}
+    }
+}
+
+/// The future of the [Pacer]s of this module.
+pub struct PacerWait<F> {
+    wait: Option<F>,
+    last: bool,
+}
+
+impl<F: Future<Output=()>> Future for PacerWait<F> {
+    type Output = bool;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<bool> {
+        // Safety: `wait` is never moved out of `self`.
+        let this = unsafe {self.get_unchecked_mut()};
+        if let Some(wait) = &mut this.wait {
+            // Safety: Pinning a field of a pinned.
+            if unsafe {Pin::new_unchecked(wait)}.poll(cx).is_pending() {
+                return Poll::Pending;
+            }
+        }
+        Poll::Ready(this.last)
+    }
+}
+
+/// The frame to render, passed to the closure of [AnimationLoop::run()].
+#[derive(Debug)]
+pub struct Frame<'f, P> {
+    /// Which frame it is, counted from `0`, skipped ones included.
+    pub index: u32,
+    /// How many frames were skipped just before this one.
+    pub skipped: u32,
+    /// The back buffer, row-major with `width` pixels per row. It holds the
+    /// previous frame rendered, e.g., to draw only what moves.
+    pub pixels: &'f mut [P],
+    /// The width of the panel, in pixels.
+    pub width: u16,
+}
+
+/// What [AnimationLoop::run()] did.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct AnimationStats {
+    /// The frames written.
+    pub frames: u32,
+    /// The frames skipped for falling behind.
+    pub skipped: u32,
+    /// The windows and pixels written by [flush_diff()], over all frames.
+    pub windows: usize,
+    pub pixels: usize,
+}
+
+/// Runs a frame loop with a pair of full-screen framebuffers; see the
+/// [module](self) doc.
+pub struct AnimationLoop<'l, S, P, W, C> {
+    commands: &'l mut Commands<S>,
+    panel: &'l Panel,
+    front: &'l mut [P],
+    back: &'l mut [P],
+    pacer: W,
+    clock: C,
+    config: DiffConfig,
+}
+
+impl<'l, S, P, W, C> AnimationLoop<'l, S, P, W, C>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+              P: Pixel + PartialEq, for<'a> W: Pacer<'a>, C: Clock {
+    /// `front` must hold what is on the screen, and `back` the same unless
+    /// it is to be flushed with the first frame. The memory is expected to
+    /// be in the color mode `P::COLMOD`.
+    ///
+    /// # Panics
+    ///
+    /// If the lengths of `front` and `back` are not the area of `panel`.
+    pub fn new(commands: &'l mut Commands<S>, panel: &'l Panel,
+               front: &'l mut [P], back: &'l mut [P], pacer: W, clock: C)
+            -> Self {
+        let area = panel.width() as usize * panel.height() as usize;
+        assert_eq!(front.len(), area);
+        assert_eq!(back.len(), area);
+        Self{commands, panel, front, back, pacer, clock,
+             config: DiffConfig::default()}
+    }
+
+    /// Changes how [flush_diff()] splits the changes of the next frames into
+    /// windows. [DiffConfig::default()] until then.
+    pub fn set_diff_config(&mut self, config: DiffConfig) {
+        self.config = config;
+    }
+
+    /// Gives the pacer and the clock back; the buffers stay borrowed until
+    /// the loop is dropped.
+    pub fn into_inner(self) -> (W, C) { (self.pacer, self.clock) }
+
+    /// Renders frames at `fps` frames per second with `render`, until it
+    /// returns `false`; that last frame isn't written.
+    ///
+    /// # Panics
+    ///
+    /// If `fps` is `0`.
+    pub async fn run<F>(&mut self, fps: u32, mut render: F) -> AnimationStats
+            where F: FnMut(&mut Frame<'_, P>) -> bool {
+        assert!(fps > 0, "Zero frames per second.");
+        let period = (1000 / fps).max(1);
+        let start = self.clock.now_ms();
+        let mut stats = AnimationStats::default();
+        let (mut index, mut skipped) = (0u32, 0u32);
+        loop {
+            let mut frame = Frame{index, skipped, pixels: self.back,
+                                  width: self.panel.width()};
+            if !render(&mut frame) { return stats; }
+            let due = index.wrapping_mul(period);
+            loop {
+                let elapsed = self.clock.now_ms().wrapping_sub(start);
+                if self.pacer.wait(due.saturating_sub(elapsed)).await {
+                    break;
+                }
+            }
+            let diff = flush_diff(self.commands, self.panel, self.front,
+                                  self.back, &self.config).await;
+            stats.frames += 1;
+            stats.windows += diff.windows;
+            stats.pixels += diff.pixels;
+            // The latest slot already started, if later than the next one.
+            let elapsed = self.clock.now_ms().wrapping_sub(start);
+            let next = (elapsed / period).max(index + 1);
+            skipped = next - index - 1;
+            stats.skipped += skipped;
+            index = next;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::cell::Cell;
+    use core::future::{Ready, ready};
+    use std::rc::Rc;
+    use std::vec::Vec;
+
+    use crate::color::Rgb565;
+    use crate::fake_device::FakeDevice;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    // The TE pin of a LCD refreshing every 16ms, on the shared clock.
+    struct FakeTe(Rc<Cell<u32>>);
+
+    impl<'a> TePin<'a> for FakeTe {
+        type VblankDone = Ready<()>;
+        fn wait_for_vblank(&'a mut self) -> Ready<()> {
+            self.0.set((self.0.get() / 16 + 1) * 16);
+            ready(())
+        }
+    }
+
+    struct FakeDelay(Rc<Cell<u32>>);
+
+    impl<'a> DelayMs<'a> for FakeDelay {
+        type DelayDone = Ready<()>;
+        fn delay_ms(&'a mut self, ms: u32) -> Ready<()> {
+            self.0.set(self.0.get() + ms);
+            ready(())
+        }
+    }
+
+    const RED: Rgb565 = Rgb565::new(0x1F, 0, 0);
+
+    // Moves a red pixel along the first row, one column per frame, and
+    // records when each frame is rendered.
+    fn animate<W>(pacer: W, now: &Rc<Cell<u32>>, render_ms: &[u32],
+                  fps: u32) -> (AnimationStats, Vec<(u32, u32, u32)>)
+            where for<'a> W: Pacer<'a> {
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device);
+        let panel = Panel::new(8, 2);
+        let mut front = [Rgb565::default(); 16];
+        let mut back = front;
+        let clock = || now.get();
+        let mut anim = AnimationLoop::new(&mut commands, &panel, &mut front,
+                                          &mut back, pacer, clock);
+        let mut rendered = Vec::new();
+        let stats = block_on(anim.run(fps, |frame| {
+            rendered.push((now.get(), frame.index, frame.skipped));
+            let Some(&ms) = render_ms.get(rendered.len() - 1) else {
+                return false;
+            };
+            now.set(now.get() + ms);
+            frame.pixels.fill(Rgb565::default());
+            frame.pixels[frame.index as usize % 8] = RED;
+            true
+        }));
+        // The last frame rendered isn't written.
+        let written = rendered[rendered.len() - 2].1;
+        assert_eq!(front[written as usize % 8], RED);
+        (stats, rendered)
+    }
+
+    #[test]
+    fn delay_pacing() {
+        let now = Rc::new(Cell::new(1000));
+        let pacer = DelayPacing(FakeDelay(now.clone()));
+        // 20 frames per second, i.e., 50ms each.
+        let (stats, rendered) = animate(pacer, &now, &[10, 10, 170, 10], 20);
+        assert_eq!(rendered, [(1000, 0, 0), (1010, 1, 0), (1050, 2, 0),
+                              // Skips the frame due at 1150.
+                              (1220, 4, 1), (1230, 5, 0)]);
+        assert_eq!(stats, AnimationStats{frames: 4, skipped: 1, windows: 4,
+                                         pixels: 1 + 2 + 2 + 3});
+    }
+
+    #[test]
+    fn te_pacing() {
+        let now = Rc::new(Cell::new(0));
+        let pacer = TePacing::new(FakeTe(now.clone()), 16);
+        // 30 frames per second, i.e., 33ms each: about every other vertical
+        // blank.
+        let (stats, rendered) = animate(pacer, &now, &[5, 5, 5, 100], 30);
+        assert_eq!(rendered, [(0, 0, 0), (16, 1, 0), (32, 2, 0), (64, 3, 0),
+                              // Skips the frame due at 132.
+                              (176, 5, 1)]);
+        assert_eq!(stats.frames, 4);
+        assert_eq!(stats.skipped, 1);
+    }
+}  // mod tests
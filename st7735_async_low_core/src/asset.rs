@@ -0,0 +1,183 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Images embedded at compile time, already in the bytes the LCD expects,
+//! e.g., for a splash screen without decoding at runtime:
+//!
+//! ```ignore
+//! const LOGO: Asset = include_rgb565!("logo.bmp");
+//!
+//! display.draw_asset(0, 0, &LOGO).await?;
+//! ```
+//!
+//! [include_rgb565!] converts an uncompressed 24- or 32-bit BMP file with
+//! `const fn`s, so that neither a build script nor a proc-macro is needed.
+//! Other formats, e.g., PNG, have to be converted to such a BMP first.
+//!
+//! [include_rgb565!]: crate::include_rgb565
+
+use crate::color::Rgb565;
+use crate::panel::Rect;
+
+/// An image of [Rgb565] pixels, row-major from the top-left corner, each as
+/// its 2 bytes in the order sent to the LCD.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Asset {
+    pub width: u16,
+    pub height: u16,
+    pub bytes: &'static [u8],
+}
+
+impl Asset {
+    /// The rectangle covered when drawn at `(x, y)`.
+    pub const fn rect(&self, x: u16, y: u16) -> Rect {
+        Rect::new(x, y, self.width, self.height)
+    }
+
+    /// Decodes the pixels back from [bytes](Self::bytes), e.g., to transform
+    /// them. Drawing them as they are needs no decoding.
+    pub fn pixels(&self) -> impl Iterator<Item=Rgb565> + 'static {
+        self.bytes.chunks_exact(2)
+            .map(|b| Rgb565::from_raw(u16::from_be_bytes([b[0], b[1]])))
+    }
+}
+
+/// Embeds a BMP file as an [Asset], the path being relative to the current
+/// file like for [include_bytes!]. Fails to compile if the file isn't an
+/// uncompressed 24- or 32-bit BMP.
+#[macro_export]
+macro_rules! include_rgb565 {
+    ($path:expr) => {{
+        const BMP: &[u8] = include_bytes!($path);
+        const SIZE: (u16, u16) = $crate::asset::bmp_size(BMP);
+        const BYTES: [u8; SIZE.0 as usize * SIZE.1 as usize * 2] =
+            $crate::asset::bmp_to_rgb565(BMP);
+        $crate::asset::Asset{width: SIZE.0, height: SIZE.1, bytes: &BYTES}
+    }};
+}
+
+const fn u16_at(bytes: &[u8], i: usize) -> u16 {
+    u16::from_le_bytes([bytes[i], bytes[i + 1]])
+}
+
+const fn u32_at(bytes: &[u8], i: usize) -> u32 {
+    u32::from_le_bytes([bytes[i], bytes[i + 1], bytes[i + 2], bytes[i + 3]])
+}
+
+// The BMP fields needed, checked.
+struct BmpHeader {
+    width: usize,
+    height: usize,
+    top_down: bool,
+    bytes_per_pixel: usize,
+    data_offset: usize,
+}
+
+const fn parse(bmp: &[u8]) -> BmpHeader {
+    assert!(bmp.len() >= 54 && bmp[0] == b'B' && bmp[1] == b'M',
+            "Not a BMP file.");
+    assert!(u16_at(bmp, 28) == 24 || u16_at(bmp, 28) == 32,
+            "Only 24- and 32-bit BMP files are supported.");
+    assert!(u32_at(bmp, 30) == 0, "Only uncompressed BMP files are supported.");
+    let width = u32_at(bmp, 18) as i32;
+    let height = u32_at(bmp, 22) as i32;
+    assert!(width > 0 && width <= u16::MAX as i32 && height != 0 &&
+            height.unsigned_abs() <= u16::MAX as u32,
+            "Unsupported BMP dimensions.");
+    let header = BmpHeader{
+        width: width as usize,
+        height: height.unsigned_abs() as usize,
+        top_down: height < 0,
+        bytes_per_pixel: u16_at(bmp, 28) as usize / 8,
+        data_offset: u32_at(bmp, 10) as usize,
+    };
+    assert!(header.data_offset + stride(&header) * header.height <= bmp.len(),
+            "Truncated BMP file.");
+    header
+}
+
+// Each row is padded to 4 bytes.
+const fn stride(header: &BmpHeader) -> usize {
+    (header.width * header.bytes_per_pixel).div_ceil(4) * 4
+}
+
+/// The width and the height of a BMP file. Used by [include_rgb565!].
+///
+/// [include_rgb565!]: crate::include_rgb565
+pub const fn bmp_size(bmp: &[u8]) -> (u16, u16) {
+    let header = parse(bmp);
+    (header.width as u16, header.height as u16)
+}
+
+/// Converts a BMP file into the [bytes](Asset::bytes) of an [Asset], `N`
+/// being twice its area. Used by [include_rgb565!].
+///
+/// [include_rgb565!]: crate::include_rgb565
+pub const fn bmp_to_rgb565<const N: usize>(bmp: &[u8]) -> [u8; N] {
+    let header = parse(bmp);
+    assert!(N == header.width * header.height * 2, "Wrong output size.");
+    let mut out = [0; N];
+    let mut y = 0;
+    while y < header.height {
+        let row = if header.top_down { y } else { header.height - 1 - y };
+        let mut i = header.data_offset + row * stride(&header);
+        let mut x = 0;
+        while x < header.width {
+            // Blue, green then red.
+            let raw = Rgb565::new(bmp[i + 2] >> 3, bmp[i + 1] >> 2,
+                                  bmp[i] >> 3).raw().to_be_bytes();
+            let o = (y * header.width + x) * 2;
+            out[o] = raw[0];
+            out[o + 1] = raw[1];
+            i += header.bytes_per_pixel;
+            x += 1;
+        }
+        y += 1;
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    const RGB_3X2: Asset = include_rgb565!(
+        concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/rgb_3x2.bmp"));
+
+    #[test]
+    fn bottom_up_24_bit() {
+        assert_eq!((RGB_3X2.width, RGB_3X2.height), (3, 2));
+        assert_eq!(RGB_3X2.pixels().map(|p| p.raw()).collect::<Vec<_>>(),
+                   [0xF800, 0x07E0, 0x001F, 0xFFFF, 0x0000, 0x8402]);
+        assert_eq!(&RGB_3X2.bytes[..4], &[0xF8, 0x00, 0x07, 0xE0]);
+        assert_eq!(RGB_3X2.rect(5, 6), Rect::new(5, 6, 3, 2));
+    }
+
+    #[test]
+    fn top_down_32_bit() {
+        // 1x2, with a negative height.
+        let mut bmp = [0; 62];
+        bmp[..2].copy_from_slice(b"BM");
+        bmp[10] = 54;
+        bmp[14] = 40;
+        bmp[18] = 1;
+        bmp[22..26].copy_from_slice(&(-2i32).to_le_bytes());
+        bmp[28] = 32;
+        bmp[54..62].copy_from_slice(&[0xFF, 0, 0, 0, 0, 0, 0xFF, 0]);
+        assert_eq!(bmp_size(&bmp), (1, 2));
+        assert_eq!(bmp_to_rgb565::<4>(&bmp), [0x00, 0x1F, 0xF8, 0x00]);
+    }
+}  // mod tests
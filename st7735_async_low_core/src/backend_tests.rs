@@ -0,0 +1,269 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A test suite for implementations of the [crate::spi] traits, generated
+//! by [st7735_backend_tests!](crate::st7735_backend_tests) in the tests of
+//! the implementation, so that it is verified without hardware.
+//!
+//! The implementation is observed through a [BackendFactory], typically
+//! over a mocked HAL recording the pins:
+//! ```ignore
+//! #[derive(Default)]
+//! struct MySpiFactory { hal: MockHal, spi: MySpi }
+//!
+//! impl BackendFactory for MySpiFactory {
+//!     type Backend = MySpi;
+//!     fn backend(&mut self) -> &mut MySpi { &mut self.spi }
+//!     fn wire(&self) -> Vec<Wire> { self.hal.recorded() }
+//! }
+//!
+//! #[cfg(test)]
+//! mod tests {
+//!     st7735_async_low::st7735_backend_tests!(super::MySpiFactory);
+//! }
+//! ```
+//!
+//! Needs the `test-utils` feature.
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Waker};
+use std::vec::Vec;
+
+use crate::fake_device::Wire;
+use crate::spi::{DcxPin, Read, ReadBits, WriteU8, WriteU8s};
+use crate::task::block_on;
+
+/// Creates a backend under test and observes its wire.
+pub trait BackendFactory: Default {
+    type Backend: DcxPin + for<'a> WriteU8<'a> + for<'a> WriteU8s<'a>;
+
+    /// The backend, the same one on every call.
+    fn backend(&mut self) -> &mut Self::Backend;
+
+    /// The bytes written so far, each with the DCX level when it was sent.
+    fn wire(&self) -> Vec<Wire>;
+}
+
+/// A [BackendFactory] whose backend also reads.
+pub trait ReadBackendFactory: BackendFactory
+        where for<'a> Self::Backend: Read<'a> {
+    /// Makes the device send the lowest `len` bits of `bits`, the highest
+    /// first, on the next [Read::start_reading()].
+    fn answer_next_read(&mut self, bits: u32, len: usize);
+}
+
+/// Generates the tests of [crate::backend_tests] for a [BackendFactory],
+/// plus the read tests with `read` for a [ReadBackendFactory].
+///
+/// [BackendFactory]: crate::backend_tests::BackendFactory
+/// [ReadBackendFactory]: crate::backend_tests::ReadBackendFactory
+#[macro_export]
+macro_rules! st7735_backend_tests {
+    ($factory:ty) => {
+        #[test]
+        fn dcx_sequencing() {
+            $crate::backend_tests::dcx_sequencing::<$factory>();
+        }
+        #[test]
+        fn byte_ordering() {
+            $crate::backend_tests::byte_ordering::<$factory>();
+        }
+        #[test]
+        fn batch_equivalence() {
+            $crate::backend_tests::batch_equivalence::<$factory>();
+        }
+        #[test]
+        fn empty_write() {
+            $crate::backend_tests::empty_write::<$factory>();
+        }
+    };
+    ($factory:ty, read) => {
+        $crate::st7735_backend_tests!($factory);
+        #[test]
+        fn read_bit_ordering() {
+            $crate::backend_tests::read_bit_ordering::<$factory>();
+        }
+    };
+}
+
+/// Bytes written after each DCX change keep the new level.
+pub fn dcx_sequencing<F: BackendFactory>() {
+    use Wire::*;
+    let mut f = F::default();
+    let b = f.backend();
+    block_on(async {
+        b.set_dcx_command_mode();
+        b.write_u8(0x2A).await;
+        b.set_dcx_data_mode();
+        b.write_u8s(&[0x00, 0x01]).await;
+        b.write_u8(0x02).await;
+        b.set_dcx_command_mode();
+        b.write_u8s(&[0x2C]).await;
+        b.set_dcx_data_mode();
+        b.write_u8(0x03).await;
+    });
+    assert_eq!(f.wire(), [Command(0x2A), Data(0x00), Data(0x01), Data(0x02),
+                          Command(0x2C), Data(0x03)],
+               "The DCX level of some bytes is wrong.");
+}
+
+/// Bytes keep their values and order, including the edge values.
+pub fn byte_ordering<F: BackendFactory>() {
+    let data = [0x00, 0x01, 0x80, 0xFF, 0x5A, 0xA5, 0x7F, 0xFE];
+    let mut f = F::default();
+    let b = f.backend();
+    b.set_dcx_data_mode();
+    block_on(b.write_u8s(&data));
+    let expected: Vec<_> = data.iter().map(|d| Wire::Data(*d)).collect();
+    assert_eq!(f.wire(), expected, "Bytes are altered or reordered.");
+}
+
+/// Writing bytes one by one, all at once or in uneven chunks is the same
+/// on the wire.
+pub fn batch_equivalence<F: BackendFactory>() {
+    let data: Vec<u8> = (0..=255).collect();
+    let write = |split: &dyn Fn(&[u8]) -> Vec<&[u8]>| {
+        let mut f = F::default();
+        let b = f.backend();
+        b.set_dcx_data_mode();
+        block_on(async {
+            for part in split(&data) {
+                if part.len() == 1 {
+                    b.write_u8(part[0]).await;
+                } else {
+                    b.write_u8s(part).await;
+                }
+            }
+        });
+        f.wire()
+    };
+    let singles = write(&|d| d.chunks(1).collect());
+    assert_eq!(write(&|d| std::vec![d]), singles,
+               "A batch differs from single writes.");
+    assert_eq!(write(&|d| d.chunks(7).collect()), singles,
+               "Chunks differ from single writes.");
+}
+
+/// An empty write completes at once, with no byte and no DCX level on the
+/// wire. See [crate::conformance].
+pub fn empty_write<F: BackendFactory>() {
+    let mut f = F::default();
+    f.backend().set_dcx_data_mode();
+    let before = f.wire();
+    // The wire can't be observed while the backend is borrowed, so the
+    // write is polled once and dropped before comparing.
+    let ready = {
+        let done = pin!(f.backend().write_u8s(&[]));
+        done.poll(&mut Context::from_waker(Waker::noop())).is_ready()
+    };
+    assert!(ready, "An empty write is pending.");
+    assert_eq!(f.wire(), before, "An empty write wrote something.");
+}
+
+/// Bits are returned the first received at the highest, including across
+/// calls of [ReadBits::read_bits()].
+pub fn read_bit_ordering<F>()
+        where F: ReadBackendFactory, for<'a> F::Backend: Read<'a> {
+    let mut f = F::default();
+    // 0, 0xB1, then 1.
+    f.answer_next_read(0b01_0110_0011, 10);
+    let bits = block_on(async {
+        let mut r = f.backend().start_reading();
+        [r.read_bits(1).await, r.read_bits(8).await, r.read_bits(1).await]
+    });
+    assert_eq!(bits, [0, 0xB1, 1], "The bits are misordered.");
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::fake_device::FakeDevice;
+    use super::*;
+
+    #[derive(Default)]
+    struct FakeFactory { device: FakeDevice }
+
+    impl BackendFactory for FakeFactory {
+        type Backend = FakeDevice;
+        fn backend(&mut self) -> &mut FakeDevice { &mut self.device }
+        fn wire(&self) -> Vec<Wire> { self.device.seq() }
+    }
+
+    impl ReadBackendFactory for FakeFactory {
+        fn answer_next_read(&mut self, bits: u32, len: usize) {
+            self.device.answer_next_read(bits, len);
+        }
+    }
+
+    crate::st7735_backend_tests!(FakeFactory, read);
+
+    /// Reads the bits in reverse, which the suite must catch.
+    #[derive(Default)]
+    struct Reversed(FakeFactory);
+
+    impl BackendFactory for Reversed {
+        type Backend = FakeDevice;
+        fn backend(&mut self) -> &mut FakeDevice { self.0.backend() }
+        fn wire(&self) -> Vec<Wire> { self.0.wire() }
+    }
+
+    impl ReadBackendFactory for Reversed {
+        fn answer_next_read(&mut self, bits: u32, len: usize) {
+            self.0.answer_next_read(bits.reverse_bits() >> (32 - len), len);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "misordered")]
+    fn catches_misordered_bits() {
+        super::read_bit_ordering::<Reversed>();
+    }
+
+    /// Sends a NOP for an empty write, which the suite must catch.
+    #[derive(Default)]
+    struct Nop(FakeDevice);
+
+    impl DcxPin for Nop {
+        fn set_dcx_command_mode(&mut self) { self.0.set_dcx_command_mode() }
+        fn set_dcx_data_mode(&mut self) { self.0.set_dcx_data_mode() }
+    }
+
+    impl<'a> WriteU8<'a> for Nop {
+        type WriteU8Done = <FakeDevice as WriteU8<'a>>::WriteU8Done;
+        fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+            self.0.write_u8(data)
+        }
+    }
+
+    impl<'a> WriteU8s<'a> for Nop {
+        type WriteU8sDone = <FakeDevice as WriteU8s<'a>>::WriteU8sDone;
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+            if !data.is_empty() { return self.0.write_u8s(data); }
+            self.0.set_dcx_command_mode();
+            self.0.write_u8s(&[0x00])
+        }
+    }
+
+    impl BackendFactory for Nop {
+        type Backend = Nop;
+        fn backend(&mut self) -> &mut Nop { self }
+        fn wire(&self) -> Vec<Wire> { self.0.seq() }
+    }
+
+    #[test]
+    #[should_panic(expected = "wrote something")]
+    fn catches_empty_write_activity() {
+        super::empty_write::<Nop>();
+    }
+}  // mod tests
@@ -0,0 +1,287 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A non-async facade over [Commands](crate::Commands) for super-loops
+//! without an executor (feature `nb`).
+//!
+//! The futures are polled with a no-op waker each time a method is called,
+//! which returns [nb::Error::WouldBlock] until the command is written, as
+//! usual with [nb]:
+//! ```ignore
+//! let mut lcd = pin!(busy::Commands::new(&mut commands));
+//! nb::block!(lcd.as_mut().dispon())?;
+//! // Or, doing other work in between:
+//! loop {
+//!     match lcd.as_mut().madctl(madctl) {
+//!         Err(nb::Error::WouldBlock) => do_other_work(),
+//!         done => break done,
+//!     }
+//! }
+//! ```
+//! A command is started once and only once per `Ok`: calling the same method
+//! with the same arguments again continues it. Calling another one first
+//! completes the pending command, returning `WouldBlock` meanwhile, then
+//! starts the new one.
+//!
+//! The rest of the commands, e.g., the memory writes, can be polled with
+//! [poll_once()].
+
+use core::convert::Infallible;
+use core::future::Future;
+use core::marker::{PhantomData, PhantomPinned};
+use core::pin::Pin;
+use core::task::{Context, Poll, Waker};
+
+use crate::command_structs::{Colmod, GammaCurve, Madctl, TeMode};
+use crate::commands::{
+    CommandWithU8Future, SimpleCommandFuture, WindowFuture};
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+
+/// Polls `future` once with a no-op waker.
+pub fn poll_once<F: Future>(future: Pin<&mut F>)
+        -> nb::Result<F::Output, Infallible> {
+    match future.poll(&mut Context::from_waker(Waker::noop())) {
+        Poll::Ready(output) => Ok(output),
+        Poll::Pending => Err(nb::Error::WouldBlock),
+    }
+}
+
+// Tells apart the commands, to know whether a call continues the pending one.
+type Key = (u8, [u8; 4]);
+
+enum Pending<'c, S> where S: 'c + WriteU8<'c> + WriteU8s<'c> {
+    Simple(SimpleCommandFuture<'c, S>),
+    WithU8(CommandWithU8Future<'c, S>),
+    Window(WindowFuture<'c, S>),
+}
+
+/// Borrows [Commands](crate::Commands) to issue them without `.await`. Needs
+/// pinning, e.g., with [core::pin::pin!], as the pending command is kept
+/// inside.
+pub struct Commands<'c, S> where S: 'c + WriteU8<'c> + WriteU8s<'c> {
+    // Declared first so that it is dropped first, as it borrows `*commands`.
+    pending: Option<(Key, Pending<'c, S>)>,
+    // Lifetime is 'c. Only borrowed by the future in `pending`, if any.
+    commands: *mut crate::Commands<S>,
+    _commands: PhantomData<&'c mut crate::Commands<S>>,
+    _pinned: PhantomPinned,
+}
+
+impl<'c, S> Commands<'c, S> where S: 'c + WriteU8<'c> + WriteU8s<'c> {
+    pub fn new(commands: &'c mut crate::Commands<S>) -> Self {
+        Self{pending: None, commands, _commands: PhantomData,
+             _pinned: PhantomPinned}
+    }
+
+    /// Whether a command is still being written.
+    pub fn is_pending(&self) -> bool { self.pending.is_some() }
+}
+
+macro_rules! simple_commands {
+    ($($name:ident: $code:literal, $doc:literal;)*) => {$(
+        #[doc = $doc]
+        pub fn $name(self: Pin<&mut Self>) -> nb::Result<(), Infallible> {
+            self.run(($code, [0; 4]), |c| Pending::Simple(c.$name()))
+        }
+    )*};
+}
+
+impl<'c, S> Commands<'c, S>
+        where S: 'c + DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Continues the pending command, if any, without starting another.
+    pub fn poll_write(self: Pin<&mut Self>) -> nb::Result<(), Infallible> {
+        // Safety: Only `pending` is pinned, and it is never moved.
+        unsafe {self.get_unchecked_mut()}.drive()
+    }
+
+    fn run<F>(self: Pin<&mut Self>, key: Key, start: F)
+            -> nb::Result<(), Infallible>
+            where F: FnOnce(&'c mut crate::Commands<S>) -> Pending<'c, S> {
+        // Safety: Only `pending` is pinned, and it is never moved.
+        let this = unsafe {self.get_unchecked_mut()};
+        if let Some((pending_key, _)) = this.pending {
+            this.drive()?;
+            if pending_key == key {
+                return Ok(());
+            }
+        }
+        // Safety: No future borrows `*commands` any more.
+        let commands: &'c mut crate::Commands<S> = unsafe {&mut *this.commands};
+        this.pending = Some((key, start(commands)));
+        this.drive()
+    }
+
+    fn drive(&mut self) -> nb::Result<(), Infallible> {
+        let pending = match &mut self.pending {
+            Some((_, pending)) => pending,
+            None => return Ok(()),
+        };
+        let mut cx = Context::from_waker(Waker::noop());
+        // Safety: Pinning a field of a pinned, dropped in place once ready.
+        let poll = unsafe {
+            match pending {
+                Pending::Simple(f) => Pin::new_unchecked(f).poll(&mut cx),
+                Pending::WithU8(f) => Pin::new_unchecked(f).poll(&mut cx),
+                Pending::Window(f) => Pin::new_unchecked(f).poll(&mut cx),
+            }
+        };
+        if poll.is_pending() {
+            return Err(nb::Error::WouldBlock);
+        }
+        self.pending = None;
+        Ok(())
+    }
+
+    simple_commands! {
+        nop: 0x00, "See [Commands::nop()](crate::Commands::nop).";
+        swreset: 0x01, "See [Commands::swreset()](crate::Commands::swreset).";
+        slpin: 0x10, "See [Commands::slpin()](crate::Commands::slpin).";
+        slpout: 0x11, "See [Commands::slpout()](crate::Commands::slpout).";
+        ptlon: 0x12, "See [Commands::ptlon()](crate::Commands::ptlon).";
+        noron: 0x13, "See [Commands::noron()](crate::Commands::noron).";
+        invoff: 0x20, "See [Commands::invoff()](crate::Commands::invoff).";
+        invon: 0x21, "See [Commands::invon()](crate::Commands::invon).";
+        dispoff: 0x28, "See [Commands::dispoff()](crate::Commands::dispoff).";
+        dispon: 0x29, "See [Commands::dispon()](crate::Commands::dispon).";
+        teoff: 0x34, "See [Commands::teoff()](crate::Commands::teoff).";
+        idmoff: 0x38, "See [Commands::idmoff()](crate::Commands::idmoff).";
+        idmon: 0x39, "See [Commands::idmon()](crate::Commands::idmon).";
+    }
+
+    /// See [Commands::caset()](crate::Commands::caset).
+    pub fn caset(self: Pin<&mut Self>, begin: u16, end: u16)
+            -> nb::Result<(), Infallible> {
+        self.run((0x2A, window_key(begin, end)),
+                 |c| Pending::Window(c.caset(begin, end)))
+    }
+
+    /// See [Commands::raset()](crate::Commands::raset).
+    pub fn raset(self: Pin<&mut Self>, begin: u16, end: u16)
+            -> nb::Result<(), Infallible> {
+        self.run((0x2B, window_key(begin, end)),
+                 |c| Pending::Window(c.raset(begin, end)))
+    }
+
+    /// See [Commands::gamset()](crate::Commands::gamset).
+    pub fn gamset(self: Pin<&mut Self>, curve: GammaCurve)
+            -> nb::Result<(), Infallible> {
+        self.run((0x26, [curve.into(), 0, 0, 0]),
+                 |c| Pending::WithU8(c.gamset(curve)))
+    }
+
+    /// See [Commands::teon_mode()](crate::Commands::teon_mode).
+    pub fn teon_mode(self: Pin<&mut Self>, mode: TeMode)
+            -> nb::Result<(), Infallible> {
+        self.run((0x35, [mode.into(), 0, 0, 0]),
+                 |c| Pending::WithU8(c.teon_mode(mode)))
+    }
+
+    /// See [Commands::madctl()](crate::Commands::madctl).
+    pub fn madctl(self: Pin<&mut Self>, data: Madctl)
+            -> nb::Result<(), Infallible> {
+        self.run((0x36, [data.into(), 0, 0, 0]),
+                 |c| Pending::WithU8(c.madctl(data)))
+    }
+
+    /// See [Commands::colmod()](crate::Commands::colmod).
+    pub fn colmod(self: Pin<&mut Self>, data: Colmod)
+            -> nb::Result<(), Infallible> {
+        self.run((0x3A, [data.into(), 0, 0, 0]),
+                 |c| Pending::WithU8(c.colmod(data)))
+    }
+}
+
+fn window_key(begin: u16, end: u16) -> [u8; 4] {
+    let [a, b] = begin.to_be_bytes();
+    let [c, d] = end.to_be_bytes();
+    [a, b, c, d]
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::pin;
+
+    use crate::fake_device::{FakeDevice, Wire::{Command, Data}};
+    use crate::task::{YieldNow, yield_now};
+    use super::*;
+
+    // Pending once per write. FakeDevice records the bytes when called, so
+    // its ready futures are just dropped.
+    struct SlowDevice(FakeDevice);
+
+    impl DcxPin for SlowDevice {
+        fn set_dcx_command_mode(&mut self) { self.0.set_dcx_command_mode() }
+        fn set_dcx_data_mode(&mut self) { self.0.set_dcx_data_mode() }
+    }
+
+    impl<'a> WriteU8<'a> for SlowDevice {
+        type WriteU8Done = YieldNow;
+
+        fn write_u8(&'a mut self, data: u8) -> YieldNow {
+            drop(self.0.write_u8(data));
+            yield_now()
+        }
+    }
+
+    impl<'a> WriteU8s<'a> for SlowDevice {
+        type WriteU8sDone = YieldNow;
+
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> YieldNow {
+            drop(self.0.write_u8s(data));
+            yield_now()
+        }
+    }
+
+    // Calls `f` until `Ok`, returning how many times it was.
+    fn count_calls(mut f: impl FnMut() -> nb::Result<(), Infallible>)
+            -> usize {
+        (1..).find(|_| f().is_ok()).unwrap()
+    }
+
+    #[test]
+    fn once_per_ok() {
+        let device = FakeDevice::new();
+        let mut commands = crate::Commands::new(SlowDevice(device.clone()));
+        let mut lcd = pin!(Commands::new(&mut commands));
+        // Pending on the command byte, then on the data.
+        assert_eq!(count_calls(|| lcd.as_mut().madctl(Madctl::from(0xC8))), 3);
+        assert_eq!(count_calls(|| lcd.as_mut().dispon()), 2);
+        assert!(!lcd.is_pending());
+        assert_eq!(device.seq(), [Command(0x36), Data(0xC8), Command(0x29)]);
+    }
+
+    #[test]
+    fn switching_completes_the_pending() {
+        let device = FakeDevice::new();
+        let mut commands = crate::Commands::new(SlowDevice(device.clone()));
+        let mut lcd = pin!(Commands::new(&mut commands));
+        assert_eq!(lcd.as_mut().caset(1, 2), Err(nb::Error::WouldBlock));
+        assert!(lcd.is_pending());
+        // Finishing CASET, then starting INVON.
+        assert_eq!(lcd.as_mut().invon(), Err(nb::Error::WouldBlock));
+        assert_eq!(lcd.as_mut().invon(), Err(nb::Error::WouldBlock));
+        assert_eq!(lcd.as_mut().poll_write(), Ok(()));
+        // Nothing is pending any more, nor restarted.
+        assert_eq!(lcd.as_mut().poll_write(), Ok(()));
+        assert_eq!(device.seq(), [Command(0x2A), Data(0), Data(1), Data(0),
+                                  Data(2), Command(0x21)]);
+    }
+
+    #[test]
+    fn poll_once_any_future() {
+        let mut f = pin!(async { yield_now().await; 7 });
+        assert_eq!(poll_once(f.as_mut()), Err(nb::Error::WouldBlock));
+        assert_eq!(poll_once(f.as_mut()), Ok(7));
+    }
+}  // mod tests
@@ -0,0 +1,204 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A lock-free single-producer single-consumer channel of [QueuedCommand]s,
+//! so that a task can draw through the task owning [Commands] without
+//! sharing the SPI.
+//!
+//! Neither half registers a waker: a full [Sender::send()] and an empty
+//! [Receiver::recv()] [yield](crate::task::yield_now) until the other half
+//! makes progress.
+
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+use crate::commands::Commands;
+use crate::panel::WindowError;
+use crate::queue::QueuedCommand;
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+use crate::task::yield_now;
+
+/// Creates a channel of at most `N` commands, e.g., in a `static` of which a
+/// `&'static mut` is then [split](CommandChannel::split).
+pub const fn command_channel<const N: usize>() -> CommandChannel<N> {
+    CommandChannel::new()
+}
+
+/// The storage of a channel, see [command_channel()].
+pub struct CommandChannel<const N: usize> {
+    slots: [UnsafeCell<QueuedCommand>; N],
+    // Both count modulo `2 * N`, so that a full channel differs from an
+    // empty one. Only the `Receiver` stores `head`, only the `Sender` `tail`.
+    head: AtomicUsize,
+    tail: AtomicUsize,
+}
+
+// Safety: A slot is written only by the `Sender` while it is free, and read
+// only by the `Receiver` after the `Sender` published it through `tail`.
+unsafe impl<const N: usize> Sync for CommandChannel<N> {}
+
+impl<const N: usize> CommandChannel<N> {
+    pub const fn new() -> Self {
+        assert!(N > 0);
+        #[allow(clippy::declare_interior_mutable_const)]
+        const EMPTY: UnsafeCell<QueuedCommand> =
+            UnsafeCell::new(QueuedCommand::Dispon);
+        Self{slots: [EMPTY; N], head: AtomicUsize::new(0),
+             tail: AtomicUsize::new(0)}
+    }
+
+    /// The two halves, each to be moved to its own task.
+    pub fn split(&mut self) -> (Sender<'_, N>, Receiver<'_, N>) {
+        (Sender{channel: self}, Receiver{channel: self})
+    }
+
+    fn len(head: usize, tail: usize) -> usize {
+        (tail + 2 * N - head) % (2 * N)
+    }
+}
+
+impl<const N: usize> Default for CommandChannel<N> {
+    fn default() -> Self { Self::new() }
+}
+
+/// The producing half of a [CommandChannel].
+pub struct Sender<'c, const N: usize> { channel: &'c CommandChannel<N> }
+
+impl<'c, const N: usize> Sender<'c, N> {
+    /// Appends `command`, or gives it back if the channel is full.
+    pub fn try_send(&mut self, command: QueuedCommand)
+            -> Result<(), QueuedCommand> {
+        let ch = self.channel;
+        let tail = ch.tail.load(Ordering::Relaxed);
+        let head = ch.head.load(Ordering::Acquire);
+        if CommandChannel::<N>::len(head, tail) == N {
+            return Err(command);
+        }
+        // Safety: The slot is free, so the `Receiver` doesn't read it.
+        unsafe {*ch.slots[tail % N].get() = command;}
+        ch.tail.store((tail + 1) % (2 * N), Ordering::Release);
+        Ok(())
+    }
+
+    /// Appends `command`, waiting while the channel is full.
+    pub async fn send(&mut self, mut command: QueuedCommand) {
+        while let Err(c) = self.try_send(command) {
+            command = c;
+            yield_now().await;
+        }
+    }
+}
+
+/// The consuming half of a [CommandChannel], for the task owning
+/// [Commands].
+pub struct Receiver<'c, const N: usize> { channel: &'c CommandChannel<N> }
+
+impl<'c, const N: usize> Receiver<'c, N> {
+    /// Removes the oldest command, if any.
+    pub fn try_recv(&mut self) -> Option<QueuedCommand> {
+        let ch = self.channel;
+        let head = ch.head.load(Ordering::Relaxed);
+        let tail = ch.tail.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+        // Safety: The `Sender` published the slot and doesn't write it until
+        // `head` moves past it.
+        let command = unsafe {*ch.slots[head % N].get()};
+        ch.head.store((head + 1) % (2 * N), Ordering::Release);
+        Some(command)
+    }
+
+    /// Removes the oldest command, waiting while the channel is empty.
+    pub async fn recv(&mut self) -> QueuedCommand {
+        loop {
+            if let Some(command) = self.try_recv() {
+                return command;
+            }
+            yield_now().await;
+        }
+    }
+
+    /// Executes and removes the commands already sent, in order, even after
+    /// one fails. Returns the first failure, as
+    /// [CommandQueue::flush()](crate::queue::CommandQueue::flush) does.
+    pub async fn drain<S>(&mut self, commands: &mut Commands<S>)
+            -> Result<(), WindowError>
+            where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+        let mut result = Ok(());
+        while let Some(command) = self.try_recv() {
+            let executed = command.execute(commands).await;
+            result = result.and(executed);
+        }
+        result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::command_structs::Madctl;
+    use crate::display::tests::{Event::*, Log, logging_device};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn fifo() {
+        let mut ch = command_channel::<2>();
+        let (mut tx, mut rx) = ch.split();
+        for _ in 0..3 {  // Wraps around.
+            assert_eq!(tx.try_send(QueuedCommand::Invon), Ok(()));
+            assert_eq!(tx.try_send(QueuedCommand::Dispon), Ok(()));
+            assert_eq!(tx.try_send(QueuedCommand::Dispoff),
+                       Err(QueuedCommand::Dispoff));
+            assert_eq!(rx.try_recv(), Some(QueuedCommand::Invon));
+            assert_eq!(rx.try_recv(), Some(QueuedCommand::Dispon));
+            assert_eq!(rx.try_recv(), None);
+        }
+    }
+
+    #[test]
+    fn drain() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut ch = command_channel::<4>();
+        let (mut tx, mut rx) = ch.split();
+        tx.try_send(QueuedCommand::Invon).unwrap();
+        tx.try_send(QueuedCommand::Dispon).unwrap();
+        assert_eq!(block_on(rx.drain(&mut commands)), Ok(()));
+        assert_eq!(*log.lock().unwrap(), [Command(0x21), Command(0x29)]);
+        assert_eq!(rx.try_recv(), None);
+    }
+
+    #[test]
+    fn across_threads() {
+        let mut ch = command_channel::<3>();
+        let (mut tx, mut rx) = ch.split();
+        let sent: Vec<_> = (0..=255u8)
+            .map(|b| QueuedCommand::Madctl(Madctl::from(b)))
+            .collect();
+        let received = std::thread::scope(|s| {
+            s.spawn(|| block_on(async {
+                for &command in &sent { tx.send(command).await; }
+            }));
+            s.spawn(|| block_on(async {
+                let mut received = Vec::new();
+                for _ in 0..sent.len() { received.push(rx.recv().await); }
+                received
+            })).join().unwrap()
+        });
+        assert_eq!(received, sent);
+    }
+}  // mod tests
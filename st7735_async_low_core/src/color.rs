@@ -0,0 +1,578 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Typed pixels, i.e., colors already in the wire format of one of the color
+//! modes (see [Colmod](crate::Colmod)).
+
+use core::marker::PhantomData;
+
+use crate::command_structs::Colmod;
+use crate::panel::Rect;
+
+/// A pixel that can be sent to the LCD memory as-is.
+pub trait Pixel: Copy {
+    /// The byte representation of a single pixel.
+    type Bytes: AsRef<[u8]>;
+
+    /// The color mode that the LCD needs to be in to understand [Self::Bytes].
+    const COLMOD: Colmod;
+
+    /// Converts into the bytes to be sent, in the sending order.
+    fn to_bytes(self) -> Self::Bytes;
+}
+
+/// A pixel in the [Colmod::R5G6B5] format.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rgb565 { raw: u16 }
+
+impl Rgb565 {
+    /// Creates a pixel from its components. Only the lowest 5, 6 and 5 bits of
+    /// `r`, `g` and `b` are used, respectively.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self{raw: ((r as u16 & 0x1F) << 11) | ((g as u16 & 0x3F) << 5) |
+                  (b as u16 & 0x1F)}
+    }
+
+    /// Creates a pixel from its 16-bit form, with red at the highest bits.
+    pub const fn from_raw(raw: u16) -> Self { Self{raw} }
+
+    /// The 16-bit form, with red at the highest bits.
+    pub const fn raw(&self) -> u16 { self.raw }
+
+    /// The 5-bit red component.
+    pub const fn r(&self) -> u8 { (self.raw >> 11) as u8 }
+    /// The 6-bit green component.
+    pub const fn g(&self) -> u8 { (self.raw >> 5 & 0x3F) as u8 }
+    /// The 5-bit blue component.
+    pub const fn b(&self) -> u8 { (self.raw & 0x1F) as u8 }
+
+    /// Each component replaced by its maximum value minus itself.
+    pub const fn inverted(&self) -> Self { Self{raw: !self.raw} }
+    /// The red and the blue components exchanged.
+    pub const fn swapped_rb(&self) -> Self {
+        Self::new(self.b(), self.g(), self.r())
+    }
+}
+
+impl Pixel for Rgb565 {
+    type Bytes = [u8; 2];
+    const COLMOD: Colmod = Colmod::R5G6B5;
+
+    fn to_bytes(self) -> [u8; 2] { self.raw.to_be_bytes() }
+}
+
+/// A pixel in the [Colmod::R6G6B6] format, i.e., the native format of the LCD.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rgb666 { r: u8, g: u8, b: u8 }
+
+impl Rgb666 {
+    /// Creates a pixel from its components. Only the lowest 6 bits of each
+    /// component are used.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self{r: r & 0x3F, g: g & 0x3F, b: b & 0x3F}
+    }
+
+    /// The 6-bit red component.
+    pub const fn r(&self) -> u8 { self.r }
+    /// The 6-bit green component.
+    pub const fn g(&self) -> u8 { self.g }
+    /// The 6-bit blue component.
+    pub const fn b(&self) -> u8 { self.b }
+
+    /// Each component replaced by its maximum value minus itself.
+    pub const fn inverted(&self) -> Self {
+        Self::new(!self.r, !self.g, !self.b)
+    }
+    /// The red and the blue components exchanged.
+    pub const fn swapped_rb(&self) -> Self {
+        Self{r: self.b, g: self.g, b: self.r}
+    }
+}
+
+impl Pixel for Rgb666 {
+    type Bytes = [u8; 3];
+    const COLMOD: Colmod = Colmod::R6G6B6;
+
+    /// Each component occupies the highest 6 bits of a byte.
+    fn to_bytes(self) -> [u8; 3] { [self.r << 2, self.g << 2, self.b << 2] }
+}
+
+impl From<Rgb666> for Rgb565 {
+    /// Drops the lowest bit of the red and blue components.
+    fn from(p: Rgb666) -> Self { Self::new(p.r >> 1, p.g, p.b >> 1) }
+}
+
+/// A transform applied to each pixel on the fly, e.g., by
+/// [RamWriter::write_pixels_with()](crate::RamWriter::write_pixels_with).
+pub trait PixelTransform<P: Pixel> {
+    fn transform(&self, pixel: P) -> P;
+}
+
+/// Keeps every pixel as-is.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Identity;
+
+impl<P: Pixel> PixelTransform<P> for Identity {
+    #[inline(always)]
+    fn transform(&self, pixel: P) -> P { pixel }
+}
+
+impl<P: Pixel, F: Fn(P) -> P> PixelTransform<P> for F {
+    fn transform(&self, pixel: P) -> P { self(pixel) }
+}
+
+/// A per-channel lookup table, i.e., each component value `v` is replaced by
+/// the `v`-th entry of the table of its channel.
+///
+/// The sizes are the numbers of values of each component, so the tables live
+/// in the struct and no allocation is needed. Useful for brightness dimming or
+/// white-point correction when the backlight is not controllable.
+///
+/// # Example
+///
+/// ```
+/// # use st7735_async_low_core::color::*;
+/// let half = Rgb565Lut::brightness(128);
+/// assert_eq!(half.transform(Rgb565::new(31, 63, 0)), Rgb565::new(15, 31, 0));
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChannelLut<const R: usize, const G: usize, const B: usize> {
+    r: [u8; R],
+    g: [u8; G],
+    b: [u8; B],
+}
+
+/// The [ChannelLut] for [Rgb565].
+pub type Rgb565Lut = ChannelLut<32, 64, 32>;
+/// The [ChannelLut] for [Rgb666].
+pub type Rgb666Lut = ChannelLut<64, 64, 64>;
+
+impl<const R: usize, const G: usize, const B: usize> ChannelLut<R, G, B> {
+    /// Creates a table with the given entries, which should not exceed the
+    /// maximum value of their channels.
+    pub const fn new(r: [u8; R], g: [u8; G], b: [u8; B]) -> Self {
+        Self{r, g, b}
+    }
+
+    /// Creates a table scaling each component by `level / 255`.
+    pub const fn brightness(level: u8) -> Self {
+        Self{r: scaled_ramp(level), g: scaled_ramp(level),
+             b: scaled_ramp(level)}
+    }
+}
+
+const fn scaled_ramp<const N: usize>(level: u8) -> [u8; N] {
+    let mut table = [0u8; N];
+    let mut i = 0;
+    while i < N {
+        table[i] = (i * level as usize / 255) as u8;
+        i += 1;
+    }
+    table
+}
+
+impl PixelTransform<Rgb565> for Rgb565Lut {
+    fn transform(&self, pixel: Rgb565) -> Rgb565 {
+        Rgb565::new(self.r[pixel.r() as usize], self.g[pixel.g() as usize],
+                    self.b[pixel.b() as usize])
+    }
+}
+
+impl PixelTransform<Rgb666> for Rgb666Lut {
+    fn transform(&self, pixel: Rgb666) -> Rgb666 {
+        Rgb666::new(self.r[pixel.r() as usize], self.g[pixel.g() as usize],
+                    self.b[pixel.b() as usize])
+    }
+}
+
+/// Software fallbacks for clone panels whose subpixels are wired in the BGR
+/// order or whose colors are inverted, regardless of
+/// [Madctl](crate::Madctl) and the inversion mode.
+///
+/// Usually chosen at runtime, e.g., after probing the IDs of the panel.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ColorFixups {
+    /// Whether to invert every pixel.
+    pub invert_colors: bool,
+    /// Whether to exchange the red and the blue components of every pixel.
+    pub swap_rb: bool,
+}
+
+impl ColorFixups {
+    /// Whether all pixels are kept as-is.
+    pub fn is_identity(&self) -> bool { !self.invert_colors && !self.swap_rb }
+}
+
+impl PixelTransform<Rgb565> for ColorFixups {
+    fn transform(&self, mut pixel: Rgb565) -> Rgb565 {
+        if self.invert_colors { pixel = pixel.inverted(); }
+        if self.swap_rb { pixel = pixel.swapped_rb(); }
+        pixel
+    }
+}
+
+impl PixelTransform<Rgb666> for ColorFixups {
+    fn transform(&self, mut pixel: Rgb666) -> Rgb666 {
+        if self.invert_colors { pixel = pixel.inverted(); }
+        if self.swap_rb { pixel = pixel.swapped_rb(); }
+        pixel
+    }
+}
+
+/// Produces the pixel at any position of the visible area, e.g., a frame
+/// buffer or a procedural pattern. See
+/// [Commands::refresh_region()](crate::Commands::refresh_region).
+pub trait PixelSource {
+    type Pixel: Pixel;
+
+    /// The pixel at column `x` and row `y`, without the offsets of the panel.
+    fn pixel_at(&self, x: u16, y: u16) -> Self::Pixel;
+}
+
+impl<P: Pixel, F: Fn(u16, u16) -> P> PixelSource for F {
+    type Pixel = P;
+
+    fn pixel_at(&self, x: u16, y: u16) -> P { self(x, y) }
+}
+
+/// A 24-bit color, as commonly found in image assets. Not a format of the
+/// LCD, so it is converted on the fly; see
+/// [RamWriter::write_rgb888_as()](crate::RamWriter::write_rgb888_as).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rgb888 { r: u8, g: u8, b: u8 }
+
+impl Rgb888 {
+    /// Creates a color from its 8-bit components.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self { Self{r, g, b} }
+
+    pub const fn r(&self) -> u8 { self.r }
+    pub const fn g(&self) -> u8 { self.g }
+    pub const fn b(&self) -> u8 { self.b }
+}
+
+impl From<Rgb888> for Rgb565 {
+    /// Keeps the highest bits of each component.
+    fn from(p: Rgb888) -> Self { Self::new(p.r >> 3, p.g >> 2, p.b >> 3) }
+}
+
+impl From<Rgb888> for Rgb666 {
+    /// Keeps the highest bits of each component.
+    fn from(p: Rgb888) -> Self { Self::new(p.r >> 2, p.g >> 2, p.b >> 2) }
+}
+
+impl From<Rgb888> for Rgb444 {
+    /// Keeps the highest bits of each component.
+    fn from(p: Rgb888) -> Self { Self::new(p.r >> 4, p.g >> 4, p.b >> 4) }
+}
+
+impl DitherSource for Rgb888 {
+    fn to_rgb8(self) -> [u8; 3] { [self.r, self.g, self.b] }
+}
+
+/// A color in the [Colmod::R4G4B4] format.
+///
+/// Not a [Pixel], because two of them share 3 bytes on the wire; see
+/// [RamWriter::write_rgb444()](crate::RamWriter::write_rgb444).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rgb444 { r: u8, g: u8, b: u8 }
+
+impl Rgb444 {
+    /// Creates a color from its components. Only the lowest 4 bits of each
+    /// component are used.
+    pub const fn new(r: u8, g: u8, b: u8) -> Self {
+        Self{r: r & 0xF, g: g & 0xF, b: b & 0xF}
+    }
+
+    /// The 4-bit red component.
+    pub const fn r(&self) -> u8 { self.r }
+    /// The 4-bit green component.
+    pub const fn g(&self) -> u8 { self.g }
+    /// The 4-bit blue component.
+    pub const fn b(&self) -> u8 { self.b }
+}
+
+/// A color that can be reduced by [bayer_rgb444()].
+pub trait DitherSource: Copy {
+    /// The components scaled to 8 bits, in the order R, G and B.
+    fn to_rgb8(self) -> [u8; 3];
+}
+
+// Scales a `bits`-bit value to 8 bits, repeating its highest bits at the
+// bottom so that the maximum maps to 255.
+const fn expand(v: u8, bits: u32) -> u8 {
+    (v << (8 - bits)) | (v >> (2 * bits - 8))
+}
+
+impl DitherSource for Rgb565 {
+    fn to_rgb8(self) -> [u8; 3] {
+        [expand(self.r(), 5), expand(self.g(), 6), expand(self.b(), 5)]
+    }
+}
+
+impl DitherSource for Rgb666 {
+    fn to_rgb8(self) -> [u8; 3] {
+        [expand(self.r, 6), expand(self.g, 6), expand(self.b, 6)]
+    }
+}
+
+const BAYER_4X4: [[u8; 4]; 4] = [
+    [0, 8, 2, 10],
+    [12, 4, 14, 6],
+    [3, 11, 1, 9],
+    [15, 7, 13, 5],
+];
+
+/// Reduces `pixels`, covering `rect` row by row, to [Rgb444] with ordered
+/// dithering, i.e., each component is rounded up or down depending on its
+/// value and the 4x4 Bayer matrix entry at its position. Keyed by the
+/// position, the pattern stays aligned across separately drawn rectangles.
+pub fn bayer_rgb444<I>(pixels: I, rect: Rect) -> BayerRgb444<I::IntoIter>
+        where I: IntoIterator, I::Item: DitherSource {
+    BayerRgb444{pixels: pixels.into_iter(), rect, index: 0}
+}
+
+/// The iterator returned by [bayer_rgb444()].
+#[derive(Clone, Debug)]
+pub struct BayerRgb444<I> {
+    pixels: I,
+    rect: Rect,
+    index: usize,
+}
+
+impl<I> Iterator for BayerRgb444<I> where I: Iterator, I::Item: DitherSource {
+    type Item = Rgb444;
+
+    fn next(&mut self) -> Option<Rgb444> {
+        let rgb = self.pixels.next()?.to_rgb8();
+        let width = self.rect.width.max(1) as usize;
+        let x = self.rect.x as usize + self.index % width;
+        let y = self.rect.y as usize + self.index / width;
+        self.index += 1;
+        // Uniformly inside (0, 255), so that the rounding is unbiased.
+        let threshold = (BAYER_4X4[y % 4][x % 4] as u16 * 2 + 1) * 255 / 32;
+        let [r, g, b] = rgb.map(|v| ((v as u16 * 15 + threshold) / 255) as u8);
+        Some(Rgb444::new(r, g, b))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.pixels.size_hint() }
+}
+
+/// Whether to dither when reducing colors to fewer bits.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Dithering {
+    /// Each value is rounded to the nearest level on its own.
+    #[default]
+    Off,
+    /// The rounding error of each pixel is carried over to the next one, so
+    /// that gradients don't show bands.
+    ErrorDiffusion,
+}
+
+/// A [Pixel] made of gray levels. See [gray8_to_rgb565()].
+pub trait FromGray8: Pixel {
+    /// The bits of the red and blue components.
+    const RB_BITS: u32;
+    /// The bits of the green component.
+    const G_BITS: u32;
+
+    /// Creates a gray pixel from the levels of its components.
+    fn from_levels(rb: u8, g: u8) -> Self;
+}
+
+impl FromGray8 for Rgb565 {
+    const RB_BITS: u32 = 5;
+    const G_BITS: u32 = 6;
+
+    fn from_levels(rb: u8, g: u8) -> Self { Self::new(rb, g, rb) }
+}
+
+impl FromGray8 for Rgb666 {
+    const RB_BITS: u32 = 6;
+    const G_BITS: u32 = 6;
+
+    fn from_levels(rb: u8, g: u8) -> Self { Self::new(rb, g, rb) }
+}
+
+/// Converts 8-bit gray levels into [Rgb565] pixels, lazily.
+pub fn gray8_to_rgb565<I>(gray: I, dithering: Dithering)
+        -> Gray8Pixels<I::IntoIter, Rgb565>
+        where I: IntoIterator<Item=u8> {
+    Gray8Pixels::new(gray.into_iter(), dithering)
+}
+
+/// Converts 8-bit gray levels into [Rgb666] pixels, lazily.
+pub fn gray8_to_rgb666<I>(gray: I, dithering: Dithering)
+        -> Gray8Pixels<I::IntoIter, Rgb666>
+        where I: IntoIterator<Item=u8> {
+    Gray8Pixels::new(gray.into_iter(), dithering)
+}
+
+/// The iterator returned by [gray8_to_rgb565()] and [gray8_to_rgb666()].
+#[derive(Clone, Debug)]
+pub struct Gray8Pixels<I, P> {
+    gray: I,
+    dithering: Dithering,
+    // The errors carried over, of the red/blue and of the green components.
+    errors: [i16; 2],
+    _p: PhantomData<P>,
+}
+
+impl<I, P> Gray8Pixels<I, P> {
+    fn new(gray: I, dithering: Dithering) -> Self {
+        Self{gray, dithering, errors: [0; 2], _p: PhantomData}
+    }
+}
+
+// Rounds `value` to the nearest of the levels of `bits` bits, and returns
+// the level and the error.
+fn quantize(value: i16, bits: u32) -> (u8, i16) {
+    let max = (1 << bits) - 1;
+    let value = value.clamp(0, 255);
+    let level = (value * max + 127) / 255;
+    (level as u8, value - level * 255 / max)
+}
+
+impl<I: Iterator<Item=u8>, P: FromGray8> Iterator for Gray8Pixels<I, P> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        let gray = self.gray.next()? as i16;
+        let (rb, rb_error) = quantize(gray + self.errors[0], P::RB_BITS);
+        let (g, g_error) = quantize(gray + self.errors[1], P::G_BITS);
+        if self.dithering == Dithering::ErrorDiffusion {
+            self.errors = [rb_error, g_error];
+        }
+        Some(P::from_levels(rb, g))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) { self.gray.size_hint() }
+}
+
+impl<I, P> ExactSizeIterator for Gray8Pixels<I, P>
+        where I: ExactSizeIterator<Item=u8>, P: FromGray8 {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rgb565_components() {
+        let p = Rgb565::new(0x15, 0x2A, 0x0F);
+        assert_eq!(p.r(), 0x15);
+        assert_eq!(p.g(), 0x2A);
+        assert_eq!(p.b(), 0x0F);
+        assert_eq!(p.raw(), 0b1010_1101_0100_1111);
+        assert_eq!(p.to_bytes(), [0b10101101, 0b01001111]);
+    }
+
+    #[test]
+    fn rgb565_truncates() {
+        assert_eq!(Rgb565::new(0xFF, 0xFF, 0xFF), Rgb565::from_raw(0xFFFF));
+    }
+
+    #[test]
+    fn rgb666_bytes() {
+        let p = Rgb666::new(0x3F, 0x01, 0x42);
+        assert_eq!(p.b(), 0x02);
+        assert_eq!(p.to_bytes(), [0xFC, 0x04, 0x08]);
+    }
+
+    #[test]
+    fn lut_full_brightness_is_identity() {
+        let lut = Rgb666Lut::brightness(255);
+        let p = Rgb666::new(0x3F, 0x15, 0x01);
+        assert_eq!(lut.transform(p), p);
+    }
+
+    #[test]
+    fn lut_zero_brightness_is_black() {
+        let lut = Rgb565Lut::brightness(0);
+        assert_eq!(lut.transform(Rgb565::from_raw(0xFFFF)), Rgb565::default());
+    }
+
+    #[test]
+    fn lut_custom() {
+        let mut r = [0; 32];
+        r[3] = 7;
+        let lut = Rgb565Lut::new(r, [1; 64], [2; 32]);
+        assert_eq!(lut.transform(Rgb565::new(3, 9, 9)), Rgb565::new(7, 1, 2));
+    }
+
+    #[test]
+    fn color_fixups() {
+        let p = Rgb565::new(1, 2, 3);
+        let mut fixups = ColorFixups::default();
+        assert!(fixups.is_identity());
+        assert_eq!(fixups.transform(p), p);
+        fixups.swap_rb = true;
+        assert_eq!(fixups.transform(p), Rgb565::new(3, 2, 1));
+        fixups.invert_colors = true;
+        assert_eq!(fixups.transform(p), Rgb565::new(28, 61, 30));
+        let q = Rgb666::new(1, 2, 3);
+        assert_eq!(fixups.transform(q), Rgb666::new(60, 61, 62));
+    }
+
+    #[test]
+    fn closure_transform() {
+        let swap = |p: Rgb666| Rgb666::new(p.b(), p.g(), p.r());
+        assert_eq!(swap.transform(Rgb666::new(1, 2, 3)), Rgb666::new(3, 2, 1));
+    }
+
+    #[test]
+    fn gray8_without_dithering() {
+        let pixels: std::vec::Vec<_> =
+            gray8_to_rgb565([0, 128, 255], Dithering::Off).collect();
+        assert_eq!(pixels, [Rgb565::new(0, 0, 0), Rgb565::new(16, 32, 16),
+                            Rgb565::new(31, 63, 31)]);
+        assert_eq!(gray8_to_rgb666([255], Dithering::Off).next(),
+                   Some(Rgb666::new(63, 63, 63)));
+    }
+
+    #[test]
+    fn gray8_with_dithering() {
+        // 4 is about half of the step between two levels of 5 bits.
+        let plain = gray8_to_rgb565([4; 100], Dithering::Off);
+        assert_eq!(plain.map(|p| p.r() as u32).sum::<u32>(), 0);
+        let dithered = gray8_to_rgb565([4; 100], Dithering::ErrorDiffusion);
+        assert_eq!(dithered.len(), 100);
+        let sum = dithered.map(|p| p.r() as u32).sum::<u32>();
+        assert!((48..=50).contains(&sum), "{}", sum);
+    }
+
+    #[test]
+    fn bayer_extremes() {
+        let pixels = [Rgb565::new(0, 0, 0), Rgb565::new(31, 63, 31)];
+        let reduced: std::vec::Vec<_> =
+            bayer_rgb444(pixels, Rect::new(5, 7, 2, 1)).collect();
+        assert_eq!(reduced, [Rgb444::new(0, 0, 0), Rgb444::new(15, 15, 15)]);
+    }
+
+    #[test]
+    fn bayer_averages() {
+        // Halfway between two levels: half of a 4x4 block rounds up.
+        let half = Rgb666::new(0x2, 0x2, 0x2);
+        assert_eq!(half.to_rgb8(), [8, 8, 8]);
+        let reduced = bayer_rgb444([half; 16], Rect::new(0, 0, 4, 4));
+        let ups = reduced.filter(|p| p.r() == 1).count();
+        assert_eq!(ups, 8);
+    }
+
+    #[test]
+    fn rgb888_conversions() {
+        let p = Rgb888::new(0xFF, 0x84, 0x0F);
+        assert_eq!(Rgb565::from(p), Rgb565::new(0x1F, 0x21, 0x01));
+        assert_eq!(Rgb666::from(p), Rgb666::new(0x3F, 0x21, 0x03));
+        assert_eq!(Rgb444::from(p), Rgb444::new(0xF, 0x8, 0x0));
+    }
+}  // mod tests
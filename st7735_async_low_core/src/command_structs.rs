@@ -0,0 +1,646 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use paste::paste;
+
+use crate::panel::{Rect, WindowError};
+
+macro_rules! define_pub_bit_type {
+    ($name:ident, zero: $zero_value:ident, one: $one_value:ident,
+                  doc: $doc:literal) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+        pub enum $name {
+            $zero_value = 0,
+            $one_value = 1,
+        }
+        impl $name {
+            fn from_bool(b: bool) -> Self {  // Private.
+                if b { Self::$one_value } else { Self::$zero_value }
+            }
+            fn to_bool(self) -> bool {  // Private.
+                match self {
+                    Self::$zero_value => false,
+                    Self::$one_value => true,
+                }
+            }
+        }
+        impl Default for $name {
+            fn default() -> Self { Self::$zero_value }
+        }
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter)
+                    -> ::core::fmt::Result {
+                <Self as core::fmt::Debug>::fmt(self, f)
+            }
+        }
+    };
+}
+macro_rules! bit_field {
+    ($name:ident, type: $type:ty, bit_offset: $i:expr) => {
+        pub fn $name(&self) -> $type {
+            <$type>::from_bool((self.data >> $i) & 1 == 1)
+        }
+        paste! {
+            pub fn [<set_ $name>](&mut self, value: $type) -> &mut Self {
+                if value.to_bool() {
+                    self.data |= 1 << $i;
+                } else {
+                    self.data &= !(1 << $i);
+                }
+                self
+            }
+        }
+    }
+}
+
+/// Defines the orientation parameters of the screen.
+///
+/// # Example
+///
+/// ```
+/// # use st7735_async_low_core::*;
+/// let mut mctl = Madctl::default();
+/// mctl.set_row_address_order(RowOrder::TopToBottom)
+///     .set_column_address_order(ColumnOrder::LeftToRight)
+///     .set_row_column_swap(RowColumnSwap::Swapped)
+///     .set_vertical_refresh_order(RowOrder::BottomToTop)
+///     .set_horizontal_refresh_order(ColumnOrder::RightToLeft)
+///     .set_rgb_order(ColorComponentOrder::BlueGreenRed);
+/// assert_eq!(mctl.row_address_order(), RowOrder::TopToBottom);
+/// assert_eq!(mctl.row_column_swap(), RowColumnSwap::Swapped);
+/// // Can invoke `Commands::madctl(mctl)` to send it to the LCD.
+/// ```
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Madctl {
+    data: u8,
+}
+impl Madctl {
+    bit_field!(row_address_order, type: RowOrder, bit_offset: 7);
+    bit_field!(column_address_order, type: ColumnOrder, bit_offset: 6);
+    bit_field!(row_column_swap, type: RowColumnSwap, bit_offset: 5);
+    bit_field!(vertical_refresh_order, type: RowOrder, bit_offset: 4);
+    bit_field!(horizontal_refresh_order, type: ColumnOrder, bit_offset: 2);
+    bit_field!(rgb_order, type: ColorComponentOrder, bit_offset: 3);
+}
+impl From<Madctl> for u8 {
+    fn from(mctl: Madctl) -> u8 { mctl.data }
+}
+impl From<u8> for Madctl {
+    fn from(data: u8) -> Self { Self{data} }
+}
+impl ::core::fmt::Display for Madctl {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+define_pub_bit_type!(RowOrder, zero: TopToBottom, one: BottomToTop,
+                     doc: "The row order of the LCD pixels.");
+
+define_pub_bit_type!(ColumnOrder, zero: LeftToRight, one: RightToLeft,
+                     doc: "The column order of the LCD pixels.");
+define_pub_bit_type!(RowColumnSwap, zero: Unswapped, one: Swapped,
+                     doc: "Whether to swap the row and column definitions, \
+                     i.e., to switch between the portrait and landscape mode.");
+define_pub_bit_type!(ColorComponentOrder, zero: RedGreenBlue, one: BlueGreenRed,
+                     doc: "R/G/B component order inside a pixel.");
+
+/// Color mode (the bit widths of the R, G and B components of a pixel).
+///
+/// The native format is 6-bit for each component. When another (smaller) mode
+/// is used, the LCD will internally translate each component into the 6-bit
+/// format with a lookup table. See Sec 9.18 "Color Depth Conversion Look Up
+/// Tables" of the ST7735S datasheet for the lookup table (LUT).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Colmod {
+    /// Each component has 4 bits. LUT will be used.
+    R4G4B4 = 0b011,
+    /// Red has 5 bits; green has 6 bits; blue has 5 bits. LUT will be used.
+    R5G6B5 = 0b101,
+    /// Each compoment has 6 bits. This is the native format; LUT will **not**
+    /// be used.
+    R6G6B6 = 0b110,
+    /// No idea when this value can be used.
+    #[default]
+    Unknown = 0b111,
+}
+impl From<Colmod> for u8 {
+    fn from(colmod: Colmod) -> u8 { colmod as u8 }
+}
+impl From<u8> for Colmod {
+    fn from(raw: u8) -> Self {
+        use Colmod::*;
+        const R4G4B4_VALUE: u8 = R4G4B4 as u8;
+        const R5G6B5_VALUE: u8 = R5G6B5 as u8;
+        const R6G6B6_VALUE: u8 = R6G6B6 as u8;
+        match raw {
+            R4G4B4_VALUE => R4G4B4,
+            R5G6B5_VALUE => R5G6B5,
+            R6G6B6_VALUE => R6G6B6,
+            _ => Unknown,
+        }
+    }
+}
+impl ::core::fmt::Display for Colmod {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// The rows shown in the partial mode, from `start` to `end`, both inclusive.
+/// The other rows show the background color.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PartialArea {
+    pub start: u16,
+    pub end: u16,
+}
+
+/// Whether the idle mode (8 colors) is used.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum IdleMode {
+    /// The full color mode.
+    #[default]
+    FullColor,
+    /// The 8-color mode, i.e., only the highest bit of each component is used.
+    Idle,
+}
+impl ::core::fmt::Display for IdleMode {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// Which blanking periods the tear effect line reports, set by TEON.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum TeMode {
+    /// Only the vertical blanking, as awaited by
+    /// [TePin](crate::te::TePin).
+    #[default]
+    VBlank = 0,
+    /// Both the vertical and the horizontal blanking.
+    VBlankAndHBlank = 1,
+}
+impl From<TeMode> for u8 {
+    fn from(mode: TeMode) -> u8 { mode as u8 }
+}
+impl ::core::fmt::Display for TeMode {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// How the `u16`s of
+/// [RamWriter::write_u16_slice()](crate::RamWriter::write_u16_slice) map to
+/// the two bytes of their RGB565 pixels, which the LCD takes high byte first.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum Endianness {
+    /// Each `u16` is the pixel, with red at the highest bits.
+    #[default]
+    Big,
+    /// The bytes of each `u16` are swapped, e.g., as read from bytes already
+    /// in the bus order on a little-endian MCU.
+    Little,
+}
+impl ::core::fmt::Display for Endianness {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// One of the predefined gamma curves, selected by GAMSET.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum GammaCurve {
+    /// Gamma 1.0 on the ST7735S (2.2 on some other variants).
+    #[default]
+    Curve1 = 0b0001,
+    Curve2 = 0b0010,
+    Curve3 = 0b0100,
+    Curve4 = 0b1000,
+}
+impl From<GammaCurve> for u8 {
+    fn from(curve: GammaCurve) -> u8 { curve as u8 }
+}
+impl ::core::fmt::Display for GammaCurve {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// How the gamma correction is configured.
+///
+/// The custom correction (GMCTRP1/GMCTRN1) is applied on top of the curve
+/// selected by GAMSET, so the two are exclusive: a custom correction always
+/// goes with [GammaCurve::Curve1].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum GammaConfig {
+    /// One of the predefined curves.
+    Predefined(GammaCurve),
+    /// The 16 parameters of each of GMCTRP1 (positive polarity) and GMCTRN1
+    /// (negative polarity).
+    Custom { positive: [u8; 16], negative: [u8; 16] },
+}
+impl Default for GammaConfig {
+    fn default() -> Self { Self::Predefined(GammaCurve::default()) }
+}
+impl GammaConfig {
+    /// Whether a custom correction fits the registers: every parameter has 6
+    /// bits. Predefined curves are always valid.
+    pub const fn is_valid(&self) -> bool {
+        match self {
+            Self::Predefined(_) => true,
+            Self::Custom{positive, negative} => {
+                let mut i = 0;
+                while i < 16 {
+                    if positive[i] > 0x3F || negative[i] > 0x3F {
+                        return false;
+                    }
+                    i += 1;
+                }
+                true
+            },
+        }
+    }
+
+    /// A dimmed variant of a custom correction, `255` being unchanged, for
+    /// panels without a controllable backlight. The voltages of the white
+    /// level and of the gray levels (the first 12 parameters of each
+    /// polarity) are scaled linearly; how each step looks depends on the
+    /// panel. Predefined curves are returned unchanged.
+    pub fn scaled(self, brightness: u8) -> Self {
+        let scale = |params: [u8; 16]| {
+            let mut scaled = params;
+            for p in &mut scaled[..12] {
+                *p = (*p as u16 * brightness as u16 / 255) as u8;
+            }
+            scaled
+        };
+        match self {
+            Self::Predefined(_) => self,
+            Self::Custom{positive, negative} => Self::Custom{
+                positive: scale(positive), negative: scale(negative)},
+        }
+    }
+}
+
+/// The frame rate of the normal mode, set by FRMCTR1 (B1h):
+/// `850kHz / ((rtna + 20) * (lines + front_porch + back_porch))`.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct FrameRate {
+    /// 4 bits.
+    pub rtna: u8,
+    /// 6 bits.
+    pub front_porch: u8,
+    /// 6 bits.
+    pub back_porch: u8,
+}
+impl Default for FrameRate {
+    /// The reset values.
+    fn default() -> Self {
+        Self{rtna: 0x01, front_porch: 0x2C, back_porch: 0x2D}
+    }
+}
+
+/// The raw parameters of the power control commands, whose meanings vary
+/// among the ST7735 variants; see the datasheet of the panel.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PowerProfile {
+    /// PWCTR1 (C0h).
+    pub pwctr1: [u8; 3],
+    /// PWCTR2 (C1h).
+    pub pwctr2: u8,
+    /// PWCTR3 (C2h), for the normal mode.
+    pub pwctr3: [u8; 2],
+    /// PWCTR4 (C3h), for the idle mode.
+    pub pwctr4: [u8; 2],
+    /// PWCTR5 (C4h), for the partial mode.
+    pub pwctr5: [u8; 2],
+    /// VMCTR1 (C5h).
+    pub vmctr1: u8,
+}
+
+/// The vertical scrolling: the scroll area set by SCRLAR (33h) and the
+/// first line of it shown, set by VSCSAD (37h).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct ScrollConfig {
+    pub top_fixed: u16,
+    pub scroll_area: u16,
+    pub bottom_fixed: u16,
+    pub start: u16,
+}
+
+/// Everything [Commands::apply_config()](crate::Commands::apply_config)
+/// configures, to re-initialize a panel identically, e.g., after a power
+/// glitch or a deep sleep. The parts set to `None` are left as they are.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct PanelConfig {
+    pub madctl: Madctl,
+    /// Never [Colmod::Unknown], which is reserved.
+    pub colmod: Option<Colmod>,
+    pub gamma: GammaConfig,
+    pub frame_rate: Option<FrameRate>,
+    pub power: Option<PowerProfile>,
+    /// The address window, in the coordinates of the LCD RAM, i.e., with the
+    /// offsets of the [Panel](crate::panel::Panel) included.
+    pub window: Option<Rect>,
+    pub scroll: Option<ScrollConfig>,
+}
+
+/// Why writing the memory is refused, see
+/// [Commands::check_ram_access()](crate::Commands::check_ram_access).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum OrderingError {
+    /// In the sleep mode, where some panels silently drop the writes.
+    Asleep,
+    /// SLPOUT was issued, but the delay it mandates wasn't confirmed with
+    /// [Commands::mark_awake()](crate::Commands::mark_awake).
+    WakingUp,
+}
+impl ::core::fmt::Display for OrderingError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// Why [Commands::push_window()](crate::Commands::push_window) or
+/// [Commands::pop_window()](crate::Commands::pop_window) sent nothing.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowStackError {
+    /// Already [WINDOW_STACK_DEPTH](crate::WINDOW_STACK_DEPTH) windows are
+    /// pushed.
+    Full,
+    /// No window is pushed.
+    Empty,
+    /// The current window isn't known, so it couldn't be restored: neither
+    /// CASET nor RASET was issued since the creation, a reset or a command
+    /// table.
+    Unknown,
+    /// The window pushed is empty or reaches past `u16::MAX`, see
+    /// [Rect::last()](crate::panel::Rect::last).
+    Invalid(WindowError),
+}
+impl ::core::fmt::Display for WindowStackError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// Which command(s) provided the IDs returned by
+/// [Commands::read_ids_robust()](crate::Commands::read_ids_robust).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum IdSource {
+    /// The single RDDID (04h).
+    Rddid,
+    /// RDID1 (DAh), RDID2 (DBh) and RDID3 (DCh).
+    Rdid123,
+}
+impl ::core::fmt::Display for IdSource {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// A parameter of
+/// [Commands::command_with_args()](crate::Commands::command_with_args).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Param {
+    U8(u8),
+    /// Sent as two bytes, the most significant first.
+    U16(u16),
+}
+impl From<u8> for Param {
+    fn from(v: u8) -> Self { Self::U8(v) }
+}
+impl From<u16> for Param {
+    fn from(v: u16) -> Self { Self::U16(v) }
+}
+
+macro_rules! define_pub_flags {
+    ($name:ident, doc: $doc:literal,
+     $($(#[doc = $flag_doc:literal])* $flag:ident = $bit:expr),+ $(,)?) => {
+        #[doc = $doc]
+        #[derive(Clone, Copy, Default, Eq, Hash, PartialEq)]
+        pub struct $name { bits: u8 }
+        impl $name {
+            $(
+                $(#[doc = $flag_doc])*
+                pub const $flag: Self = Self{bits: 1 << $bit};
+            )+
+            /// No flag set.
+            pub const fn empty() -> Self { Self{bits: 0} }
+            /// The raw register value.
+            pub const fn bits(&self) -> u8 { self.bits }
+            /// Whether all flags of `other` are set.
+            pub const fn contains(&self, other: Self) -> bool {
+                self.bits & other.bits == other.bits
+            }
+            pub fn insert(&mut self, other: Self) { self.bits |= other.bits; }
+            pub fn remove(&mut self, other: Self) { self.bits &= !other.bits; }
+        }
+        impl From<u8> for $name {
+            /// Keeps all bits, including those without a named flag.
+            fn from(bits: u8) -> Self { Self{bits} }
+        }
+        impl From<$name> for u8 {
+            fn from(flags: $name) -> u8 { flags.bits }
+        }
+        impl ::core::ops::BitOr for $name {
+            type Output = Self;
+            fn bitor(self, other: Self) -> Self {
+                Self{bits: self.bits | other.bits}
+            }
+        }
+        impl ::core::fmt::Debug for $name {
+            /// Lists the set flags, e.g., `PowerMode(SLEEP_OUT | DISPLAY_ON)`.
+            fn fmt(&self, f: &mut ::core::fmt::Formatter)
+                    -> ::core::fmt::Result {
+                write!(f, "{}(", stringify!($name))?;
+                let mut rest = self.bits;
+                let mut first = true;
+                $(
+                    if self.contains(Self::$flag) {
+                        if !first { f.write_str(" | ")?; }
+                        f.write_str(stringify!($flag))?;
+                        rest &= !Self::$flag.bits;
+                        first = false;
+                    }
+                )+
+                if rest != 0 {
+                    if !first { f.write_str(" | ")?; }
+                    write!(f, "{:#04X}", rest)?;
+                }
+                f.write_str(")")
+            }
+        }
+        impl ::core::fmt::Display for $name {
+            fn fmt(&self, f: &mut ::core::fmt::Formatter)
+                    -> ::core::fmt::Result {
+                <Self as core::fmt::Debug>::fmt(self, f)
+            }
+        }
+    };
+}
+
+define_pub_flags!(PowerMode, doc: "The power mode, as read by \
+                  [Commands::rddpm()](crate::Commands::rddpm).",
+    /// The booster voltage is on.
+    BOOSTER_ON = 7,
+    /// The idle mode is on.
+    IDLE_ON = 6,
+    /// The partial mode is on.
+    PARTIAL_ON = 5,
+    /// Out of the sleep mode.
+    SLEEP_OUT = 4,
+    /// The normal mode is on.
+    NORMAL_ON = 3,
+    /// The display is on.
+    DISPLAY_ON = 2,
+);
+
+define_pub_flags!(ImageMode, doc: "The image mode, as read by \
+                  [Commands::rddim()](crate::Commands::rddim). The lowest 3 \
+                  bits select the gamma curve.",
+    /// The vertical scrolling is on.
+    VERTICAL_SCROLL_ON = 7,
+    /// The inversion is on.
+    INVERSION_ON = 5,
+);
+
+define_pub_flags!(PanelState, doc: "The modes of the panel as set by the \
+                  commands issued through [Commands](crate::Commands), see \
+                  [Commands::state()](crate::Commands::state). Empty after a \
+                  reset. The bits of the flags shared with [PowerMode] are \
+                  the same.",
+    /// IDMON was issued after IDMOFF.
+    IDLE_ON = 6,
+    /// PTLON was issued after NORON.
+    PARTIAL_ON = 5,
+    /// SLPOUT was issued after SLPIN.
+    SLEEP_OUT = 4,
+    /// DISPON was issued after DISPOFF.
+    DISPLAY_ON = 2,
+    /// INVON was issued after INVOFF.
+    INVERSION_ON = 0,
+);
+
+define_pub_flags!(Flip, doc: "How to mirror an image while it is streamed, \
+                  see [Display::blit_flipped()]\
+                  (crate::display::Display::blit_flipped).",
+    /// Reverses the columns, i.e., mirrors left and right.
+    HORIZONTAL = 0,
+    /// Reverses the rows, e.g., for an image stored bottom-up.
+    VERTICAL = 1,
+);
+
+impl PanelState {
+    /// Updates the state after `cmd` is issued.
+    pub fn apply(&mut self, cmd: u8) {
+        match cmd {
+            0x01 => *self = Self::empty(),  // SWRESET.
+            0x10 => self.remove(Self::SLEEP_OUT),
+            0x11 => self.insert(Self::SLEEP_OUT),
+            0x12 => self.insert(Self::PARTIAL_ON),
+            0x13 => self.remove(Self::PARTIAL_ON),
+            0x20 => self.remove(Self::INVERSION_ON),
+            0x21 => self.insert(Self::INVERSION_ON),
+            0x28 => self.remove(Self::DISPLAY_ON),
+            0x29 => self.insert(Self::DISPLAY_ON),
+            0x38 => self.remove(Self::IDLE_ON),
+            0x39 => self.insert(Self::IDLE_ON),
+            _ => {},
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::format;
+    use super::*;
+
+    #[test]
+    fn gamma_validity() {
+        assert_eq!(GammaConfig::default(),
+                   GammaConfig::Predefined(GammaCurve::Curve1));
+        assert!(GammaConfig::Predefined(GammaCurve::Curve4).is_valid());
+        let mut positive = [0x3F; 16];
+        let negative = [0; 16];
+        assert!(GammaConfig::Custom{positive, negative}.is_valid());
+        positive[15] = 0x40;
+        assert!(!GammaConfig::Custom{positive, negative}.is_valid());
+    }
+
+    #[test]
+    fn scaled_gamma() {
+        let positive = [0x3F; 16];
+        let negative = [0x20; 16];
+        let custom = GammaConfig::Custom{positive, negative};
+        assert_eq!(custom.scaled(255), custom);
+        let mut half = ([0x1F; 16], [0x10; 16]);
+        half.0[12..].copy_from_slice(&[0x3F; 4]);
+        half.1[12..].copy_from_slice(&[0x20; 4]);
+        assert_eq!(custom.scaled(128),
+                   GammaConfig::Custom{positive: half.0, negative: half.1});
+        let predefined = GammaConfig::Predefined(GammaCurve::Curve3);
+        assert_eq!(predefined.scaled(0), predefined);
+    }
+
+    #[test]
+    fn panel_state() {
+        let mut state = PanelState::empty();
+        for cmd in [0x11, 0x29, 0x21, 0x12, 0x39, 0x2C] {
+            state.apply(cmd);
+        }
+        assert_eq!(format!("{:?}", state), "PanelState(IDLE_ON | PARTIAL_ON \
+                   | SLEEP_OUT | DISPLAY_ON | INVERSION_ON)");
+        for cmd in [0x10, 0x28, 0x20, 0x13, 0x38] {
+            state.apply(cmd);
+        }
+        assert_eq!(state, PanelState::empty());
+        state.apply(0x11);
+        state.apply(0x01);
+        assert_eq!(state, PanelState::empty());
+    }
+
+    #[test]
+    fn flags() {
+        let pm = PowerMode::from(0b1001_1100);
+        assert!(pm.contains(PowerMode::SLEEP_OUT));
+        assert!(pm.contains(PowerMode::NORMAL_ON | PowerMode::DISPLAY_ON));
+        assert!(!pm.contains(PowerMode::IDLE_ON | PowerMode::SLEEP_OUT));
+        assert_eq!(u8::from(pm), 0b1001_1100);
+
+        let mut pm = PowerMode::empty();
+        pm.insert(PowerMode::SLEEP_OUT | PowerMode::DISPLAY_ON);
+        pm.remove(PowerMode::SLEEP_OUT);
+        assert_eq!(pm, PowerMode::DISPLAY_ON);
+    }
+
+    #[test]
+    fn debug() {
+        assert_eq!(format!("{:?}", PowerMode::from(0b1001_0100)),
+                   "PowerMode(BOOSTER_ON | SLEEP_OUT | DISPLAY_ON)");
+        assert_eq!(format!("{:?}", ImageMode::from(0x21)),
+                   "ImageMode(INVERSION_ON | 0x01)");
+        assert_eq!(format!("{}", ImageMode::empty()), "ImageMode()");
+    }
+}  // mod tests
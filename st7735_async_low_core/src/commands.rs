@@ -0,0 +1,2315 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+use core::future::Future;
+use core::marker::PhantomPinned;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::color::{
+    Identity, Pixel, PixelSource, PixelTransform, Rgb444, Rgb565, Rgb666,
+    Rgb888};
+use crate::command_structs::*;
+use crate::controller::ControllerProfile;
+use crate::panel::{
+    ConstWindow, Panel, Rect, WindowError};
+use crate::spi::{
+    DcxPin, Read, ReadBits as _, ReadBytes as _, WriteRepeat, WriteU16s,
+    WriteU8, WriteU8s, WriteU8sStatic};
+use crate::stream::{self, PixelStream};
+use crate::task::yield_now;
+use crate::te::TePin;
+
+/// How many windows [Commands::push_window()] can save.
+pub const WINDOW_STACK_DEPTH: usize = 4;
+
+/// The size of the batches of [Commands::command_with_args()].
+const ARGS_BATCH: usize = 16;
+
+/// The size of the chunks of [RamWriter::write_stream()].
+const STREAM_CHUNK: usize = 64;
+
+/// How many words [RamWriter::write_u16_slice()] swaps at a time.
+const SWAP_CHUNK: usize = 32;
+/// How many bytes of repeated pixels [Commands::fill()] writes at a time, a
+/// multiple of both 2 and 3 bytes per pixel.
+const FILL_CHUNK: usize = 48;
+
+/// Commands of ST7735 in their original form, except that the parameters
+/// of each command are typed.
+///
+/// It tracks the modes set by the commands issued, see [state()](Self::state).
+#[derive(Debug)]
+pub struct Commands<S> {
+    spi: S,
+    state: PanelState,
+    // SLPOUT was issued but `mark_awake()` wasn't called since.
+    waking_up: bool,
+    strict_ordering: bool,
+    // The last CASET and RASET, both inclusive; `None` if unknown.
+    columns: Option<(u16, u16)>,
+    rows: Option<(u16, u16)>,
+    windows: [Rect; WINDOW_STACK_DEPTH],
+    num_windows: usize,
+    profile: ControllerProfile,
+    #[cfg(feature = "dedup")]
+    sent: SentRegisters,
+}
+
+impl<S: DcxPin> Commands<S> {
+    /// Creates a new instance with an spi object. The panel is assumed to be
+    /// freshly reset.
+    pub fn new(mut spi: S) -> Self {
+        spi.set_dcx_command_mode();
+        Self{spi, state: PanelState::empty(), waking_up: false,
+             strict_ordering: false, columns: None, rows: None,
+             windows: Default::default(), num_windows: 0,
+             profile: ControllerProfile::default(),
+             #[cfg(feature = "dedup")]
+             sent: SentRegisters::default()}
+    }
+}
+
+impl<S> Commands<S> {
+    /// Drives a sibling controller of ST7735S, e.g.,
+    /// [ControllerProfile::ST7789].
+    pub fn set_profile(&mut self, profile: ControllerProfile) {
+        self.profile = profile;
+    }
+
+    /// The controller driven, as set by [set_profile()](Self::set_profile).
+    /// [ControllerProfile::ST7735S] unless set.
+    pub fn profile(&self) -> &ControllerProfile { &self.profile }
+
+    /// Takes back the spi object, e.g., a [Recorder](crate::record::Recorder)
+    /// to get its recording.
+    pub fn into_inner(self) -> S { self.spi }
+
+    /// The modes of the panel as set by the commands issued so far, e.g.,
+    /// for assertions in tests or for helpers to pick a strategy. Commands
+    /// are accounted for when issued, i.e., when their futures are created.
+    pub fn state(&self) -> PanelState { self.state }
+
+    /// Forgets the tracked modes, e.g., after a hardware reset. So are the
+    /// address window and the windows [pushed](Self::push_window).
+    pub fn reset_state(&mut self) {
+        self.state = PanelState::empty();
+        self.waking_up = false;
+        self.forget_window();
+        self.num_windows = 0;
+        #[cfg(feature = "dedup")]
+        self.invalidate();
+    }
+
+    /// The address window set by the last CASET and RASET, in the memory
+    /// coordinates, i.e., with the offsets of the [Panel]. `None` if not
+    /// known, see [WindowStackError::Unknown].
+    pub fn window(&self) -> Option<Rect> {
+        let ((x0, x1), (y0, y1)) = (self.columns?, self.rows?);
+        if x1 < x0 || y1 < y0 { return None; }
+        Some(Rect::new(x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+    }
+
+    fn forget_window(&mut self) {
+        self.columns = None;
+        self.rows = None;
+    }
+
+    /// Tells that the delay mandated after SLPOUT (120ms) has elapsed.
+    /// [Display](crate::display::Display) does so by itself.
+    pub fn mark_awake(&mut self) { self.waking_up = false; }
+
+    /// Whether writing the memory is expected to work: SLPOUT was issued
+    /// and its delay [confirmed](Self::mark_awake).
+    pub fn check_ram_access(&self) -> Result<(), OrderingError> {
+        if !self.state.contains(PanelState::SLEEP_OUT) {
+            Err(OrderingError::Asleep)
+        } else if self.waking_up {
+            Err(OrderingError::WakingUp)
+        } else {
+            Ok(())
+        }
+    }
+
+    /// In the strict ordering mode, [ramwr()](Self::ramwr) and
+    /// [rgbset()](Self::rgbset) panic when
+    /// [check_ram_access()](Self::check_ram_access) fails, to catch in host
+    /// tests a classic bring-up bug: writing the memory while asleep, which
+    /// some panels silently ignore. Off by default.
+    pub fn set_strict_ordering(&mut self, strict: bool) {
+        self.strict_ordering = strict;
+    }
+
+    /// With `dedup` on, [gamset()](Self::gamset), [madctl()](Self::madctl)
+    /// and [colmod()](Self::colmod) are skipped when they would send the
+    /// value they last sent, e.g., for UI code setting them every frame.
+    /// Off by default (feature `dedup`).
+    ///
+    /// Like [state()](Self::state), the values are accounted for when the
+    /// commands are issued. [invalidate()](Self::invalidate) whenever the
+    /// panel may have lost them behind the back of this instance.
+    #[cfg(feature = "dedup")]
+    pub fn set_dedup(&mut self, dedup: bool) { self.sent.enabled = dedup; }
+
+    /// Forgets the values last sent, so that the next commands are sent even
+    /// with [set_dedup()](Self::set_dedup) on. Done by SWRESET and
+    /// [reset_state()](Self::reset_state) already.
+    #[cfg(feature = "dedup")]
+    pub fn invalidate(&mut self) { self.sent.clear(); }
+
+    fn track(&mut self, cmd: u8) {
+        self.state.apply(cmd);
+        #[cfg(feature = "dedup")]
+        self.sent.forget(cmd);
+        match cmd {
+            0x11 => self.waking_up = true,  // SLPOUT.
+            0x01 => {  // SWRESET.
+                self.waking_up = false;
+                self.forget_window();
+            },
+            0x10 => self.waking_up = false,  // SLPIN.
+            _ => {},
+        }
+    }
+
+    fn check_strict_ordering(&self) {
+        if self.strict_ordering {
+            if let Err(e) = self.check_ram_access() {
+                panic!("Memory written out of order: {}.", e);
+            }
+        }
+    }
+}
+
+impl<S> Commands<S> where S: DcxPin,
+                          for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Sets the column address window as `begin` to `end`, both inclusive.
+    #[inline(always)]
+    pub fn caset(&mut self, begin: u16, end: u16) -> WindowFuture<'_, S> {
+        self.columns = Some((begin, end));
+        self.command_future(0x2A, u16_pair(begin, end))
+    }
+
+    /// Sets the row address window as `begin` to `end`, both inclusive.
+    #[inline(always)]
+    pub fn raset(&mut self, begin: u16, end: u16) -> WindowFuture<'_, S> {
+        self.rows = Some((begin, end));
+        self.command_future(0x2B, u16_pair(begin, end))
+    }
+
+    /// Same as [caset()](Self::caset), but only if the columns are visible on
+    /// `panel`. Nothing is sent otherwise.
+    pub async fn caset_checked(&mut self, panel: &Panel, begin: u16, end: u16)
+            -> Result<(), WindowError> {
+        panel.check_columns(begin, end)?;
+        self.caset(begin, end).await;
+        Ok(())
+    }
+
+    /// Same as [raset()](Self::raset), but only if the rows are visible on
+    /// `panel`. Nothing is sent otherwise.
+    pub async fn raset_checked(&mut self, panel: &Panel, begin: u16, end: u16)
+            -> Result<(), WindowError> {
+        panel.check_rows(begin, end)?;
+        self.raset(begin, end).await;
+        Ok(())
+    }
+
+    /// Same as [caset()](Self::caset), but both addresses are clamped into the
+    /// memory of the controller, see [ControllerProfile::memory_columns].
+    pub async fn caset_clamped(&mut self, begin: u16, end: u16) {
+        let last = self.profile.memory_columns - 1;
+        self.caset(begin.min(last), end.min(last)).await;
+    }
+
+    /// Same as [raset()](Self::raset), but both addresses are clamped into the
+    /// memory of the controller, see [ControllerProfile::memory_rows].
+    pub async fn raset_clamped(&mut self, begin: u16, end: u16) {
+        let last = self.profile.memory_rows - 1;
+        self.raset(begin.min(last), end.min(last)).await;
+    }
+
+    /// Sets the column and row address windows to the compile-time window
+    /// `W`, typically a [Window](crate::panel::Window).
+    ///
+    /// An empty `W` fails the compilation.
+    #[inline(always)]
+    pub async fn set_window_const<W: ConstWindow>(&mut self) {
+        #[allow(clippy::let_unit_value)]
+        let () = W::VALID;
+        self.caset(W::X0, W::X1).await;
+        self.raset(W::Y0, W::Y1).await;
+    }
+
+    /// Saves the current address window and sets it to `rect`, in the memory
+    /// coordinates, e.g., to clip the drawing of a widget. Restore it with
+    /// [pop_window()](Self::pop_window). Nothing is sent on errors.
+    pub async fn push_window(&mut self, rect: Rect)
+            -> Result<(), WindowStackError> {
+        rect.last().map_err(WindowStackError::Invalid)?;
+        if self.num_windows == WINDOW_STACK_DEPTH {
+            return Err(WindowStackError::Full);
+        }
+        let parent = self.window().ok_or(WindowStackError::Unknown)?;
+        self.windows[self.num_windows] = parent;
+        self.num_windows += 1;
+        self.set_window(rect).await.map_err(WindowStackError::Invalid)
+    }
+
+    /// Restores the address window saved by the last
+    /// [push_window()](Self::push_window).
+    pub async fn pop_window(&mut self) -> Result<(), WindowStackError> {
+        if self.num_windows == 0 { return Err(WindowStackError::Empty); }
+        self.num_windows -= 1;
+        self.set_window(self.windows[self.num_windows]).await
+            .map_err(WindowStackError::Invalid)
+    }
+
+    // Nothing is sent if `rect` has no last column or row.
+    async fn set_window(&mut self, rect: Rect) -> Result<(), WindowError> {
+        let (x1, y1) = rect.last()?;
+        self.caset(rect.x, x1).await;
+        self.raset(rect.y, y1).await;
+        Ok(())
+    }
+
+    /// Starts writing memory. The returned object can be used to actually do
+    /// the memory writing.
+    ///
+    /// # Panics
+    ///
+    /// In the [strict ordering](Self::set_strict_ordering) mode, if
+    /// [check_ram_access()](Self::check_ram_access) fails.
+    #[inline(always)]
+    pub async fn ramwr(&mut self) -> RamWriter<'_, S> {
+        self.check_strict_ordering();
+        self.command(0x2C).await;
+        self.spi.set_dcx_data_mode();
+        // `RamWriter::drop()` will restore to command mode.
+        RamWriter{spi: &mut self.spi, columns: &mut self.columns,
+                  rows: &mut self.rows}
+    }
+
+    /// Starts writing the RGB lookup table (see the ST7735S datasheet
+    /// sec 9.18).
+    ///
+    /// The returned object can be used to actually do the memory
+    /// writing. The user is expected to write exactly 128 bytes, which is
+    /// **not** enforced by the library.
+    ///
+    /// The lookup table is needed when the color mode
+    /// (see [colmod()](Self::colmod))
+    /// is *not* [Colmod::R6G6B6].
+    ///
+    /// # Panics
+    ///
+    /// Same as [ramwr()](Self::ramwr).
+    #[inline(always)]
+    pub async fn rgbset(&mut self) -> RamWriter<'_, S> {
+        self.check_strict_ordering();
+        self.command(0x2D).await;
+        self.spi.set_dcx_data_mode();
+        // `RamWriter::drop()` will restore to command mode.
+        RamWriter{spi: &mut self.spi, columns: &mut self.columns,
+                  rows: &mut self.rows}
+    }
+
+    /// Same as [ramwr()](Self::ramwr), but fails without writing anything
+    /// if [check_ram_access()](Self::check_ram_access) does, in any mode.
+    pub async fn try_ramwr(&mut self)
+            -> Result<RamWriter<'_, S>, OrderingError> {
+        self.check_ram_access()?;
+        Ok(self.ramwr().await)
+    }
+
+    /// Same as [rgbset()](Self::rgbset), but fails without writing anything
+    /// if [check_ram_access()](Self::check_ram_access) does, in any mode.
+    pub async fn try_rgbset(&mut self)
+            -> Result<RamWriter<'_, S>, OrderingError> {
+        self.check_ram_access()?;
+        Ok(self.rgbset().await)
+    }
+
+    /// Sets the partial area address window as `begin` to `end`, both
+    /// inclusive.
+    #[inline(always)]
+    pub async fn ptlar(&mut self, begin: u16, end: u16) {
+        self.command_with_args(0x30, [Param::U16(begin), Param::U16(end)])
+            .await;
+    }
+
+    /// Sets the scroll area address windows.
+    #[inline(always)]
+    pub async fn scrlar(&mut self, top: u16, visible: u16, bottom: u16) {
+        let args = [top, visible, bottom].map(Param::U16);
+        self.command_with_args(0x33, args).await;
+    }
+
+    /// Sends `cmd` with `args` as its parameters, for the commands without
+    /// a dedicated method. The parameters are sent in batches of up to 16
+    /// bytes.
+    ///
+    /// Using it for CASET or RASET makes [window()](Self::window) unknown.
+    pub async fn command_with_args<I>(&mut self, cmd: u8, args: I)
+            where I: IntoIterator<Item=Param> {
+        if cmd == 0x2A || cmd == 0x2B { self.forget_window(); }
+        self.track(cmd);
+        self.spi.write_u8(cmd).await;
+        self.spi.set_dcx_data_mode();
+        let mut buffer = [0u8; ARGS_BATCH];
+        let mut len = 0;
+        for arg in args {
+            if len + 2 > buffer.len() {
+                self.spi.write_u8s(&buffer[..len]).await;
+                len = 0;
+            }
+            match arg {
+                Param::U8(v) => { buffer[len] = v; len += 1; }
+                Param::U16(v) => {
+                    buffer[len..len + 2].copy_from_slice(&v.to_be_bytes());
+                    len += 2;
+                }
+            }
+        }
+        if len > 0 { self.spi.write_u8s(&buffer[..len]).await; }
+        self.spi.set_dcx_command_mode();
+    }
+
+    #[inline(always)]
+    fn command_future<const N: usize>(&mut self, cmd: u8, data: [u8; N])
+            -> CommandFuture<'_, S, N> {
+        #[cfg(feature = "dedup")]
+        if self.sent.is_redundant(cmd, &data) {
+            return CommandFuture{state: CommandState::Skipped,
+                                 spi: &mut self.spi, cmd, data,
+                                 _pinned: PhantomPinned};
+        }
+        self.track(cmd);
+        #[cfg(feature = "dedup")]
+        self.sent.record(cmd, &data);
+        CommandFuture{state: CommandState::Start, spi: &mut self.spi, cmd, data,
+                      _pinned: PhantomPinned}
+    }
+
+    #[inline(always)]
+    async fn command_with_u8s(&mut self, cmd: u8, data: &[u8]) {
+        self.track(cmd);
+        self.spi.write_u8(cmd).await;
+        self.spi.set_dcx_data_mode();
+        self.spi.write_u8s(data).await;
+        self.spi.set_dcx_command_mode();
+    }
+
+    #[inline(always)]
+    async fn command(&mut self, cmd: u8) {
+        self.track(cmd);
+        self.spi.write_u8(cmd).await;
+    }
+
+    /// Writes `cmd` as a command without any data. For the interpreters of
+    /// command tables.
+    pub(crate) async fn raw_command(&mut self, cmd: u8) {
+        if cmd == 0x2A || cmd == 0x2B { self.forget_window(); }
+        self.command(cmd).await;
+    }
+
+    /// Writes `data` as the data of the previous command. For the
+    /// interpreters of command tables.
+    pub(crate) async fn raw_data(&mut self, data: &[u8]) {
+        self.spi.set_dcx_data_mode();
+        self.spi.write_u8s(data).await;
+        self.spi.set_dcx_command_mode();
+    }
+
+    /// Does nothing.
+    #[inline(always)]
+    pub fn nop(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x00, []) }
+    /// Software-resets.
+    #[inline(always)]
+    pub fn swreset(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x01, []) }
+    /// Enters the sleep mode.
+    #[inline(always)]
+    pub fn slpin(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x10, []) }
+    /// Exits the sleep mode.
+    #[inline(always)]
+    pub fn slpout(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x11, []) }
+    /// Enters the partial mode.
+    #[inline(always)]
+    pub fn ptlon(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x12, []) }
+    /// Enters the normal mode (i.e., exits the partial mode).
+    #[inline(always)]
+    pub fn noron(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x13, []) }
+    /// Disables the inversion mode.
+    #[inline(always)]
+    pub fn invoff(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x20, []) }
+    /// Enables the inversion mode.
+    #[inline(always)]
+    pub fn invon(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x21, []) }
+    /// Selects one of the predefined gamma curves.
+    #[inline(always)]
+    pub fn gamset(&mut self, curve: GammaCurve) -> CommandWithU8Future<'_, S> {
+        self.command_future(0x26, [curve.into()]) }
+    /// Turns the display/screen off.
+    #[inline(always)]
+    pub fn dispoff(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x28, []) }
+    /// Turns the display/screen on.
+    #[inline(always)]
+    pub fn dispon(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x29, []) }
+    /// Turns the tear effect line off.
+    #[inline(always)]
+    pub fn teoff(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x34, []) }
+    /// Turns the tear effect line on with the given mode, `true` being
+    /// [TeMode::VBlankAndHBlank].
+    #[deprecated(note = "use `teon_mode()`")]
+    #[inline(always)]
+    pub fn teon(&mut self, te_mode: bool) -> CommandWithU8Future<'_, S> {
+        self.teon_mode(if te_mode {TeMode::VBlankAndHBlank}
+                       else {TeMode::VBlank}) }
+    /// Turns the tear effect line on with the given mode.
+    #[inline(always)]
+    pub fn teon_mode(&mut self, mode: TeMode) -> CommandWithU8Future<'_, S> {
+        self.command_future(0x35, [mode.into()]) }
+    /// Sets the MADCTL register.
+    #[inline(always)]
+    pub fn madctl(&mut self, data: Madctl) -> CommandWithU8Future<'_, S> {
+        self.command_future(0x36, [data.into()]) }
+    /// Sets the first line of the scroll area shown at the top of it. See
+    /// [scrlar()](Self::scrlar).
+    #[inline(always)]
+    pub async fn vscsad(&mut self, line: u16) {
+        self.command_with_u8s(0x37, &line.to_be_bytes()).await; }
+    /// Turns the idle mode off, i.e., enables the full color mode.
+    #[inline(always)]
+    pub fn idmoff(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x38, []) }
+    /// Turns the idle mode on, i.e., enables the 8-color mode.
+    #[inline(always)]
+    pub fn idmon(&mut self) -> SimpleCommandFuture<'_, S> {
+        self.command_future(0x39, []) }
+    /// Sets the color mode, i.e., how many bits of the R, G and B components
+    /// have.
+    #[inline(always)]
+    pub fn colmod(&mut self, data: Colmod) -> CommandWithU8Future<'_, S> {
+        let data = self.profile.colmod_parameter(data.into());
+        self.command_future(0x3A, [data]) }
+
+    /// Scrolls from line `from` to line `to` by `step` lines per frame, i.e.,
+    /// sends [vscsad()](Self::vscsad) once per vertical blank reported by
+    /// `te`, which must have been enabled with
+    /// [teon_mode(TeMode::VBlank)](Self::teon_mode). The scroll area should
+    /// have been set by [scrlar()](Self::scrlar). A `step` of `0` jumps
+    /// directly to `to`.
+    pub async fn animate_scroll<T>(&mut self, from: u16, to: u16, step: u16,
+                                   te: &mut T)
+            where for<'a> T: TePin<'a> {
+        let mut line = if step == 0 { to } else { from };
+        loop {
+            te.wait_for_vblank().await;
+            self.vscsad(line).await;
+            if line == to {
+                return;
+            }
+            line = if line < to {
+                line.saturating_add(step).min(to)
+            } else {
+                line.saturating_sub(step).max(to)
+            };
+        }
+    }
+
+    /// Redraws the dirty `rect` from `source`, and returns the rectangle
+    /// actually written. `rect` and `source` are in the coordinates of
+    /// `panel` under `madctl`, see [Panel::oriented()].
+    ///
+    /// A small `rect` gets a partial update: the partial mode is entered on
+    /// the rows of `rect` with PTLAR and PTLON, only its own pixels are
+    /// written, and the normal mode is restored with NORON. Once `rect`
+    /// covers most (3/4) of the panel, the whole panel is written instead,
+    /// in the normal mode, as a single window which is as cheap and also
+    /// catches up any other dirty pixel. Nothing is sent if `rect` is empty,
+    /// which is written as it is, or isn't inside the panel.
+    pub async fn refresh_region<P>(&mut self, panel: &Panel, madctl: Madctl,
+                                   rect: Rect, source: &P)
+            -> Result<Rect, WindowError>
+            where P: PixelSource {
+        if rect.is_empty() {
+            return Ok(rect);
+        }
+        let panel = panel.oriented(madctl);
+        let (end_x, end_y) = rect.last()?;
+        if end_x >= panel.width() || end_y >= panel.height() {
+            return Err(WindowError::OutOfRange);
+        }
+        let full = Rect::new(0, 0, panel.width(), panel.height());
+        let partial = rect.area() * 4 < full.area() * 3;
+        let rect = if partial { rect } else { full };
+        let memory = |at: u16, offset: u16| {
+            at.checked_add(offset).ok_or(WindowError::OutOfRange)
+        };
+        let window = Rect::new(memory(rect.x, panel.column_offset())?,
+                               memory(rect.y, panel.row_offset())?,
+                               rect.width, rect.height);
+        let (x1, y1) = window.last()?;
+
+        if partial {
+            // The partial area is in the physical rows, which CASET
+            // addresses when MV is set.
+            let (begin, end) = match madctl.row_column_swap() {
+                RowColumnSwap::Unswapped => (window.y, y1),
+                RowColumnSwap::Swapped => (window.x, x1),
+            };
+            self.ptlar(begin, end).await;
+            self.ptlon().await;
+        }
+        self.caset(window.x, x1).await;
+        self.raset(window.y, y1).await;
+        let pixels = (rect.y..rect.y + rect.height).flat_map(|y| {
+            (rect.x..rect.x + rect.width).map(move |x| source.pixel_at(x, y))
+        });
+        self.ramwr().await.write_pixels(pixels).await;
+        if partial {
+            self.noron().await;
+        }
+        Ok(rect)
+    }
+
+    // Panel functions skipped, except for the gamma correction below.
+
+    /// Configures the gamma correction. For a custom correction, selects
+    /// [GammaCurve::Curve1] first, then sends GMCTRP1 (E0h) and GMCTRN1 (E1h).
+    pub async fn apply_gamma(&mut self, config: GammaConfig) {
+        match config {
+            GammaConfig::Predefined(curve) => self.gamset(curve).await,
+            GammaConfig::Custom{positive, negative} => {
+                self.gamset(GammaCurve::Curve1).await;
+                self.command_with_u8s(0xE0, &positive).await;
+                self.command_with_u8s(0xE1, &negative).await;
+            },
+        }
+    }
+
+    /// Enters the partial mode showing only `area`, then the idle mode if
+    /// requested, in the order recommended by the datasheet.
+    pub async fn enter_low_power(&mut self, area: PartialArea, idle: IdleMode) {
+        self.ptlar(area.start, area.end).await;
+        self.ptlon().await;
+        if idle == IdleMode::Idle {
+            self.idmon().await;
+        }
+    }
+
+    /// Reverts [enter_low_power()](Self::enter_low_power), i.e., exits the
+    /// idle mode and then the partial mode.
+    pub async fn exit_low_power(&mut self) {
+        self.idmoff().await;
+        self.noron().await;
+    }
+
+    /// Sets the frame rate of the normal mode.
+    pub async fn frmctr1(&mut self, rate: FrameRate) {
+        self.command_with_u8s(
+            0xB1, &[rate.rtna, rate.front_porch, rate.back_porch]).await;
+    }
+
+    /// Sends PWCTR1 to PWCTR5, then VMCTR1.
+    pub async fn apply_power_profile(&mut self, profile: &PowerProfile) {
+        self.command_with_u8s(0xC0, &profile.pwctr1).await;
+        self.command_with_u8s(0xC1, &[profile.pwctr2]).await;
+        self.command_with_u8s(0xC2, &profile.pwctr3).await;
+        self.command_with_u8s(0xC3, &profile.pwctr4).await;
+        self.command_with_u8s(0xC4, &profile.pwctr5).await;
+        self.command_with_u8s(0xC5, &[profile.vmctr1]).await;
+    }
+
+    /// Applies `config`: the power profile and the frame rate first, then the
+    /// color mode, the memory access, the gamma, the scrolling and finally
+    /// the address window. A window without a last column or row, see
+    /// [Rect::last()], is skipped.
+    pub async fn apply_config(&mut self, config: &PanelConfig) {
+        if let Some(power) = &config.power {
+            self.apply_power_profile(power).await;
+        }
+        if let Some(rate) = config.frame_rate {
+            self.frmctr1(rate).await;
+        }
+        if let Some(colmod) = config.colmod {
+            self.colmod(colmod).await;
+        }
+        self.madctl(config.madctl).await;
+        self.apply_gamma(config.gamma).await;
+        if let Some(scroll) = config.scroll {
+            self.scrlar(scroll.top_fixed, scroll.scroll_area,
+                        scroll.bottom_fixed).await;
+            self.vscsad(scroll.start).await;
+        }
+        if let Some(window) = config.window {
+            self.set_window(window).await.ok();
+        }
+    }
+}
+
+/// The values last sent by the commands [Commands::set_dedup()] skips.
+#[cfg(feature = "dedup")]
+#[derive(Debug, Default)]
+struct SentRegisters {
+    enabled: bool,
+    gamset: Option<u8>,
+    madctl: Option<u8>,
+    colmod: Option<u8>,
+}
+
+#[cfg(feature = "dedup")]
+impl SentRegisters {
+    fn slot(&mut self, cmd: u8) -> Option<&mut Option<u8>> {
+        match cmd {
+            0x26 => Some(&mut self.gamset),
+            0x36 => Some(&mut self.madctl),
+            0x3A => Some(&mut self.colmod),
+            _ => None,
+        }
+    }
+
+    fn clear(&mut self) {
+        *self = Self{enabled: self.enabled, ..Default::default()};
+    }
+
+    fn is_redundant(&mut self, cmd: u8, data: &[u8]) -> bool {
+        let enabled = self.enabled;
+        match (self.slot(cmd), data) {
+            (Some(Some(sent)), [value]) => enabled && sent == value,
+            _ => false,
+        }
+    }
+
+    fn record(&mut self, cmd: u8, data: &[u8]) {
+        if let (Some(slot), [value]) = (self.slot(cmd), data) {
+            *slot = Some(*value);
+        }
+    }
+
+    // Called for every command issued, including by
+    // [Commands::command_with_args()] and the command tables.
+    fn forget(&mut self, cmd: u8) {
+        if cmd == 0x01 {  // SWRESET.
+            self.clear();
+        } else if let Some(slot) = self.slot(cmd) {
+            *slot = None;
+        }
+    }
+}
+
+fn u16_pair(first: u16, second: u16) -> [u8; 4] {
+    let [a, b] = first.to_be_bytes();
+    let [c, d] = second.to_be_bytes();
+    [a, b, c, d]
+}
+
+/// The future of a command with `N` bytes of data, named so that it can be
+/// stored, e.g., in a static task arena. Returned by the commands most used
+/// on hot paths; see the aliases below.
+pub struct CommandFuture<'a, S, const N: usize>
+        where S: WriteU8<'a> + WriteU8s<'a> {
+    // Declared first so that it is dropped first, as it can borrow `data`.
+    state: CommandState<<S as WriteU8<'a>>::WriteU8Done,
+                        <S as WriteU8s<'a>>::WriteU8sDone>,
+    // Lifetime is 'a. Only borrowed by one future in `state` at a time.
+    spi: *mut S,
+    cmd: u8,
+    data: [u8; N],
+    _pinned: PhantomPinned,
+}
+
+/// The future of [Commands::caset()] and [Commands::raset()].
+pub type WindowFuture<'a, S> = CommandFuture<'a, S, 4>;
+/// The future of the commands without data, e.g., [Commands::dispon()].
+pub type SimpleCommandFuture<'a, S> = CommandFuture<'a, S, 0>;
+/// The future of the commands with a single byte of data, e.g.,
+/// [Commands::madctl()].
+pub type CommandWithU8Future<'a, S> = CommandFuture<'a, S, 1>;
+
+// `Skipped` is for the commands deduplicated by [Commands::set_dedup()].
+enum CommandState<C, D> {
+    Start,
+    #[cfg(feature = "dedup")]
+    Skipped,
+    Command(C),
+    Data(D),
+    Done,
+}
+
+// Safety: `spi` stands for the `&'a mut S` the future was created from,
+// which can be sent if `S` can.
+unsafe impl<'a, S, const N: usize> Send for CommandFuture<'a, S, N>
+        where S: Send + WriteU8<'a> + WriteU8s<'a>,
+              <S as WriteU8<'a>>::WriteU8Done: Send,
+              <S as WriteU8s<'a>>::WriteU8sDone: Send {}
+
+impl<'a, S, const N: usize> Future for CommandFuture<'a, S, N>
+        where S: 'a + DcxPin + WriteU8<'a> + WriteU8s<'a> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: `state` is pinned by never being moved, only replaced after
+        // being dropped. `data` is never moved either, as `Self` is !Unpin.
+        let cf = unsafe {self.get_unchecked_mut()};
+        loop {
+            match &mut cf.state {
+                CommandState::Start => {
+                    // Safety: No future in `state` borrows `*spi`.
+                    let spi: &'a mut S = unsafe {&mut *cf.spi};
+                    cf.state = CommandState::Command(spi.write_u8(cf.cmd));
+                },
+                #[cfg(feature = "dedup")]
+                CommandState::Skipped => {
+                    cf.state = CommandState::Done;
+                    return Poll::Ready(());
+                },
+                CommandState::Command(done) => {
+                    // Safety: Pinning a field of a pinned.
+                    if unsafe {Pin::new_unchecked(done)}.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    cf.state = CommandState::Done;
+                    if N == 0 {
+                        return Poll::Ready(());
+                    }
+                    // Safety: The future borrowing `*spi` is dropped; `data`
+                    // lives and stays in place as long as the new one does.
+                    let spi: &'a mut S = unsafe {&mut *cf.spi};
+                    let data: &'a [u8] = unsafe {&*(&cf.data[..] as *const _)};
+                    spi.set_dcx_data_mode();
+                    cf.state = CommandState::Data(spi.write_u8s(data));
+                },
+                CommandState::Data(done) => {
+                    // Safety: Pinning a field of a pinned.
+                    if unsafe {Pin::new_unchecked(done)}.poll(cx).is_pending() {
+                        return Poll::Pending;
+                    }
+                    cf.state = CommandState::Done;
+                    // Safety: The future borrowing `*spi` is dropped.
+                    unsafe {&mut *cf.spi}.set_dcx_command_mode();
+                    return Poll::Ready(());
+                },
+                CommandState::Done => panic!("Polled after completion."),
+            }
+        }
+    }
+}
+
+/// Observes the progress of a long transfer, e.g., to update a progress bar or
+/// to feed a watchdog. See [RamWriter::write_chunked()].
+pub trait TransferProgress {
+    /// Called with the number of bytes written so far.
+    fn on_progress(&mut self, written: usize);
+}
+
+impl<F: FnMut(usize)> TransferProgress for F {
+    fn on_progress(&mut self, written: usize) { self(written) }
+}
+
+/// A helper RAII object for writing *data* after a *command*.
+///
+/// It holds the only access to the DCX pin while alive: the pin stays in the
+/// data mode until it is dropped, and neither the [Commands] nor the `S`
+/// under it can be used in the meantime.
+///
+/// ```compile_fail
+/// # use core::future::{ready, Ready};
+/// # use st7735_async_low_core::{spi::*, Commands};
+/// # struct Bus;
+/// # impl DcxPin for Bus {
+/// #     fn set_dcx_command_mode(&mut self) {}
+/// #     fn set_dcx_data_mode(&mut self) {}
+/// # }
+/// # impl<'a> WriteU8<'a> for Bus {
+/// #     type WriteU8Done = Ready<()>;
+/// #     fn write_u8(&'a mut self, _: u8) -> Ready<()> { ready(()) }
+/// # }
+/// # impl<'a> WriteU8s<'a> for Bus {
+/// #     type WriteU8sDone = Ready<()>;
+/// #     fn write_u8s(&'a mut self, _: &'a [u8]) -> Ready<()> { ready(()) }
+/// # }
+/// async fn corrupt(cmds: &mut Commands<Bus>) {
+///     let mut w = cmds.ramwr().await;
+///     cmds.nop().await;  // Error: `cmds` is borrowed by `w`.
+///     w.write_u8s(&[0xFF]).await;
+/// }
+/// ```
+#[derive(Debug)]
+pub struct RamWriter<'s, S: DcxPin> {
+    spi: &'s mut S,
+    // Those of the `Commands`, updated by `rewindow()`.
+    columns: &'s mut Option<(u16, u16)>,
+    rows: &'s mut Option<(u16, u16)>,
+}
+
+impl<'s, S: DcxPin> Drop for RamWriter<'s, S> {
+    fn drop(&mut self) { self.spi.set_dcx_command_mode(); }
+}
+
+impl<'s, S> RamWriter<'s, S> where S: DcxPin, for<'a> S: WriteU8s<'a> {
+    /// Writes typed pixels one after another. The LCD is expected to be in
+    /// the color mode `P::COLMOD`, which is **not** checked.
+    ///
+    /// Combined with [scan_order()](crate::scan::scan_order), the pixels of a
+    /// buffer can be written in whatever orientation the LCD is in.
+    pub async fn write_pixels<P, I>(&mut self, pixels: I)
+            where P: Pixel, I: IntoIterator<Item=P> {
+        self.write_pixels_with(pixels, &Identity).await;
+    }
+
+    /// Same as [write_pixels()](Self::write_pixels), but each pixel goes
+    /// through `transform` before being written.
+    pub async fn write_pixels_with<P, I, T>(&mut self, pixels: I, transform: &T)
+            where P: Pixel, I: IntoIterator<Item=P>, T: PixelTransform<P> {
+        let pixels = pixels.into_iter().map(|p| transform.transform(p));
+        self.write_stream(stream::pixels(pixels)).await;
+    }
+
+    /// Same as [write_pixels()](Self::write_pixels), but yields to the
+    /// executor (see [yield_now()](crate::task::yield_now)) after every
+    /// `yield_every` pixels, so that a long transfer doesn't starve other tasks
+    /// of a single-threaded executor. Never yields if `yield_every` is `0`.
+    pub async fn write_pixels_yielding<P, I>(&mut self, pixels: I,
+                                             yield_every: usize)
+            where P: Pixel, I: IntoIterator<Item=P> {
+        if yield_every == 0 {
+            return self.write_pixels(pixels).await;
+        }
+        let mut pixels = pixels.into_iter();
+        loop {
+            let mut n = 0;
+            let group = pixels.by_ref().take(yield_every).inspect(|_| n += 1);
+            self.write_stream(stream::pixels(group)).await;
+            if n < yield_every { return; }
+            yield_now().await;
+        }
+    }
+}
+
+impl<'s, S> RamWriter<'s, S> where S: DcxPin, for<'a> S: WriteU8s<'a> {
+    /// Writes `data` in chunks of `chunk_size` bytes (at least 1), reporting
+    /// to `progress` after each of them; the last report is the length of
+    /// `data`.
+    ///
+    /// A chunk is shorter when [WriteU8s::preferred_chunk()] of the backend
+    /// asks so before it.
+    pub async fn write_chunked<T>(&mut self, data: &[u8], chunk_size: usize,
+                                  progress: &mut T)
+            where T: TransferProgress {
+        let mut written = 0;
+        while written < data.len() {
+            let n = chunk_size.min(self.spi.preferred_chunk()).max(1);
+            let chunk = &data[written..data.len().min(written + n)];
+            self.spi.write_u8s(chunk).await;
+            written += chunk.len();
+            progress.on_progress(written);
+        }
+    }
+
+    /// Writes all of `stream`, in chunks of up to 64 bytes, or fewer if
+    /// [WriteU8s::preferred_chunk()] of the backend asks so. No color fixup
+    /// is applied to the bytes.
+    pub async fn write_stream<T: PixelStream>(&mut self, stream: T) {
+        self.write_stream_reporting(stream, &mut |_| {}).await;
+    }
+
+    // Writes all of `stream` as `write_stream()` does, passing the length of
+    // each chunk to `on_chunk` once written.
+    async fn write_stream_reporting<T, F>(&mut self, mut stream: T,
+                                          on_chunk: &mut F)
+            where T: PixelStream, F: FnMut(usize) {
+        let mut buffer = [0; STREAM_CHUNK];
+        let n = STREAM_CHUNK.min(self.spi.preferred_chunk()).max(1);
+        loop {
+            let len = stream.next_chunk(&mut buffer[..n]);
+            if len == 0 { return; }
+            self.spi.write_u8s(&buffer[..len]).await;
+            on_chunk(len);
+        }
+    }
+
+    /// Same as [write_pixels()](Self::write_pixels), but reports to
+    /// `progress` once at least `every` bytes have been written since the
+    /// previous report, and after the last pixel. The reports fall between
+    /// the chunks of [write_stream()](Self::write_stream).
+    pub async fn write_pixels_with_progress<P, I, T>(&mut self, pixels: I,
+                                                     every: usize,
+                                                     progress: &mut T)
+            where P: Pixel, I: IntoIterator<Item=P>, T: TransferProgress {
+        let (mut written, mut reported) = (0, 0);
+        self.write_stream_reporting(stream::pixels(pixels), &mut |len| {
+            written += len;
+            if written - reported >= every {
+                reported = written;
+                progress.on_progress(written);
+            }
+        }).await;
+        if written != reported {
+            progress.on_progress(written);
+        }
+    }
+
+    /// Writes colors in the [Colmod::R4G4B4] format, packing every two of
+    /// them into 3 bytes. The LCD is expected to be in that mode, which is
+    /// **not** checked.
+    pub async fn write_rgb444<I>(&mut self, pixels: I)
+            where I: IntoIterator<Item=Rgb444> {
+        self.write_stream(stream::rgb444(pixels)).await;
+    }
+
+    /// Writes 24-bit colors, converted on the fly into the packing of
+    /// `colmod`, which is expected to be the current mode of the LCD.
+    ///
+    /// # Panics
+    ///
+    /// If `colmod` is [Colmod::Unknown].
+    pub async fn write_rgb888_as<I>(&mut self, colmod: Colmod, pixels: I)
+            where I: IntoIterator<Item=Rgb888> {
+        let pixels = pixels.into_iter();
+        match colmod {
+            Colmod::R4G4B4 => self.write_rgb444(pixels.map(Rgb444::from)).await,
+            Colmod::R5G6B5 => self.write_pixels(pixels.map(Rgb565::from)).await,
+            Colmod::R6G6B6 => self.write_pixels(pixels.map(Rgb666::from)).await,
+            Colmod::Unknown => panic!("Unknown color mode."),
+        }
+    }
+}
+
+impl<'s, S> RamWriter<'s, S>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Ends the current write, sets the address window to `rect`, in the
+    /// memory coordinates, and starts writing memory again with RAMWR, e.g.,
+    /// to fill several rectangles in a row. Also after
+    /// [rgbset()](Commands::rgbset). Nothing is sent if `rect` is empty or
+    /// reaches past `u16::MAX`, see [Rect::last()].
+    pub async fn rewindow(&mut self, rect: Rect) -> Result<(), WindowError> {
+        let (x1, y1) = rect.last()?;
+        let columns = (rect.x, x1);
+        let rows = (rect.y, y1);
+        self.spi.set_dcx_command_mode();
+        self.address(0x2A, columns).await;
+        self.address(0x2B, rows).await;
+        self.spi.write_u8(0x2C).await;
+        self.spi.set_dcx_data_mode();
+        *self.columns = Some(columns);
+        *self.rows = Some(rows);
+        Ok(())
+    }
+
+    async fn address(&mut self, cmd: u8, (begin, end): (u16, u16)) {
+        self.spi.write_u8(cmd).await;
+        self.spi.set_dcx_data_mode();
+        self.spi.write_u8s(&u16_pair(begin, end)).await;
+        self.spi.set_dcx_command_mode();
+    }
+}
+
+impl<'s, S> RamWriter<'s, S> where S: DcxPin, for<'a> S: WriteRepeat<'a> {
+    /// Writes `color_bytes`, typically a single pixel, `count` times.
+    pub async fn write_repeated(&mut self, color_bytes: &[u8], count: usize) {
+        self.spi.write_repeated(color_bytes, count).await;
+    }
+}
+
+impl<'s, S> RamWriter<'s, S> where S: DcxPin, for<'a> S: WriteU16s<'a> {
+    /// Writes RGB565 pixels held as `u16`, e.g., a framebuffer, in the
+    /// [Endianness] they are stored in. [Endianness::Big] hands `data` to
+    /// [WriteU16s] untouched, e.g., to a DMA with 16-bit frames; otherwise
+    /// the bytes are swapped into a buffer, 32 words at a time.
+    pub async fn write_u16_slice(&mut self, data: &[u16],
+                                 endianness: Endianness) {
+        match endianness {
+            Endianness::Big => self.spi.write_u16s(data).await,
+            Endianness::Little => {
+                let mut buffer = [0; SWAP_CHUNK];
+                for chunk in data.chunks(SWAP_CHUNK) {
+                    for (swapped, word) in buffer.iter_mut().zip(chunk) {
+                        *swapped = word.swap_bytes();
+                    }
+                    self.spi.write_u16s(&buffer[..chunk.len()]).await;
+                }
+            },
+        }
+    }
+}
+
+impl<'s, S> RamWriter<'s, S> where S: DcxPin, for<'a> S: WriteU8sStatic<'a> {
+    /// Writes raw pixel bytes living for the whole program, e.g., an image in
+    /// flash, so that DMA can read them in place.
+    pub async fn write_static(&mut self, data: &'static [u8]) {
+        self.spi.write_u8s_static(data).await;
+    }
+}
+
+impl<S> Commands<S> where S: DcxPin, for<'a> S: WriteU8sStatic<'a> {
+    /// Like `raw_data()`, but through [WriteU8sStatic].
+    pub(crate) async fn raw_data_static(&mut self, data: &'static [u8]) {
+        self.spi.set_dcx_data_mode();
+        self.spi.write_u8s_static(data).await;
+        self.spi.set_dcx_command_mode();
+    }
+}
+
+impl<'a, 's, S: DcxPin + WriteU8<'a>> WriteU8<'a> for RamWriter<'s, S> {
+    type WriteU8Done = <S as WriteU8<'a>>::WriteU8Done;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.spi.write_u8(data)
+    }
+}
+
+impl<'a, 's, S: DcxPin + WriteU8s<'a>> WriteU8s<'a> for RamWriter<'s, S> {
+    type WriteU8sDone = <S as WriteU8s<'a>>::WriteU8sDone;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.spi.write_u8s(data)
+    }
+
+    fn preferred_chunk(&self) -> usize { self.spi.preferred_chunk() }
+}
+
+impl<S> Commands<S> where S: DcxPin,
+                          for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Fills the `w` by `h` pixels starting at column `x` and row `y` with
+    /// `color`, as [fill()](Self::fill) does.
+    ///
+    /// Notice that this leaves the address window at the rectangle.
+    #[deprecated(note = "use `fill()`")]
+    pub async fn fill_rect<P: Pixel>(&mut self, x: u16, y: u16, w: u16, h: u16,
+                                     color: P) -> Result<(), WindowError> {
+        self.fill(Rect::new(x, y, w, h), color).await
+    }
+
+    /// Fills `rect`, in the memory coordinates, with `color`, writing a few
+    /// pixels at a time. See [fill_repeated()](Self::fill_repeated) for
+    /// [WriteRepeat]. Nothing is sent if `rect` is empty or reaches past
+    /// `u16::MAX`, see [Rect::last()].
+    ///
+    /// Notice that this leaves the address window at `rect`.
+    pub async fn fill<P: Pixel>(&mut self, rect: Rect, color: P)
+            -> Result<(), WindowError> {
+        let (x1, y1) = rect.last()?;
+        let bytes = color.to_bytes();
+        let bytes = bytes.as_ref();
+        let mut chunk = [0; FILL_CHUNK];
+        for pixel in chunk.chunks_exact_mut(bytes.len()) {
+            pixel.copy_from_slice(bytes);
+        }
+        let per_chunk = FILL_CHUNK / bytes.len();
+        self.caset(rect.x, x1).await;
+        self.raset(rect.y, y1).await;
+        let mut w = self.ramwr().await;
+        let mut left = rect.area();
+        while left > 0 {
+            let n = left.min(per_chunk);
+            w.write_u8s(&chunk[..n * bytes.len()]).await;
+            left -= n;
+        }
+        Ok(())
+    }
+}
+
+impl<S> Commands<S>
+        where S: DcxPin,
+              for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteRepeat<'a> {
+    /// Same as [fill()](Self::fill), but with a single
+    /// [write_repeated()](WriteRepeat::write_repeated), e.g., for a DMA
+    /// repeating a pixel.
+    pub async fn fill_repeated<P: Pixel>(&mut self, rect: Rect, color: P)
+            -> Result<(), WindowError> {
+        let (x1, y1) = rect.last()?;
+        self.caset(rect.x, x1).await;
+        self.raset(rect.y, y1).await;
+        let bytes = color.to_bytes();
+        self.ramwr().await.write_repeated(bytes.as_ref(), rect.area()).await;
+        Ok(())
+    }
+}
+
+impl<S> Commands<S> where S: DcxPin,
+                          for<'a> S: WriteU8<'a> + Read<'a> {
+    async fn read_command(&mut self, cmd: u8, num_bits: usize) -> u32 {
+        self.spi.write_u8(cmd).await;
+        let mut r = self.spi.start_reading();
+        r.read_bits(num_bits).await
+    }
+
+    // RD* (except RDDID and RDID*) skipped.
+
+    /// Reads the memory from the start of the address window into `pixels`.
+    ///
+    /// The LCD always sends pixels in the native [Colmod::R6G6B6] format,
+    /// regardless of the current color mode.
+    pub async fn ramrd(&mut self, pixels: &mut [Rgb666]) {
+        self.ramrd_with(pixels.len(), |i, p| pixels[i] = p).await;
+    }
+
+    /// Reads `n` pixels, handing each to `f` with its index.
+    pub(crate) async fn ramrd_with<F>(&mut self, n: usize, mut f: F)
+            where F: FnMut(usize, Rgb666) {
+        self.spi.write_u8(0x2E).await;
+        let mut r = self.spi.start_reading();
+        r.read_bits(1).await;  // The dummy clock cycle.
+        for i in 0..n {
+            let mut v = [0; 3];
+            r.read_bytes(&mut v, false).await;
+            f(i, Rgb666::new(v[0] >> 2, v[1] >> 2, v[2] >> 2));
+        }
+    }
+
+    /// Reads `ID1`, `ID2` and `ID3` of the screen with a single command.
+    #[inline(always)]
+    pub async fn rddid(&mut self) -> [u8; 3] {
+        let r = self.read_command(0x04, 25).await;
+        [(r >> 16) as u8, (r >> 8 & 0xFF) as u8, (r & 0xFF) as u8]
+    }
+
+    /// Reads the power mode.
+    #[inline(always)]
+    pub async fn rddpm(&mut self) -> PowerMode {
+        PowerMode::from(self.read_command(0x0A, 8).await as u8)
+    }
+
+    /// Reads the memory data access control.
+    #[inline(always)]
+    pub async fn rddmadctl(&mut self) -> Madctl {
+        Madctl::from(self.read_command(0x0B, 8).await as u8)
+    }
+
+    /// Reads the color mode.
+    #[inline(always)]
+    pub async fn rddcolmod(&mut self) -> Colmod {
+        Colmod::from(self.read_command(0x0C, 8).await as u8 & 0b111)
+    }
+
+    /// Reads the image mode.
+    #[inline(always)]
+    pub async fn rddim(&mut self) -> ImageMode {
+        ImageMode::from(self.read_command(0x0D, 8).await as u8)
+    }
+
+    /// Reads back what of [PanelConfig] is readable: the memory access, the
+    /// color mode and the selected gamma curve. The gamma is always reported
+    /// as [GammaConfig::Predefined], as a custom correction can't be read;
+    /// the rest is `None`, so is a color mode read as [Colmod::Unknown].
+    pub async fn read_config(&mut self) -> PanelConfig {
+        let madctl = self.rddmadctl().await;
+        let colmod = self.rddcolmod().await;
+        let curve = match u8::from(self.rddim().await) & 0b111 {
+            1 => GammaCurve::Curve2,
+            2 => GammaCurve::Curve3,
+            3 => GammaCurve::Curve4,
+            _ => GammaCurve::Curve1,
+        };
+        let colmod = Some(colmod).filter(|&c| c != Colmod::Unknown);
+        PanelConfig{madctl, colmod, gamma: GammaConfig::Predefined(curve),
+                    ..Default::default()}
+    }
+
+    /// Reads `ID1`, i.e., the manufacturer ID. Unless reprogrammed, the value
+    /// should be 0x7C (decimal 124).
+    #[inline(always)]
+    pub async fn rdid1(&mut self) -> u8 {
+        self.read_command(0xDA, 8).await as u8
+    }
+
+    /// Reads `ID2`' i.e., the LCD's "module/driver version ID". The highest
+    /// bit is always 1.
+    #[inline(always)]
+    pub async fn rdid2(&mut self) -> u8 {
+        self.read_command(0xDB, 8).await as u8
+    }
+
+    /// Reads `ID3`, i.e., the LCD's "module/driver ID".
+    #[inline(always)]
+    pub async fn rdid3(&mut self) -> u8 {
+        self.read_command(0xDC, 8).await as u8
+    }
+
+    /// Reads `ID1`, `ID2` and `ID3`, preferring [rddid()](Self::rddid) but
+    /// falling back to [rdid1()](Self::rdid1), [rdid2()](Self::rdid2) and
+    /// [rdid3()](Self::rdid3) if its `ID2` lacks the highest bit, as some
+    /// clone panels return garbage for RDDID. Returns `None` if neither path
+    /// gives a valid `ID2`.
+    pub async fn read_ids_robust(&mut self) -> Option<(IdSource, [u8; 3])> {
+        let ids = self.rddid().await;
+        if ids[1] & 0x80 != 0 {
+            return Some((IdSource::Rddid, ids));
+        }
+        let ids = [self.rdid1().await, self.rdid2().await, self.rdid3().await];
+        if ids[1] & 0x80 != 0 {
+            Some((IdSource::Rdid123, ids))
+        } else {
+            None
+        }
+    }
+}
+
+impl<S> Commands<S> where S: DcxPin,
+                          for<'a> S: WriteU8<'a> + WriteU8s<'a> + Read<'a> {
+    /// Reads the `w` by `h` pixels starting at column `x` and row `y` into
+    /// `out`, row by row. Useful to verify what was drawn.
+    ///
+    /// Notice that this leaves the address window at the rectangle.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `out` is not `w * h`.
+    #[deprecated(note = "use `read_pixels()`")]
+    pub async fn read_rect(&mut self, x: u16, y: u16, w: u16, h: u16,
+                           out: &mut [Rgb565]) -> Result<(), WindowError> {
+        self.read_pixels(Rect::new(x, y, w, h), out).await
+    }
+
+    /// Reads the pixels of `rect`, in the memory coordinates, into `out`, row
+    /// by row. Useful to verify what was drawn.
+    ///
+    /// Notice that this leaves the address window at `rect`. Nothing is sent
+    /// if `rect` is empty or reaches past `u16::MAX`, see [Rect::last()].
+    ///
+    /// # Panics
+    ///
+    /// If the length of `out` is not the area of `rect`.
+    pub async fn read_pixels(&mut self, rect: Rect, out: &mut [Rgb565])
+            -> Result<(), WindowError> {
+        assert_eq!(out.len(), rect.area());
+        self.set_window(rect).await?;
+        self.ramrd_with(out.len(), |i, p| out[i] = p.into()).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use mockall::{predicate, Sequence};
+
+    use crate::testing_device::{block_on, MockDevice, MockPlainIO};
+    use super::*;
+
+    macro_rules! test_simple_write_with_name {
+        ($name:tt, $fn:tt $args:tt, code: $code:expr, data: $data:expr) => {
+            #[test]
+            fn $name() {
+                let mut cmds = create_mock();
+                cmds.spi.expect_standard_write_command($code, $data);
+                block_on(cmds.$fn$args);
+            }
+        };
+    }
+    macro_rules! test_simple_write {
+        ($fn:tt $args:tt, code: $code:expr, data: $data:expr) => {
+            test_simple_write_with_name!(
+                $fn, $fn $args, code: $code, data: $data);
+        };
+    }
+
+    test_simple_write!(nop(), code: 0x00, data: &[]);
+    test_simple_write!(swreset(), code: 0x01, data: &[]);
+    test_simple_write!(slpin(), code: 0x10, data: &[]);
+    test_simple_write!(slpout(), code: 0x11, data: &[]);
+    test_simple_write!(ptlon(), code: 0x12, data: &[]);
+    test_simple_write!(noron(), code: 0x13, data: &[]);
+    test_simple_write!(invoff(), code: 0x20, data: &[]);
+    test_simple_write!(invon(), code: 0x21, data: &[]);
+    test_simple_write!(gamset(GammaCurve::Curve3), code: 0x26, data: &[0x04]);
+    test_simple_write!(dispoff(), code: 0x28, data: &[]);
+    test_simple_write!(dispon(), code: 0x29, data: &[]);
+    test_simple_write!(caset(0x1234, 0x5678), code: 0x2A,
+                       data: &[0x12, 0x34, 0x56, 0x78]);
+    test_simple_write!(raset(0x9876, 0x5432), code: 0x2B,
+                       data: &[0x98, 0x76, 0x54, 0x32]);
+    #[test]
+    fn caset_checked() {
+        let panel = Panel::new(128, 128).with_offsets(2, 3);
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2A, &[0, 2, 0, 129]);
+        assert_eq!(block_on(cmds.caset_checked(&panel, 2, 129)), Ok(()));
+        assert_eq!(block_on(cmds.caset_checked(&panel, 2, 130)),
+                   Err(WindowError::OutOfRange));
+    }
+    #[test]
+    fn raset_checked() {
+        let panel = Panel::new(128, 128).with_offsets(2, 3);
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2B, &[0, 3, 0, 3]);
+        assert_eq!(block_on(cmds.raset_checked(&panel, 3, 3)), Ok(()));
+        assert_eq!(block_on(cmds.raset_checked(&panel, 4, 3)),
+                   Err(WindowError::Reversed));
+    }
+    #[test]
+    fn named_future_when_pending() {
+        // Each write is pending once before being forwarded.
+        struct Yielding<F> { yielded: bool, f: F }
+        impl<F: Future + Unpin> Future for Yielding<F> {
+            type Output = F::Output;
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                    -> Poll<F::Output> {
+                if !self.yielded {
+                    self.yielded = true;
+                    cx.waker().wake_by_ref();
+                    return Poll::Pending;
+                }
+                Pin::new(&mut self.f).poll(cx)
+            }
+        }
+        struct Slow(MockDevice);
+        impl DcxPin for Slow {
+            fn set_dcx_command_mode(&mut self) { self.0.set_dcx_command_mode() }
+            fn set_dcx_data_mode(&mut self) { self.0.set_dcx_data_mode() }
+        }
+        impl<'a> WriteU8<'a> for Slow {
+            type WriteU8Done =
+                Yielding<<MockDevice as WriteU8<'a>>::WriteU8Done>;
+            fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+                Yielding{yielded: false, f: self.0.write_u8(data)}
+            }
+        }
+        impl<'a> WriteU8s<'a> for Slow {
+            type WriteU8sDone =
+                Yielding<<MockDevice as WriteU8s<'a>>::WriteU8sDone>;
+            fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+                Yielding{yielded: false, f: self.0.write_u8s(data)}
+            }
+        }
+
+        let mut device = MockDevice::new();
+        device.expect_standard_write_command(0x2A, &[0x12, 0x34, 0x56, 0x78]);
+        device.expect_standard_write_command(0x29, &[]);
+        let mut cmds = Commands::new(Slow(device));
+        let caset: WindowFuture<'_, Slow> = cmds.caset(0x1234, 0x5678);
+        block_on(caset);
+        assert!(!cmds.spi.0.is_data_mode());
+        let dispon: SimpleCommandFuture<'_, Slow> = cmds.dispon();
+        block_on(dispon);
+    }
+
+    test_simple_write!(caset_clamped(5, 200), code: 0x2A,
+                       data: &[0, 5, 0, 131]);
+    test_simple_write!(raset_clamped(300, 200), code: 0x2B,
+                       data: &[0, 161, 0, 161]);
+    #[test]
+    fn set_window_const() {
+        use crate::panel::Window;
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2A, &[0x00, 0x03, 0x01, 0x04]);
+        cmds.spi.expect_standard_write_command(0x2B, &[0x00, 0x05, 0x00, 0x05]);
+        block_on(cmds.set_window_const::<Window<3, 5, 0x104, 5>>());
+    }
+    #[test]
+    fn push_invalid_window() {
+        let mut cmds = create_mock();
+        block_on(async {
+            assert_eq!(cmds.push_window(Rect::new(1, 1, 0, 1)).await,
+                       Err(WindowStackError::Invalid(WindowError::Reversed)));
+            assert_eq!(cmds.push_window(Rect::new(1, u16::MAX, 1, 2)).await,
+                       Err(WindowStackError::Invalid(
+                           WindowError::OutOfRange)));
+        });
+    }
+    #[test]
+    fn push_and_pop_window() {
+        use crate::fake_device::{FakeDevice, Wire::*};
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(device.clone());
+        let rect = Rect::new(2, 3, 4, 5);
+        block_on(async {
+            assert_eq!(cmds.push_window(rect).await,
+                       Err(WindowStackError::Unknown));
+            assert_eq!(cmds.pop_window().await, Err(WindowStackError::Empty));
+            cmds.caset(0, 127).await;
+            cmds.raset(0, 159).await;
+            device.clear();
+
+            assert_eq!(cmds.push_window(rect).await, Ok(()));
+            assert_eq!(cmds.window(), Some(rect));
+            assert_eq!(cmds.pop_window().await, Ok(()));
+        });
+        assert_eq!(device.seq(), [
+            Command(0x2A), Data(0), Data(2), Data(0), Data(5),
+            Command(0x2B), Data(0), Data(3), Data(0), Data(7),
+            Command(0x2A), Data(0), Data(0), Data(0), Data(127),
+            Command(0x2B), Data(0), Data(0), Data(0), Data(159)]);
+        assert_eq!(cmds.window(), Some(Rect::new(0, 0, 128, 160)));
+    }
+    #[test]
+    fn ramwr_rewindow() {
+        use crate::fake_device::{FakeDevice, Wire::*};
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(device.clone());
+        block_on(async {
+            let mut w = cmds.ramwr().await;
+            w.write_u8s(&[0xAB]).await;
+            assert_eq!(w.rewindow(Rect::new(1, 2, 3, 4)).await, Ok(()));
+            w.write_u8s(&[0xCD]).await;
+            assert_eq!(w.rewindow(Rect::new(1, 2, 0, 4)).await,
+                       Err(WindowError::Reversed));
+            assert_eq!(w.rewindow(Rect::new(u16::MAX, 2, 2, 1)).await,
+                       Err(WindowError::OutOfRange));
+        });
+        assert_eq!(device.seq(), [
+            Command(0x2C), Data(0xAB),
+            Command(0x2A), Data(0), Data(1), Data(0), Data(3),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(5),
+            Command(0x2C), Data(0xCD)]);
+        assert_eq!(cmds.window(), Some(Rect::new(1, 2, 3, 4)));
+    }
+    #[test]
+    fn ramwr_u16_slice() {
+        use std::vec::Vec;
+        use crate::adapters::AdapterU16;
+        use crate::fake_device::{FakeDevice, Wire::*};
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(AdapterU16::new(device.clone()));
+        block_on(async {
+            let mut w = cmds.ramwr().await;
+            w.write_u16_slice(&[0xF800, 0x07E0], Endianness::Big).await;
+            w.write_u16_slice(&[0x00F8, 0xE007], Endianness::Little).await;
+        });
+        assert_eq!(device.seq(), [
+            Command(0x2C), Data(0xF8), Data(0x00), Data(0x07), Data(0xE0),
+            Data(0xF8), Data(0x00), Data(0x07), Data(0xE0)]);
+        // More than a buffer of swapped words.
+        device.clear();
+        let words: Vec<u16> = (0..40).map(|i| i << 8 | 0x80).collect();
+        block_on(async {
+            cmds.ramwr().await
+                .write_u16_slice(&words, Endianness::Little).await;
+        });
+        let seq = device.seq();
+        assert_eq!(seq.len(), 1 + 80);
+        assert_eq!(seq[79..], [Data(0x80), Data(39)]);
+    }
+    #[test]
+    fn push_window_full() {
+        let mut cmds = Commands::new(crate::fake_device::FakeDevice::new());
+        block_on(async {
+            cmds.set_window_const::<crate::panel::Window<0, 0, 9, 9>>().await;
+            for i in 0..WINDOW_STACK_DEPTH as u16 {
+                let rect = Rect::new(i, i, 1, 1);
+                assert_eq!(cmds.push_window(rect).await, Ok(()));
+            }
+            assert_eq!(cmds.push_window(Rect::new(0, 0, 1, 1)).await,
+                       Err(WindowStackError::Full));
+            assert_eq!(cmds.window(), Some(Rect::new(3, 3, 1, 1)));
+            for _ in 0..WINDOW_STACK_DEPTH {
+                assert_eq!(cmds.pop_window().await, Ok(()));
+            }
+        });
+        assert_eq!(cmds.window(), Some(Rect::new(0, 0, 10, 10)));
+        cmds.reset_state();
+        assert_eq!(cmds.window(), None);
+    }
+    #[test]
+    fn ramwr() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0x01, 0x23, 0x45, 0x67, 0x89, 0xAB, 0xCD]);
+        block_on(async {
+            let mut rw = cmds.ramwr().await;
+            rw.write_u8(0x01).await;
+            rw.write_u8s(&[0x23, 0x45]).await;
+            rw.write_u8s(&[]).await;
+            rw.write_u8s(&[0x67, 0x89, 0xAB, 0xCD]).await;
+        });
+    }
+    #[test]
+    fn rgbset() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2D, &[0x35; 128]);
+        block_on(async {
+            let mut rw = cmds.rgbset().await;
+            rw.write_u8(0x35).await;
+            rw.write_u8s(&[0x35; 27]).await;
+            rw.write_u8s(&[0x35; 50]).await;
+            rw.write_u8s(&[0x35; 50]).await;
+        });
+    }
+    #[test]
+    fn ramwr_write_pixels() {
+        use crate::color::Rgb565;
+        use crate::scan::scan_order;
+        let mut mctl = Madctl::default();
+        mctl.set_row_address_order(RowOrder::TopToBottom)
+            .set_column_address_order(ColumnOrder::RightToLeft)
+            .set_row_column_swap(RowColumnSwap::Unswapped);
+        let image = [Rgb565::from_raw(0x0102), Rgb565::from_raw(0x0304),
+                     Rgb565::from_raw(0x0506), Rgb565::from_raw(0x0708)];
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0x03, 0x04, 0x01, 0x02, 0x07, 0x08, 0x05, 0x06]);
+        block_on(async {
+            let mut rw = cmds.ramwr().await;
+            rw.write_pixels(scan_order(&image, 2, 2, mctl)).await;
+        });
+    }
+    #[test]
+    fn ramwr_write_pixels_with() {
+        use crate::color::{Rgb666, Rgb666Lut};
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0x7C, 0x00, 0x04, 0x00, 0x00, 0x00]);
+        block_on(async {
+            let mut rw = cmds.ramwr().await;
+            let dim = Rgb666Lut::brightness(128);
+            rw.write_pixels_with([Rgb666::new(0x3F, 0, 3), Rgb666::default()],
+                                 &dim).await;
+        });
+    }
+    #[test]
+    fn ramwr_write_rgb444() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0x12, 0x34, 0x56, 0x78, 0x90]);
+        block_on(async {
+            cmds.ramwr().await.write_rgb444([
+                Rgb444::new(1, 2, 3), Rgb444::new(4, 5, 6),
+                Rgb444::new(7, 8, 9)]).await;
+        });
+    }
+
+    #[test]
+    fn ramwr_write_rgb888_as() {
+        let pixels = [Rgb888::new(0xFF, 0x84, 0x0F); 2];
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2C, &[0xF8, 0x0F, 0x80]);
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0xFC, 0x21, 0xFC, 0x21]);
+        cmds.spi.expect_standard_write_command(
+            0x2C, &[0xFC, 0x84, 0x0C, 0xFC, 0x84, 0x0C]);
+        block_on(async {
+            for colmod in [Colmod::R4G4B4, Colmod::R5G6B5, Colmod::R6G6B6] {
+                cmds.ramwr().await.write_rgb888_as(colmod, pixels).await;
+            }
+        });
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn fill_rect() {
+        use crate::color::Rgb565;
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2A, &[0, 1, 0, 2]);
+        cmds.spi.expect_standard_write_command(0x2B, &[0, 3, 0, 5]);
+        cmds.spi.expect_standard_write_command(0x2C, &[0xAB, 0xCD].repeat(6));
+        let color = Rgb565::from_raw(0xABCD);
+        assert_eq!(block_on(cmds.fill_rect(1, 3, 2, 3, color)), Ok(()));
+    }
+
+    #[test]
+    fn fill_chunked() {
+        use crate::color::Rgb666;
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        let color = Rgb666::new(1, 2, 3);
+        // 17 pixels of 3 bytes take two chunks.
+        block_on(cmds.fill(Rect::new(0, 0, 17, 1), color)).unwrap();
+        let log = log.lock().unwrap();
+        assert_eq!(log[10], Command(0x2C));
+        assert_eq!(log.len(), 11 + 17 * 3);
+        assert!(log[11..].chunks(3).all(|p| p == [Data(4), Data(8), Data(12)]));
+    }
+
+    #[test]
+    fn fill_rejects() {
+        use crate::color::Rgb565;
+        let mut cmds = create_mock();
+        let color = Rgb565::default();
+        for rect in [Rect::new(0, 0, 0, 1), Rect::new(0, 0, 1, 0)] {
+            assert_eq!(block_on(cmds.fill(rect, color)),
+                       Err(WindowError::Reversed));
+            assert_eq!(block_on(cmds.fill_repeated(rect, color)),
+                       Err(WindowError::Reversed));
+        }
+        for rect in [Rect::new(u16::MAX, 0, 2, 1),
+                     Rect::new(0, 2, 1, u16::MAX)] {
+            assert_eq!(block_on(cmds.fill(rect, color)),
+                       Err(WindowError::OutOfRange));
+            assert_eq!(block_on(cmds.fill_repeated(rect, color)),
+                       Err(WindowError::OutOfRange));
+        }
+    }
+    #[test]
+    fn ramwr_write_pixels_yielding() {
+        use core::future::Future as _;
+        use core::task::{Context, Waker};
+        use crate::color::Rgb565;
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2C, &[0x12, 0x34].repeat(5));
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut pendings = 0;
+        let mut f = std::boxed::Box::pin(async {
+            let mut rw = cmds.ramwr().await;
+            rw.write_pixels_yielding([Rgb565::from_raw(0x1234); 5], 2).await;
+        });
+        while f.as_mut().poll(&mut cx).is_pending() { pendings += 1; }
+        assert_eq!(pendings, 2);
+    }
+    #[test]
+    fn ramwr_write_chunked() {
+        let mut cmds = create_mock();
+        let data: std::vec::Vec<u8> = (0..10).collect();
+        cmds.spi.expect_standard_write_command(0x2C, &data);
+        let mut reports = std::vec::Vec::new();
+        block_on(async {
+            let mut rw = cmds.ramwr().await;
+            rw.write_chunked(&data, 4, &mut |n| reports.push(n)).await;
+        });
+        assert_eq!(reports, [4, 8, 10]);
+    }
+    #[test]
+    fn ramwr_write_chunked_preferred() {
+        use core::future::{ready, Ready};
+        /// A FIFO with room for 3 bytes, then 2, alternately.
+        #[derive(Default)]
+        struct Fifo { writes: std::vec::Vec<usize> }
+        impl DcxPin for Fifo {
+            fn set_dcx_command_mode(&mut self) {}
+            fn set_dcx_data_mode(&mut self) {}
+        }
+        impl<'a> WriteU8<'a> for Fifo {
+            type WriteU8Done = Ready<()>;
+            fn write_u8(&'a mut self, _: u8) -> Ready<()> { ready(()) }
+        }
+        impl<'a> WriteU8s<'a> for Fifo {
+            type WriteU8sDone = Ready<()>;
+            fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+                self.writes.push(data.len());
+                ready(())
+            }
+            fn preferred_chunk(&self) -> usize { 3 - self.writes.len() % 2 }
+        }
+
+        let mut cmds = Commands::new(Fifo::default());
+        let mut reports = std::vec::Vec::new();
+        block_on(async {
+            let mut rw = cmds.ramwr().await;
+            rw.write_chunked(&[0; 10], 4, &mut |n| reports.push(n)).await;
+        });
+        assert_eq!(cmds.spi.writes, [3, 2, 3, 2]);
+        assert_eq!(reports, [3, 5, 8, 10]);
+    }
+    #[test]
+    fn ramwr_write_pixels_with_progress() {
+        use crate::color::Rgb565;
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2C, &[0x12, 0x34].repeat(40));
+        cmds.spi.expect_standard_write_command(0x2C, &[0x12, 0x34].repeat(40));
+        let mut reports = std::vec::Vec::new();
+        block_on(async {
+            // 80 bytes, in chunks of 64 and 16.
+            for every in [3, 100] {
+                let mut rw = cmds.ramwr().await;
+                rw.write_pixels_with_progress(
+                    [Rgb565::from_raw(0x1234); 40], every,
+                    &mut |n| reports.push(n)).await;
+            }
+        });
+        assert_eq!(reports, [64, 80, 80]);
+    }
+    test_simple_write!(ptlar(0x1357, 0x2468), code: 0x30,
+                       data: &[0x13, 0x57, 0x24, 0x68]);
+    test_simple_write!(scrlar(0x2143, 0x3254, 0x4365), code: 0x33,
+                       data: &[0x21, 0x43, 0x32, 0x54, 0x43, 0x65]);
+    #[test]
+    fn command_with_args() {
+        let mut cmds = create_mock();
+        let mut data = std::vec::Vec::from([0x12, 0x34, 0x56]);
+        for i in 0..10u8 { data.extend([i, 0xA0 + i]); }
+        cmds.spi.expect_standard_write_command(0xB1, &data);
+        let args = [Param::U16(0x1234), Param::U8(0x56)].iter().copied()
+            .chain((0..10).map(|i| Param::from(u16::from_be_bytes(
+                [i, 0xA0 + i]))));
+        block_on(cmds.command_with_args(0xB1, args));
+        assert!(!cmds.spi.is_data_mode());
+    }
+    test_simple_write!(teoff(), code: 0x34, data: &[]);
+    #[test]
+    #[allow(deprecated)]
+    fn teon_mode0() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x35, &[0x00]);
+        block_on(cmds.teon(false));
+    }
+    #[test]
+    #[allow(deprecated)]
+    fn teon_mode1() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x35, &[0x01]);
+        block_on(cmds.teon(true));
+    }
+    test_simple_write_with_name!(
+        teon_vblank, teon_mode(TeMode::VBlank), code: 0x35, data: &[0x00]);
+    test_simple_write_with_name!(
+        teon_vblank_and_hblank, teon_mode(TeMode::VBlankAndHBlank),
+        code: 0x35, data: &[0x01]);
+    #[test]
+    fn madctl_test0() {
+        use crate::command_structs::{
+            Madctl, RowOrder, ColumnOrder, RowColumnSwap, ColorComponentOrder};
+        let mut mctl = Madctl::default();
+        mctl.set_row_address_order(RowOrder::TopToBottom)
+            .set_column_address_order(ColumnOrder::LeftToRight)
+            .set_row_column_swap(RowColumnSwap::Swapped)
+            .set_vertical_refresh_order(RowOrder::BottomToTop)
+            .set_horizontal_refresh_order(ColumnOrder::RightToLeft)
+            .set_rgb_order(ColorComponentOrder::BlueGreenRed);
+
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x36, &[0x3C]);
+        block_on(cmds.madctl(mctl));
+    }
+    #[test]
+    fn madctl_test1() {
+        use crate::command_structs::{
+            Madctl, RowOrder, ColumnOrder, RowColumnSwap, ColorComponentOrder};
+        let mut mctl = Madctl::default();
+        mctl.set_row_address_order(RowOrder::BottomToTop)
+            .set_column_address_order(ColumnOrder::RightToLeft)
+            .set_row_column_swap(RowColumnSwap::Unswapped)
+            .set_vertical_refresh_order(RowOrder::TopToBottom)
+            .set_horizontal_refresh_order(ColumnOrder::LeftToRight)
+            .set_rgb_order(ColorComponentOrder::RedGreenBlue);
+
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x36, &[0xC0]);
+        block_on(cmds.madctl(mctl));
+    }
+    test_simple_write!(vscsad(0x1234), code: 0x37, data: &[0x12, 0x34]);
+    test_simple_write!(idmoff(), code: 0x38, data: &[]);
+    test_simple_write!(idmon(), code: 0x39, data: &[]);
+    test_simple_write_with_name!(colmod_r4g4b4, colmod(Colmod::R4G4B4),
+                                 code: 0x3A, data: &[0b011]);
+    test_simple_write_with_name!(colmod_r5g6b5, colmod(Colmod::R5G6B5),
+                                 code: 0x3A, data: &[0b101]);
+    test_simple_write_with_name!(colmod_r6g6b6, colmod(Colmod::R6G6B6),
+                                 code: 0x3A, data: &[0b110]);
+    #[cfg(feature = "dedup")]
+    #[test]
+    fn dedup() {
+        use crate::command_structs::{ColorComponentOrder, Madctl};
+        use crate::fake_device::{FakeDevice, Wire::*};
+        let rgb = Madctl::default();
+        let mut bgr = Madctl::default();
+        bgr.set_rgb_order(ColorComponentOrder::BlueGreenRed);
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(device.clone());
+
+        // Off by default.
+        block_on(cmds.madctl(rgb));
+        block_on(cmds.madctl(rgb));
+        assert_eq!(device.seq(), [Command(0x36), Data(0x00),
+                                  Command(0x36), Data(0x00)]);
+        device.clear();
+
+        cmds.set_dedup(true);
+        block_on(cmds.madctl(rgb));
+        block_on(cmds.colmod(Colmod::R5G6B5));
+        block_on(cmds.madctl(bgr));
+        block_on(cmds.colmod(Colmod::R5G6B5));
+        block_on(cmds.madctl(bgr));
+        assert_eq!(device.seq(), [Command(0x3A), Data(0b101),
+                                  Command(0x36), Data(0x08)]);
+        device.clear();
+
+        cmds.invalidate();
+        block_on(cmds.madctl(bgr));
+        block_on(cmds.swreset());
+        block_on(cmds.colmod(Colmod::R5G6B5));
+        // Another path to the register.
+        block_on(cmds.command_with_args(0x3A, [Param::U8(0b110)]
+                                              .iter().copied()));
+        block_on(cmds.colmod(Colmod::R5G6B5));
+        assert_eq!(device.seq(), [Command(0x36), Data(0x08), Command(0x01),
+                                  Command(0x3A), Data(0b101),
+                                  Command(0x3A), Data(0b110),
+                                  Command(0x3A), Data(0b101)]);
+    }
+    #[test]
+    fn st7789_profile() {
+        let mut cmds = create_mock();
+        cmds.set_profile(ControllerProfile::ST7789);
+        cmds.spi.expect_standard_write_command(0x3A, &[0x55]);
+        cmds.spi.expect_standard_write_command(0x2A, &[0, 200, 0, 239]);
+        cmds.spi.expect_standard_write_command(0x2B, &[1, 44, 1, 63]);
+        block_on(async {
+            cmds.colmod(Colmod::R5G6B5).await;
+            cmds.caset_clamped(200, 300).await;
+            cmds.raset_clamped(300, 400).await;
+        });
+    }
+
+    // Panel functions skipped.
+
+    test_simple_write!(
+        frmctr1(FrameRate::default()), code: 0xB1, data: &[0x01, 0x2C, 0x2D]);
+
+    #[test]
+    fn state_mirrors_commands() {
+        use crate::display::tests::{FakeDelay, Log, logging_device};
+        use crate::ops::{Op, run_ops};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        assert_eq!(cmds.state(), PanelState::empty());
+        block_on(async {
+            cmds.slpout().await;
+            cmds.dispon().await;
+            cmds.invon().await;
+            cmds.enter_low_power(PartialArea{start: 0, end: 9},
+                                 IdleMode::Idle).await;
+        });
+        assert_eq!(cmds.state(),
+                   PanelState::SLEEP_OUT | PanelState::DISPLAY_ON |
+                   PanelState::INVERSION_ON | PanelState::PARTIAL_ON |
+                   PanelState::IDLE_ON);
+        block_on(async {
+            cmds.exit_low_power().await;
+            cmds.invoff().await;
+        });
+        assert_eq!(cmds.state(),
+                   PanelState::SLEEP_OUT | PanelState::DISPLAY_ON);
+        // Command tables are tracked too.
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_ops(&mut cmds, &[Op::Cmd(0x28), Op::Cmd(0x10)],
+                         &mut delay));
+        assert_eq!(cmds.state(), PanelState::empty());
+        block_on(cmds.slpout());
+        cmds.reset_state();
+        assert_eq!(cmds.state(), PanelState::empty());
+    }
+
+    #[test]
+    fn ram_access_ordering() {
+        let mut cmds = create_mock();
+        cmds.spi.mock().expect_write_command().return_const(());
+        assert_eq!(cmds.check_ram_access(), Err(OrderingError::Asleep));
+        assert!(block_on(cmds.try_ramwr()).is_err());
+        block_on(cmds.slpout());
+        assert_eq!(cmds.check_ram_access(), Err(OrderingError::WakingUp));
+        assert!(block_on(cmds.try_rgbset()).is_err());
+        cmds.mark_awake();
+        assert_eq!(cmds.check_ram_access(), Ok(()));
+        assert!(block_on(cmds.try_ramwr()).is_ok());
+        block_on(cmds.slpin());
+        assert_eq!(cmds.check_ram_access(), Err(OrderingError::Asleep));
+    }
+
+    #[test]
+    #[should_panic(expected = "Memory written out of order: WakingUp.")]
+    fn strict_ordering() {
+        let mut cmds = create_mock();
+        cmds.spi.mock().expect_write_command().return_const(());
+        cmds.set_strict_ordering(true);
+        block_on(async {
+            cmds.slpout().await;
+            cmds.ramwr().await;
+        });
+    }
+
+    #[test]
+    fn apply_config() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        let config = PanelConfig{
+            madctl: Madctl::from(0xC8),
+            colmod: Some(Colmod::R5G6B5),
+            gamma: GammaConfig::Predefined(GammaCurve::Curve2),
+            frame_rate: Some(FrameRate{
+                rtna: 0x05, front_porch: 0x3C, back_porch: 0x3C}),
+            power: Some(PowerProfile{
+                pwctr1: [0xA2, 0x02, 0x84], pwctr2: 0xC5,
+                pwctr3: [0x0A, 0x00], pwctr4: [0x8A, 0x2A],
+                pwctr5: [0x8A, 0xEE], vmctr1: 0x0E}),
+            window: Some(Rect::new(2, 1, 128, 160)),
+            scroll: Some(ScrollConfig{
+                top_fixed: 0, scroll_area: 160, bottom_fixed: 0, start: 7}),
+        };
+        block_on(cmds.apply_config(&config));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0xC0), Data(0xA2), Data(0x02), Data(0x84),
+            Command(0xC1), Data(0xC5),
+            Command(0xC2), Data(0x0A), Data(0x00),
+            Command(0xC3), Data(0x8A), Data(0x2A),
+            Command(0xC4), Data(0x8A), Data(0xEE),
+            Command(0xC5), Data(0x0E),
+            Command(0xB1), Data(0x05), Data(0x3C), Data(0x3C),
+            Command(0x3A), Data(0b101),
+            Command(0x36), Data(0xC8),
+            Command(0x26), Data(0b0010),
+            Command(0x33), Data(0), Data(0), Data(0), Data(160), Data(0),
+            Data(0),
+            Command(0x37), Data(0), Data(7),
+            Command(0x2A), Data(0), Data(2), Data(0), Data(129),
+            Command(0x2B), Data(0), Data(1), Data(0), Data(160)]);
+    }
+
+    #[test]
+    fn apply_default_config() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        let config = PanelConfig{
+            window: Some(Rect::new(0, 0, 0, 1)), ..Default::default()};
+        block_on(cmds.apply_config(&config));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x36), Data(0x00), Command(0x26), Data(0b0001)]);
+    }
+
+    #[test]
+    fn enter_low_power() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        block_on(cmds.enter_low_power(PartialArea{start: 0x10, end: 0x120},
+                                      IdleMode::Idle));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x30), Data(0x00), Data(0x10), Data(0x01), Data(0x20),
+            Command(0x12), Command(0x39)]);
+    }
+
+    #[test]
+    fn enter_low_power_full_color() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        block_on(cmds.enter_low_power(PartialArea{start: 1, end: 2},
+                                      IdleMode::FullColor));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x30), Data(0), Data(1), Data(0), Data(2), Command(0x12)]);
+    }
+
+    test_simple_write_with_name!(
+        apply_gamma_predefined,
+        apply_gamma(GammaConfig::Predefined(GammaCurve::Curve2)),
+        code: 0x26, data: &[0x02]);
+    #[test]
+    fn apply_gamma_custom() {
+        let positive = [0x11; 16];
+        let negative = [0x22; 16];
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x26, &[0x01]);
+        cmds.spi.expect_standard_write_command(0xE0, &positive);
+        cmds.spi.expect_standard_write_command(0xE1, &negative);
+        block_on(cmds.apply_gamma(GammaConfig::Custom{positive, negative}));
+    }
+    #[test]
+    fn animate_scroll() {
+        use crate::display::tests::{Event::*, FakeTe, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        let mut te = FakeTe(log.clone());
+        block_on(cmds.animate_scroll(10, 3, 4, &mut te));
+        assert_eq!(*log.lock().unwrap(), [
+            Vblank, Command(0x37), Data(0), Data(10),
+            Vblank, Command(0x37), Data(0), Data(6),
+            Vblank, Command(0x37), Data(0), Data(3)]);
+        log.lock().unwrap().clear();
+        block_on(cmds.animate_scroll(3, 300, 0, &mut te));
+        assert_eq!(*log.lock().unwrap(), [
+            Vblank, Command(0x37), Data(1), Data(44)]);
+    }
+    #[test]
+    fn refresh_region() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        let panel = Panel::new(4, 2).with_offsets(1, 2);
+        let madctl = Madctl::default();
+        let source = |x: u16, y: u16| Rgb565::from_raw(y << 8 | x);
+
+        let small = Rect::new(1, 1, 2, 1);
+        assert_eq!(block_on(cmds.refresh_region(&panel, madctl, small,
+                                                &source)),
+                   Ok(small));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x30), Data(0), Data(3), Data(0), Data(3),
+            Command(0x12),
+            Command(0x2A), Data(0), Data(2), Data(0), Data(3),
+            Command(0x2B), Data(0), Data(3), Data(0), Data(3),
+            Command(0x2C), Data(1), Data(1), Data(1), Data(2),
+            Command(0x13)]);
+
+        // The whole panel, in the normal mode.
+        log.lock().unwrap().clear();
+        assert_eq!(block_on(cmds.refresh_region(
+                       &panel, madctl, Rect::new(0, 0, 3, 2), &source)),
+                   Ok(Rect::new(0, 0, 4, 2)));
+        let log_len = log.lock().unwrap().len();
+        assert_eq!(log_len, 11 + 2 * 8);
+        assert_eq!(log.lock().unwrap()[0], Command(0x2A));
+
+        log.lock().unwrap().clear();
+        assert_eq!(block_on(cmds.refresh_region(
+                       &panel, madctl, Rect::new(3, 0, 2, 1), &source)),
+                   Err(WindowError::OutOfRange));
+        assert_eq!(block_on(cmds.refresh_region(
+                       &panel, madctl, Rect::new(1, 1, 0, 1), &source)),
+                   Ok(Rect::new(1, 1, 0, 1)));
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn refresh_region_swapped() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        // Addressed as 2x4 with the offsets (2, 1).
+        let panel = Panel::new(4, 2).with_offsets(1, 2);
+        let mut madctl = Madctl::default();
+        madctl.set_row_column_swap(RowColumnSwap::Swapped);
+        let source = |x: u16, y: u16| Rgb565::from_raw(y << 8 | x);
+
+        let rect = Rect::new(1, 1, 1, 2);
+        assert_eq!(block_on(cmds.refresh_region(&panel, madctl, rect,
+                                                &source)),
+                   Ok(rect));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x30), Data(0), Data(3), Data(0), Data(3),
+            Command(0x12),
+            Command(0x2A), Data(0), Data(3), Data(0), Data(3),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(3),
+            Command(0x2C), Data(1), Data(1), Data(2), Data(1),
+            Command(0x13)]);
+        assert_eq!(block_on(cmds.refresh_region(
+                       &panel, madctl, Rect::new(0, 3, 2, 2), &source)),
+                   Err(WindowError::OutOfRange));
+    }
+
+    #[test]
+    fn exit_low_power() {
+        use crate::display::tests::{Event::*, Log, logging_device};
+        let log = Log::default();
+        let mut cmds = Commands::new(logging_device(&log));
+        block_on(cmds.exit_low_power());
+        assert_eq!(*log.lock().unwrap(), [Command(0x38), Command(0x13)]);
+    }
+
+    impl Commands<MockDevice> {
+        fn mock(&mut self) -> &mut MockPlainIO {
+            self.spi.mock()
+        }
+    }
+
+    fn create_mock() -> Commands<MockDevice> {
+        Commands::new(Default::default())
+    }
+
+    fn set_read_command_expectations(
+            mock: &mut MockPlainIO, code: u8, bits: &str) {
+        let bits = bits.replace('_', "");
+        let mut seq = Sequence::new();
+        mock.expect_write_command()
+            .with(predicate::eq(code))
+            .times(1)
+            .in_sequence(&mut seq);
+        mock.expect_start_reading()
+            .times(1)
+            .in_sequence(&mut seq);
+        for c in bits.chars() {
+            mock.expect_read_bit()
+                .times(1)
+                .in_sequence(&mut seq)
+                .returning(move || c != '0');
+        }
+        mock.expect_finish_reading()
+            .times(1)
+            .in_sequence(&mut seq);
+    }
+
+    #[test]
+    fn command_futures_are_send() {
+        fn assert_send<T: Send>(_: &T) {}
+        let mut cmds = create_mock();
+        assert_send(&cmds.dispon());
+        assert_send(&cmds.caset(0, 1));
+        assert_send(&cmds.madctl(Madctl::default()));
+    }
+
+    #[test]
+    fn rdid1() {
+        let mut cmds = create_mock();
+        const DATA: u8 = 0b10100110;
+        set_read_command_expectations(
+                cmds.mock(), 0xDA, &std::format!("{:08b}", DATA));
+        let v = block_on(cmds.rdid1());
+        assert_eq!(v, DATA);
+        // Left to SpeedSwitching.
+        assert_eq!(cmds.spi.speed_switches(), 0);
+    }
+
+    #[test]
+    fn rdid2() {
+        let mut cmds = create_mock();
+        const DATA: u8 = 0b01010111;
+        set_read_command_expectations(
+                cmds.mock(), 0xDB, &std::format!("{:08b}", DATA));
+        let v = block_on(cmds.rdid2());
+        assert_eq!(v, DATA);
+    }
+
+    #[test]
+    fn rdid3() {
+        let mut cmds = create_mock();
+        const DATA: u8 = 0b01100111;
+        set_read_command_expectations(
+                cmds.mock(), 0xDC, &std::format!("{:08b}", DATA));
+        let v = block_on(cmds.rdid3());
+        assert_eq!(v, DATA);
+    }
+
+    #[test]
+    fn ramrd() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(
+                cmds.mock(), 0x2E,
+                "1\
+                 111111_00_000001_00_100000_11_\
+                 000000_11_111111_00_010101_00");
+        let mut pixels = [Rgb666::default(); 2];
+        block_on(cmds.ramrd(&mut pixels));
+        // Left to SpeedSwitching.
+        assert_eq!(cmds.spi.speed_switches(), 0);
+        assert_eq!(pixels, [Rgb666::new(0x3F, 0x01, 0x20),
+                            Rgb666::new(0x00, 0x3F, 0x15)]);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn read_rect() {
+        let mut cmds = create_mock();
+        cmds.spi.expect_standard_write_command(0x2A, &[0, 5, 0, 6]);
+        cmds.spi.expect_standard_write_command(0x2B, &[0, 7, 0, 7]);
+        set_read_command_expectations(
+                cmds.mock(), 0x2E,
+                "0\
+                 111110_00_000001_00_100000_00_\
+                 000010_00_111111_00_010101_00");
+        let mut pixels = [Rgb565::default(); 2];
+        assert_eq!(block_on(cmds.read_rect(5, 7, 2, 1, &mut pixels)), Ok(()));
+        assert_eq!(pixels, [Rgb565::new(0x1F, 0x01, 0x10),
+                            Rgb565::new(0x01, 0x3F, 0x0A)]);
+    }
+
+    #[test]
+    fn read_pixels_rejects() {
+        let mut cmds = create_mock();
+        assert_eq!(block_on(cmds.read_pixels(Rect::new(5, 7, 0, 1), &mut [])),
+                   Err(WindowError::Reversed));
+        let mut pixels = [Rgb565::default(); 2];
+        assert_eq!(block_on(cmds.read_pixels(Rect::new(u16::MAX, 7, 2, 1),
+                                             &mut pixels)),
+                   Err(WindowError::OutOfRange));
+    }
+
+    #[test]
+    fn rddid() {
+        let mut cmds = create_mock();
+        const DATA_U32: u32 = 0b0_11110000_11010010_01100001;
+        const DATA_ARR: [u8; 3] = [0b11110000, 0b11010010, 0b01100001];
+        set_read_command_expectations(
+                cmds.mock(), 0x04, &std::format!("{:25b}", DATA_U32));
+        let v = block_on(cmds.rddid());
+        assert_eq!(v, DATA_ARR);
+    }
+
+    #[test]
+    fn rddpm() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(cmds.mock(), 0x0A, "1001_1100");
+        let pm = block_on(cmds.rddpm());
+        assert!(pm.contains(PowerMode::SLEEP_OUT | PowerMode::DISPLAY_ON));
+        assert!(!pm.contains(PowerMode::IDLE_ON));
+    }
+
+    #[test]
+    fn rddmadctl() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(cmds.mock(), 0x0B, "1100_1000");
+        assert_eq!(u8::from(block_on(cmds.rddmadctl())), 0xC8);
+    }
+
+    #[test]
+    fn rddcolmod() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(cmds.mock(), 0x0C, "0000_0110");
+        assert_eq!(block_on(cmds.rddcolmod()), Colmod::R6G6B6);
+    }
+
+    #[test]
+    fn read_config() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(cmds.mock(), 0x0B, "0110_0000");
+        set_read_command_expectations(cmds.mock(), 0x0C, "0101_0101");
+        set_read_command_expectations(cmds.mock(), 0x0D, "0010_0010");
+        let config = block_on(cmds.read_config());
+        assert_eq!(u8::from(config.madctl), 0x60);
+        assert_eq!(config, PanelConfig{
+            madctl: config.madctl, colmod: Some(Colmod::R5G6B5),
+            gamma: GammaConfig::Predefined(GammaCurve::Curve3),
+            ..Default::default()});
+    }
+
+    #[test]
+    fn rddim() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(cmds.mock(), 0x0D, "0010_0000");
+        assert_eq!(block_on(cmds.rddim()), ImageMode::INVERSION_ON);
+    }
+
+    #[test]
+    fn read_ids_robust_rddid() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(
+                cmds.mock(), 0x04, "0_01111100_10000011_00001111");
+        assert_eq!(block_on(cmds.read_ids_robust()),
+                   Some((IdSource::Rddid, [0x7C, 0x83, 0x0F])));
+    }
+
+    #[test]
+    fn read_ids_robust_fallback() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(
+                cmds.mock(), 0x04, "0_11111111_01111111_11111111");
+        set_read_command_expectations(cmds.mock(), 0xDA, "01111100");
+        set_read_command_expectations(cmds.mock(), 0xDB, "10000011");
+        set_read_command_expectations(cmds.mock(), 0xDC, "00001111");
+        assert_eq!(block_on(cmds.read_ids_robust()),
+                   Some((IdSource::Rdid123, [0x7C, 0x83, 0x0F])));
+    }
+
+    #[test]
+    fn read_ids_robust_none() {
+        let mut cmds = create_mock();
+        set_read_command_expectations(
+                cmds.mock(), 0x04, "0_00000000_00000000_00000000");
+        for code in [0xDA, 0xDB, 0xDC] {
+            set_read_command_expectations(cmds.mock(), code, "00000000");
+        }
+        assert_eq!(block_on(cmds.read_ids_robust()), None);
+    }
+
+}  // mod tests
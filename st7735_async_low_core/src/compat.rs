@@ -0,0 +1,103 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The deprecated APIs and their typed replacements, which send the same
+//! bytes, as tested here:
+//!
+//! * [Commands::teon()] taking a `bool`: [Commands::teon_mode()] taking a
+//!   [TeMode].
+//! * [Commands::fill_rect()] taking `x, y, w, h`: [Commands::fill()] taking
+//!   a [Rect](crate::panel::Rect).
+//! * [Commands::read_rect()] taking `x, y, w, h`: [Commands::read_pixels()]
+//!   taking a [Rect](crate::panel::Rect).
+//!
+//! [Commands::teon()]: crate::Commands::teon
+//! [Commands::teon_mode()]: crate::Commands::teon_mode
+//! [TeMode]: crate::TeMode
+//! [Commands::fill_rect()]: crate::Commands::fill_rect
+//! [Commands::fill()]: crate::Commands::fill
+//! [Commands::read_rect()]: crate::Commands::read_rect
+//! [Commands::read_pixels()]: crate::Commands::read_pixels
+
+#[cfg(test)]
+#[allow(deprecated)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::Commands;
+    use crate::color::Rgb565;
+    use crate::command_structs::TeMode;
+    use crate::fake_device::{FakeDevice, Wire};
+    use crate::panel::Rect;
+    use crate::testing_device::block_on;
+
+    /// The deprecated methods covered below, as listed in the changelog.
+    const COVERED: &[&str] = &["teon", "fill_rect", "read_rect"];
+
+    /// The wire of `f` run on a fresh [Commands].
+    fn wire<F>(f: F) -> Vec<Wire>
+            where F: FnOnce(&mut Commands<FakeDevice>) {
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(device.clone());
+        f(&mut cmds);
+        device.seq()
+    }
+
+    #[test]
+    fn teon() {
+        for (old, new) in [(false, TeMode::VBlank),
+                           (true, TeMode::VBlankAndHBlank)] {
+            assert_eq!(wire(|c| block_on(c.teon(old))),
+                       wire(|c| block_on(c.teon_mode(new))));
+        }
+    }
+
+    #[test]
+    fn fill_rect() {
+        let color = Rgb565::from_raw(0x1234);
+        for (x, y, w, h) in [(1, 2, 3, 4), (5, 6, 0, 2), (7, 8, 2, 0)] {
+            assert_eq!(
+                wire(|c| {
+                    block_on(c.fill_rect(x, y, w, h, color)).ok();
+                }),
+                wire(|c| {
+                    block_on(c.fill(Rect::new(x, y, w, h), color)).ok();
+                }));
+        }
+    }
+
+    #[test]
+    fn read_rect() {
+        let mut out = [Rgb565::default(); 6];
+        assert_eq!(
+            wire(|c| {
+                block_on(c.read_rect(1, 2, 3, 2, &mut out)).ok();
+            }),
+            wire(|c| {
+                block_on(c.read_pixels(Rect::new(1, 2, 3, 2), &mut out)).ok();
+            }));
+    }
+
+    #[test]
+    fn changelog_lists_covered() {
+        let changelog = include_str!("../../CHANGELOG.md");
+        let section = changelog.split("### Deprecated").nth(1).unwrap();
+        let deprecated: Vec<&str> = section.split("\n#").next().unwrap()
+            .lines()
+            .filter_map(|l| l.strip_prefix("- `Commands::"))
+            .map(|l| l.split("()").next().unwrap())
+            .collect();
+        assert_eq!(deprecated, COVERED);
+    }
+}  // mod tests
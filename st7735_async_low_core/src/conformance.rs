@@ -0,0 +1,258 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Checks of the contract of the write traits in [crate::spi] on empty
+//! writes, for implementations to run in their own tests.
+//!
+//! Writing nothing, i.e., [WriteU8s::write_u8s()] or
+//! [WriteU8sStatic::write_u8s_static()] with no data, or
+//! [WriteRepeat::write_repeated()] with no data or a zero `count`, must:
+//! * put no byte on the wire;
+//! * not toggle DCX;
+//! * resolve on the first poll.
+//!
+//! The helpers and adapters of this crate may issue such writes, e.g., for
+//! an empty image.
+//!
+//! The checks need to observe the wire: `wire` returns how many bytes were
+//! written and DCX toggles happened so far, e.g., from counters of a mocked
+//! HAL shared with the test:
+//! ```ignore
+//! let mut spi = MySpi::new(mock_hal.clone());
+//! conformance::check_write_u8s(&mut spi, || mock_hal.activity())?;
+//! ```
+
+use core::future::Future;
+use core::pin::pin;
+use core::task::{Context, Waker};
+
+use crate::spi::{WriteRepeat, WriteU8s, WriteU8sStatic};
+
+/// The write checked.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum EmptyWrite {
+    /// `write_u8s(&[])`.
+    WriteU8s,
+    /// `write_u8s_static(&[])`.
+    WriteU8sStatic,
+    /// `write_repeated(&[], n)` with `n > 0`.
+    RepeatNoData,
+    /// `write_repeated(data, 0)` with non-empty `data`.
+    RepeatZeroTimes,
+}
+
+/// How an empty write broke the contract.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Violation {
+    /// The future was pending on its first poll.
+    Pending(EmptyWrite),
+    /// Bytes were written or DCX was toggled.
+    WireActivity(EmptyWrite),
+}
+impl ::core::fmt::Display for Violation {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// Checks `write_u8s(&[])`.
+pub fn check_write_u8s<S, F>(spi: &mut S, wire: F) -> Result<(), Violation>
+        where for<'a> S: WriteU8s<'a>, F: Fn() -> usize {
+    let before = wire();
+    check(EmptyWrite::WriteU8s, before, &wire, spi.write_u8s(&[]))
+}
+
+/// Checks `write_u8s_static(&[])`.
+pub fn check_write_u8s_static<S, F>(spi: &mut S, wire: F)
+        -> Result<(), Violation>
+        where for<'a> S: WriteU8sStatic<'a>, F: Fn() -> usize {
+    let before = wire();
+    check(EmptyWrite::WriteU8sStatic, before, &wire,
+          spi.write_u8s_static(&[]))
+}
+
+/// Checks `write_repeated()` with no data, then with a zero count.
+pub fn check_write_repeated<S, F>(spi: &mut S, wire: F)
+        -> Result<(), Violation>
+        where for<'a> S: WriteRepeat<'a>, F: Fn() -> usize {
+    let before = wire();
+    check(EmptyWrite::RepeatNoData, before, &wire,
+          spi.write_repeated(&[], 3))?;
+    check(EmptyWrite::RepeatZeroTimes, before, &wire,
+          spi.write_repeated(&[0x12, 0x34], 0))
+}
+
+// `before` is taken before the future is created, as some implementations
+// start the transfer right away.
+fn check<D>(write: EmptyWrite, before: usize, wire: &dyn Fn() -> usize,
+            done: D) -> Result<(), Violation>
+        where D: Future<Output=()> {
+    let mut done = pin!(done);
+    let ready = done.as_mut().poll(&mut Context::from_waker(Waker::noop()))
+        .is_ready();
+    if !ready { return Err(Violation::Pending(write)); }
+    if wire() != before { return Err(Violation::WireActivity(write)); }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::{Ready, ready};
+    use std::{cell::Cell, rc::Rc};
+
+    use crate::Commands;
+    use crate::adapters::*;
+    use crate::fake_device::FakeDevice;
+    use crate::spi::{DcxPin, WriteU8};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    /// Counts the wire activity into `activity`.
+    #[derive(Clone, Default)]
+    struct Probe { activity: Rc<Cell<usize>> }
+
+    impl Probe {
+        fn add(&self, n: usize) { self.activity.set(self.activity.get() + n); }
+        fn wire(&self) -> impl Fn() -> usize {
+            let activity = self.activity.clone();
+            move || activity.get()
+        }
+    }
+
+    impl DcxPin for Probe {
+        fn set_dcx_command_mode(&mut self) { self.add(1); }
+        fn set_dcx_data_mode(&mut self) { self.add(1); }
+    }
+
+    impl<'a> WriteU8<'a> for Probe {
+        type WriteU8Done = Ready<()>;
+        fn write_u8(&'a mut self, _: u8) -> Ready<()> {
+            self.add(1);
+            ready(())
+        }
+    }
+
+    impl<'a> WriteU8s<'a> for Probe {
+        type WriteU8sDone = Ready<()>;
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+            self.add(data.len());
+            ready(())
+        }
+    }
+
+    impl<'a> WriteU8sStatic<'a> for Probe {
+        type WriteU8sStaticDone = Ready<()>;
+        fn write_u8s_static(&'a mut self, data: &'static [u8]) -> Ready<()> {
+            self.add(data.len());
+            ready(())
+        }
+    }
+
+    impl<'a> WriteRepeat<'a> for Probe {
+        type WriteRepeatDone = Ready<()>;
+        fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+                -> Ready<()> {
+            self.add(data.len() * count);
+            ready(())
+        }
+    }
+
+    #[test]
+    fn violations() {
+        /// Toggles DCX on every write.
+        struct Toggling(Probe);
+        impl<'a> WriteU8s<'a> for Toggling {
+            type WriteU8sDone = Ready<()>;
+            fn write_u8s(&'a mut self, _: &'a [u8]) -> Ready<()> {
+                self.0.set_dcx_data_mode();
+                ready(())
+            }
+        }
+        let probe = Probe::default();
+        let wire = probe.wire();
+        assert_eq!(check_write_u8s(&mut Toggling(probe), wire),
+                   Err(Violation::WireActivity(EmptyWrite::WriteU8s)));
+
+        struct Pending;
+        impl<'a> WriteU8s<'a> for Pending {
+            type WriteU8sDone = core::future::Pending<()>;
+            fn write_u8s(&'a mut self, _: &'a [u8]) -> Self::WriteU8sDone {
+                core::future::pending()
+            }
+        }
+        assert_eq!(check_write_u8s(&mut Pending, || 0),
+                   Err(Violation::Pending(EmptyWrite::WriteU8s)));
+    }
+
+    #[test]
+    fn adapters() {
+        let p = Probe::default();
+        assert_eq!(check_write_u8s(&mut p.clone(), p.wire()), Ok(()));
+        assert_eq!(check_write_u8s_static(&mut p.clone(), p.wire()), Ok(()));
+        assert_eq!(check_write_repeated(&mut p.clone(), p.wire()), Ok(()));
+
+        let mut a = AdapterU8::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        let mut a = AdapterU8s::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        let mut a = AdapterRepeat::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_repeated(&mut a, p.wire()), Ok(()));
+        let mut a = AdapterStatic::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_u8s_static(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_repeated(&mut a, p.wire()), Ok(()));
+        let mut a = Counting::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        let mut a = Crc32::new(p.clone());
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_u8s_static(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_repeated(&mut a, p.wire()), Ok(()));
+        let mut a = WithIdleHook::new(p.clone(), || {});
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        assert_eq!(check_write_repeated(&mut a, p.wire()), Ok(()));
+        let mut a = ReadBitOrder::new(p.clone(), BitOrder::LsbFirst);
+        assert_eq!(check_write_u8s(&mut a, p.wire()), Ok(()));
+        assert_eq!(p.activity.get(), 0);
+    }
+
+    #[test]
+    fn ram_writer() {
+        let p = Probe::default();
+        let mut cmds = Commands::new(p.clone());
+        let mut w = block_on(cmds.ramwr());
+        assert_eq!(check_write_u8s(&mut w, p.wire()), Ok(()));
+    }
+
+    #[test]
+    fn fake_device() {
+        let device = FakeDevice::new();
+        let d = device.clone();
+        let wire = move || d.seq().len();
+        assert_eq!(check_write_u8s(&mut device.clone(), &wire), Ok(()));
+        assert_eq!(check_write_u8s_static(&mut device.clone(), &wire), Ok(()));
+        assert_eq!(check_write_repeated(&mut device.clone(), &wire), Ok(()));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn dyn_backend() {
+        use std::boxed::Box;
+        use crate::dyn_backend::DynWrite;
+        let p = Probe::default();
+        let mut b: Box<dyn DynWrite> = Box::new(p.clone());
+        assert_eq!(check_write_u8s(&mut b, p.wire()), Ok(()));
+    }
+}  // mod tests
@@ -0,0 +1,85 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The few behaviors differing among ST7735 and its nearly
+//! command-compatible siblings, so that [Commands](crate::Commands) drives
+//! them all.
+
+use crate::panel::{MAX_COLUMNS, MAX_ROWS};
+
+/// The behaviors of a controller that [Commands](crate::Commands) and
+/// [Display](crate::display::Display) depend on. See
+/// [Commands::set_profile()](crate::Commands::set_profile).
+///
+/// The offsets of the visible area depend on the module rather than on the
+/// controller, see [Panel](crate::panel::Panel).
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct ControllerProfile {
+    /// The number of columns in the memory, which
+    /// [Commands::caset_clamped()](crate::Commands::caset_clamped) clamps to.
+    pub memory_columns: u16,
+    /// The number of rows in the memory, which
+    /// [Commands::raset_clamped()](crate::Commands::raset_clamped) clamps to.
+    pub memory_rows: u16,
+    /// Whether COLMOD also sets the format of the RGB interface in bits 4 to
+    /// 6, e.g., `0x55` instead of `0x05` for 16 bits per pixel.
+    pub colmod_rgb_interface: bool,
+    /// Whether the panels usually need the inversion on, e.g., the IPS ones.
+    /// See the InitProfile of
+    /// [for_controller()](crate::display::InitProfile::for_controller).
+    pub inversion: bool,
+    /// How long to wait after SLPOUT before sending other commands.
+    pub slpout_delay_ms: u32,
+    /// How long to wait after SLPIN before sending SLPOUT.
+    pub slpin_delay_ms: u32,
+}
+
+impl ControllerProfile {
+    /// ST7735S, the default.
+    pub const ST7735S: Self = Self{
+        memory_columns: MAX_COLUMNS, memory_rows: MAX_ROWS,
+        colmod_rgb_interface: false, inversion: false,
+        slpout_delay_ms: 120, slpin_delay_ms: 120};
+    /// ST7735 and ST7735R behave as ST7735S.
+    pub const ST7735: Self = Self::ST7735S;
+    /// ST7789, with 240x320 pixels of memory. It is ready for commands 5ms
+    /// after SLPOUT, but as ST7735S still needs 120ms after SLPIN before
+    /// SLPOUT.
+    pub const ST7789: Self = Self{
+        memory_columns: 240, memory_rows: 320,
+        colmod_rgb_interface: true, inversion: true,
+        slpout_delay_ms: 5, slpin_delay_ms: 120};
+
+    /// The COLMOD parameter for `colmod`, the raw value of a
+    /// [Colmod](crate::Colmod).
+    pub const fn colmod_parameter(&self, colmod: u8) -> u8 {
+        if self.colmod_rgb_interface { colmod << 4 | colmod } else { colmod }
+    }
+}
+
+impl Default for ControllerProfile {
+    fn default() -> Self { Self::ST7735S }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colmod_parameter() {
+        assert_eq!(ControllerProfile::ST7735S.colmod_parameter(0x05), 0x05);
+        assert_eq!(ControllerProfile::ST7789.colmod_parameter(0x05), 0x55);
+        assert_eq!(ControllerProfile::ST7789.colmod_parameter(0x06), 0x66);
+    }
+}  // mod tests
@@ -0,0 +1,369 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Minimal decoders of images loaded at runtime, e.g., from an SD card,
+//! streaming [Rgb565] bytes to
+//! [RamWriter::write_stream()](crate::RamWriter::write_stream) as they are
+//! decoded, without a frame buffer:
+//!
+//! ```ignore
+//! let image = decode::qoi(file_bytes)?;
+//! display.draw_stream(image.rect(0, 0), image).await?;
+//! ```
+//!
+//! * [qoi()] reads its input once, in order, from any iterator of bytes; it
+//!   needs 256 bytes of state.
+//! * [bmp()] reads 16- and 24-bit uncompressed BMP files, which are usually
+//!   stored bottom-up, so it needs the whole file, e.g., in flash.
+//!
+//! An input ending early only ends the stream early. For images known at
+//! compile time, see [include_rgb565!](crate::include_rgb565).
+//!
+//! Needs the `decode` feature.
+
+use core::convert::TryFrom;
+
+use crate::color::Rgb565;
+use crate::panel::Rect;
+use crate::stream::{PixelStream, Pixels, pixels};
+
+/// Why an image can't be decoded, found from its header.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum DecodeError {
+    /// The input isn't of the format expected.
+    BadMagic,
+    /// The format is, but not this variant of it, e.g., a compressed BMP.
+    Unsupported,
+    /// The header is cut short.
+    Truncated,
+    /// Wider or taller than 65535 pixels, or empty.
+    BadSize,
+}
+
+impl ::core::fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// A [PixelStream] of the [Rgb565] bytes of a decoded image, row-major from
+/// the top-left corner.
+#[derive(Clone, Debug)]
+pub struct Image<I> {
+    pub width: u16,
+    pub height: u16,
+    pixels: Pixels<Rgb565, I>,
+}
+
+impl<I> Image<I> {
+    /// The rectangle covered when drawn at `(x, y)`.
+    pub const fn rect(&self, x: u16, y: u16) -> Rect {
+        Rect::new(x, y, self.width, self.height)
+    }
+}
+
+impl<I: Iterator<Item=Rgb565>> PixelStream for Image<I> {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize {
+        self.pixels.next_chunk(out)
+    }
+}
+
+fn size(width: u32, height: u32) -> Result<(u16, u16), DecodeError> {
+    match (u16::try_from(width), u16::try_from(height)) {
+        (Ok(w), Ok(h)) if w > 0 && h > 0 => Ok((w, h)),
+        _ => Err(DecodeError::BadSize),
+    }
+}
+
+fn to_rgb565(r: u8, g: u8, b: u8) -> Rgb565 {
+    Rgb565::new(r >> 3, g >> 2, b >> 3)
+}
+
+/// Starts decoding a QOI image, the alpha channel being ignored.
+pub fn qoi<I: IntoIterator<Item=u8>>(data: I)
+        -> Result<Image<QoiPixels<I::IntoIter>>, DecodeError> {
+    let mut data = data.into_iter();
+    let mut header = [0; 14];
+    for b in &mut header {
+        *b = data.next().ok_or(DecodeError::Truncated)?;
+    }
+    if &header[..4] != b"qoif" {
+        return Err(DecodeError::BadMagic);
+    }
+    let be32 = |i: usize| u32::from_be_bytes(
+        [header[i], header[i + 1], header[i + 2], header[i + 3]]);
+    let (width, height) = size(be32(4), be32(8))?;
+    let left = width as usize * height as usize;
+    Ok(Image{width, height, pixels: pixels(QoiPixels{
+        data, left, px: [0, 0, 0, 255], index: [[0; 4]; 64], run: 0})})
+}
+
+/// The pixels of [qoi()].
+#[derive(Clone, Debug)]
+pub struct QoiPixels<I> {
+    data: I,
+    left: usize,
+    // RGBA.
+    px: [u8; 4],
+    index: [[u8; 4]; 64],
+    // How many more times `px` repeats.
+    run: u8,
+}
+
+impl<I: Iterator<Item=u8>> QoiPixels<I> {
+    fn decode(&mut self) -> Option<()> {
+        if self.run > 0 {
+            self.run -= 1;
+            return Some(());
+        }
+        let op = self.data.next()?;
+        let px = &mut self.px;
+        match op {
+            0xFE => for c in &mut px[..3] { *c = self.data.next()?; },
+            0xFF => for c in &mut px[..] { *c = self.data.next()?; },
+            _ => match op >> 6 {
+                0 => *px = self.index[op as usize],
+                1 => for (i, c) in px[..3].iter_mut().enumerate() {
+                    let d = (op >> (4 - 2 * i) & 3).wrapping_sub(2);
+                    *c = c.wrapping_add(d);
+                },
+                2 => {
+                    let dg = (op & 0x3F).wrapping_sub(32);
+                    let b = self.data.next()?;
+                    px[0] = px[0].wrapping_add(dg.wrapping_add(b >> 4)
+                                                 .wrapping_sub(8));
+                    px[1] = px[1].wrapping_add(dg);
+                    px[2] = px[2].wrapping_add(dg.wrapping_add(b & 0xF)
+                                                 .wrapping_sub(8));
+                },
+                _ => self.run = op & 0x3F,
+            },
+        }
+        let [r, g, b, a] = *px;
+        let hash = r as usize * 3 + g as usize * 5 + b as usize * 7 +
+                   a as usize * 11;
+        self.index[hash % 64] = *px;
+        Some(())
+    }
+}
+
+impl<I: Iterator<Item=u8>> Iterator for QoiPixels<I> {
+    type Item = Rgb565;
+
+    fn next(&mut self) -> Option<Rgb565> {
+        if self.left == 0 {
+            return None;
+        }
+        self.left -= 1;
+        self.decode().or_else(|| { self.left = 0; None })?;
+        Some(to_rgb565(self.px[0], self.px[1], self.px[2]))
+    }
+}
+
+/// Starts decoding a BMP file: 16-bit, either uncompressed (5 bits per
+/// component) or with the bit fields of [Rgb565], or 24-bit uncompressed.
+pub fn bmp(data: &[u8]) -> Result<Image<BmpPixels<'_>>, DecodeError> {
+    if data.len() < 2 || &data[..2] != b"BM" {
+        return Err(DecodeError::BadMagic);
+    }
+    if data.len() < 54 {
+        return Err(DecodeError::Truncated);
+    }
+    let le16 = |i: usize| u16::from_le_bytes([data[i], data[i + 1]]);
+    let le32 = |i: usize| u32::from_le_bytes(
+        [data[i], data[i + 1], data[i + 2], data[i + 3]]);
+    let format = match (le16(28), le32(30)) {
+        (24, 0) => BmpFormat::Bgr888,
+        (16, 0) => BmpFormat::Rgb555,
+        (16, 3) if data.len() >= 66 &&
+                   (le32(54), le32(58), le32(62)) == (0xF800, 0x07E0, 0x1F) =>
+            BmpFormat::Rgb565,
+        _ => return Err(DecodeError::Unsupported),
+    };
+    let height = le32(22) as i32;
+    let (width, abs_height) = size(le32(18), height.unsigned_abs())?;
+    let bytes_per_pixel = if format == BmpFormat::Bgr888 { 3 } else { 2 };
+    let stride = (width as usize * bytes_per_pixel).div_ceil(4) * 4;
+    let offset = le32(10) as usize;
+    Ok(Image{width, height: abs_height, pixels: pixels(BmpPixels{
+        data, format, width: width as usize, height: abs_height as usize,
+        top_down: height < 0, stride, offset, pos: 0})})
+}
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum BmpFormat { Rgb555, Rgb565, Bgr888 }
+
+/// The pixels of [bmp()].
+#[derive(Clone, Debug)]
+pub struct BmpPixels<'a> {
+    data: &'a [u8],
+    format: BmpFormat,
+    width: usize,
+    height: usize,
+    top_down: bool,
+    stride: usize,
+    offset: usize,
+    // Of the next pixel, in the order of the image.
+    pos: usize,
+}
+
+impl Iterator for BmpPixels<'_> {
+    type Item = Rgb565;
+
+    fn next(&mut self) -> Option<Rgb565> {
+        if self.pos == self.width * self.height {
+            return None;
+        }
+        let (x, y) = (self.pos % self.width, self.pos / self.width);
+        self.pos += 1;
+        let row = if self.top_down { y } else { self.height - 1 - y };
+        let i = self.offset + row * self.stride;
+        let d = match self.format {
+            BmpFormat::Bgr888 => self.data.get(i + 3 * x..i + 3 * x + 3),
+            _ => self.data.get(i + 2 * x..i + 2 * x + 2),
+        };
+        let d = d.or_else(|| { self.pos = self.width * self.height; None })?;
+        Some(match self.format {
+            BmpFormat::Bgr888 => to_rgb565(d[2], d[1], d[0]),
+            BmpFormat::Rgb565 =>
+                Rgb565::from_raw(u16::from_le_bytes([d[0], d[1]])),
+            BmpFormat::Rgb555 => {
+                let raw = u16::from_le_bytes([d[0], d[1]]);
+                // The highest bit of green is copied into the lowest one.
+                let g = (raw >> 5 & 0x1F) as u8;
+                Rgb565::new((raw >> 10) as u8, g << 1 | g >> 4, raw as u8)
+            },
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::display::{Display, NoBacklight};
+    use crate::display::tests::{FakeDelay, FakeReset, Log};
+    use crate::fake_device::FakeDevice;
+    use crate::panel::Panel;
+    use crate::snapshot::assert_snapshot;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    // 3x2, with every kind of chunk.
+    const QOI_3X2: [u8; 31] = [
+        b'q', b'o', b'i', b'f', 0, 0, 0, 3, 0, 0, 0, 2, 4, 0,
+        0xFE, 0x10, 0x20, 0x30,  // RGB.
+        0x72,  // DIFF.
+        0xB4, 0x0F,  // LUMA.
+        0x15,  // INDEX, of the first pixel.
+        0xC1,  // RUN of 2.
+        0, 0, 0, 0, 0, 0, 0, 1];
+
+    fn raw<T: PixelStream>(mut image: T) -> Vec<u16> {
+        let mut all = Vec::new();
+        let mut buffer = [0; 8];
+        loop {
+            let n = image.next_chunk(&mut buffer);
+            if n == 0 { return all; }
+            all.extend(buffer[..n].chunks(2)
+                .map(|b| u16::from_be_bytes([b[0], b[1]])));
+        }
+    }
+
+    // Draws `image` at the top-left corner, returning what is sent.
+    fn draw<T: PixelStream>(rect: Rect, image: T) -> FakeDevice {
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log),
+            NoBacklight, Panel::new(128, 160));
+        assert_eq!(block_on(display.draw_stream(rect, image)), Ok(()));
+        device
+    }
+
+    #[test]
+    fn qoi_chunks() {
+        let image = qoi(QOI_3X2.iter().copied()).unwrap();
+        assert_eq!(image.rect(1, 2), Rect::new(1, 2, 3, 2));
+        assert_eq!(raw(image),
+                   [0x1106, 0x10E6, 0x1989, 0x1106, 0x1106, 0x1106]);
+    }
+
+    #[test]
+    fn qoi_golden() {
+        let image = qoi(QOI_3X2.iter().copied()).unwrap();
+        assert_snapshot(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/decode_qoi.txt"),
+            &draw(image.rect(0, 0), image).seq());
+    }
+
+    #[test]
+    fn qoi_errors() {
+        assert_eq!(qoi(QOI_3X2[..13].iter().copied()).unwrap_err(),
+                   DecodeError::Truncated);
+        let mut data = QOI_3X2;
+        data[0] = b'Q';
+        assert_eq!(qoi(data.iter().copied()).unwrap_err(),
+                   DecodeError::BadMagic);
+        data = QOI_3X2;
+        data[11] = 0;
+        assert_eq!(qoi(data.iter().copied()).unwrap_err(),
+                   DecodeError::BadSize);
+        // Cut in the middle of the pixels.
+        let image = qoi(QOI_3X2[..20].iter().copied()).unwrap();
+        assert_eq!(raw(image), [0x1106, 0x10E6]);
+    }
+
+    #[test]
+    fn bmp_24_bit_golden() {
+        let data = include_bytes!(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/testdata/rgb_3x2.bmp"));
+        let image = bmp(data).unwrap();
+        assert_eq!(raw(image.clone()),
+                   [0xF800, 0x07E0, 0x001F, 0xFFFF, 0x0000, 0x8402]);
+        assert_snapshot(
+            concat!(env!("CARGO_MANIFEST_DIR"), "/snapshots/decode_bmp.txt"),
+            &draw(image.rect(0, 0), image).seq());
+    }
+
+    #[test]
+    fn bmp_16_bit() {
+        // 1x2 top-down, then bottom-up with a padded row.
+        let mut data = [0; 74];
+        data[..2].copy_from_slice(b"BM");
+        data[10] = 66;
+        data[18] = 1;
+        data[22..26].copy_from_slice(&(-2i32).to_le_bytes());
+        data[28] = 16;
+        data[30] = 3;
+        data[54..66].copy_from_slice(
+            &[0x00, 0xF8, 0, 0, 0xE0, 0x07, 0, 0, 0x1F, 0, 0, 0]);
+        data[66..70].copy_from_slice(&[0x34, 0x12, 0, 0]);
+        data[70..72].copy_from_slice(&[0xCD, 0xAB]);
+        assert_eq!(raw(bmp(&data).unwrap()),
+                   [0x1234, 0xABCD]);
+
+        data[22..26].copy_from_slice(&2i32.to_le_bytes());
+        data[30] = 0;
+        // Red 0x1F, green 0x10 then blue 0x01.
+        data[66..68].copy_from_slice(&0x7E01u16.to_le_bytes());
+        assert_eq!(raw(bmp(&data).unwrap()), [0x57AD, 0xFC21]);
+
+        data[30] = 1;
+        assert_eq!(bmp(&data).unwrap_err(), DecodeError::Unsupported);
+        assert_eq!(bmp(&data[..40]).unwrap_err(), DecodeError::Truncated);
+        assert_eq!(bmp(b"PNG").unwrap_err(), DecodeError::BadMagic);
+    }
+}  // mod tests
@@ -0,0 +1,25 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Traits for waiting, as required by the ST7735 datasheet between some
+//! commands.
+
+use core::future::Future;
+
+/// Defines how to wait asynchronously for some milliseconds.
+pub trait DelayMs<'a> {
+    type DelayDone : 'a + Future<Output=()>;
+
+    fn delay_ms(&'a mut self, ms: u32) -> Self::DelayDone;
+}
@@ -0,0 +1,328 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Flushing only what changed between two full-screen framebuffers, for UIs
+//! re-rendering the whole frame each time.
+//!
+//! The changed pixels are grouped into windows: first into bands of rows,
+//! then each band into spans of columns, each trimmed to the rows actually
+//! changed in it. Runs of at most [DiffConfig::merge_gap] unchanged rows or
+//! columns are bridged, as each window costs 11 bytes of CASET, RASET and
+//! RAMWR on top of its pixels. With [DiffConfig::coalesce], the windows
+//! sharing their rows, e.g., the spans of a line of text, also share the
+//! RASET.
+
+use crate::color::Pixel;
+use crate::commands::Commands;
+use crate::panel::{Panel, Rect};
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+
+/// The tuning knobs of [flush_diff()].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DiffConfig {
+    /// When more windows are needed, a single window bounding all the
+    /// changes is written instead.
+    pub max_windows: usize,
+    /// Unchanged rows or columns up to this many between two changes are
+    /// written anyway, rather than starting another window.
+    pub merge_gap: u16,
+    /// Skips the CASET or the RASET of a window whose columns or rows are
+    /// those of the previous window, saving 5 bytes each.
+    pub coalesce: bool,
+}
+
+impl Default for DiffConfig {
+    fn default() -> Self {
+        Self{max_windows: 16, merge_gap: 4, coalesce: false}
+    }
+}
+
+/// What [flush_diff()] wrote.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct DiffStats {
+    pub windows: usize,
+    pub pixels: usize,
+}
+
+/// The windows covering the pixels differing between `front` and `back`, both
+/// row-major with `width` pixels per row. See the [module](self) doc.
+pub fn diff_windows<'a, P: PartialEq>(front: &'a [P], back: &'a [P],
+                                      width: u16, merge_gap: u16)
+        -> DiffWindows<'a, P> {
+    assert_eq!(front.len(), back.len());
+    let height = if width == 0 { 0 } else { front.len() / width as usize };
+    DiffWindows{front, back, width, height: height as u16, merge_gap,
+                next_row: 0, band: None}
+}
+
+/// The iterator returned by [diff_windows()].
+#[derive(Clone, Debug)]
+pub struct DiffWindows<'a, P> {
+    front: &'a [P],
+    back: &'a [P],
+    width: u16,
+    height: u16,
+    merge_gap: u16,
+    next_row: u16,
+    // The rows of the current band, both inclusive, and the next column to
+    // look at in it.
+    band: Option<(u16, u16, u16)>,
+}
+
+impl<'a, P: PartialEq> DiffWindows<'a, P> {
+    fn changed(&self, x: u16, y: u16) -> bool {
+        let i = y as usize * self.width as usize + x as usize;
+        self.front[i] != self.back[i]
+    }
+
+    fn row_changed(&self, y: u16, x0: u16, x1: u16) -> bool {
+        (x0..=x1).any(|x| self.changed(x, y))
+    }
+
+    fn column_changed(&self, x: u16, y0: u16, y1: u16) -> bool {
+        (y0..=y1).any(|y| self.changed(x, y))
+    }
+
+    // Finds the next band of rows from `next_row`.
+    fn next_band(&mut self) -> Option<(u16, u16)> {
+        let last_column = self.width.checked_sub(1)?;
+        let y0 = (self.next_row..self.height)
+            .find(|&y| self.row_changed(y, 0, last_column))?;
+        let mut y1 = y0;
+        let mut y = y0 + 1;
+        while y < self.height && y - y1 <= self.merge_gap.saturating_add(1) {
+            if self.row_changed(y, 0, last_column) { y1 = y; }
+            y += 1;
+        }
+        self.next_row = y1 + 1;
+        Some((y0, y1))
+    }
+}
+
+impl<'a, P: PartialEq> Iterator for DiffWindows<'a, P> {
+    type Item = Rect;
+
+    fn next(&mut self) -> Option<Rect> {
+        loop {
+            let (y0, y1, x) = match self.band {
+                Some(band) => band,
+                None => {
+                    let (y0, y1) = self.next_band()?;
+                    (y0, y1, 0)
+                },
+            };
+            let Some(x0) = (x..self.width)
+                    .find(|&x| self.column_changed(x, y0, y1)) else {
+                self.band = None;
+                continue;
+            };
+            let mut x1 = x0;
+            let mut x = x0 + 1;
+            while x < self.width && x - x1 <= self.merge_gap.saturating_add(1) {
+                if self.column_changed(x, y0, y1) { x1 = x; }
+                x += 1;
+            }
+            self.band = Some((y0, y1, x1 + 1));
+            let top = (y0..=y1).find(|&y| self.row_changed(y, x0, x1))?;
+            let bottom = (top..=y1).rev()
+                .find(|&y| self.row_changed(y, x0, x1))?;
+            return Some(Rect::new(x0, top, x1 - x0 + 1, bottom - top + 1));
+        }
+    }
+}
+
+/// Writes the pixels of `back` differing from `front`, both row-major and
+/// covering the whole `panel`, then copies `back` into `front`. The LCD is
+/// expected to be in the color mode `P::COLMOD`.
+///
+/// # Panics
+///
+/// If the lengths of `front` and `back` are not the area of `panel`.
+pub async fn flush_diff<S, P>(commands: &mut Commands<S>, panel: &Panel,
+                              front: &mut [P], back: &[P],
+                              config: &DiffConfig) -> DiffStats
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+              P: Pixel + PartialEq {
+    let area = panel.width() as usize * panel.height() as usize;
+    assert_eq!(front.len(), area);
+    assert_eq!(back.len(), area);
+    let windows = diff_windows(front, back, panel.width(), config.merge_gap);
+    let mut stats = DiffStats::default();
+    if windows.clone().count() <= config.max_windows {
+        for rect in windows {
+            write_window(commands, panel, back, rect, config.coalesce).await;
+            stats.windows += 1;
+            stats.pixels += rect.area();
+        }
+    } else if let Some(rect) = windows.reduce(|a, b| bounding(&a, &b)) {
+        write_window(commands, panel, back, rect, config.coalesce).await;
+        stats = DiffStats{windows: 1, pixels: rect.area()};
+    }
+    front.copy_from_slice(back);
+    stats
+}
+
+fn bounding(a: &Rect, b: &Rect) -> Rect {
+    let x = a.x.min(b.x);
+    let y = a.y.min(b.y);
+    let right = (a.x + a.width).max(b.x + b.width);
+    let bottom = (a.y + a.height).max(b.y + b.height);
+    Rect::new(x, y, right - x, bottom - y)
+}
+
+async fn write_window<S, P>(commands: &mut Commands<S>, panel: &Panel,
+                            back: &[P], rect: Rect, coalesce: bool)
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>, P: Pixel {
+    let x = panel.column_offset() + rect.x;
+    let y = panel.row_offset() + rect.y;
+    // In the memory coordinates, as set by the last CASET and RASET.
+    let last = commands.window().filter(|_| coalesce);
+    if last.map(|w| (w.x, w.width)) != Some((x, rect.width)) {
+        commands.caset(x, x + rect.width - 1).await;
+    }
+    if last.map(|w| (w.y, w.height)) != Some((y, rect.height)) {
+        commands.raset(y, y + rect.height - 1).await;
+    }
+    let width = panel.width() as usize;
+    let rows = (rect.y as usize..(rect.y + rect.height) as usize)
+        .flat_map(|row| {
+            let start = row * width + rect.x as usize;
+            back[start..start + rect.width as usize].iter().copied()
+        });
+    commands.ramwr().await.write_pixels(rows).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{vec, vec::Vec};
+
+    use crate::color::Rgb565;
+    use crate::fake_device::{FakeDevice, Wire::*};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    fn windows(front: &[u8], back: &[u8], width: u16, gap: u16) -> Vec<Rect> {
+        diff_windows(front, back, width, gap).collect()
+    }
+
+    #[test]
+    fn no_change() {
+        let frame = [0u8; 12];
+        assert!(windows(&frame, &frame, 4, 0).is_empty());
+        assert!(windows(&[0u8; 0], &[], 0, 0).is_empty());
+    }
+
+    #[test]
+    fn spans_and_bands() {
+        let front = [0u8; 8 * 6];
+        let back = [
+            1, 0, 0, 0, 0, 0, 1, 1,
+            0, 0, 0, 0, 0, 0, 0, 1,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 1, 0, 1, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0,
+        ];
+        assert_eq!(windows(&front, &back, 8, 0), [
+            Rect::new(0, 0, 1, 1), Rect::new(6, 0, 2, 2),
+            Rect::new(2, 4, 1, 1), Rect::new(4, 4, 1, 1)]);
+        // Bridges the single unchanged column between the changes in row 4.
+        assert_eq!(windows(&front, &back, 8, 1), [
+            Rect::new(0, 0, 1, 1), Rect::new(6, 0, 2, 2),
+            Rect::new(2, 4, 3, 1)]);
+        // Bridges the 2 unchanged rows too, then all the columns.
+        assert_eq!(windows(&front, &back, 8, 2), [Rect::new(0, 0, 8, 5)]);
+        assert_eq!(windows(&front, &back, 8, u16::MAX),
+                   [Rect::new(0, 0, 8, 5)]);
+    }
+
+    fn flush(front: &mut [Rgb565], back: &[Rgb565], config: &DiffConfig)
+            -> (DiffStats, usize) {
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        let panel = Panel::new(16, 8).with_offsets(2, 1);
+        let stats = block_on(flush_diff(&mut commands, &panel, front, back,
+                                        config));
+        assert_eq!(front, back);
+        (stats, device.seq().len())
+    }
+
+    #[test]
+    fn bytes_sent() {
+        let mut front = vec![Rgb565::default(); 16 * 8];
+        let mut back = front.clone();
+        back[0] = Rgb565::new(1, 1, 1);
+        back[16 * 8 - 1] = Rgb565::new(1, 1, 1);
+        let config = DiffConfig::default();
+        // Two single-pixel windows, each of 11 bytes of commands.
+        assert_eq!(flush(&mut front, &back, &config),
+                   (DiffStats{windows: 2, pixels: 2}, 2 * (11 + 2)));
+        // Nothing changed since.
+        assert_eq!(flush(&mut front, &back, &config),
+                   (DiffStats::default(), 0));
+        // Too many windows: the whole screen at once.
+        back[0] = Rgb565::default();
+        back[16 * 8 - 1] = Rgb565::default();
+        let config = DiffConfig{max_windows: 1, ..config};
+        assert_eq!(flush(&mut front, &back, &config),
+                   (DiffStats{windows: 1, pixels: 16 * 8}, 11 + 16 * 8 * 2));
+    }
+
+    #[test]
+    fn coalesced() {
+        let mut front = vec![Rgb565::default(); 16 * 8];
+        let mut back = front.clone();
+        // Two spans of the same row, then one of the same columns below.
+        back[16 * 2 + 1] = Rgb565::new(1, 1, 1);
+        back[16 * 2 + 12] = Rgb565::new(1, 1, 1);
+        back[16 * 7 + 12] = Rgb565::new(1, 1, 1);
+        let config = DiffConfig{merge_gap: 0, ..DiffConfig::default()};
+        let stats = DiffStats{windows: 3, pixels: 3};
+        assert_eq!(flush(&mut front.clone(), &back, &config),
+                   (stats, 3 * (11 + 2)));
+        let config = DiffConfig{coalesce: true, ..config};
+        assert_eq!(flush(&mut front, &back, &config),
+                   (stats, 3 * (11 + 2) - 5 - 5));
+
+        // The second span of the row without RASET.
+        let mut front = vec![Rgb565::default(); 16 * 3];
+        back.truncate(16 * 3);
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        block_on(flush_diff(&mut commands, &Panel::new(16, 3), &mut front,
+                            &back, &config));
+        assert_eq!(device.seq(), [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(1),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(2),
+            Command(0x2C), Data(0x08), Data(0x21),
+            Command(0x2A), Data(0), Data(12), Data(0), Data(12),
+            Command(0x2C), Data(0x08), Data(0x21)]);
+    }
+
+    #[test]
+    fn offsets() {
+        let mut front = vec![Rgb565::default(); 16 * 8];
+        let mut back = front.clone();
+        back[16 + 3] = Rgb565::new(0x1F, 0, 0);
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        let panel = Panel::new(16, 8).with_offsets(2, 1);
+        block_on(flush_diff(&mut commands, &panel, &mut front, &back,
+                            &DiffConfig::default()));
+        assert_eq!(device.seq(), [
+            Command(0x2A), Data(0), Data(5), Data(0), Data(5),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(2),
+            Command(0x2C), Data(0xF8), Data(0x00)]);
+    }
+}  // mod tests
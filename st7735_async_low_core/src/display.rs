@@ -0,0 +1,1752 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A higher-level facade bundling [Commands] with the other pins and the
+//! geometry of a panel.
+
+use core::future::{Future, Ready, ready};
+
+use crate::asset::Asset;
+use crate::color::{
+    ColorFixups, DitherSource, Pixel, PixelTransform, bayer_rgb444};
+use crate::command_structs::{
+    Colmod, Flip, FrameRate, GammaConfig, IdSource, Madctl, PanelConfig,
+    PanelState, PowerMode, PowerProfile, RowColumnSwap};
+use crate::commands::{Commands, RamWriter};
+use crate::controller::ControllerProfile;
+use crate::delay::DelayMs;
+use crate::flip::{FlipError, PageFlip};
+use crate::panel::{Panel, Rect, WindowError};
+use crate::presets;
+use crate::scan::{flipped, transposed_flipped};
+use crate::spi::{DcxPin, Read, WriteRepeat, WriteU8, WriteU8s};
+use crate::sprite::{Sprite, opaque_runs};
+use crate::stream::PixelStream;
+
+/// Defines how the `RESX` pin operates.
+pub trait ResetPin {
+    /// Toggles the RESX pin to LOW, i.e., starts resetting.
+    fn set_reset_low(&mut self);
+    /// Toggles the RESX pin to HIGH, i.e., finishes resetting.
+    fn set_reset_high(&mut self);
+}
+
+/// Defines how the backlight LED, often PWM-driven, is controlled.
+pub trait Backlight<'a> {
+    type SetBrightnessDone : 'a + Future<Output=()>;
+
+    /// Sets the brightness, `0` being off and `255` being the brightest.
+    fn set_brightness(&'a mut self, brightness: u8) -> Self::SetBrightnessDone;
+}
+
+/// Defines how the supply of the LCD, e.g., a regulator or a load switch, is
+/// turned on and off. See [Display::power_cycle()].
+pub trait PowerRail<'a> {
+    type EnableDone : 'a + Future<Output=()>;
+    type DisableDone : 'a + Future<Output=()>;
+
+    /// Turns the supply on, resolving once it is stable.
+    fn enable(&'a mut self) -> Self::EnableDone;
+    /// Turns the supply off.
+    fn disable(&'a mut self) -> Self::DisableDone;
+}
+
+/// A [Backlight] for panels whose backlight is not controllable.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct NoBacklight;
+
+impl<'a> Backlight<'a> for NoBacklight {
+    type SetBrightnessDone = Ready<()>;
+
+    fn set_brightness(&'a mut self, _brightness: u8) -> Ready<()> { ready(()) }
+}
+
+/// The configuration sent by [Display::init()].
+#[derive(Clone, Copy, Debug)]
+pub struct InitProfile {
+    pub colmod: Colmod,
+    pub madctl: Madctl,
+    /// Whether to enable the inversion mode, needed by some panels.
+    pub inversion: bool,
+    /// The gamma correction to apply, if not the default one of the LCD.
+    pub gamma: Option<GammaConfig>,
+}
+
+impl Default for InitProfile {
+    fn default() -> Self {
+        Self{colmod: Colmod::R5G6B5, madctl: Madctl::default(),
+             inversion: false, gamma: None}
+    }
+}
+
+impl InitProfile {
+    /// The default configuration, with the inversion usually needed by the
+    /// panels of `controller`.
+    pub fn for_controller(controller: &ControllerProfile) -> Self {
+        Self{inversion: controller.inversion, ..Self::default()}
+    }
+
+    /// Replaces the gamma correction, e.g., by one of the [presets].
+    pub fn with_gamma(self, gamma: GammaConfig) -> Self {
+        Self{gamma: Some(gamma), ..self}
+    }
+
+    // What `Display::init()` configures, the default gamma of the LCD being
+    // the first curve.
+    fn config(&self) -> PanelConfig {
+        PanelConfig{madctl: self.madctl, colmod: Some(self.colmod),
+                    gamma: self.gamma.unwrap_or_default(),
+                    ..PanelConfig::default()}
+    }
+
+    /// Reads back MADCTL, COLMOD and the power mode after
+    /// [Display::init()] with this profile, to catch early a miswired or
+    /// too slow bus, or a missing level shifter, during the bring-up.
+    pub async fn verify<S>(&self, commands: &mut Commands<S>)
+            -> Result<(), InitMismatch>
+            where S: DcxPin, for<'a> S: WriteU8<'a> + Read<'a> {
+        let mut mismatch = InitMismatch::default();
+        // The lowest 2 bits are read as 0.
+        let madctl = commands.rddmadctl().await;
+        if u8::from(madctl) != u8::from(self.madctl) & 0xFC {
+            mismatch.madctl = Some((self.madctl, madctl));
+        }
+        let colmod = commands.rddcolmod().await;
+        if colmod != self.colmod {
+            mismatch.colmod = Some((self.colmod, colmod));
+        }
+        let power_mode = commands.rddpm().await;
+        let expected = PowerMode::SLEEP_OUT | PowerMode::NORMAL_ON |
+                       PowerMode::DISPLAY_ON;
+        // The booster isn't set by `init()`.
+        let mask = expected | PowerMode::IDLE_ON | PowerMode::PARTIAL_ON;
+        if power_mode.bits() & mask.bits() != expected.bits() {
+            mismatch.power_mode = Some((expected, power_mode));
+        }
+        if mismatch == InitMismatch::default() { Ok(()) } else { Err(mismatch) }
+    }
+}
+
+/// What [InitProfile::verify()] read back differently from the profile, each
+/// as the intended value then the value read.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct InitMismatch {
+    pub madctl: Option<(Madctl, Madctl)>,
+    pub colmod: Option<(Colmod, Colmod)>,
+    /// The power mode expected has the sleep mode off, and the normal mode
+    /// and the display on. The booster isn't checked.
+    pub power_mode: Option<(PowerMode, PowerMode)>,
+}
+
+impl ::core::fmt::Display for InitMismatch {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// What [Display::health_check()] found.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Health {
+    /// The LCD was awake and on, as expected.
+    Ok,
+    /// The LCD had lost its state and was re-initialized. Holds the power
+    /// mode read before.
+    Recovered(PowerMode),
+}
+
+/// The common ST7735R modules, named after the color of the tab of their
+/// protective film. See [Display::init_variant()].
+///
+/// As in the vendor's initialization code, the variants only differ in
+/// their geometry and memory offsets, see [panel()](Self::panel), and in the
+/// RGB order of MADCTL. All share the rest of the initialization, including
+/// the [ADAFRUIT_1_8_TFT](presets::ADAFRUIT_1_8_TFT) gamma, the 1.44" one
+/// included. For another gamma, e.g., of a clone, see
+/// [InitProfile::with_gamma()].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum PanelVariant {
+    /// 1.8" 128x160, BGR.
+    RedTab,
+    /// 1.8" 128x160, BGR, with the visible area shifted in the memory.
+    GreenTab,
+    /// 1.8" 128x160, RGB.
+    BlackTab,
+    /// 1.44" 128x128, BGR, with the visible area shifted in the memory.
+    GreenTab144,
+}
+
+impl PanelVariant {
+    /// The visible area.
+    pub const fn panel(self) -> Panel {
+        match self {
+            Self::RedTab | Self::BlackTab => Panel::new(128, 160),
+            Self::GreenTab => Panel::new(128, 160).with_offsets(2, 1),
+            Self::GreenTab144 => Panel::new(128, 128).with_offsets(2, 3),
+        }
+    }
+
+    /// The configuration for the portrait orientation, with the pins at the
+    /// bottom. Only MADCTL differs between the variants.
+    pub fn profile(self) -> InitProfile {
+        // MX, MY and, except for the black tab, BGR.
+        let madctl = match self {
+            Self::BlackTab => 0xC0,
+            _ => 0xC8,
+        };
+        InitProfile{madctl: Madctl::from(madctl),
+                    gamma: Some(presets::ADAFRUIT_1_8_TFT),
+                    ..InitProfile::default()}
+    }
+}
+
+impl ::core::fmt::Display for PanelVariant {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// What identifies the LCD and its configuration, see [Display::info()].
+/// Formats as one line, e.g., `ids=7C 89 F0 (Rddid) variant=GreenTab
+/// colmod=R5G6B5 madctl=C8 128x160+2+1`, meant for logs and bug reports.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct DisplayInfo {
+    /// `ID1`, `ID2` and `ID3` and how they were read, `None` if unreadable.
+    pub ids: Option<(IdSource, [u8; 3])>,
+    /// The variant given to [Display::init_variant()], if initialized so.
+    pub variant: Option<PanelVariant>,
+    /// As read back from the LCD.
+    pub colmod: Colmod,
+    /// As read back from the LCD.
+    pub madctl: Madctl,
+    /// The geometry and memory offsets the firmware is configured with.
+    pub panel: Panel,
+}
+
+impl ::core::fmt::Display for DisplayInfo {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        match self.ids {
+            Some((source, [id1, id2, id3])) =>
+                write!(f, "ids={:02X} {:02X} {:02X} ({})",
+                       id1, id2, id3, source)?,
+            None => f.write_str("ids=?")?,
+        }
+        match self.variant {
+            Some(variant) => write!(f, " variant={}", variant)?,
+            None => f.write_str(" variant=?")?,
+        }
+        let panel = &self.panel;
+        write!(f, " colmod={} madctl={:02X} {}x{}+{}+{}",
+               self.colmod, u8::from(self.madctl), panel.width(),
+               panel.height(), panel.column_offset(), panel.row_offset())
+    }
+}
+
+#[cfg(feature = "defmt")]
+impl defmt::Format for DisplayInfo {
+    fn format(&self, f: defmt::Formatter) {
+        defmt::write!(f, "{}", defmt::Display2Format(self))
+    }
+}
+
+// How often `fade_in()` and `fade_out()` step the brightness, about a frame.
+const FADE_STEP_MS: u32 = 16;
+
+/// Bundles [Commands] with the reset pin, a delay, the backlight and the
+/// [Panel] geometry.
+#[derive(Debug)]
+pub struct Display<S, RST, D, BL = NoBacklight> {
+    commands: Commands<S>,
+    reset: RST,
+    delay: D,
+    backlight: BL,
+    panel: Panel,
+    // What `recover()` replays: set from the profile by `init()`, then
+    // updated by the setters.
+    config: PanelConfig,
+    color_fixups: ColorFixups,
+    // The last profile passed to `init()`, for its inversion.
+    profile: Option<InitProfile>,
+    // Set by `init_variant()`, reported by `info()`.
+    variant: Option<PanelVariant>,
+    brightness: u8,
+}
+
+impl<S: DcxPin, RST, D, BL> Display<S, RST, D, BL> {
+    pub fn new(spi: S, reset: RST, delay: D, backlight: BL, panel: Panel)
+            -> Self {
+        Self{commands: Commands::new(spi), reset, delay, backlight, panel,
+             config: InitProfile::default().config(),
+             color_fixups: ColorFixups::default(), profile: None,
+             variant: None, brightness: 0}
+    }
+
+    /// The underlying commands, for everything not covered by [Display].
+    pub fn commands(&mut self) -> &mut Commands<S> { &mut self.commands }
+
+    pub fn panel(&self) -> &Panel { &self.panel }
+
+    /// The panel as addressed under the current orientation, i.e., with the
+    /// width and height swapped if MADCTL exchanges rows and columns. See
+    /// [Panel::oriented()].
+    pub fn effective_panel(&self) -> Panel {
+        self.panel.oriented(self.config.madctl)
+    }
+
+    /// The orientation last set by [init()](Self::init) or
+    /// [set_orientation()](Self::set_orientation).
+    pub fn orientation(&self) -> Madctl { self.config.madctl }
+
+    /// What [recover()](Self::recover) replays: the last profile passed to
+    /// [init()](Self::init), updated by the setters since, e.g.,
+    /// [set_orientation()](Self::set_orientation) or
+    /// [set_frame_rate()](Self::set_frame_rate).
+    pub fn config(&self) -> &PanelConfig { &self.config }
+
+    /// Sets the software color fixups applied by
+    /// [draw_pixels()](Self::draw_pixels).
+    pub fn set_color_fixups(&mut self, color_fixups: ColorFixups) {
+        self.color_fixups = color_fixups;
+    }
+
+    pub fn color_fixups(&self) -> ColorFixups { self.color_fixups }
+}
+
+impl<S, RST, D, BL> Display<S, RST, D, BL>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+              RST: ResetPin, for<'a> D: DelayMs<'a>,
+              for<'a> BL: Backlight<'a> {
+    /// Resets the LCD, wakes it up and turns the display on, then turns the
+    /// backlight on with `brightness`.
+    ///
+    /// The backlight stays off until `DISPON` has been sent; otherwise the
+    /// uninitialized (often white) screen would flash.
+    pub async fn power_on(&mut self, brightness: u8) {
+        self.backlight.set_brightness(0).await;
+        self.hardware_reset().await;
+        self.wake().await;
+        self.commands.dispon().await;
+        self.set_brightness(brightness).await;
+    }
+
+    /// Resets the LCD and configures it with `profile`, ending with the
+    /// display on. The backlight is turned off and left off; use
+    /// [set_brightness()](Self::set_brightness) afterwards.
+    pub async fn init(&mut self, profile: &InitProfile) {
+        self.profile = Some(*profile);
+        self.config = profile.config();
+        self.variant = None;
+        self.set_brightness(0).await;
+        self.hardware_reset().await;
+        self.configure(profile).await;
+    }
+
+    // The part of `init()` after the reset.
+    async fn configure(&mut self, profile: &InitProfile) {
+        self.wake().await;
+        self.commands.colmod(profile.colmod).await;
+        self.set_orientation(profile.madctl).await;
+        if profile.inversion {
+            self.commands.invon().await;
+        } else {
+            self.commands.invoff().await;
+        }
+        if let Some(gamma) = profile.gamma {
+            self.commands.apply_gamma(gamma).await;
+        }
+        self.commands.noron().await;
+        self.commands.dispon().await;
+    }
+
+    /// Replaces the panel by the one of `variant`, then
+    /// [initializes](Self::init) with its profile.
+    pub async fn init_variant(&mut self, variant: PanelVariant) {
+        self.panel = variant.panel();
+        self.init(&variant.profile()).await;
+        self.variant = Some(variant);
+    }
+
+    /// Recovers from garbage on the screen, e.g., after a glitch, without
+    /// the reset pin: sends `SWRESET`, waits the 120ms it requires, then
+    /// replays the [config()](Self::config), i.e., the last
+    /// [init()](Self::init) (or the default profile if none) with what the
+    /// setters changed since, and restores the brightness. The memory is
+    /// lost, so the screen has to be redrawn.
+    pub async fn recover(&mut self) {
+        let brightness = self.brightness;
+        self.set_brightness(0).await;
+        self.commands.swreset().await;
+        self.delay.delay_ms(120).await;
+        self.commands.reset_state();
+        self.replay().await;
+        self.set_brightness(brightness).await;
+    }
+
+    // The part of `recover()` and `health_check()` after the reset: as
+    // `configure()`, but with the stored config.
+    async fn replay(&mut self) {
+        self.wake().await;
+        let config = self.config;
+        self.commands.apply_config(&config).await;
+        if self.recovery_profile().inversion {
+            self.commands.invon().await;
+        } else {
+            self.commands.invoff().await;
+        }
+        self.commands.noron().await;
+        self.commands.dispon().await;
+    }
+
+    // What `recover()` configures, as a profile: the last one, with what
+    // was changed since.
+    fn recovery_profile(&self) -> InitProfile {
+        let config = &self.config;
+        let profile = self.profile.unwrap_or_default();
+        InitProfile{madctl: config.madctl,
+                    colmod: config.colmod.unwrap_or(profile.colmod),
+                    gamma: Some(config.gamma), ..profile}
+    }
+
+    async fn hardware_reset(&mut self) {
+        self.reset.set_reset_low();
+        self.delay.delay_ms(1).await;  // At least 10us.
+        self.reset.set_reset_high();
+        self.delay.delay_ms(120).await;  // At most 120ms.
+        self.commands.reset_state();
+    }
+
+    /// Enters the sleep mode and waits until another `SLPOUT` is allowed.
+    pub async fn sleep(&mut self) {
+        self.commands.slpin().await;
+        let ms = self.commands.profile().slpin_delay_ms;
+        self.delay.delay_ms(ms).await;
+    }
+
+    /// Exits the sleep mode and waits until the LCD is ready for other
+    /// commands.
+    pub async fn wake(&mut self) {
+        self.commands.slpout().await;
+        let ms = self.commands.profile().slpout_delay_ms;
+        self.delay.delay_ms(ms).await;
+        self.commands.mark_awake();
+    }
+
+    /// Sets the MADCTL register, i.e., the orientation of the screen.
+    pub async fn set_orientation(&mut self, madctl: Madctl) {
+        self.commands.madctl(madctl).await;
+        self.config.madctl = madctl;
+    }
+
+    /// Sets the frame rate of the normal mode, see
+    /// [Commands::frmctr1()], kept by [recover()](Self::recover).
+    pub async fn set_frame_rate(&mut self, rate: FrameRate) {
+        self.commands.frmctr1(rate).await;
+        self.config.frame_rate = Some(rate);
+    }
+
+    /// Applies the power settings `power`, see
+    /// [Commands::apply_power_profile()], kept by
+    /// [recover()](Self::recover).
+    pub async fn set_power_profile(&mut self, power: &PowerProfile) {
+        self.commands.apply_power_profile(power).await;
+        self.config.power = Some(*power);
+    }
+
+    /// Sets the address window to `rect`, after checking that it is inside
+    /// the [effective panel](Self::effective_panel). Nothing is sent if not.
+    pub async fn set_window_checked(&mut self, rect: Rect)
+            -> Result<(), WindowError> {
+        let (x, x1, y, y1) = self.memory_window(rect)?;
+        self.commands.caset(x, x1).await;
+        self.commands.raset(y, y1).await;
+        Ok(())
+    }
+
+    // The memory columns then rows of `rect`, all inclusive, if visible.
+    fn memory_window(&self, rect: Rect)
+            -> Result<(u16, u16, u16, u16), WindowError> {
+        if rect.is_empty() {
+            return Err(WindowError::Reversed);
+        }
+        let panel = self.effective_panel();
+        let x = rect.x.checked_add(panel.column_offset())
+            .ok_or(WindowError::OutOfRange)?;
+        let y = rect.y.checked_add(panel.row_offset())
+            .ok_or(WindowError::OutOfRange)?;
+        let x1 = x.checked_add(rect.width - 1).ok_or(WindowError::OutOfRange)?;
+        let y1 = y.checked_add(rect.height - 1)
+            .ok_or(WindowError::OutOfRange)?;
+        panel.check_columns(x, x1)?;
+        panel.check_rows(y, y1)?;
+        Ok((x, x1, y, y1))
+    }
+
+    /// Sets the address window to `rect` and starts writing memory into it.
+    /// `rect` is relative to the [effective panel](Self::effective_panel).
+    /// Nothing is sent if `rect` isn't inside it, as for
+    /// [set_window_checked()](Self::set_window_checked).
+    pub async fn window_writer(&mut self, rect: Rect)
+            -> Result<RamWriter<'_, S>, WindowError> {
+        self.set_window_checked(rect).await?;
+        Ok(self.commands.ramwr().await)
+    }
+
+    /// Writes `pixels` into `rect`, row by row, applying the color fixups
+    /// (see [set_color_fixups()](Self::set_color_fixups)). Nothing is sent
+    /// if `rect` isn't inside the [effective panel](Self::effective_panel).
+    pub async fn draw_pixels<P, I>(&mut self, rect: Rect, pixels: I)
+            -> Result<(), WindowError>
+            where P: Pixel, I: IntoIterator<Item=P>,
+                  ColorFixups: PixelTransform<P> {
+        let fixups = self.color_fixups;
+        self.window_writer(rect).await?
+            .write_pixels_with(pixels, &fixups).await;
+        Ok(())
+    }
+
+    /// Writes `stream` into `rect`, row by row, e.g., a decoded image. Unlike
+    /// [draw_pixels()](Self::draw_pixels), the color fixups are **not**
+    /// applied, the stream being bytes. Nothing is sent if `rect` isn't
+    /// inside the [effective panel](Self::effective_panel).
+    pub async fn draw_stream<T: PixelStream>(&mut self, rect: Rect, stream: T)
+            -> Result<(), WindowError> {
+        self.window_writer(rect).await?.write_stream(stream).await;
+        Ok(())
+    }
+
+    /// Writes `pixels`, a row-major image filling `rect`, both in the
+    /// orientation of the panel **without** the row/column swap of the
+    /// current orientation. That is, when the MV bit is set, the window is
+    /// set to the swapped `rect` and the image is streamed
+    /// [transposed()](crate::scan::transposed), sparing a transposition by
+    /// the caller. The color fixups are applied.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `pixels` is not the area of `rect`.
+    pub async fn blit<P>(&mut self, rect: Rect, pixels: &[P])
+            -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        self.blit_flipped(rect, pixels, Flip::empty()).await
+    }
+
+    /// Same as [blit()](Self::blit), but the image is mirrored by `flip`,
+    /// e.g., [Flip::VERTICAL] for an asset stored bottom-up. The pixels are
+    /// streamed in the mirrored order, without an intermediate buffer.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `pixels` is not the area of `rect`.
+    pub async fn blit_flipped<P>(&mut self, rect: Rect, pixels: &[P],
+                                 flip: Flip) -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        assert_eq!(pixels.len(), rect.area());
+        let fixups = self.color_fixups;
+        let (w, h) = (rect.width as usize, rect.height as usize);
+        let swap = self.config.madctl.row_column_swap();
+        let pixels = if swap == RowColumnSwap::Unswapped {
+            self.set_window_checked(rect).await?;
+            flipped(pixels, w, h, flip)
+        } else {
+            let swapped = Rect::new(rect.y, rect.x, rect.height, rect.width);
+            self.set_window_checked(swapped).await?;
+            transposed_flipped(pixels, w, h, flip)
+        };
+        self.commands.ramwr().await.write_pixels_with(pixels, &fixups).await;
+        Ok(())
+    }
+
+    /// Draws `asset`, e.g., embedded by
+    /// [include_rgb565!](crate::include_rgb565), with its top-left corner at
+    /// `(x, y)` of the [effective panel](Self::effective_panel). The bytes
+    /// are written as they are, unless there are color fixups to apply.
+    pub async fn draw_asset(&mut self, x: u16, y: u16, asset: &Asset)
+            -> Result<(), WindowError> {
+        self.set_window_checked(asset.rect(x, y)).await?;
+        let fixups = self.color_fixups;
+        let mut w = self.commands.ramwr().await;
+        if fixups.is_identity() {
+            w.write_u8s(asset.bytes).await;
+        } else {
+            w.write_pixels_with(asset.pixels(), &fixups).await;
+        }
+        Ok(())
+    }
+
+    /// Draws `sprite` with its top-left corner at `(x, y)` of the
+    /// [effective panel](Self::effective_panel), clipped to it, so that a
+    /// cursor can partly leave the screen. The pixels equal to `transparent`,
+    /// compared before the color fixups, are skipped. See
+    /// [sprite](crate::sprite). Only fails if the panel is too large to be
+    /// addressed with its offsets.
+    pub async fn draw_sprite<P>(&mut self, x: i32, y: i32,
+                                sprite: &Sprite<'_, P>,
+                                transparent: Option<P>)
+            -> Result<(), WindowError>
+            where P: Pixel + PartialEq, ColorFixups: PixelTransform<P> {
+        let panel = self.effective_panel();
+        let (on_panel, in_sprite) = match sprite.clip(x, y, &panel) {
+            Some(clipped) => clipped,
+            None => return Ok(()),
+        };
+        let fixups = self.color_fixups;
+        let columns = in_sprite.x as usize..
+                      (in_sprite.x + in_sprite.width) as usize;
+        if transparent.is_none() {
+            let rows = (0..in_sprite.height).flat_map(|dy| {
+                sprite.row(in_sprite.y + dy)[columns.clone()].iter().copied()
+            });
+            return self.draw_pixels(on_panel, rows).await;
+        }
+        for dy in 0..in_sprite.height {
+            let row = &sprite.row(in_sprite.y + dy)[columns.clone()];
+            for run in opaque_runs(row, transparent) {
+                let rect = Rect::new(on_panel.x + run.start as u16,
+                                     on_panel.y + dy, run.len() as u16, 1);
+                self.window_writer(rect).await?
+                    .write_pixels_with(row[run].iter().copied(), &fixups)
+                    .await;
+            }
+        }
+        Ok(())
+    }
+
+    /// Starts page flipping the last `rows` rows of the panel, see the
+    /// [flip](crate::flip) module: sets the scroll area so that what the
+    /// band shows now is page 0, the front page. Fails if the orientation or
+    /// the memory doesn't allow it; nothing is sent then.
+    pub async fn page_flip(&mut self, rows: u16)
+            -> Result<PageFlip, FlipError> {
+        let memory_rows = self.commands.profile().memory_rows;
+        let flip = PageFlip::new(&self.panel, self.config.madctl, memory_rows,
+                                 rows)?;
+        let scroll = flip.scroll_config();
+        self.commands.scrlar(scroll.top_fixed, scroll.scroll_area,
+                             scroll.bottom_fixed).await;
+        self.commands.vscsad(scroll.start).await;
+        Ok(flip)
+    }
+
+    /// Writes `pixels` into `rect` of the back page of `flip`, row by row,
+    /// applying the color fixups. `rect` is relative to the band, i.e., its
+    /// rows are below [rows()](PageFlip::rows).
+    pub async fn draw_back<P, I>(&mut self, flip: &PageFlip, rect: Rect,
+                                 pixels: I) -> Result<(), WindowError>
+            where P: Pixel, I: IntoIterator<Item=P>,
+                  ColorFixups: PixelTransform<P> {
+        if rect.is_empty() {
+            return Err(WindowError::Reversed);
+        }
+        if u32::from(rect.x) + u32::from(rect.width) >
+                    u32::from(self.panel.width()) ||
+                u32::from(rect.y) + u32::from(rect.height) >
+                    u32::from(flip.rows()) {
+            return Err(WindowError::OutOfRange);
+        }
+        let x = rect.x + self.panel.column_offset();
+        let y = flip.memory_row(flip.back(), rect.y);
+        self.commands.caset(x, x + rect.width - 1).await;
+        self.commands.raset(y, y + rect.height - 1).await;
+        let fixups = self.color_fixups;
+        self.commands.ramwr().await.write_pixels_with(pixels, &fixups).await;
+        Ok(())
+    }
+
+    /// Shows the back page of `flip`, which becomes the front page. To not
+    /// tear, call right after a vertical blank, see
+    /// [TePin](crate::te::TePin).
+    pub async fn flip(&mut self, flip: &mut PageFlip) {
+        let start = flip.swap();
+        self.commands.vscsad(start).await;
+    }
+
+    /// Leaves the scroll mode, back to the normal mode. The band then shows
+    /// page 0 whatever the front page was, so flip once more beforehand to
+    /// keep showing page 1.
+    pub async fn end_page_flip(&mut self, flip: PageFlip) {
+        let _ = flip;
+        self.commands.noron().await;
+    }
+
+    /// Writes `pixels` into `rect`, row by row, reduced to
+    /// [Colmod::R4G4B4] with [bayer_rgb444()]. The LCD is expected to be in
+    /// that mode. The color fixups are **not** applied. Nothing is sent if
+    /// `rect` isn't inside the [effective panel](Self::effective_panel).
+    pub async fn draw_dithered_rgb444<I>(&mut self, rect: Rect, pixels: I)
+            -> Result<(), WindowError>
+            where I: IntoIterator, I::Item: DitherSource {
+        self.window_writer(rect).await?
+            .write_rgb444(bayer_rgb444(pixels, rect)).await;
+        Ok(())
+    }
+
+    /// Turns the backlight off, then the display off and then puts the LCD
+    /// into the sleep mode.
+    pub async fn power_off(&mut self) {
+        self.set_brightness(0).await;
+        self.commands.dispoff().await;
+        self.commands.slpin().await;
+    }
+
+    /// Sets the brightness of the backlight.
+    pub async fn set_brightness(&mut self, brightness: u8) {
+        self.brightness = brightness;
+        self.backlight.set_brightness(brightness).await;
+    }
+
+    /// Fades the backlight from its current brightness to `brightness` over
+    /// `duration_ms`. If the display is off, the backlight is first turned
+    /// off and `DISPON` sent, so that the uninitialized or stale memory
+    /// isn't flashed; e.g., after [init()](Self::init).
+    pub async fn fade_in(&mut self, brightness: u8, duration_ms: u32) {
+        if !self.commands.state().contains(PanelState::DISPLAY_ON) {
+            self.set_brightness(0).await;
+            self.commands.dispon().await;
+        }
+        self.ramp_brightness(brightness, duration_ms).await;
+    }
+
+    /// Fades the backlight out over `duration_ms`, then turns the display
+    /// off. Undone by [fade_in()](Self::fade_in).
+    pub async fn fade_out(&mut self, duration_ms: u32) {
+        self.ramp_brightness(0, duration_ms).await;
+        self.commands.dispoff().await;
+    }
+
+    // Steps linearly every FADE_STEP_MS, reaching `to` after `duration_ms`
+    // rounded down to whole steps.
+    async fn ramp_brightness(&mut self, to: u8, duration_ms: u32) {
+        let from = i64::from(self.brightness);
+        let steps = i64::from(duration_ms / FADE_STEP_MS);
+        for i in 1..=steps {
+            self.delay.delay_ms(FADE_STEP_MS).await;
+            let level = from + (i64::from(to) - from) * i / steps;
+            self.set_brightness(level as u8).await;
+        }
+        if steps == 0 {
+            self.set_brightness(to).await;
+        }
+    }
+
+    /// Dims the screen by applying `base`, a custom gamma correction, e.g.,
+    /// one of the [presets], [scaled](GammaConfig::scaled) by `brightness`.
+    /// For panels whose backlight isn't controllable; the display stays on,
+    /// so nothing flickers.
+    pub async fn set_brightness_via_gamma(&mut self, base: GammaConfig,
+                                          brightness: u8) {
+        let gamma = base.scaled(brightness);
+        self.commands.apply_gamma(gamma).await;
+        self.config.gamma = gamma;
+    }
+
+    /// Turns the whole screen into the frame `frame`, row-major in the
+    /// [effective panel](Self::effective_panel), e.g., to
+    /// [unblank()](Self::unblank) it. The color fixups are applied.
+    ///
+    /// # Panics
+    ///
+    /// If the length of `frame` is not the area of the effective panel.
+    pub async fn draw_frame<P>(&mut self, frame: &[P])
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        let rect = self.screen();
+        assert_eq!(frame.len(), rect.area());
+        // Fails only for an empty or unaddressable panel.
+        let _ = self.draw_pixels(rect, frame.iter().copied()).await;
+    }
+
+    /// Restores the screen after [blank()](Self::blank) from `frame`, the
+    /// framebuffer of the application. Same as
+    /// [draw_frame()](Self::draw_frame).
+    pub async fn unblank<P>(&mut self, frame: &[P])
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        self.draw_frame(frame).await;
+    }
+
+    fn screen(&self) -> Rect {
+        let panel = self.effective_panel();
+        Rect::new(0, 0, panel.width(), panel.height())
+    }
+
+    /// [Powers off](Self::power_off) the LCD, waits until its sleep mode is
+    /// entered, then cuts `rail` while holding RESX low so that the pin
+    /// doesn't feed the unpowered LCD.
+    pub async fn power_down<P>(&mut self, rail: &mut P)
+            where for<'a> P: PowerRail<'a> {
+        self.power_off().await;
+        let ms = self.commands.profile().slpin_delay_ms;
+        self.delay.delay_ms(ms).await;
+        self.reset.set_reset_low();
+        rail.disable().await;
+    }
+
+    /// Turns `rail` on, then [initializes](Self::init) the LCD with
+    /// `profile`, which starts with the reset. The backlight is left off.
+    pub async fn power_up<P>(&mut self, rail: &mut P, profile: &InitProfile)
+            where for<'a> P: PowerRail<'a> {
+        rail.enable().await;
+        self.init(profile).await;
+    }
+
+    /// [Powers down](Self::power_down) the LCD and
+    /// [up](Self::power_up) again, e.g., around the sleep of a
+    /// battery-powered device, which can wait between the two halves
+    /// instead.
+    pub async fn power_cycle<P>(&mut self, rail: &mut P, profile: &InitProfile)
+            where for<'a> P: PowerRail<'a> {
+        self.power_down(rail).await;
+        self.power_up(rail, profile).await;
+    }
+}
+
+impl<S, RST, D, BL> Display<S, RST, D, BL>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> + Read<'a>,
+              RST: ResetPin, for<'a> D: DelayMs<'a>,
+              for<'a> BL: Backlight<'a> {
+    /// Reads the power mode and, if the LCD is unexpectedly asleep or its
+    /// display off, e.g., after a brown-out or an ESD event reset it, resets
+    /// it with the reset pin and then, as [recover()](Self::recover) does,
+    /// replays the [config()](Self::config) and restores the brightness. The
+    /// orientation and the [variant](Self::init_variant) are kept. Meant to
+    /// be called periodically.
+    ///
+    /// Requires a working read path: a panel never answering reads as on is
+    /// re-initialized each time.
+    pub async fn health_check(&mut self) -> Health {
+        let expected = self.commands.state();
+        let power_mode = self.commands.rddpm().await;
+        let lost = |panel_flag, power_flag| {
+            expected.contains(panel_flag) && !power_mode.contains(power_flag)
+        };
+        if !lost(PanelState::SLEEP_OUT, PowerMode::SLEEP_OUT) &&
+                !lost(PanelState::DISPLAY_ON, PowerMode::DISPLAY_ON) {
+            return Health::Ok;
+        }
+        let brightness = self.brightness;
+        self.set_brightness(0).await;
+        self.hardware_reset().await;
+        self.replay().await;
+        self.set_brightness(brightness).await;
+        Health::Recovered(power_mode)
+    }
+
+    /// [Recovers](Self::recover), then reads back the key registers as
+    /// [InitProfile::verify()] does, to tell whether the LCD responds again.
+    pub async fn recover_verified(&mut self) -> Result<(), InitMismatch> {
+        self.recover().await;
+        self.recovery_profile().verify(&mut self.commands).await
+    }
+
+    /// Reads the IDs, the color mode and the memory access from the LCD and
+    /// bundles them with what the firmware configured, so that a log line
+    /// tells which panel it came from.
+    pub async fn info(&mut self) -> DisplayInfo {
+        let ids = self.commands.read_ids_robust().await;
+        let colmod = self.commands.rddcolmod().await;
+        let madctl = self.commands.rddmadctl().await;
+        DisplayInfo{ids, variant: self.variant, colmod, madctl,
+                    panel: self.panel}
+    }
+}
+
+impl<S, RST, D, BL> Display<S, RST, D, BL>
+        where S: DcxPin,
+              for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteRepeat<'a>,
+              RST: ResetPin, for<'a> D: DelayMs<'a>,
+              for<'a> BL: Backlight<'a> {
+    /// Fills the whole screen with `color`, as an alternative to
+    /// [DISPOFF](Commands::dispoff) for boards showing artifacts on it. The
+    /// color fixups are applied. Restore the screen with
+    /// [unblank()](Self::unblank).
+    pub async fn blank<P>(&mut self, color: P)
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        let color = self.color_fixups.transform(color);
+        let rect = self.screen();
+        // Fails only for an empty or unaddressable panel.
+        if let Ok(mut w) = self.window_writer(rect).await {
+            w.write_repeated(color.to_bytes().as_ref(), rect.area()).await;
+        }
+    }
+
+    /// Draws a horizontal line of `len` pixels from `(x, y)` to the right,
+    /// as a single-row window filled with `color`. The color fixups are
+    /// applied. Nothing is sent if the line is empty, and `Ok(())` returned,
+    /// or if it isn't inside the [effective panel](Self::effective_panel).
+    pub async fn draw_hline<P>(&mut self, x: u16, y: u16, len: u16, color: P)
+            -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        if len == 0 {
+            return Ok(());
+        }
+        self.fill_checked(Rect::new(x, y, len, 1), color).await
+    }
+
+    /// Draws a vertical line of `len` pixels from `(x, y)` downwards, as a
+    /// single-column window. See [draw_hline()](Self::draw_hline).
+    pub async fn draw_vline<P>(&mut self, x: u16, y: u16, len: u16, color: P)
+            -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        if len == 0 {
+            return Ok(());
+        }
+        self.fill_checked(Rect::new(x, y, 1, len), color).await
+    }
+
+    /// Draws the 1-pixel border of `rect`, each pixel once: the top and the
+    /// bottom rows, then the left and the right columns between them. Nothing
+    /// is sent unless the whole `rect` is inside the
+    /// [effective panel](Self::effective_panel).
+    pub async fn draw_rect_outline<P>(&mut self, rect: Rect, color: P)
+            -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        self.memory_window(rect)?;
+        let Rect{x, y, width, height} = rect;
+        self.draw_hline(x, y, width, color).await?;
+        if height > 1 {
+            self.draw_hline(x, y + height - 1, width, color).await?;
+        }
+        if height > 2 {
+            self.draw_vline(x, y + 1, height - 2, color).await?;
+            if width > 1 {
+                self.draw_vline(x + width - 1, y + 1, height - 2, color).await?;
+            }
+        }
+        Ok(())
+    }
+
+    async fn fill_checked<P>(&mut self, rect: Rect, color: P)
+            -> Result<(), WindowError>
+            where P: Pixel, ColorFixups: PixelTransform<P> {
+        self.set_window_checked(rect).await?;
+        let color = self.color_fixups.transform(color);
+        self.commands.ramwr().await
+            .write_repeated(color.to_bytes().as_ref(), rect.area()).await;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::{
+        collections::BTreeMap, string::ToString, sync::{Arc, Mutex}, vec,
+        vec::Vec};
+
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    pub(crate) enum Event {
+        Command(u8),
+        Data(u8),
+        Reset(bool),
+        Delay(u32),
+        Brightness(u8),
+        Vblank,
+        Rail(bool),
+    }
+
+    pub(crate) type Log = Arc<Mutex<Vec<Event>>>;
+
+    pub(crate) struct FakeReset(pub Log);
+    impl ResetPin for FakeReset {
+        fn set_reset_low(&mut self) {
+            self.0.lock().unwrap().push(Event::Reset(false));
+        }
+        fn set_reset_high(&mut self) {
+            self.0.lock().unwrap().push(Event::Reset(true));
+        }
+    }
+
+    pub(crate) struct FakeDelay(pub Log);
+    impl<'a> DelayMs<'a> for FakeDelay {
+        type DelayDone = Ready<()>;
+        fn delay_ms(&'a mut self, ms: u32) -> Ready<()> {
+            self.0.lock().unwrap().push(Event::Delay(ms));
+            ready(())
+        }
+    }
+
+    pub(crate) struct FakeBacklight(pub Log);
+    impl<'a> Backlight<'a> for FakeBacklight {
+        type SetBrightnessDone = Ready<()>;
+        fn set_brightness(&'a mut self, brightness: u8) -> Ready<()> {
+            self.0.lock().unwrap().push(Event::Brightness(brightness));
+            ready(())
+        }
+    }
+
+    struct FakeRail(Log);
+    impl<'a> PowerRail<'a> for FakeRail {
+        type EnableDone = Ready<()>;
+        type DisableDone = Ready<()>;
+        fn enable(&'a mut self) -> Ready<()> {
+            self.0.lock().unwrap().push(Event::Rail(true));
+            ready(())
+        }
+        fn disable(&'a mut self) -> Ready<()> {
+            self.0.lock().unwrap().push(Event::Rail(false));
+            ready(())
+        }
+    }
+
+    pub(crate) struct FakeTe(pub Log);
+    impl<'a> crate::te::TePin<'a> for FakeTe {
+        type VblankDone = Ready<()>;
+        fn wait_for_vblank(&'a mut self) -> Ready<()> {
+            self.0.lock().unwrap().push(Event::Vblank);
+            ready(())
+        }
+    }
+
+    /// A [MockDevice] logging every written byte into `log`.
+    pub(crate) fn logging_device(log: &Log) -> MockDevice {
+        let mut d = MockDevice::new();
+        let l = log.clone();
+        d.mock().expect_write_command()
+            .returning(move |b| l.lock().unwrap().push(Event::Command(b)));
+        let l = log.clone();
+        d.mock().expect_write_data()
+            .returning(move |b| l.lock().unwrap().push(Event::Data(b)));
+        d
+    }
+
+    fn create_display(log: &Log)
+            -> Display<MockDevice, FakeReset, FakeDelay, FakeBacklight> {
+        Display::new(logging_device(log), FakeReset(log.clone()),
+                     FakeDelay(log.clone()), FakeBacklight(log.clone()),
+                     Panel::new(128, 160))
+    }
+
+    #[test]
+    fn power_on() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(display.power_on(200));
+        assert_eq!(*log.lock().unwrap(), vec![
+            Brightness(0), Reset(false), Delay(1), Reset(true), Delay(120),
+            Command(0x11), Delay(120), Command(0x29), Brightness(200)]);
+        use crate::command_structs::PanelState;
+        assert_eq!(display.commands().state(),
+                   PanelState::SLEEP_OUT | PanelState::DISPLAY_ON);
+        assert_eq!(display.commands().check_ram_access(), Ok(()));
+    }
+
+    #[test]
+    fn power_off() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(display.power_off());
+        assert_eq!(*log.lock().unwrap(),
+                   vec![Brightness(0), Command(0x28), Command(0x10)]);
+    }
+
+    #[test]
+    fn power_cycle() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        let mut rail = FakeRail(log.clone());
+        block_on(display.power_cycle(&mut rail, &InitProfile::default()));
+        let log = log.lock().unwrap();
+        assert_eq!(log[..9], [
+            Brightness(0), Command(0x28), Command(0x10), Delay(120),
+            Reset(false), Rail(false), Rail(true), Brightness(0),
+            Reset(false)]);
+        assert_eq!(log[log.len() - 2..], [Command(0x13), Command(0x29)]);
+    }
+
+    #[test]
+    fn init() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        let mut profile = InitProfile::default();
+        profile.madctl.set_row_column_swap(
+            crate::command_structs::RowColumnSwap::Swapped);
+        profile.inversion = true;
+        block_on(display.init(&profile));
+        assert_eq!(*log.lock().unwrap(), vec![
+            Brightness(0), Reset(false), Delay(1), Reset(true), Delay(120),
+            Command(0x11), Delay(120), Command(0x3A), Data(0b101),
+            Command(0x36), Data(0x20), Command(0x21), Command(0x13),
+            Command(0x29)]);
+        assert_eq!(u8::from(display.orientation()), 0x20);
+    }
+
+    #[test]
+    fn init_variant() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(display.init_variant(PanelVariant::GreenTab144));
+        assert_eq!(*display.panel(), Panel::new(128, 128).with_offsets(2, 3));
+        assert_eq!(u8::from(display.orientation()), 0xC8);
+        let log = log.lock().unwrap();
+        assert_eq!(log[9..15], [
+            Command(0x36), Data(0xC8), Command(0x20),
+            Command(0x26), Data(0x01), Command(0xE0)]);
+        assert_eq!(log[log.len() - 2..], [Command(0x13), Command(0x29)]);
+    }
+
+    #[test]
+    fn variant_profiles() {
+        use PanelVariant::*;
+        for (variant, madctl) in [(RedTab, 0xC8), (GreenTab, 0xC8),
+                                  (BlackTab, 0xC0), (GreenTab144, 0xC8)] {
+            let profile = variant.profile();
+            assert_eq!(profile.madctl, Madctl::from(madctl));
+            assert_eq!(profile.gamma, Some(presets::ADAFRUIT_1_8_TFT));
+            assert_eq!(profile.colmod, Colmod::R5G6B5);
+            assert!(!profile.inversion);
+        }
+    }
+
+    #[test]
+    fn init_with_gamma_preset() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        let profile = InitProfile::default()
+            .with_gamma(presets::WAVESHARE_1_44);
+        block_on(display.init(&profile));
+        let log = log.lock().unwrap();
+        let gmctrp1 = log.iter().position(|e| *e == Command(0xE0)).unwrap();
+        assert_eq!(log[gmctrp1 - 2..gmctrp1 + 3], [
+            Command(0x26), Data(0x01), Command(0xE0), Data(0x0F), Data(0x1A)]);
+    }
+
+    #[test]
+    fn brightness_via_gamma() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(display.set_brightness_via_gamma(presets::ADAFRUIT_1_8_TFT,
+                                                  128));
+        let log = log.lock().unwrap();
+        // The first two parameters halved.
+        assert_eq!(log[..5], [
+            Command(0x26), Data(0x01), Command(0xE0), Data(0x01), Data(0x0E)]);
+        // The last one kept.
+        assert_eq!(log[log.len() - 1], Data(0x10));
+    }
+
+    #[test]
+    fn verify_init() {
+        use crate::fake_device::FakeDevice;
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            NoBacklight, Panel::new(128, 160));
+        let profile = PanelVariant::RedTab.profile();
+        block_on(display.init(&profile));
+        assert_eq!(block_on(profile.verify(display.commands())), Ok(()));
+
+        // As if the bus dropped the data of MADCTL.
+        device.answer_next_read(0x00, 8);
+        block_on(display.commands().dispoff());
+        let mismatch = block_on(profile.verify(display.commands()))
+            .unwrap_err();
+        assert_eq!(mismatch, InitMismatch{
+            madctl: Some((Madctl::from(0xC8), Madctl::from(0x00))),
+            colmod: None,
+            power_mode: Some((
+                PowerMode::SLEEP_OUT | PowerMode::NORMAL_ON |
+                PowerMode::DISPLAY_ON,
+                PowerMode::BOOSTER_ON | PowerMode::SLEEP_OUT |
+                PowerMode::NORMAL_ON)),
+        });
+    }
+
+    #[test]
+    fn health_check() {
+        use crate::fake_device::{FakeDevice, Wire};
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            FakeBacklight(log.clone()), Panel::new(128, 160));
+        block_on(async {
+            display.init_variant(PanelVariant::GreenTab).await;
+            display.set_orientation(Madctl::from(0x68)).await;
+            display.set_brightness(200).await;
+        });
+        assert_eq!(block_on(display.health_check()), Health::Ok);
+
+        // As if an ESD event reset the LCD: in the sleep mode, display off.
+        log.lock().unwrap().clear();
+        device.clear();
+        device.answer_next_read(0x80, 8);
+        assert_eq!(block_on(display.health_check()),
+                   Health::Recovered(PowerMode::BOOSTER_ON));
+        let profile = InitProfile{madctl: Madctl::from(0x68),
+                                  ..PanelVariant::GreenTab.profile()};
+        assert_eq!(block_on(profile.verify(display.commands())), Ok(()));
+        {
+            let log = log.lock().unwrap();
+            assert_eq!(log.first(), Some(&Event::Brightness(0)));
+            assert!(log.contains(&Event::Reset(false)));
+            assert_eq!(log.last(), Some(&Event::Brightness(200)));
+        }
+        // The orientation set after `init_variant()` and the variant are
+        // kept.
+        let seq = device.seq();
+        let madctl = seq.iter().position(|w| *w == Wire::Command(0x36));
+        assert_eq!(seq[madctl.unwrap() + 1], Wire::Data(0x68));
+        assert_eq!(display.orientation(), Madctl::from(0x68));
+        let info = block_on(display.info());
+        assert_eq!(info.variant, Some(PanelVariant::GreenTab));
+    }
+
+    #[test]
+    fn recover() {
+        use crate::fake_device::{FakeDevice, Wire};
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            FakeBacklight(log.clone()), Panel::new(128, 160));
+        block_on(async {
+            display.init(&PanelVariant::RedTab.profile()).await;
+            display.set_orientation(Madctl::from(0x68)).await;
+            display.set_brightness(200).await;
+        });
+        log.lock().unwrap().clear();
+        device.clear();
+        assert_eq!(block_on(display.recover_verified()), Ok(()));
+        assert_eq!(*log.lock().unwrap(), [
+            Event::Brightness(0), Event::Delay(120), Event::Delay(120),
+            Event::Brightness(200)]);
+        let seq = device.seq();
+        assert_eq!(seq[..2], [Wire::Command(0x01), Wire::Command(0x11)]);
+        // The orientation set after `init()` is kept.
+        let madctl = seq.iter().position(|w| *w == Wire::Command(0x36));
+        assert_eq!(seq[madctl.unwrap() + 1], Wire::Data(0x68));
+        assert_eq!(display.orientation(), Madctl::from(0x68));
+    }
+
+    #[test]
+    fn recover_replays_config() {
+        use crate::fake_device::{FakeDevice, Wire};
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            NoBacklight, Panel::new(128, 160));
+        let rate = FrameRate{rtna: 0x05, front_porch: 0x3C, back_porch: 0x3C};
+        let power = PowerProfile{
+            pwctr1: [0xA2, 0x02, 0x84], pwctr2: 0xC5, pwctr3: [0x0A, 0x00],
+            pwctr4: [0x8A, 0x2A], pwctr5: [0x8A, 0xEE], vmctr1: 0x0E};
+        let gamma = presets::ADAFRUIT_1_8_TFT.scaled(100);
+        block_on(async {
+            display.init(&PanelVariant::RedTab.profile()).await;
+            display.set_frame_rate(rate).await;
+            display.set_power_profile(&power).await;
+            display.set_brightness_via_gamma(presets::ADAFRUIT_1_8_TFT, 100)
+                .await;
+        });
+        let config = PanelConfig{
+            madctl: Madctl::from(0xC8), colmod: Some(Colmod::R5G6B5), gamma,
+            frame_rate: Some(rate), power: Some(power),
+            ..PanelConfig::default()};
+        assert_eq!(*display.config(), config);
+
+        device.clear();
+        block_on(display.recover());
+        let expected = FakeDevice::new();
+        block_on(async {
+            let mut commands = Commands::new(expected.clone());
+            commands.apply_config(&config).await;
+            commands.invoff().await;
+            commands.noron().await;
+            commands.dispon().await;
+        });
+        let seq = device.seq();
+        assert_eq!(seq[..2], [Wire::Command(0x01), Wire::Command(0x11)]);
+        assert_eq!(seq[2..], expected.seq()[..]);
+
+        // A new `init()` starts from its profile again.
+        block_on(display.init(&PanelVariant::RedTab.profile()));
+        assert_eq!(display.config().frame_rate, None);
+        assert_eq!(display.config().gamma, presets::ADAFRUIT_1_8_TFT);
+    }
+
+    #[test]
+    fn info() {
+        use crate::fake_device::FakeDevice;
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            FakeBacklight(log.clone()), Panel::new(128, 160));
+        block_on(display.init_variant(PanelVariant::GreenTab));
+        let info = block_on(display.info());
+        assert_eq!(info, DisplayInfo{
+            ids: Some((IdSource::Rddid, [0x7C, 0x89, 0xF0])),
+            variant: Some(PanelVariant::GreenTab),
+            colmod: Colmod::R5G6B5, madctl: Madctl::from(0xC8),
+            panel: Panel::new(128, 160).with_offsets(2, 1)});
+        assert_eq!(info.to_string(),
+                   "ids=7C 89 F0 (Rddid) variant=GreenTab colmod=R5G6B5 \
+                    madctl=C8 128x160+2+1");
+
+        // Neither ID path answers; a plain `init()` forgets the variant.
+        device.set_id([0, 0, 0]);
+        block_on(display.init(&InitProfile::default()));
+        let info = block_on(display.info());
+        assert_eq!(info.ids, None);
+        assert_eq!(info.variant, None);
+        assert!(info.to_string().starts_with("ids=? variant=? "));
+    }
+
+    #[test]
+    fn fade() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(display.fade_in(200, 64));
+        assert_eq!(*log.lock().unwrap(), [
+            Brightness(0), Command(0x29),
+            Delay(16), Brightness(50), Delay(16), Brightness(100),
+            Delay(16), Brightness(150), Delay(16), Brightness(200)]);
+
+        log.lock().unwrap().clear();
+        block_on(display.fade_out(40));
+        block_on(display.fade_in(30, 0));
+        assert_eq!(*log.lock().unwrap(), [
+            Delay(16), Brightness(100), Delay(16), Brightness(0),
+            Command(0x28),
+            Brightness(0), Command(0x29), Brightness(30)]);
+
+        // Already on: only the backlight changes.
+        log.lock().unwrap().clear();
+        block_on(display.fade_in(60, 16));
+        assert_eq!(*log.lock().unwrap(), [Delay(16), Brightness(60)]);
+    }
+
+    #[test]
+    fn sleep_and_wake() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        block_on(async {
+            display.sleep().await;
+            display.wake().await;
+        });
+        assert_eq!(*log.lock().unwrap(),
+                   vec![Command(0x10), Delay(120), Command(0x11), Delay(120)]);
+    }
+
+    #[test]
+    fn sleep_and_wake_st7789() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        display.commands().set_profile(ControllerProfile::ST7789);
+        block_on(async {
+            display.sleep().await;
+            display.wake().await;
+        });
+        assert_eq!(*log.lock().unwrap(),
+                   vec![Command(0x10), Delay(120), Command(0x11), Delay(5)]);
+        assert!(InitProfile::for_controller(&ControllerProfile::ST7789)
+                .inversion);
+    }
+
+    #[test]
+    fn blank_and_unblank() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight,
+            Panel::new(2, 1).with_offsets(1, 2));
+        block_on(async {
+            display.blank(Rgb565::from_raw(0x1234)).await;
+            display.unblank(&[Rgb565::from_raw(0xABCD),
+                              Rgb565::from_raw(0x5678)]).await;
+        });
+        let window = [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(2),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(2),
+            Command(0x2C)];
+        let log = log.lock().unwrap();
+        assert_eq!(log[..11], window);
+        assert_eq!(log[11..15],
+                   [Data(0x12), Data(0x34), Data(0x12), Data(0x34)]);
+        assert_eq!(log[15..26], window);
+        assert_eq!(log[26..], [Data(0xAB), Data(0xCD), Data(0x56), Data(0x78)]);
+    }
+
+    #[test]
+    fn draw_stream() {
+        use crate::color::Rgb565;
+        use crate::stream::runs;
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        assert_eq!(block_on(display.draw_stream(
+            Rect::new(1, 2, 1, 3), runs([(3, Rgb565::from_raw(0x1234))]))),
+            Ok(()));
+        assert_eq!(log.lock().unwrap()[11..], [
+            Data(0x12), Data(0x34), Data(0x12), Data(0x34),
+            Data(0x12), Data(0x34)]);
+    }
+
+    #[test]
+    fn window_writer() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight,
+            Panel::new(128, 128).with_offsets(2, 3));
+        block_on(async {
+            let w = display.window_writer(Rect::new(10, 20, 4, 5)).await;
+            w.unwrap().write_u8s(&[0xAB]).await;
+        });
+        assert_eq!(*log.lock().unwrap(), vec![
+            Command(0x2A), Data(0), Data(12), Data(0), Data(15),
+            Command(0x2B), Data(0), Data(23), Data(0), Data(27),
+            Command(0x2C), Data(0xAB)]);
+
+        // Past the panel, then past `u16::MAX` with the offsets.
+        log.lock().unwrap().clear();
+        block_on(async {
+            let rect = Rect::new(125, 0, 4, 1);
+            assert!(matches!(display.window_writer(rect).await,
+                             Err(WindowError::OutOfRange)));
+            let rect = Rect::new(u16::MAX - 1, 0, 1, 1);
+            assert!(matches!(display.window_writer(rect).await,
+                             Err(WindowError::OutOfRange)));
+            assert_eq!(display.draw_pixels(Rect::new(0, 0, 0, 1),
+                                           [Rgb565::from_raw(0)]).await,
+                       Err(WindowError::Reversed));
+        });
+        assert!(log.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn set_window_checked_follows_orientation() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight,
+            Panel::new(128, 160).with_offsets(2, 1));
+        let wide = Rect::new(150, 0, 10, 1);
+        assert_eq!(block_on(display.set_window_checked(wide)),
+                   Err(WindowError::OutOfRange));
+        assert_eq!(block_on(display.set_window_checked(Rect::new(0, 0, 0, 1))),
+                   Err(WindowError::Reversed));
+        assert!(log.lock().unwrap().is_empty());
+
+        block_on(display.set_orientation(Madctl::from(0x20)));
+        assert_eq!(display.effective_panel().width(), 160);
+        assert_eq!(block_on(display.set_window_checked(wide)), Ok(()));
+        assert_eq!(block_on(display.set_window_checked(
+            Rect::new(0, 120, 1, 9))), Err(WindowError::OutOfRange));
+        assert_eq!(log.lock().unwrap()[2..], [
+            Command(0x2A), Data(0), Data(151), Data(0), Data(160),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(2)]);
+    }
+
+    #[test]
+    fn blit() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight,
+            Panel::new(128, 160).with_offsets(2, 1));
+        let pixels: Vec<_> = (1..=6).map(Rgb565::from_raw).collect();
+        let rect = Rect::new(1, 3, 2, 3);
+        assert_eq!(block_on(display.blit(rect, &pixels)), Ok(()));
+        assert_eq!(log.lock().unwrap()[..11], [
+            Command(0x2A), Data(0), Data(3), Data(0), Data(4),
+            Command(0x2B), Data(0), Data(4), Data(0), Data(6),
+            Command(0x2C)]);
+        assert_eq!(log.lock().unwrap()[11..].iter().step_by(2).count(), 6);
+        log.lock().unwrap().clear();
+
+        // Transposed with MV, and so is the window.
+        block_on(display.set_orientation(Madctl::from(0x20)));
+        assert_eq!(block_on(display.blit(rect, &pixels)), Ok(()));
+        let log = log.lock().unwrap();
+        assert_eq!(log[2..13], [
+            Command(0x2A), Data(0), Data(4), Data(0), Data(6),
+            Command(0x2B), Data(0), Data(3), Data(0), Data(4),
+            Command(0x2C)]);
+        let low_bytes: Vec<_> = log[13..].iter().skip(1).step_by(2).collect();
+        assert_eq!(low_bytes, [&Data(1), &Data(3), &Data(5), &Data(2),
+                               &Data(4), &Data(6)]);
+    }
+
+    #[test]
+    fn draw_asset() {
+        use crate::asset::Asset;
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        let asset =
+            Asset{width: 2, height: 1, bytes: &[0xF8, 0x00, 0x12, 0x34]};
+        assert_eq!(block_on(display.draw_asset(3, 4, &asset)), Ok(()));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x2A), Data(0), Data(3), Data(0), Data(4),
+            Command(0x2B), Data(0), Data(4), Data(0), Data(4),
+            Command(0x2C), Data(0xF8), Data(0x00), Data(0x12), Data(0x34)]);
+        assert_eq!(block_on(display.draw_asset(127, 0, &asset)),
+                   Err(WindowError::OutOfRange));
+
+        log.lock().unwrap().clear();
+        display.set_color_fixups(ColorFixups{swap_rb: true,
+                                             ..Default::default()});
+        assert_eq!(block_on(display.draw_asset(3, 4, &asset)), Ok(()));
+        assert_eq!(log.lock().unwrap()[11..],
+                   [Data(0x00), Data(0x1F), Data(0xA2), Data(0x22)]);
+    }
+
+    #[test]
+    fn page_flip() {
+        use Event::*;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), FakeBacklight(log.clone()),
+            PanelVariant::GreenTab144.panel());
+        assert_eq!(block_on(display.page_flip(32)),
+                   Err(FlipError::NoRoom{rows: 32, spare: 31}));
+        let mut flip = block_on(display.page_flip(16)).unwrap();
+        // Page 0 at the rows 115 to 130, page 1 at 131 to 146.
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x33), Data(0), Data(115), Data(0), Data(32), Data(0),
+            Data(15),
+            Command(0x37), Data(0), Data(115)]);
+
+        log.lock().unwrap().clear();
+        let pixel = crate::color::Rgb565::from_raw(0x1234);
+        assert_eq!(block_on(display.draw_back(&flip, Rect::new(0, 16, 1, 1),
+                                              [pixel])),
+                   Err(WindowError::OutOfRange));
+        assert_eq!(block_on(display.draw_back(&flip, Rect::new(1, 15, 1, 1),
+                                              [pixel])),
+                   Ok(()));
+        block_on(display.flip(&mut flip));
+        block_on(display.flip(&mut flip));
+        block_on(display.end_page_flip(flip));
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x2A), Data(0), Data(3), Data(0), Data(3),
+            Command(0x2B), Data(0), Data(146), Data(0), Data(146),
+            Command(0x2C), Data(0x12), Data(0x34),
+            Command(0x37), Data(0), Data(131),
+            Command(0x37), Data(0), Data(115),
+            Command(0x13)]);
+    }
+
+    #[test]
+    fn draw_sprite() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        let (t, a, b) = (Rgb565::from_raw(0), Rgb565::from_raw(0x0A0A),
+                         Rgb565::from_raw(0x0B0B));
+        let pixels = [a, t, b, b,
+                      t, t, t, a];
+        let sprite = Sprite::new(4, 2, &pixels);
+        assert_eq!(block_on(display.draw_sprite(-1, 159, &sprite, Some(t))),
+                   Ok(()));
+        // Only the first row is visible, without the first column.
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(2),
+            Command(0x2B), Data(0), Data(159), Data(0), Data(159),
+            Command(0x2C), Data(0x0B), Data(0x0B), Data(0x0B), Data(0x0B)]);
+        log.lock().unwrap().clear();
+
+        assert_eq!(block_on(display.draw_sprite(10, 20, &sprite, Some(t))),
+                   Ok(()));
+        let windows: Vec<_> = log.lock().unwrap().iter()
+            .filter(|e| **e == Command(0x2C)).cloned().collect();
+        assert_eq!(windows.len(), 3);
+        log.lock().unwrap().clear();
+
+        // Opaque, in a single window.
+        assert_eq!(block_on(display.draw_sprite(0, 0, &sprite, None)), Ok(()));
+        let log = log.lock().unwrap();
+        assert_eq!(log.iter().filter(|e| **e == Command(0x2C)).count(), 1);
+        assert_eq!(log.len(), 11 + 16);
+    }
+
+    // The pixels written by `seq`, with how many times each, given CASET and
+    // RASET before each RAMWR.
+    fn touched(seq: &[crate::fake_device::Wire])
+            -> BTreeMap<(u16, u16), usize> {
+        use crate::fake_device::Wire::{Command, Data};
+        let mut touched = BTreeMap::new();
+        let (mut cmd, mut params) = (0, Vec::new());
+        let (mut columns, mut rows) = ((0, 0), (0, 0));
+        let mut next = (0, 0);
+        for wire in seq {
+            match *wire {
+                Command(c) => {
+                    cmd = c;
+                    params.clear();
+                    next = (columns.0, rows.0);
+                },
+                Data(d) => {
+                    params.push(d);
+                    let word = |i: usize| {
+                        u16::from_be_bytes([params[i], params[i + 1]])
+                    };
+                    match (cmd, params.len()) {
+                        (0x2A, 4) => columns = (word(0), word(2)),
+                        (0x2B, 4) => rows = (word(0), word(2)),
+                        (0x2C, n) if n % 2 == 0 => {
+                            *touched.entry(next).or_insert(0) += 1;
+                            next.0 += 1;
+                            if next.0 > columns.1 {
+                                next = (columns.0, next.1 + 1);
+                            }
+                        },
+                        _ => {},
+                    }
+                },
+            }
+        }
+        touched
+    }
+
+    #[test]
+    fn lines_and_outline() {
+        use crate::color::Rgb565;
+        use crate::fake_device::FakeDevice;
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            NoBacklight, Panel::new(128, 160).with_offsets(2, 1));
+        let color = Rgb565::from_raw(0x1234);
+        let pixels = |xs: std::ops::Range<u16>, ys: std::ops::Range<u16>| {
+            ys.flat_map(|y| xs.clone().map(move |x| ((x + 2, y + 1), 1)))
+                .collect::<BTreeMap<_, _>>()
+        };
+
+        assert_eq!(block_on(display.draw_hline(2, 3, 4, color)), Ok(()));
+        assert_eq!(touched(&device.seq()), pixels(2..6, 3..4));
+        device.clear();
+        assert_eq!(block_on(display.draw_vline(5, 1, 3, color)), Ok(()));
+        assert_eq!(touched(&device.seq()), pixels(5..6, 1..4));
+        device.clear();
+
+        // Each border pixel exactly once.
+        let rect = Rect::new(10, 20, 4, 3);
+        assert_eq!(block_on(display.draw_rect_outline(rect, color)), Ok(()));
+        let mut expected = pixels(10..14, 20..23);
+        expected.remove(&(11 + 2, 21 + 1));
+        expected.remove(&(12 + 2, 21 + 1));
+        assert_eq!(touched(&device.seq()), expected);
+        device.clear();
+
+        for (w, h) in [(1, 1), (1, 4), (5, 2)] {
+            let rect = Rect::new(0, 0, w, h);
+            assert_eq!(block_on(display.draw_rect_outline(rect, color)),
+                       Ok(()));
+            assert_eq!(touched(&device.seq()), pixels(0..w, 0..h));
+            device.clear();
+        }
+
+        assert_eq!(block_on(display.draw_rect_outline(
+                       Rect::new(120, 0, 9, 2), color)),
+                   Err(WindowError::OutOfRange));
+        assert_eq!(block_on(display.draw_hline(0, 0, 0, color)), Ok(()));
+        assert_eq!(block_on(display.draw_vline(200, 0, 0, color)), Ok(()));
+        assert_eq!(block_on(display.draw_hline(126, 0, 3, color)),
+                   Err(WindowError::OutOfRange));
+        assert!(device.seq().is_empty());
+    }
+
+    #[test]
+    fn blit_flipped() {
+        use crate::color::Rgb565;
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight, Panel::new(128, 160));
+        // 3x2, i.e., rows [1, 2, 3] and [4, 5, 6].
+        let pixels: Vec<_> = (1..=6).map(Rgb565::from_raw).collect();
+        let rect = Rect::new(0, 0, 3, 2);
+        let streamed = |display: &mut Display<_, _, _>, flip| {
+            log.lock().unwrap().clear();
+            assert_eq!(block_on(display.blit_flipped(rect, &pixels, flip)),
+                       Ok(()));
+            let log = log.lock().unwrap();
+            let start = log.iter().position(|e| *e == Event::Command(0x2C));
+            log[start.unwrap() + 2..].iter().step_by(2).map(|e| match e {
+                Event::Data(b) => *b,
+                e => panic!("Unexpected {:?}.", e),
+            }).collect::<Vec<_>>()
+        };
+        assert_eq!(streamed(&mut display, Flip::VERTICAL), [4, 5, 6, 1, 2, 3]);
+        assert_eq!(streamed(&mut display, Flip::HORIZONTAL),
+                   [3, 2, 1, 6, 5, 4]);
+
+        // Under MV, the column address walks along the rows of the image.
+        block_on(display.set_orientation(Madctl::from(0x20)));
+        assert_eq!(streamed(&mut display, Flip::VERTICAL), [4, 1, 5, 2, 6, 3]);
+    }
+
+    #[test]
+    fn draw_pixels_with_fixups() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        display.set_color_fixups(ColorFixups{invert_colors: false,
+                                             swap_rb: true});
+        assert_eq!(block_on(display.draw_pixels(Rect::new(0, 0, 1, 1),
+                                                [Rgb565::new(0x1F, 0, 0)])),
+                   Ok(()));
+        assert_eq!(log.lock().unwrap()[10..], [
+            Command(0x2C), Data(0x00), Data(0x1F)]);
+    }
+
+    #[test]
+    fn draw_dithered_rgb444() {
+        use crate::color::Rgb565;
+        use Event::*;
+        let log = Log::default();
+        let mut display = create_display(&log);
+        assert_eq!(block_on(display.draw_dithered_rgb444(
+            Rect::new(0, 0, 2, 1), [Rgb565::new(31, 63, 31); 2])), Ok(()));
+        assert_eq!(log.lock().unwrap()[10..], [
+            Command(0x2C), Data(0xFF), Data(0xFF), Data(0xFF)]);
+    }
+
+    #[test]
+    fn no_backlight() {
+        let log = Log::default();
+        let mut display = Display::new(
+            logging_device(&log), FakeReset(log.clone()),
+            FakeDelay(log.clone()), NoBacklight, Panel::new(128, 128));
+        block_on(display.set_brightness(10));
+        assert!(log.lock().unwrap().is_empty());
+        assert_eq!(display.panel().width(), 128);
+    }
+}  // mod tests
@@ -0,0 +1,169 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Object-safe versions of the traits in [crate::spi], with boxed futures, to
+//! pick a backend at runtime, e.g., a simulator or the hardware.
+//!
+//! Any backend implementing the [crate::spi] traits is a [DynWrite], and a
+//! [DynRead] if it reads. In turn, a `Box<dyn DynWrite>` or a
+//! `Box<dyn DynBackend>` implements the [crate::spi] traits, so that
+//! [Commands](crate::Commands) is used as usual:
+//! ```ignore
+//! let backend: Box<dyn DynBackend> = if simulated {
+//!     Box::new(simulator)
+//! } else {
+//!     Box::new(spi)
+//! };
+//! let mut commands = Commands::new(backend);
+//! ```
+//! Each write allocates, so keep this for where a generic parameter doesn't
+//! fit.
+//!
+//! Needs the `alloc` feature.
+
+use alloc::boxed::Box;
+use core::future::Future;
+use core::pin::Pin;
+
+use crate::spi::{DcxPin, Read, ReadBits, WriteU8, WriteU8s};
+
+/// A boxed future.
+pub type BoxFuture<'a, T> = Pin<Box<dyn Future<Output=T> + 'a>>;
+
+/// The object-safe combination of [DcxPin], [WriteU8] and [WriteU8s].
+pub trait DynWrite: DcxPin {
+    fn write_u8_boxed(&mut self, data: u8) -> BoxFuture<'_, ()>;
+    fn write_u8s_boxed<'a>(&'a mut self, data: &'a [u8]) -> BoxFuture<'a, ()>;
+    fn preferred_chunk(&self) -> usize;
+}
+
+impl<T> DynWrite for T
+        where T: DcxPin, for<'a> T: WriteU8<'a> + WriteU8s<'a> {
+    fn write_u8_boxed(&mut self, data: u8) -> BoxFuture<'_, ()> {
+        Box::pin(self.write_u8(data))
+    }
+
+    fn write_u8s_boxed<'a>(&'a mut self, data: &'a [u8]) -> BoxFuture<'a, ()> {
+        Box::pin(self.write_u8s(data))
+    }
+
+    fn preferred_chunk(&self) -> usize { WriteU8s::preferred_chunk(self) }
+}
+
+/// The object-safe version of [ReadBits].
+pub trait DynReadBits {
+    fn read_bits_boxed(&mut self, num_bits: usize) -> BoxFuture<'_, u32>;
+}
+
+impl<T> DynReadBits for T where for<'a> T: ReadBits<'a> {
+    fn read_bits_boxed(&mut self, num_bits: usize) -> BoxFuture<'_, u32> {
+        Box::pin(self.read_bits(num_bits))
+    }
+}
+
+/// The object-safe version of [Read].
+pub trait DynRead {
+    fn start_reading_boxed(&mut self) -> Box<dyn DynReadBits + '_>;
+}
+
+impl<T> DynRead for T where for<'a> T: Read<'a> {
+    fn start_reading_boxed(&mut self) -> Box<dyn DynReadBits + '_> {
+        Box::new(self.start_reading())
+    }
+}
+
+/// A backend that both writes and reads.
+pub trait DynBackend: DynWrite + DynRead {}
+
+impl<T: DynWrite + DynRead + ?Sized> DynBackend for T {}
+
+impl<T: DynWrite + ?Sized> DcxPin for Box<T> {
+    fn set_dcx_command_mode(&mut self) { (**self).set_dcx_command_mode() }
+    fn set_dcx_data_mode(&mut self) { (**self).set_dcx_data_mode() }
+}
+
+impl<'a, T: DynWrite + ?Sized> WriteU8<'a> for Box<T> {
+    type WriteU8Done = BoxFuture<'a, ()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        (**self).write_u8_boxed(data)
+    }
+}
+
+impl<'a, T: DynWrite + ?Sized> WriteU8s<'a> for Box<T> {
+    type WriteU8sDone = BoxFuture<'a, ()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        (**self).write_u8s_boxed(data)
+    }
+
+    fn preferred_chunk(&self) -> usize { DynWrite::preferred_chunk(&**self) }
+}
+
+impl<'a, T: DynRead + ?Sized> Read<'a> for Box<T> {
+    type ReadBitsType = Box<dyn DynReadBits + 'a>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        (**self).start_reading_boxed()
+    }
+}
+
+impl<'a, 'b> ReadBits<'b> for Box<dyn DynReadBits + 'a> {
+    type ReadBitsDone = BoxFuture<'b, u32>;
+
+    fn read_bits(&'b mut self, num_bits: usize) -> Self::ReadBitsDone {
+        (**self).read_bits_boxed(num_bits)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use crate::Commands;
+    use crate::display::tests::{Event::*, Log, logging_device};
+    use crate::testing_device::{MockDevice, block_on};
+    use super::*;
+
+    #[test]
+    fn switches_backends() {
+        let (first, second) = (Log::default(), Log::default());
+        let backends: Vec<Box<dyn DynWrite>> = std::vec![
+            Box::new(logging_device(&first)),
+            Box::new(logging_device(&second)),
+        ];
+        for (i, backend) in backends.into_iter().enumerate() {
+            let mut commands = Commands::new(backend);
+            block_on(commands.vscsad(i as u16 + 1));
+        }
+        assert_eq!(*first.lock().unwrap(),
+                   [Command(0x37), Data(0), Data(1)]);
+        assert_eq!(*second.lock().unwrap(),
+                   [Command(0x37), Data(0), Data(2)]);
+    }
+
+    #[test]
+    fn reads() {
+        let mut device = MockDevice::new();
+        device.mock().expect_write_command().return_const(());
+        device.mock().expect_start_reading().times(1).return_const(());
+        let mut bits = [true, false].iter().cycle();
+        device.mock().expect_read_bit()
+            .times(8)
+            .returning(move || *bits.next().unwrap());
+        device.mock().expect_finish_reading().times(1).return_const(());
+        let backend: Box<dyn DynBackend> = Box::new(device);
+        let mut commands = Commands::new(backend);
+        assert_eq!(block_on(commands.rdid1()), 0b10101010);
+    }
+}  // mod tests
@@ -0,0 +1,283 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A fake backend recording the bytes on the wire, for tests of code built on
+//! [Commands](crate::Commands).
+//!
+//! It also answers RDDID, RDDMADCTL and RDDCOLMOD consistently with what was
+//! written before, like a loopback, so write-then-verify logic can be tested
+//! on the host.
+//!
+//! Needs the `test-utils` feature.
+
+use core::future::{Ready, ready};
+use std::{cell::RefCell, rc::Rc, vec::Vec};
+
+use crate::spi::{
+    DcxPin, Read, ReadBits, WriteRepeat, WriteU8, WriteU8s,
+    WriteU8sStatic};
+
+/// One byte on the wire, with the DCX mode it is written in.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Wire {
+    Command(u8),
+    Data(u8),
+}
+
+/// The ID returned by RDDID unless [set_id()](FakeDevice::set_id) is called.
+pub const DEFAULT_ID: [u8; 3] = [0x7C, 0x89, 0xF0];
+
+/// The registers that can be read back.
+#[derive(Clone, Copy, Debug)]
+struct Registers {
+    id: [u8; 3],
+    madctl: u8,
+    colmod: u8,
+    /// As answered to RDDPM.
+    power_mode: u8,
+    /// The last command byte, and how many data bytes followed it.
+    command: u8,
+    num_params: usize,
+    /// Overrides the answer to the next read.
+    next_read: Option<(u32, usize)>,
+}
+
+impl Default for Registers {
+    fn default() -> Self {
+        // MADCTL, COLMOD and the power mode as after a reset: asleep, in
+        // the normal mode, with the display off.
+        Self{id: DEFAULT_ID, madctl: 0x00, colmod: 0x06, power_mode: 0x08,
+             command: 0x00, num_params: 0, next_read: None}
+    }
+}
+
+impl Registers {
+    fn write(&mut self, is_data_mode: bool, b: u8) {
+        if !is_data_mode {
+            if b == 0x01 { *self = Self{id: self.id, ..Default::default()}; }
+            self.power_mode = match b {
+                0x10 => self.power_mode & !0x90,  // SLPIN.
+                0x11 => self.power_mode | 0x90,  // SLPOUT, with the booster.
+                0x12 => self.power_mode & !0x08 | 0x20,  // PTLON.
+                0x13 => self.power_mode & !0x20 | 0x08,  // NORON.
+                0x28 => self.power_mode & !0x04,  // DISPOFF.
+                0x29 => self.power_mode | 0x04,  // DISPON.
+                0x38 => self.power_mode & !0x40,  // IDMOFF.
+                0x39 => self.power_mode | 0x40,  // IDMON.
+                _ => self.power_mode,
+            };
+            self.command = b;
+            self.num_params = 0;
+            return;
+        }
+        match (self.command, self.num_params) {
+            (0x36, 0) => self.madctl = b,
+            (0x3A, 0) => self.colmod = b,
+            _ => {},
+        }
+        self.num_params += 1;
+    }
+
+    /// The bits answering the last command, and how many there are.
+    fn response(&self) -> (u32, usize) {
+        let [id1, id2, id3] = self.id;
+        match self.command {
+            // With the dummy bit in front.
+            0x04 => (u32::from_be_bytes([0, id1, id2, id3]), 25),
+            0x0A => (self.power_mode as u32, 8),
+            0x0B => (self.madctl as u32, 8),
+            0x0C => (self.colmod as u32, 8),
+            _ => (0, 0),
+        }
+    }
+}
+
+/// A backend recording every byte written, and answering some reads.
+///
+/// Clones share the recording: keep one to inspect with [seq()](Self::seq)
+/// after handing the other to [Commands](crate::Commands).
+#[derive(Clone, Debug, Default)]
+pub struct FakeDevice {
+    seq: Rc<RefCell<Vec<Wire>>>,
+    regs: Rc<RefCell<Registers>>,
+    is_data_mode: bool,
+}
+
+impl FakeDevice {
+    pub fn new() -> Self { Default::default() }
+
+    /// Sets the ID answered to RDDID.
+    pub fn set_id(&self, id: [u8; 3]) { self.regs.borrow_mut().id = id; }
+
+    /// Answers the next read, whatever the command, with the lowest `len`
+    /// bits of `bits`, the highest first.
+    pub fn answer_next_read(&self, bits: u32, len: usize) {
+        assert!(len <= 32);
+        self.regs.borrow_mut().next_read = Some((bits, len));
+    }
+
+    /// The bytes written so far, in order.
+    pub fn seq(&self) -> Vec<Wire> { self.seq.borrow().clone() }
+
+    /// Forgets the bytes written so far.
+    pub fn clear(&self) { self.seq.borrow_mut().clear() }
+
+    fn record(&self, data: &[u8]) {
+        let mut regs = self.regs.borrow_mut();
+        for b in data { regs.write(self.is_data_mode, *b); }
+        let mut seq = self.seq.borrow_mut();
+        if self.is_data_mode {
+            seq.extend(data.iter().map(|b| Wire::Data(*b)));
+        } else {
+            seq.extend(data.iter().map(|b| Wire::Command(*b)));
+        }
+    }
+}
+
+impl DcxPin for FakeDevice {
+    fn set_dcx_command_mode(&mut self) { self.is_data_mode = false; }
+    fn set_dcx_data_mode(&mut self) { self.is_data_mode = true; }
+}
+
+impl<'a> WriteU8<'a> for FakeDevice {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.record(&[data]);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8s<'a> for FakeDevice {
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.record(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8sStatic<'a> for FakeDevice {
+    type WriteU8sStaticDone = Ready<()>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+            -> Self::WriteU8sStaticDone {
+        self.record(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteRepeat<'a> for FakeDevice {
+    type WriteRepeatDone = Ready<()>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Self::WriteRepeatDone {
+        for _ in 0..count { self.record(data); }
+        ready(())
+    }
+}
+
+impl<'a> Read<'a> for FakeDevice {
+    type ReadBitsType = FakeReader;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        let mut regs = self.regs.borrow_mut();
+        let (bits, len) = regs.next_read.take()
+            .unwrap_or_else(|| regs.response());
+        FakeReader{bits, len}
+    }
+}
+
+/// Reads the answer of a [FakeDevice] to the last command, then zeros.
+#[derive(Debug)]
+pub struct FakeReader {
+    bits: u32,
+    /// How many of the lowest `bits` are still unread.
+    len: usize,
+}
+
+impl<'a> ReadBits<'a> for FakeReader {
+    type ReadBitsDone = Ready<u32>;
+
+    fn read_bits(&'a mut self, num_bits: usize) -> Self::ReadBitsDone {
+        assert!(num_bits <= 32);
+        let mut v = 0u32;
+        for _ in 0..num_bits {
+            let bit = if self.len == 0 { 0 } else {
+                self.len -= 1;
+                self.bits >> self.len & 1
+            };
+            v = v << 1 | bit;
+        }
+        ready(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{Colmod, Commands, Madctl, PowerMode};
+    use crate::testing_device::block_on;
+    use super::*;
+    use Wire::*;
+
+    #[test]
+    fn records() {
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        block_on(async {
+            commands.caset(1, 2).await;
+            commands.dispon().await;
+        });
+        assert_eq!(device.seq(), [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(2),
+            Command(0x29)]);
+        block_on(async {
+            commands.ramwr().await.write_static(&[0x12, 0x34]).await;
+        });
+        assert_eq!(device.seq()[6..], [Command(0x2C), Data(0x12), Data(0x34)]);
+        device.clear();
+        assert!(device.seq().is_empty());
+    }
+
+    #[test]
+    fn reads_back_writes() {
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        block_on(async {
+            assert_eq!(commands.rddid().await, DEFAULT_ID);
+            assert_eq!(commands.rddmadctl().await, Madctl::from(0x00));
+            assert_eq!(commands.rddcolmod().await, Colmod::R6G6B6);
+            assert_eq!(commands.rddpm().await, PowerMode::NORMAL_ON);
+
+            commands.slpout().await;
+            commands.dispon().await;
+            assert_eq!(commands.rddpm().await,
+                       PowerMode::BOOSTER_ON | PowerMode::SLEEP_OUT |
+                       PowerMode::NORMAL_ON | PowerMode::DISPLAY_ON);
+            commands.madctl(Madctl::from(0xA8)).await;
+            commands.colmod(Colmod::R5G6B5).await;
+            device.set_id([1, 2, 3]);
+            assert_eq!(commands.rddid().await, [1, 2, 3]);
+            assert_eq!(commands.rddmadctl().await, Madctl::from(0xA8));
+            assert_eq!(commands.rddcolmod().await, Colmod::R5G6B5);
+
+            // SWRESET restores the registers, but not the ID.
+            commands.swreset().await;
+            assert_eq!(commands.rddmadctl().await, Madctl::from(0x00));
+            assert_eq!(commands.rddcolmod().await, Colmod::R6G6B6);
+            assert_eq!(commands.rddpm().await, PowerMode::NORMAL_ON);
+            assert_eq!(commands.rddid().await, [1, 2, 3]);
+        });
+    }
+}  // mod tests
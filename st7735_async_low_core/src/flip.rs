@@ -0,0 +1,202 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Page flipping of a band of the screen, using the memory rows that the
+//! panel doesn't show as the back page, see [Display::page_flip()].
+//!
+//! The memory of the controller is usually taller than the panel, e.g., 162
+//! rows for the 128 rows of a 1.44" ST7735 panel. The band, i.e., the last
+//! `rows` visible rows across the whole width, and as many spare rows below
+//! it make up the vertical scroll area. The page not shown is redrawn at
+//! leisure, then [Display::flip()] moves the scroll start address to show it
+//! at once, which doesn't tear when sent right after a vertical blank, see
+//! [TePin](crate::te::TePin).
+//!
+//! How many rows can be flipped depends on the spare rows below the panel,
+//! i.e., on the memory rows minus the row offset and the height:
+//!
+//! | Panel                  | Controller  | Offsets | Rows flippable     |
+//! |------------------------|-------------|---------|--------------------|
+//! | 1.8" 128x160, red tab  | ST7735S     | 0, 0    | 2                  |
+//! | 1.8" 128x160, green tab| ST7735S     | 2, 1    | 1                  |
+//! | 1.44" 128x128          | ST7735S     | 2, 3    | 31                 |
+//! | 0.96" 80x160           | ST7735S     | 26, 1   | 1                  |
+//! | 1.3" 240x240           | ST7789      | 0, 0    | 80                 |
+//! | 1.14" 135x240          | ST7789      | 52, 40  | 40                 |
+//! | 2.0" 240x320           | ST7789      | 0, 0    | 0, not supported   |
+//!
+//! So a whole screen is only flippable when it is at most half the memory
+//! rows past the row offset; the usual use is a status bar or a ticker at
+//! the bottom. The scroll moves the rows the panel scans, so an
+//! orientation exchanging rows and columns is refused.
+//!
+//! [Display::page_flip()]: crate::display::Display::page_flip
+//! [Display::flip()]: crate::display::Display::flip
+
+use crate::command_structs::{Madctl, RowColumnSwap, RowOrder, ScrollConfig};
+use crate::panel::Panel;
+
+/// Why [PageFlip::new()] refuses.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum FlipError {
+    /// The band is empty or taller than the panel.
+    BadRows,
+    /// Not enough memory rows below the panel for the back page.
+    NoRoom{rows: u16, spare: u16},
+    /// The orientation exchanges rows and columns.
+    Swapped,
+}
+
+impl ::core::fmt::Display for FlipError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// The memory rows of the two pages of a band and which one is shown.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct PageFlip {
+    panel: Panel,
+    memory_rows: u16,
+    row_order: RowOrder,
+    // The memory row, as addressed by RASET, of the first row of page 0, the
+    // shown part of the band before scrolling. Page 1 follows.
+    top: u16,
+    rows: u16,
+    front: u8,
+}
+
+impl PageFlip {
+    /// The memory rows below `panel`, the most [new()](Self::new) accepts.
+    pub fn spare_rows(panel: &Panel, memory_rows: u16) -> u16 {
+        memory_rows.saturating_sub(panel.row_offset() + panel.height())
+    }
+
+    /// Flips the last `rows` rows of `panel`, oriented by `madctl`, in a
+    /// memory of `memory_rows` rows. Page 0 is shown first.
+    pub fn new(panel: &Panel, madctl: Madctl, memory_rows: u16, rows: u16)
+            -> Result<Self, FlipError> {
+        if madctl.row_column_swap() == RowColumnSwap::Swapped {
+            return Err(FlipError::Swapped);
+        }
+        if rows == 0 || rows > panel.height() {
+            return Err(FlipError::BadRows);
+        }
+        let spare = Self::spare_rows(panel, memory_rows);
+        if rows > spare {
+            return Err(FlipError::NoRoom{rows, spare});
+        }
+        Ok(Self{panel: *panel, memory_rows,
+                row_order: madctl.row_address_order(),
+                top: panel.row_offset() + panel.height() - rows, rows,
+                front: 0})
+    }
+
+    /// The number of rows of the band.
+    pub fn rows(&self) -> u16 { self.rows }
+
+    /// The row of the panel where the band starts.
+    pub fn band_y(&self) -> u16 { self.panel.height() - self.rows }
+
+    /// The page shown, `0` or `1`.
+    pub fn front(&self) -> u8 { self.front }
+
+    /// The page drawn into by
+    /// [Display::draw_back()](crate::display::Display::draw_back).
+    pub fn back(&self) -> u8 { 1 - self.front }
+
+    /// The memory row, as addressed by RASET, of row `y` of `page`.
+    pub fn memory_row(&self, page: u8, y: u16) -> u16 {
+        debug_assert!(page < 2 && y < self.rows);
+        self.top + page as u16 * self.rows + y
+    }
+
+    /// The scroll area: the band and the back page, showing the front page.
+    /// Unlike RASET, it counts the memory lines in the scanning order,
+    /// regardless of the row order of MADCTL.
+    pub fn scroll_config(&self) -> ScrollConfig {
+        let top_fixed = self.top_fixed();
+        let scroll_area = 2 * self.rows;
+        ScrollConfig{
+            top_fixed, scroll_area,
+            bottom_fixed: self.memory_rows - top_fixed - scroll_area,
+            start: self.start(self.front)}
+    }
+
+    // With the rows reversed, the pages are counted from the other end, and
+    // page 1 comes before page 0 when scanning.
+    fn top_fixed(&self) -> u16 {
+        match self.row_order {
+            RowOrder::TopToBottom => self.top,
+            RowOrder::BottomToTop =>
+                self.memory_rows - self.top - 2 * self.rows,
+        }
+    }
+
+    // The scroll start address showing `page`. The area being exactly two
+    // pages, this holds for both row orders.
+    fn start(&self, page: u8) -> u16 {
+        self.top_fixed() + page as u16 * self.rows
+    }
+
+    /// Makes the back page the front one, returning the scroll start address
+    /// to show it.
+    pub(crate) fn swap(&mut self) -> u16 {
+        self.front = self.back();
+        self.start(self.front)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn capability() {
+        let tab144 = Panel::new(128, 128).with_offsets(2, 3);
+        assert_eq!(PageFlip::spare_rows(&tab144, 162), 31);
+        assert_eq!(PageFlip::spare_rows(&Panel::new(128, 160), 162), 2);
+        assert_eq!(PageFlip::spare_rows(&Panel::new(240, 320), 320), 0);
+        let madctl = Madctl::default();
+        assert_eq!(PageFlip::new(&tab144, madctl, 162, 32),
+                   Err(FlipError::NoRoom{rows: 32, spare: 31}));
+        assert_eq!(PageFlip::new(&tab144, madctl, 162, 0),
+                   Err(FlipError::BadRows));
+        assert_eq!(PageFlip::new(&tab144, Madctl::from(0x20), 162, 8),
+                   Err(FlipError::Swapped));
+    }
+
+    #[test]
+    fn pages() {
+        let panel = Panel::new(128, 128).with_offsets(2, 3);
+        let mut flip = PageFlip::new(&panel, Madctl::default(), 162, 16)
+            .unwrap();
+        assert_eq!(flip.band_y(), 112);
+        assert_eq!((flip.memory_row(0, 0), flip.memory_row(1, 15)),
+                   (115, 146));
+        assert_eq!(flip.scroll_config(), ScrollConfig{
+            top_fixed: 115, scroll_area: 32, bottom_fixed: 15, start: 115});
+        assert_eq!(flip.back(), 1);
+        assert_eq!(flip.swap(), 131);
+        assert_eq!(flip.swap(), 115);
+
+        // The rows reversed: the pages are above the band when scanning.
+        let mut flip = PageFlip::new(&panel, Madctl::from(0x80), 162, 16)
+            .unwrap();
+        assert_eq!(flip.scroll_config(), ScrollConfig{
+            top_fixed: 15, scroll_area: 32, bottom_fixed: 115, start: 15});
+        assert_eq!(flip.swap(), 31);
+    }
+}  // mod tests
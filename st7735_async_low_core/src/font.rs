@@ -0,0 +1,380 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A built-in 5x7 font covering the printable ASCII characters, and a
+//! [GlyphCache] for drawing text with it.
+
+use crate::color::Pixel;
+use crate::commands::Commands;
+use crate::panel::Panel;
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+
+/// The width of a glyph, in pixels.
+pub const GLYPH_WIDTH: usize = 5;
+/// The height of a glyph, in pixels.
+pub const GLYPH_HEIGHT: usize = 7;
+
+/// The width of a character cell: a glyph plus one pixel of spacing on the
+/// right.
+pub const CELL_WIDTH: u16 = GLYPH_WIDTH as u16 + 1;
+/// The height of a character cell: a glyph plus one pixel of spacing at the
+/// bottom.
+pub const CELL_HEIGHT: u16 = GLYPH_HEIGHT as u16 + 1;
+
+const CELL_PIXELS: usize = CELL_WIDTH as usize * CELL_HEIGHT as usize;
+// Enough for the 3 bytes of Rgb666.
+const MAX_CELL_BYTES: usize = CELL_PIXELS * 3;
+
+// Column by column from the left, each with the top pixel at bit 0.
+const GLYPHS: [[u8; GLYPH_WIDTH]; 95] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00],  // ' '
+    [0x00, 0x00, 0x5F, 0x00, 0x00],  // '!'
+    [0x00, 0x07, 0x00, 0x07, 0x00],  // '"'
+    [0x14, 0x7F, 0x14, 0x7F, 0x14],  // '#'
+    [0x24, 0x2A, 0x7F, 0x2A, 0x12],  // '$'
+    [0x23, 0x13, 0x08, 0x64, 0x62],  // '%'
+    [0x36, 0x49, 0x55, 0x22, 0x50],  // '&'
+    [0x00, 0x05, 0x03, 0x00, 0x00],  // '\''
+    [0x00, 0x1C, 0x22, 0x41, 0x00],  // '('
+    [0x00, 0x41, 0x22, 0x1C, 0x00],  // ')'
+    [0x08, 0x2A, 0x1C, 0x2A, 0x08],  // '*'
+    [0x08, 0x08, 0x3E, 0x08, 0x08],  // '+'
+    [0x00, 0x50, 0x30, 0x00, 0x00],  // ','
+    [0x08, 0x08, 0x08, 0x08, 0x08],  // '-'
+    [0x00, 0x60, 0x60, 0x00, 0x00],  // '.'
+    [0x20, 0x10, 0x08, 0x04, 0x02],  // '/'
+    [0x3E, 0x51, 0x49, 0x45, 0x3E],  // '0'
+    [0x00, 0x42, 0x7F, 0x40, 0x00],  // '1'
+    [0x42, 0x61, 0x51, 0x49, 0x46],  // '2'
+    [0x21, 0x41, 0x45, 0x4B, 0x31],  // '3'
+    [0x18, 0x14, 0x12, 0x7F, 0x10],  // '4'
+    [0x27, 0x45, 0x45, 0x45, 0x39],  // '5'
+    [0x3C, 0x4A, 0x49, 0x49, 0x30],  // '6'
+    [0x01, 0x71, 0x09, 0x05, 0x03],  // '7'
+    [0x36, 0x49, 0x49, 0x49, 0x36],  // '8'
+    [0x06, 0x49, 0x49, 0x29, 0x1E],  // '9'
+    [0x00, 0x36, 0x36, 0x00, 0x00],  // ':'
+    [0x00, 0x56, 0x36, 0x00, 0x00],  // ';'
+    [0x08, 0x14, 0x22, 0x41, 0x00],  // '<'
+    [0x14, 0x14, 0x14, 0x14, 0x14],  // '='
+    [0x00, 0x41, 0x22, 0x14, 0x08],  // '>'
+    [0x02, 0x01, 0x51, 0x09, 0x06],  // '?'
+    [0x32, 0x49, 0x79, 0x41, 0x3E],  // '@'
+    [0x7E, 0x11, 0x11, 0x11, 0x7E],  // 'A'
+    [0x7F, 0x49, 0x49, 0x49, 0x36],  // 'B'
+    [0x3E, 0x41, 0x41, 0x41, 0x22],  // 'C'
+    [0x7F, 0x41, 0x41, 0x22, 0x1C],  // 'D'
+    [0x7F, 0x49, 0x49, 0x49, 0x41],  // 'E'
+    [0x7F, 0x09, 0x09, 0x09, 0x01],  // 'F'
+    [0x3E, 0x41, 0x49, 0x49, 0x7A],  // 'G'
+    [0x7F, 0x08, 0x08, 0x08, 0x7F],  // 'H'
+    [0x00, 0x41, 0x7F, 0x41, 0x00],  // 'I'
+    [0x20, 0x40, 0x41, 0x3F, 0x01],  // 'J'
+    [0x7F, 0x08, 0x14, 0x22, 0x41],  // 'K'
+    [0x7F, 0x40, 0x40, 0x40, 0x40],  // 'L'
+    [0x7F, 0x02, 0x0C, 0x02, 0x7F],  // 'M'
+    [0x7F, 0x04, 0x08, 0x10, 0x7F],  // 'N'
+    [0x3E, 0x41, 0x41, 0x41, 0x3E],  // 'O'
+    [0x7F, 0x09, 0x09, 0x09, 0x06],  // 'P'
+    [0x3E, 0x41, 0x51, 0x21, 0x5E],  // 'Q'
+    [0x7F, 0x09, 0x19, 0x29, 0x46],  // 'R'
+    [0x46, 0x49, 0x49, 0x49, 0x31],  // 'S'
+    [0x01, 0x01, 0x7F, 0x01, 0x01],  // 'T'
+    [0x3F, 0x40, 0x40, 0x40, 0x3F],  // 'U'
+    [0x1F, 0x20, 0x40, 0x20, 0x1F],  // 'V'
+    [0x3F, 0x40, 0x38, 0x40, 0x3F],  // 'W'
+    [0x63, 0x14, 0x08, 0x14, 0x63],  // 'X'
+    [0x07, 0x08, 0x70, 0x08, 0x07],  // 'Y'
+    [0x61, 0x51, 0x49, 0x45, 0x43],  // 'Z'
+    [0x00, 0x7F, 0x41, 0x41, 0x00],  // '['
+    [0x02, 0x04, 0x08, 0x10, 0x20],  // '\\'
+    [0x00, 0x41, 0x41, 0x7F, 0x00],  // ']'
+    [0x04, 0x02, 0x01, 0x02, 0x04],  // '^'
+    [0x40, 0x40, 0x40, 0x40, 0x40],  // '_'
+    [0x00, 0x01, 0x02, 0x04, 0x00],  // '`'
+    [0x20, 0x54, 0x54, 0x54, 0x78],  // 'a'
+    [0x7F, 0x48, 0x44, 0x44, 0x38],  // 'b'
+    [0x38, 0x44, 0x44, 0x44, 0x20],  // 'c'
+    [0x38, 0x44, 0x44, 0x48, 0x7F],  // 'd'
+    [0x38, 0x54, 0x54, 0x54, 0x18],  // 'e'
+    [0x08, 0x7E, 0x09, 0x01, 0x02],  // 'f'
+    [0x0C, 0x52, 0x52, 0x52, 0x3E],  // 'g'
+    [0x7F, 0x08, 0x04, 0x04, 0x78],  // 'h'
+    [0x00, 0x44, 0x7D, 0x40, 0x00],  // 'i'
+    [0x20, 0x40, 0x44, 0x3D, 0x00],  // 'j'
+    [0x7F, 0x10, 0x28, 0x44, 0x00],  // 'k'
+    [0x00, 0x41, 0x7F, 0x40, 0x00],  // 'l'
+    [0x7C, 0x04, 0x18, 0x04, 0x78],  // 'm'
+    [0x7C, 0x08, 0x04, 0x04, 0x78],  // 'n'
+    [0x38, 0x44, 0x44, 0x44, 0x38],  // 'o'
+    [0x7C, 0x14, 0x14, 0x14, 0x08],  // 'p'
+    [0x08, 0x14, 0x14, 0x18, 0x7C],  // 'q'
+    [0x7C, 0x08, 0x04, 0x04, 0x08],  // 'r'
+    [0x48, 0x54, 0x54, 0x54, 0x20],  // 's'
+    [0x04, 0x3F, 0x44, 0x40, 0x20],  // 't'
+    [0x3C, 0x40, 0x40, 0x20, 0x7C],  // 'u'
+    [0x1C, 0x20, 0x40, 0x20, 0x1C],  // 'v'
+    [0x3C, 0x40, 0x30, 0x40, 0x3C],  // 'w'
+    [0x44, 0x28, 0x10, 0x28, 0x44],  // 'x'
+    [0x0C, 0x50, 0x50, 0x50, 0x3C],  // 'y'
+    [0x44, 0x64, 0x54, 0x4C, 0x44],  // 'z'
+    [0x00, 0x08, 0x36, 0x41, 0x00],  // '{'
+    [0x00, 0x00, 0x7F, 0x00, 0x00],  // '|'
+    [0x00, 0x41, 0x36, 0x08, 0x00],  // '}'
+    [0x08, 0x04, 0x08, 0x10, 0x08],  // '~'
+];
+
+/// The glyph of `c`, column by column from the left, each with the top pixel
+/// at bit 0. Characters outside of the printable ASCII range show as `?`.
+pub fn glyph(c: char) -> [u8; GLYPH_WIDTH] {
+    match c {
+        ' '..='~' => GLYPHS[c as usize - ' ' as usize],
+        _ => GLYPHS['?' as usize - ' ' as usize],
+    }
+}
+
+/// Whether the pixel at column `x` and row `y` of the glyph of `c` is set.
+pub fn is_set(c: char, x: usize, y: usize) -> bool {
+    x < GLYPH_WIDTH && y < GLYPH_HEIGHT && glyph(c)[x] >> y & 1 != 0
+}
+
+/// The character cells last drawn, up to `N` of them, packed into the bytes
+/// written to the memory, so that text redrawn often, e.g., a counter,
+/// isn't converted pixel by pixel each time. The least recently used cell
+/// is evicted when full.
+///
+/// The storage is fixed, so that it can be a `static`: each of the `N`
+/// slots holds 144 bytes of pixels, enough for 3-byte pixels, plus 8 bytes
+/// for the character and its last use.
+#[derive(Clone, Debug)]
+pub struct GlyphCache<P, const N: usize> {
+    foreground: P,
+    background: P,
+    cells: [CachedCell; N],
+    // Incremented at each lookup, to find the least recently used cell.
+    tick: u32,
+    hits: u32,
+    misses: u32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct CachedCell {
+    // `None` if the slot is empty.
+    c: Option<char>,
+    last_used: u32,
+    bytes: [u8; MAX_CELL_BYTES],
+}
+
+impl CachedCell {
+    const EMPTY: Self = Self{c: None, last_used: 0, bytes: [0; MAX_CELL_BYTES]};
+}
+
+impl<P: Pixel, const N: usize> GlyphCache<P, N> {
+    /// An empty cache, drawing `foreground` on `background`.
+    pub const fn new(foreground: P, background: P) -> Self {
+        Self{foreground, background, cells: [CachedCell::EMPTY; N], tick: 0,
+             hits: 0, misses: 0}
+    }
+
+    /// Changes the colors, which empties the cache.
+    pub fn set_colors(&mut self, foreground: P, background: P) {
+        self.foreground = foreground;
+        self.background = background;
+        self.cells = [CachedCell::EMPTY; N];
+    }
+
+    /// How many lookups found their cell in the cache.
+    pub fn hits(&self) -> u32 { self.hits }
+    /// How many lookups had to convert their cell.
+    pub fn misses(&self) -> u32 { self.misses }
+
+    /// The bytes of the cell of `c`, row by row, as written to the memory in
+    /// the color mode `P::COLMOD`.
+    ///
+    /// # Panics
+    ///
+    /// If a pixel is more than 3 bytes.
+    pub fn cell(&mut self, c: char) -> &[u8] {
+        let len = CELL_PIXELS * self.background.to_bytes().as_ref().len();
+        assert!(len <= MAX_CELL_BYTES, "Pixels of more than 3 bytes.");
+        self.tick = self.tick.wrapping_add(1);
+        let tick = self.tick;
+        let i = match self.cells.iter().position(|cell| cell.c == Some(c)) {
+            Some(i) => {
+                self.hits += 1;
+                i
+            },
+            None => {
+                self.misses += 1;
+                let i = self.victim();
+                self.cells[i] = CachedCell{c: Some(c), last_used: tick,
+                                           bytes: self.pack(c)};
+                i
+            },
+        };
+        self.cells[i].last_used = tick;
+        &self.cells[i].bytes[..len]
+    }
+
+    /// Writes `text` on a single line of cells from `x` and `y`, in the
+    /// memory coordinates, each cell with its own address window. The LCD
+    /// is expected to be in the color mode `P::COLMOD`.
+    ///
+    /// Stops at the first cell not fully visible on `panel`, and returns the
+    /// number of characters drawn.
+    pub async fn draw<S>(&mut self, commands: &mut Commands<S>,
+                         panel: &Panel, x: u16, y: u16, text: &str) -> usize
+            where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+        let y1 = match y.checked_add(CELL_HEIGHT - 1) {
+            Some(y1) if panel.check_rows(y, y1).is_ok() => y1,
+            _ => return 0,
+        };
+        let mut x0 = x;
+        let mut drawn = 0;
+        for c in text.chars() {
+            let x1 = match x0.checked_add(CELL_WIDTH - 1) {
+                Some(x1) if panel.check_columns(x0, x1).is_ok() => x1,
+                _ => break,
+            };
+            commands.caset(x0, x1).await;
+            commands.raset(y, y1).await;
+            let bytes = self.cell(c);
+            commands.ramwr().await.write_u8s(bytes).await;
+            drawn += 1;
+            x0 = match x1.checked_add(1) {
+                Some(x0) => x0,
+                None => break,
+            };
+        }
+        drawn
+    }
+
+    // An empty slot, or else the least recently used one.
+    fn victim(&self) -> usize {
+        (0..N).max_by_key(|&i| match self.cells[i].c {
+            None => u32::MAX,
+            Some(_) => self.tick.wrapping_sub(self.cells[i].last_used),
+        }).expect("A GlyphCache of 0 cells.")
+    }
+
+    fn pack(&self, c: char) -> [u8; MAX_CELL_BYTES] {
+        let (on, off) = (self.foreground.to_bytes(),
+                         self.background.to_bytes());
+        let mut bytes = [0; MAX_CELL_BYTES];
+        let mut n = 0;
+        for y in 0..CELL_HEIGHT as usize {
+            for x in 0..CELL_WIDTH as usize {
+                let pixel = if is_set(c, x, y) { on.as_ref() }
+                            else { off.as_ref() };
+                bytes[n..n + pixel.len()].copy_from_slice(pixel);
+                n += pixel.len();
+            }
+        }
+        bytes
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn lookup() {
+        assert_eq!(glyph(' '), [0; 5]);
+        assert_eq!(glyph('A'), [0x7E, 0x11, 0x11, 0x11, 0x7E]);
+        assert_eq!(glyph('~'), [0x08, 0x04, 0x08, 0x10, 0x08]);
+        assert_eq!(glyph('é'), glyph('?'));
+    }
+
+    #[test]
+    fn glyph_cache() {
+        use crate::color::Rgb565;
+        use crate::fake_device::{FakeDevice, Wire::*};
+        use crate::testing_device::block_on;
+        let (on, off) = (Rgb565::from_raw(0xFFFF), Rgb565::from_raw(0x0000));
+        let mut cache = GlyphCache::<_, 2>::new(on, off);
+        // The top row of '1': only its third pixel is on.
+        assert_eq!(cache.cell('1')[..12], [0, 0, 0, 0, 0xFF, 0xFF,
+                                           0, 0, 0, 0, 0, 0]);
+        assert_eq!(cache.cell('1').len(), 6 * 8 * 2);
+        cache.cell('2');
+        cache.cell('1');
+        // Evicts '2', the least recently used.
+        cache.cell('3');
+        cache.cell('1');
+        assert_eq!((cache.hits(), cache.misses()), (3, 3));
+        cache.cell('2');
+        assert_eq!((cache.hits(), cache.misses()), (3, 4));
+
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        let panel = Panel::new(128, 160);
+        assert_eq!(block_on(cache.draw(&mut commands, &panel, 10, 20, "21")),
+                   2);
+        assert_eq!((cache.hits(), cache.misses()), (5, 4));
+        let seq = device.seq();
+        let cell = 5 + 5 + 1 + 6 * 8 * 2;
+        assert_eq!(seq.len(), 2 * cell);
+        assert_eq!(seq[cell..cell + 10], [
+            Command(0x2A), Data(0), Data(16), Data(0), Data(21),
+            Command(0x2B), Data(0), Data(20), Data(0), Data(27)]);
+        assert_eq!(seq[cell + 11..cell + 17], [
+            Data(0), Data(0), Data(0), Data(0), Data(0xFF), Data(0xFF)]);
+    }
+
+    #[test]
+    fn draw_clips() {
+        use crate::color::Rgb565;
+        use crate::fake_device::FakeDevice;
+        use crate::testing_device::block_on;
+        let mut cache = GlyphCache::<_, 1>::new(Rgb565::from_raw(0xFFFF),
+                                                Rgb565::from_raw(0));
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        // Room for 2 cells from column 1, and 1 row of cells from row 2.
+        let panel = Panel::new(13, 9).with_offsets(1, 2);
+        let mut draw = |x, y, text| {
+            block_on(cache.draw(&mut commands, &panel, x, y, text))
+        };
+        assert_eq!(draw(1, 2, "ABC"), 2);
+        assert_eq!(draw(7, 2, "AB"), 1);
+        assert_eq!(draw(0, 2, "A"), 0);
+        assert_eq!(draw(1, 4, "A"), 0);
+        assert_eq!(draw(u16::MAX - 6, 2, "A"), 0);
+        assert_eq!(draw(1, u16::MAX, "A"), 0);
+        let cell = 5 + 5 + 1 + 6 * 8 * 2;
+        assert_eq!(device.seq().len(), 3 * cell);
+
+        let panel = Panel::new(u16::MAX, 8).with_offsets(1, 0);
+        let mut draw = |x, text| {
+            block_on(cache.draw(&mut commands, &panel, x, 0, text))
+        };
+        // The cell after the one ending at u16::MAX would wrap.
+        assert_eq!(draw(u16::MAX - 11, "ABC"), 2);
+    }
+
+    #[test]
+    fn slot_size() {
+        assert_eq!(core::mem::size_of::<CachedCell>(), 144 + 8);
+    }
+
+    #[test]
+    fn pixels() {
+        // The bar of '1' and the bottom-right of 'L'.
+        assert!((0..7).all(|y| is_set('1', 2, y)));
+        assert!(is_set('L', 4, 6));
+        assert!(!is_set('L', 4, 5));
+        assert!(!is_set('L', 5, 6));
+    }
+}  // mod tests
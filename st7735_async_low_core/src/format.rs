@@ -0,0 +1,144 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The color mode carried as a type, so that writing pixels of another
+//! format than the one set by COLMOD doesn't compile.
+//!
+//! [Commands::set_format()] sends COLMOD and returns a [PixelFormat] token,
+//! which [Commands::ramwr_as()] turns into a [TypedRamWriter] accepting only
+//! the pixels of that format:
+//!
+//! ```compile_fail
+//! # use core::future::{ready, Ready};
+//! # use st7735_async_low_core::{spi::*, Commands};
+//! # use st7735_async_low_core::color::{Rgb565, Rgb666};
+//! # struct Bus;
+//! # impl DcxPin for Bus {
+//! #     fn set_dcx_command_mode(&mut self) {}
+//! #     fn set_dcx_data_mode(&mut self) {}
+//! # }
+//! # impl<'a> WriteU8<'a> for Bus {
+//! #     type WriteU8Done = Ready<()>;
+//! #     fn write_u8(&'a mut self, _: u8) -> Ready<()> { ready(()) }
+//! # }
+//! # impl<'a> WriteU8s<'a> for Bus {
+//! #     type WriteU8sDone = Ready<()>;
+//! #     fn write_u8s(&'a mut self, _: &'a [u8]) -> Ready<()> { ready(()) }
+//! # }
+//! async fn garble(cmds: &mut Commands<Bus>) {
+//!     let format = cmds.set_format::<Rgb565>().await;
+//!     let mut w = cmds.ramwr_as(format).await;
+//!     w.write_pixels([Rgb666::new(1, 2, 3)]).await;  // Error: not Rgb565.
+//! }
+//! ```
+//!
+//! The token stays valid until the next COLMOD, e.g., by
+//! [Commands::colmod()], which the compiler can't follow.
+//!
+//! [Commands::set_format()]: crate::Commands::set_format
+//! [Commands::ramwr_as()]: crate::Commands::ramwr_as
+//! [Commands::colmod()]: crate::Commands::colmod
+
+use core::marker::PhantomData;
+
+use crate::color::{Pixel, PixelTransform};
+use crate::commands::{Commands, RamWriter};
+use crate::spi::{DcxPin, WriteRepeat, WriteU8, WriteU8s};
+
+/// Proof that the LCD was set to the color mode of `P`, see the
+/// [module](self) doc.
+#[derive(Debug)]
+pub struct PixelFormat<P> { _p: PhantomData<P> }
+
+impl<P> Clone for PixelFormat<P> {
+    fn clone(&self) -> Self { *self }
+}
+impl<P> Copy for PixelFormat<P> {}
+
+impl<S> Commands<S> where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Sets the color mode to `P::COLMOD`, returning the token for
+    /// [ramwr_as()](Self::ramwr_as).
+    pub async fn set_format<P: Pixel>(&mut self) -> PixelFormat<P> {
+        self.colmod(P::COLMOD).await;
+        PixelFormat{_p: PhantomData}
+    }
+
+    /// Same as [ramwr()](Self::ramwr), but the writer only accepts pixels of
+    /// `format`.
+    pub async fn ramwr_as<P: Pixel>(&mut self, format: PixelFormat<P>)
+            -> TypedRamWriter<'_, S, P> {
+        TypedRamWriter{w: self.ramwr().await, _format: format}
+    }
+}
+
+/// A [RamWriter] only writing pixels of `P`, returned by
+/// [Commands::ramwr_as()].
+#[derive(Debug)]
+pub struct TypedRamWriter<'s, S: DcxPin, P> {
+    w: RamWriter<'s, S>,
+    _format: PixelFormat<P>,
+}
+
+impl<'s, S: DcxPin, P> TypedRamWriter<'s, S, P> {
+    /// The untyped writer, e.g., for raw bytes already in the format.
+    pub fn into_inner(self) -> RamWriter<'s, S> { self.w }
+}
+
+impl<'s, S, P> TypedRamWriter<'s, S, P>
+        where S: DcxPin, for<'a> S: WriteU8s<'a>, P: Pixel {
+    /// See [RamWriter::write_pixels()].
+    pub async fn write_pixels<I>(&mut self, pixels: I)
+            where I: IntoIterator<Item=P> {
+        self.w.write_pixels(pixels).await;
+    }
+
+    /// See [RamWriter::write_pixels_with()].
+    pub async fn write_pixels_with<I, T>(&mut self, pixels: I, transform: &T)
+            where I: IntoIterator<Item=P>, T: PixelTransform<P> {
+        self.w.write_pixels_with(pixels, transform).await;
+    }
+}
+
+impl<'s, S, P> TypedRamWriter<'s, S, P>
+        where S: DcxPin, for<'a> S: WriteRepeat<'a>, P: Pixel {
+    /// Writes `pixel` `count` times.
+    pub async fn write_repeated(&mut self, pixel: P, count: usize) {
+        let bytes = pixel.to_bytes();
+        self.w.write_repeated(bytes.as_ref(), count).await;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::color::Rgb666;
+    use crate::fake_device::{FakeDevice, Wire::*};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn typed_writes() {
+        let device = FakeDevice::new();
+        let mut cmds = Commands::new(device.clone());
+        block_on(async {
+            let format = cmds.set_format::<Rgb666>().await;
+            let mut w = cmds.ramwr_as(format).await;
+            w.write_pixels([Rgb666::new(1, 2, 3)]).await;
+            w.write_repeated(Rgb666::new(4, 5, 6), 2).await;
+        });
+        assert_eq!(device.seq(), [
+            Command(0x3A), Data(0b110), Command(0x2C),
+            Data(4), Data(8), Data(12),
+            Data(16), Data(20), Data(24), Data(16), Data(20), Data(24)]);
+    }
+}  // mod tests
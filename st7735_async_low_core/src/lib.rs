@@ -0,0 +1,88 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! This crates aims to provide the native ST7735 commands in their original
+//! form, thus is a low-level library.
+//!
+//! This is the HAL-independent core of `st7735-async-low`, which re-exports
+//! everything here under the same paths. Depend on this crate directly to be
+//! sure that no HAL is pulled in.
+//!
+//! A user of this crate should implement the write traits in [crate::spi], then
+//! wrap it with [Commands](crate::Commands) to use the commands. An example can
+//! be found at the [examples/stm32f3348_disco](https://github.com/jeru/st7735-async-low/tree/main/st7735_async_low/examples/stm32f3348_disco)
+//! directory of the crate.
+//!
+//! [Display](crate::display::Display) additionally bundles the reset pin, the
+//! backlight and the panel geometry, for the common power sequences.
+
+#![no_std]
+
+#[cfg(feature = "alloc")] extern crate alloc;
+#[cfg(any(test, feature = "test-utils"))] extern crate std;
+#[cfg(test)] extern crate tokio;
+#[cfg(test)] extern crate mockall;
+
+pub mod adapters;
+pub mod animation;
+pub mod asset;
+#[cfg(feature = "nb")] pub mod busy;
+#[cfg(any(test, feature = "test-utils"))] pub mod backend_tests;
+pub mod channel;
+pub mod color;
+pub mod compat;
+pub mod conformance;
+pub mod controller;
+mod command_structs;
+pub use command_structs::{
+    Colmod, ColorComponentOrder, ColumnOrder, Endianness, Flip, FrameRate,
+    GammaConfig, GammaCurve, IdSource, IdleMode, ImageMode, Madctl,
+    OrderingError, PanelConfig, PanelState, Param, PartialArea, PowerMode,
+    PowerProfile, RowColumnSwap, RowOrder, ScrollConfig, TeMode,
+    WindowStackError};
+mod commands;
+pub use commands::{
+    CommandFuture, CommandWithU8Future, Commands, RamWriter,
+    SimpleCommandFuture, TransferProgress, WINDOW_STACK_DEPTH, WindowFuture};
+#[cfg(feature = "decode")] pub mod decode;
+pub mod delay;
+pub mod diff;
+pub mod display;
+#[cfg(feature = "alloc")] pub mod dyn_backend;
+#[cfg(any(test, feature = "test-utils"))] pub mod fake_device;
+pub mod flip;
+pub mod font;
+pub mod format;
+pub mod lines;
+pub mod ops;
+pub mod panel;
+pub mod panic_screen;
+pub mod pipeline;
+pub mod presets;
+pub mod queue;
+pub mod record;
+pub mod scan;
+pub mod self_test;
+#[cfg(any(test, feature = "test-utils"))] pub mod scripted_device;
+#[cfg(any(test, feature = "test-utils"))] pub mod snapshot;
+#[cfg(feature = "critical-section")] pub mod shared;
+pub mod spi;
+pub mod sprite;
+pub mod stream;
+pub mod task;
+pub mod te;
+pub mod throughput;
+pub mod typestate;
+
+#[cfg(test)] pub mod testing_device;
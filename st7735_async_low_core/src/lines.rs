@@ -0,0 +1,156 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Line-by-line rendering overlapping with the transfer, for images too large
+//! to buffer whole.
+//!
+//! With a [WriteU8s] implementation based on DMA or interrupts, the CPU is
+//! idle while a line is sent. [PingPongLines] uses that time to render the
+//! next line into a second buffer:
+//! ```ignore
+//! let mut lines = PingPongLines::<{128 * 2}>::new();
+//! let mut w = commands.ramwr().await;
+//! lines.stream(&mut w, 160, |y, line| render_row(y, line)).await;
+//! ```
+
+use core::future::Future;
+use core::mem::swap;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+use crate::spi::WriteU8s;
+
+/// Two line buffers of `W` bytes each: while one is written, the other is
+/// rendered, then they swap. See [stream()](Self::stream).
+#[derive(Clone, Debug)]
+pub struct PingPongLines<const W: usize> {
+    buffers: [[u8; W]; 2],
+}
+
+impl<const W: usize> Default for PingPongLines<W> {
+    fn default() -> Self { Self::new() }
+}
+
+impl<const W: usize> PingPongLines<W> {
+    pub const fn new() -> Self { Self{buffers: [[0; W]; 2]} }
+
+    /// Writes `count` lines with `w`, each rendered by `render` with its
+    /// index. Except for the first line, `render` is called right after the
+    /// write of the previous line has started, i.e., has been polled once.
+    pub async fn stream<S, F>(&mut self, w: &mut S, count: usize,
+                              mut render: F)
+            where for<'a> S: WriteU8s<'a>, F: FnMut(usize, &mut [u8; W]) {
+        if count == 0 { return; }
+        let (first, second) = self.buffers.split_at_mut(1);
+        let (mut front, mut back) = (&mut first[0], &mut second[0]);
+        render(0, front);
+        for i in 1..count {
+            let back_ref = &mut *back;
+            let render = &mut render;
+            WriteWhile{write: w.write_u8s(&front[..]),
+                       work: Some(move || render(i, back_ref))}.await;
+            swap(&mut front, &mut back);
+        }
+        w.write_u8s(&front[..]).await;
+    }
+}
+
+/// Polls `write` once to start it, then runs `work` before waiting for it.
+struct WriteWhile<F, G> {
+    write: F,
+    work: Option<G>,
+}
+
+impl<F: Future, G: FnOnce()> Future for WriteWhile<F, G> {
+    type Output = F::Output;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<F::Output> {
+        // Safety: Only `Self::write` needs pinning, which is never moved.
+        let ww = unsafe {self.get_unchecked_mut()};
+        // Safety: Pinning a field of a pinned.
+        let polled = unsafe {Pin::new_unchecked(&mut ww.write)}.poll(cx);
+        if let Some(work) = ww.work.take() { work(); }
+        polled
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{cell::RefCell, rc::Rc, vec::Vec};
+    use super::*;
+    use crate::testing_device::block_on;
+
+    #[derive(Clone, Copy, Debug, Eq, PartialEq)]
+    enum Event {
+        Render(usize),
+        WriteStart(u8),
+        WriteDone(u8),
+    }
+
+    /// Takes 3 polls per write.
+    struct Slow { log: Rc<RefCell<Vec<Event>>> }
+
+    struct SlowWrite { log: Rc<RefCell<Vec<Event>>>, first: u8, polls: u8 }
+
+    impl Future for SlowWrite {
+        type Output = ();
+
+        fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+            self.polls += 1;
+            match self.polls {
+                1 => self.log.borrow_mut().push(Event::WriteStart(self.first)),
+                3 => {
+                    self.log.borrow_mut().push(Event::WriteDone(self.first));
+                    return Poll::Ready(());
+                },
+                _ => {},
+            }
+            cx.waker().wake_by_ref();
+            Poll::Pending
+        }
+    }
+
+    impl<'a> WriteU8s<'a> for Slow {
+        type WriteU8sDone = SlowWrite;
+
+        fn write_u8s(&'a mut self, data: &'a [u8]) -> SlowWrite {
+            SlowWrite{log: self.log.clone(), first: data[0], polls: 0}
+        }
+    }
+
+    #[test]
+    fn overlaps() {
+        let log = Rc::new(RefCell::new(Vec::new()));
+        let mut slow = Slow{log: log.clone()};
+        let mut lines = PingPongLines::<2>::new();
+        block_on(lines.stream(&mut slow, 3, |i, line| {
+            log.borrow_mut().push(Event::Render(i));
+            *line = [i as u8; 2];
+        }));
+        use Event::*;
+        assert_eq!(*log.borrow(), [
+            Render(0),
+            WriteStart(0), Render(1), WriteDone(0),
+            WriteStart(1), Render(2), WriteDone(1),
+            WriteStart(2), WriteDone(2)]);
+    }
+
+    #[test]
+    fn no_lines() {
+        let mut slow = Slow{log: Default::default()};
+        block_on(PingPongLines::<2>::default().stream(
+            &mut slow, 0, |_, _| panic!("Nothing to render.")));
+        assert!(slow.log.borrow().is_empty());
+    }
+}  // mod tests
@@ -0,0 +1,351 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Table-driven command sequences, e.g., vendor init sequences shipped as
+//! data instead of code.
+//!
+//! # Example
+//!
+//! ```
+//! # use st7735_async_low_core::ops::Op;
+//! static INIT: &[Op] = &[
+//!     Op::Cmd(0x01), Op::DelayMs(150),  // SWRESET.
+//!     Op::Cmd(0x11), Op::DelayMs(120),  // SLPOUT.
+//!     Op::Cmd(0x3A), Op::Data(&[0x05]),  // COLMOD.
+//!     Op::Cmd(0x29),  // DISPON.
+//! ];
+//! // Can invoke `run_ops(&mut commands, INIT, &mut delay)`.
+//! ```
+//!
+//! For the smallest flash parts, [encode_ops!] turns such a table into bytes
+//! at compile time, 2 per command plus its data and 2 per delay, played by
+//! [run_encoded()]:
+//! ```
+//! # use st7735_async_low_core::{encode_ops, ops::Op};
+//! const INIT: &[Op] = &[Op::Cmd(0x11), Op::DelayMs(120), Op::Cmd(0x29)];
+//! static ENCODED: &[u8] = encode_ops!(INIT);
+//! assert_eq!(ENCODED, [0x11, 0x80, 0, 120, 0x29, 0]);
+//! // Can invoke `run_encoded(&mut commands, ENCODED, &mut delay)`.
+//! ```
+//!
+//! [encode_ops!]: crate::encode_ops!
+
+use crate::commands::Commands;
+use crate::delay::DelayMs;
+use crate::spi::{DcxPin, WriteU8, WriteU8s, WriteU8sStatic};
+
+/// A single step of a command table.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum Op {
+    /// Writes a command byte.
+    Cmd(u8),
+    /// Writes the data bytes of the previous command.
+    Data(&'static [u8]),
+    /// Waits for some milliseconds.
+    DelayMs(u16),
+}
+
+/// Executes `ops` in order. The delay following SLPOUT is taken as the one
+/// it mandates: the panel is then [marked awake](Commands::mark_awake).
+pub async fn run_ops<S, D>(commands: &mut Commands<S>, ops: &[Op],
+                           delay: &mut D)
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+              for<'a> D: DelayMs<'a> {
+    for op in ops {
+        match *op {
+            Op::Cmd(cmd) => commands.raw_command(cmd).await,
+            Op::Data(data) => commands.raw_data(data).await,
+            Op::DelayMs(ms) => {
+                delay.delay_ms(ms as u32).await;
+                commands.mark_awake();
+            },
+        }
+    }
+}
+
+/// Like [run_ops()], but writes the data through [WriteU8sStatic], e.g., for
+/// DMA to read tables in flash in place.
+pub async fn run_ops_static<S, D>(commands: &mut Commands<S>, ops: &[Op],
+                                  delay: &mut D)
+        where S: DcxPin,
+              for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteU8sStatic<'a>,
+              for<'a> D: DelayMs<'a> {
+    for op in ops {
+        match *op {
+            Op::Cmd(cmd) => commands.raw_command(cmd).await,
+            Op::Data(data) => commands.raw_data_static(data).await,
+            Op::DelayMs(ms) => {
+                delay.delay_ms(ms as u32).await;
+                commands.mark_awake();
+            },
+        }
+    }
+}
+
+/// Encodes a `const` table of [Op]s into a `&'static [u8]` at compile time,
+/// to be played by [run_encoded()]. Each command is followed by its data,
+/// at most 127 bytes, and at most one delay. Fails to compile otherwise.
+#[macro_export]
+macro_rules! encode_ops {
+    ($ops:expr) => {{
+        const BYTES: [u8; $crate::ops::encoded_len($ops)] =
+            $crate::ops::encode_ops($ops);
+        &BYTES
+    }};
+}
+
+// The encoding of a command is the command byte, a byte with the length of
+// its data and the highest bit set if a delay follows, the data, then the
+// delay if any, as a big-endian u16.
+const DELAY_FOLLOWS: u8 = 0x80;
+
+// Writes the encoding of `ops` into `out` unless it is empty, returning its
+// length.
+const fn encode(ops: &[Op], out: &mut [u8]) -> usize {
+    let write = !out.is_empty();
+    let (mut i, mut n) = (0, 0);
+    while i < ops.len() {
+        let cmd = match ops[i] {
+            Op::Cmd(cmd) => cmd,
+            _ => panic!("Data or a delay not right after a command."),
+        };
+        i += 1;
+        if write { out[n] = cmd; }
+        let header = n + 1;
+        n += 2;
+        let mut len = 0;
+        while i < ops.len() {
+            let data = match ops[i] {
+                Op::Data(data) => data,
+                _ => break,
+            };
+            let mut j = 0;
+            while j < data.len() {
+                if write { out[n] = data[j]; }
+                n += 1;
+                j += 1;
+            }
+            len += data.len();
+            i += 1;
+        }
+        assert!(len < DELAY_FOLLOWS as usize,
+                "More than 127 bytes of data for a command.");
+        let mut flags = len as u8;
+        if i < ops.len() {
+            if let Op::DelayMs(ms) = ops[i] {
+                flags |= DELAY_FOLLOWS;
+                let [hi, lo] = ms.to_be_bytes();
+                if write {
+                    out[n] = hi;
+                    out[n + 1] = lo;
+                }
+                n += 2;
+                i += 1;
+            }
+        }
+        if write { out[header] = flags; }
+    }
+    n
+}
+
+/// The length of the encoding of `ops`. Used by [encode_ops!].
+///
+/// [encode_ops!]: crate::encode_ops!
+pub const fn encoded_len(ops: &[Op]) -> usize { encode(ops, &mut []) }
+
+/// Encodes `ops`, `N` being [encoded_len()]. Used by [encode_ops!].
+///
+/// [encode_ops!]: crate::encode_ops!
+pub const fn encode_ops<const N: usize>(ops: &[Op]) -> [u8; N] {
+    let mut out = [0; N];
+    assert!(encode(ops, &mut out) == N);
+    out
+}
+
+// The commands of an encoding: the command, its data and its delay.
+struct Records<'b> { rest: &'b [u8] }
+
+impl<'b> Iterator for Records<'b> {
+    type Item = (u8, &'b [u8], Option<u16>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (cmd, flags, tail) = match self.rest {
+            [] => return None,
+            [cmd, flags, tail @ ..] => (*cmd, *flags, tail),
+            _ => panic!("Truncated encoded ops."),
+        };
+        let (data, mut tail) =
+            tail.split_at((flags & !DELAY_FOLLOWS) as usize);
+        let mut delay = None;
+        if flags & DELAY_FOLLOWS != 0 {
+            delay = Some(u16::from_be_bytes([tail[0], tail[1]]));
+            tail = &tail[2..];
+        }
+        self.rest = tail;
+        Some((cmd, data, delay))
+    }
+}
+
+/// Executes the ops encoded by [encode_ops!] in order, as [run_ops()] does.
+///
+/// # Panics
+///
+/// If `bytes` is truncated.
+///
+/// [encode_ops!]: crate::encode_ops!
+pub async fn run_encoded<S, D>(commands: &mut Commands<S>, bytes: &[u8],
+                               delay: &mut D)
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+              for<'a> D: DelayMs<'a> {
+    for (cmd, data, ms) in (Records{rest: bytes}) {
+        commands.raw_command(cmd).await;
+        if !data.is_empty() {
+            commands.raw_data(data).await;
+        }
+        if let Some(ms) = ms {
+            delay.delay_ms(ms as u32).await;
+            commands.mark_awake();
+        }
+    }
+}
+
+/// Like [run_encoded()], but writes the data through [WriteU8sStatic],
+/// e.g., for DMA to read the bytes in flash in place.
+pub async fn run_encoded_static<S, D>(commands: &mut Commands<S>,
+                                      bytes: &'static [u8], delay: &mut D)
+        where S: DcxPin,
+              for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteU8sStatic<'a>,
+              for<'a> D: DelayMs<'a> {
+    for (cmd, data, ms) in (Records{rest: bytes}) {
+        commands.raw_command(cmd).await;
+        if !data.is_empty() {
+            commands.raw_data_static(data).await;
+        }
+        if let Some(ms) = ms {
+            delay.delay_ms(ms as u32).await;
+            commands.mark_awake();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::adapters::AdapterStatic;
+    use crate::display::tests::{Event, FakeDelay, Log, logging_device};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn run() {
+        use Event::*;
+        static OPS: &[Op] = &[
+            Op::Cmd(0x01), Op::DelayMs(150),
+            Op::Cmd(0x2A), Op::Data(&[0x00, 0x02]), Op::Data(&[0x00, 0x81]),
+            Op::Cmd(0x29),
+        ];
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_ops(&mut commands, OPS, &mut delay));
+        assert_eq!(*log.lock().unwrap(), vec![
+            Command(0x01), Delay(150), Command(0x2A), Data(0x00), Data(0x02),
+            Data(0x00), Data(0x81), Command(0x29)]);
+    }
+
+    #[test]
+    fn run_static() {
+        use Event::*;
+        static OPS: &[Op] = &[
+            Op::Cmd(0x2A), Op::Data(&[0x00, 0x02, 0x00, 0x81]), Op::Cmd(0x29),
+        ];
+        let log = Log::default();
+        let mut commands =
+            Commands::new(AdapterStatic::new(logging_device(&log)));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_ops_static(&mut commands, OPS, &mut delay));
+        assert_eq!(*log.lock().unwrap(), vec![
+            Command(0x2A), Data(0x00), Data(0x02), Data(0x00), Data(0x81),
+            Command(0x29)]);
+    }
+
+    #[test]
+    fn encoded() {
+        use Event::*;
+        const OPS: &[Op] = &[
+            Op::Cmd(0x01), Op::DelayMs(0x1234),
+            Op::Cmd(0x2A), Op::Data(&[0x00, 0x02]), Op::Data(&[0x00, 0x81]),
+            Op::Cmd(0x29),
+        ];
+        static ENCODED: &[u8] = encode_ops!(OPS);
+        assert_eq!(ENCODED, [0x01, 0x80, 0x12, 0x34,
+                             0x2A, 4, 0x00, 0x02, 0x00, 0x81,
+                             0x29, 0]);
+        let expected = vec![
+            Command(0x01), Delay(0x1234), Command(0x2A), Data(0x00),
+            Data(0x02), Data(0x00), Data(0x81), Command(0x29)];
+
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_encoded(&mut commands, ENCODED, &mut delay));
+        assert_eq!(*log.lock().unwrap(), expected);
+
+        let log = Log::default();
+        let mut commands =
+            Commands::new(AdapterStatic::new(logging_device(&log)));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_encoded_static(&mut commands, ENCODED, &mut delay));
+        assert_eq!(*log.lock().unwrap(), expected);
+        assert_eq!(encoded_len(&[]), 0);
+    }
+
+    #[test]
+    #[should_panic]
+    fn truncated() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_encoded(&mut commands, &[0x2A, 4, 0x00], &mut delay));
+    }
+
+    #[test]
+    fn awake_after_delay() {
+        use crate::command_structs::OrderingError;
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut delay = FakeDelay(log.clone());
+        static SLPOUT: &[Op] = &[Op::Cmd(0x11)];
+        block_on(run_ops(&mut commands, SLPOUT, &mut delay));
+        assert_eq!(commands.check_ram_access(), Err(OrderingError::WakingUp));
+        const WAIT: &[Op] = &[Op::Cmd(0x11), Op::DelayMs(120)];
+        block_on(run_ops(&mut commands, WAIT, &mut delay));
+        assert_eq!(commands.check_ram_access(), Ok(()));
+
+        let mut commands = Commands::new(logging_device(&log));
+        static ENCODED: &[u8] = encode_ops!(WAIT);
+        block_on(run_encoded(&mut commands, ENCODED, &mut delay));
+        assert_eq!(commands.check_ram_access(), Ok(()));
+    }
+
+    #[test]
+    fn empty() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut delay = FakeDelay(log.clone());
+        block_on(run_ops(&mut commands, &[], &mut delay));
+        assert!(log.lock().unwrap().is_empty());
+    }
+}  // mod tests
@@ -0,0 +1,221 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Geometry of the LCD panel.
+
+use crate::command_structs::{Madctl, RowColumnSwap};
+
+/// The number of columns in the memory of the controller.
+pub const MAX_COLUMNS: u16 = 132;
+/// The number of rows in the memory of the controller.
+pub const MAX_ROWS: u16 = 162;
+
+/// Why an address window is rejected.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum WindowError {
+    /// The end address is smaller than the begin address.
+    Reversed,
+    /// Some address is outside the visible area of the [Panel].
+    OutOfRange,
+}
+
+impl ::core::fmt::Display for WindowError {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// The size of the visible area of a panel and where it starts in the memory
+/// of the controller.
+///
+/// ST7735 has memory for 132x162 pixels but most panels show a smaller area,
+/// e.g., 128x128 or 128x160, and may be wired with an offset.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Panel {
+    width: u16,
+    height: u16,
+    column_offset: u16,
+    row_offset: u16,
+}
+
+impl Panel {
+    /// Creates a panel of the given visible size and without offsets.
+    pub const fn new(width: u16, height: u16) -> Self {
+        Self{width, height, column_offset: 0, row_offset: 0}
+    }
+
+    /// Returns a copy with the visible area starting at the given memory
+    /// column and row.
+    pub const fn with_offsets(self, column_offset: u16, row_offset: u16)
+            -> Self {
+        Self{column_offset, row_offset, ..self}
+    }
+
+    /// The number of visible columns.
+    pub const fn width(&self) -> u16 { self.width }
+    /// The number of visible rows.
+    pub const fn height(&self) -> u16 { self.height }
+    /// The memory column of the first visible column.
+    pub const fn column_offset(&self) -> u16 { self.column_offset }
+    /// The memory row of the first visible row.
+    pub const fn row_offset(&self) -> u16 { self.row_offset }
+
+    /// Returns the panel as addressed under `madctl`. When it swaps rows and
+    /// columns, CASET and RASET address the physical rows and columns
+    /// respectively, so the sizes and the offsets swap.
+    pub fn oriented(self, madctl: Madctl) -> Self {
+        if madctl.row_column_swap() == RowColumnSwap::Unswapped {
+            return self;
+        }
+        Self{width: self.height, height: self.width,
+             column_offset: self.row_offset, row_offset: self.column_offset}
+    }
+
+    /// Checks that the memory columns `begin` to `end`, both inclusive, are
+    /// visible.
+    pub fn check_columns(&self, begin: u16, end: u16)
+            -> Result<(), WindowError> {
+        check_range(begin, end, self.column_offset, self.width)
+    }
+
+    /// Checks that the memory rows `begin` to `end`, both inclusive, are
+    /// visible.
+    pub fn check_rows(&self, begin: u16, end: u16) -> Result<(), WindowError> {
+        check_range(begin, end, self.row_offset, self.height)
+    }
+}
+
+fn check_range(begin: u16, end: u16, offset: u16, size: u16)
+        -> Result<(), WindowError> {
+    if begin > end {
+        Err(WindowError::Reversed)
+    } else if begin < offset || end - offset >= size {
+        Err(WindowError::OutOfRange)
+    } else {
+        Ok(())
+    }
+}
+
+/// A rectangle in the pixel coordinates of the visible area, i.e., without
+/// the offsets of the [Panel].
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct Rect {
+    pub x: u16,
+    pub y: u16,
+    pub width: u16,
+    pub height: u16,
+}
+
+impl Rect {
+    pub const fn new(x: u16, y: u16, width: u16, height: u16) -> Self {
+        Self{x, y, width, height}
+    }
+
+    /// The number of pixels inside.
+    pub const fn area(&self) -> usize {
+        self.width as usize * self.height as usize
+    }
+
+    pub const fn is_empty(&self) -> bool {
+        self.width == 0 || self.height == 0
+    }
+
+    /// The last column and the last row inside, e.g., for CASET and RASET.
+    /// [WindowError::Reversed] if empty, [WindowError::OutOfRange] if past
+    /// `u16::MAX`.
+    pub const fn last(&self) -> Result<(u16, u16), WindowError> {
+        if self.is_empty() {
+            return Err(WindowError::Reversed);
+        }
+        match (self.x.checked_add(self.width - 1),
+               self.y.checked_add(self.height - 1)) {
+            (Some(x1), Some(y1)) => Ok((x1, y1)),
+            _ => Err(WindowError::OutOfRange),
+        }
+    }
+}
+
+
+/// An address window fixed at compile time, from column `X0` to `X1` and
+/// from row `Y0` to `Y1`, all inclusive. See
+/// [Commands::set_window_const()](crate::Commands::set_window_const).
+///
+/// An empty window is rejected at compile time:
+///
+/// ```compile_fail
+/// # use st7735_async_low_core::panel::{ConstWindow, Window};
+/// let () = <Window<10, 0, 9, 0> as ConstWindow>::VALID;
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Window<const X0: u16, const Y0: u16, const X1: u16, const Y1: u16>;
+
+/// Implemented by [Window], to be used as a type parameter.
+pub trait ConstWindow {
+    const X0: u16;
+    const Y0: u16;
+    const X1: u16;
+    const Y1: u16;
+    /// Fails to evaluate if the window is empty.
+    const VALID: ();
+    /// The same window as a [Rect].
+    const RECT: Rect;
+}
+
+impl<const X0: u16, const Y0: u16, const X1: u16, const Y1: u16> ConstWindow
+        for Window<X0, Y0, X1, Y1> {
+    const X0: u16 = X0;
+    const Y0: u16 = Y0;
+    const X1: u16 = X1;
+    const Y1: u16 = Y1;
+    const VALID: () = assert!(X0 <= X1 && Y0 <= Y1, "empty window");
+    const RECT: Rect = Rect::new(X0, Y0, X1 - X0 + 1, Y1 - Y0 + 1);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_columns() {
+        let panel = Panel::new(128, 160).with_offsets(2, 1);
+        assert_eq!(panel.check_columns(2, 129), Ok(()));
+        assert_eq!(panel.check_columns(5, 5), Ok(()));
+        assert_eq!(panel.check_columns(2, 130), Err(WindowError::OutOfRange));
+        assert_eq!(panel.check_columns(1, 10), Err(WindowError::OutOfRange));
+        assert_eq!(panel.check_columns(6, 5), Err(WindowError::Reversed));
+    }
+
+    #[test]
+    fn check_rows() {
+        let panel = Panel::new(128, 160).with_offsets(2, 1);
+        assert_eq!(panel.check_rows(1, 160), Ok(()));
+        assert_eq!(panel.check_rows(1, 161), Err(WindowError::OutOfRange));
+        assert_eq!(panel.check_rows(0, 0), Err(WindowError::OutOfRange));
+    }
+
+    #[test]
+    fn oriented() {
+        let panel = Panel::new(128, 160).with_offsets(2, 1);
+        assert_eq!(panel.oriented(Madctl::from(0xC8)), panel);
+        assert_eq!(panel.oriented(Madctl::from(0x20)),
+                   Panel::new(160, 128).with_offsets(1, 2));
+    }
+
+    #[test]
+    fn const_window_rect() {
+        assert_eq!(<Window<1, 2, 10, 2> as ConstWindow>::RECT,
+                   Rect::new(1, 2, 10, 1));
+    }
+}  // mod tests
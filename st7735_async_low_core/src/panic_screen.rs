@@ -0,0 +1,131 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A last-resort screen for `#[panic_handler]`s: blocking, allocation-free and
+//! independent of any executor.
+
+use crate::color::Rgb565;
+use crate::command_structs::Colmod;
+use crate::commands::Commands;
+use crate::font::{CELL_HEIGHT, CELL_WIDTH, is_set};
+use crate::panel::Panel;
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+use crate::task::block_on;
+
+/// The background color.
+pub const BACKGROUND: Rgb565 = Rgb565::new(0x1F, 0, 0);
+/// The text color.
+pub const FOREGROUND: Rgb565 = Rgb565::new(0x1F, 0x3F, 0x1F);
+
+/// Fills `panel` with [BACKGROUND] and writes `msg` over it with the
+/// [built-in font](crate::font), wrapping at the right edge and at `\n`, and
+/// dropping what doesn't fit. Switches the LCD to [Colmod::R5G6B5] and turns
+/// the display on.
+///
+/// Each command is driven by [block_on()], busy-waiting. Hence `commands`
+/// should be blocking, e.g., over the facade's `BlockingSpi`: a bus
+/// completing its transfers in interrupts would hang if they are disabled,
+/// as they often are in a panic handler.
+pub fn draw<S>(msg: &str, commands: &mut Commands<S>, panel: &Panel)
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    let (width, height) = (panel.width(), panel.height());
+    if width == 0 || height == 0 {
+        return;
+    }
+    let (x0, y0) = (panel.column_offset(), panel.row_offset());
+    block_on(async {
+        commands.colmod(Colmod::R5G6B5).await;
+        commands.caset(x0, x0 + width - 1).await;
+        commands.raset(y0, y0 + height - 1).await;
+        let count = width as usize * height as usize;
+        commands.ramwr().await
+            .write_pixels(core::iter::repeat_n(BACKGROUND, count)).await;
+
+        let (columns, rows) = (width / CELL_WIDTH, height / CELL_HEIGHT);
+        let (mut column, mut row) = (0, 0);
+        // Too small for a single cell.
+        let msg = if columns == 0 || rows == 0 { "" } else { msg };
+        for c in msg.chars() {
+            if c == '\n' || column == columns {
+                column = 0;
+                row += 1;
+            }
+            if row >= rows {
+                break;
+            }
+            if c == '\n' {
+                continue;
+            }
+            let x = x0 + column * CELL_WIDTH;
+            let y = y0 + row * CELL_HEIGHT;
+            commands.caset(x, x + CELL_WIDTH - 1).await;
+            commands.raset(y, y + CELL_HEIGHT - 1).await;
+            let pixels = (0..CELL_HEIGHT as usize).flat_map(|py| {
+                (0..CELL_WIDTH as usize).map(move |px| {
+                    if is_set(c, px, py) { FOREGROUND } else { BACKGROUND }
+                })
+            });
+            commands.ramwr().await.write_pixels(pixels).await;
+            column += 1;
+        }
+        commands.dispon().await;
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::tests::{Event::*, Log, logging_device};
+    use super::*;
+
+    #[test]
+    fn wraps_and_clips() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        // Room for 2x1 characters.
+        draw("AB\nC", &mut commands, &Panel::new(13, 9).with_offsets(1, 2));
+        let log = log.lock().unwrap();
+        let fill = 2 + 10 + 1 + 13 * 9 * 2;
+        assert_eq!(log[..13], [
+            Command(0x3A), Data(0b101),
+            Command(0x2A), Data(0), Data(1), Data(0), Data(13),
+            Command(0x2B), Data(0), Data(2), Data(0), Data(10),
+            Command(0x2C)]);
+        // 'A', then 'B' at the second cell, without 'C'.
+        let glyph = 10 + 1 + 6 * 8 * 2;
+        assert_eq!(log.len(), fill + 2 * glyph + 1);
+        assert_eq!(log[fill..fill + 5], [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(6)]);
+        assert_eq!(log[fill + glyph..fill + glyph + 5], [
+            Command(0x2A), Data(0), Data(7), Data(0), Data(12)]);
+        // The top-left pixel of 'A' is off; the next one is on.
+        assert_eq!(log[fill + 11..fill + 15], [
+            Data(0xF8), Data(0x00), Data(0xFF), Data(0xFF)]);
+        assert_eq!(log[log.len() - 1], Command(0x29));
+    }
+
+    #[test]
+    fn tiny_panel() {
+        for panel in [Panel::new(5, 9), Panel::new(13, 7)] {
+            let log = Log::default();
+            let mut commands = Commands::new(logging_device(&log));
+            draw("AB", &mut commands, &panel);
+            let log = log.lock().unwrap();
+            // Only filled.
+            let fill = 2 + 10 + 1 + panel.width() as usize
+                * panel.height() as usize * 2;
+            assert_eq!(log.len(), fill + 1);
+            assert_eq!(log[fill], Command(0x29));
+        }
+    }
+}  // mod tests
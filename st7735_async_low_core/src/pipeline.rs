@@ -0,0 +1,340 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A lower-level, two-phase command API for backends with a transmit queue.
+//!
+//! With [Commands](crate::Commands), a command's future finishes only after
+//! its bytes are handed to the backend, one [WriteU8s] call at a time. With a
+//! [Pipeline], issuing a command finishes as soon as its bytes are *queued*,
+//! and returns a [Pending] token; awaiting the token with
+//! [wait()](Pipeline::wait) later is only needed when the caller has to know
+//! the bytes are sent, e.g., before reusing a buffer shared with the DMA or
+//! before a delay. Meanwhile the CPU is free to compute the next pixels:
+//!
+//! ```ignore
+//! let mut p = Pipeline::new(queue);
+//! p.caset(0, 127).await;
+//! p.raset(0, 159).await;
+//! p.ramwr().await;
+//! for y in 0..160 {
+//!     let row = render_row(y);  // Overlaps with sending the previous row.
+//!     p.data(&row).await;
+//! }
+//! p.flush().await;
+//! ```
+//!
+//! The pipeline doesn't track the panel's state like
+//! [Commands::state()](crate::Commands::state) does.
+//!
+//! [WriteU8s]: crate::spi::WriteU8s
+
+use core::future::poll_fn;
+
+use crate::spi::WriteQueue;
+
+/// Marks bytes queued by a [Pipeline], to [wait](Pipeline::wait) for them to
+/// be sent.
+///
+/// It is typed by the backend's [WriteQueue::Ticket], so it can't be waited on
+/// with a pipeline of another kind of backend.
+#[must_use = "dropping a Pending doesn't wait for the bytes to be sent"]
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Pending<T> {
+    ticket: Option<T>,
+}
+
+impl<T> Pending<T> {
+    /// A token of no bytes, which is already sent.
+    pub fn sent() -> Self { Self{ticket: None} }
+}
+
+/// How many bytes a [Pipeline] lets queued before waiting for some of them to
+/// be sent.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub enum QueueDepth {
+    /// As many as the [queue depth](WriteQueue::queue_depth) of the backend.
+    #[default]
+    Backend,
+    /// At most this many, e.g., to keep the latency of a deep queue low or
+    /// to share a FIFO with other traffic. Data is then queued in pieces of
+    /// half of it, so that one piece is queued while the other is sent.
+    Bytes(usize),
+}
+
+// How many pieces a paced pipeline tracks. When all are in flight, the next
+// one waits for the oldest, even if the bytes would fit.
+const TRACKED_PIECES: usize = 4;
+
+/// Issues commands to a [WriteQueue] without waiting for them to be sent.
+pub struct Pipeline<Q: WriteQueue> {
+    queue: Q,
+    last: Option<Q::Ticket>,
+    depth: QueueDepth,
+    // With `QueueDepth::Bytes`, the last pieces queued and their lengths,
+    // oldest first, which may not be sent yet.
+    in_flight: [Option<(Q::Ticket, usize)>; TRACKED_PIECES],
+}
+
+impl<Q: WriteQueue> Pipeline<Q> {
+    pub fn new(queue: Q) -> Self {
+        Self{queue, last: None, depth: QueueDepth::Backend,
+             in_flight: [None; TRACKED_PIECES]}
+    }
+
+    /// Bounds the bytes queued from now on. See [QueueDepth].
+    ///
+    /// # Panics
+    ///
+    /// On `QueueDepth::Bytes(0)`.
+    pub fn set_queue_depth(&mut self, depth: QueueDepth) {
+        assert_ne!(depth, QueueDepth::Bytes(0), "Nothing could be queued.");
+        self.depth = depth;
+    }
+
+    pub fn queue_depth(&self) -> QueueDepth { self.depth }
+
+    pub fn inner(&mut self) -> &mut Q { &mut self.queue }
+
+    /// Unwraps the queue, which may still have bytes to send; see
+    /// [flush()](Self::flush).
+    pub fn into_inner(self) -> Q { self.queue }
+
+    /// Queues the command byte `cmd` followed by `params`.
+    pub async fn command(&mut self, cmd: u8, params: &[u8])
+            -> Pending<Q::Ticket> {
+        self.queue.set_dcx_command_mode();
+        let pending = self.enqueue(&[cmd]).await;
+        self.queue.set_dcx_data_mode();
+        if params.is_empty() { pending } else { self.enqueue(params).await }
+    }
+
+    /// Queues more data for the last command, e.g., pixels after
+    /// [ramwr()](Self::ramwr). Data longer than the
+    /// [queue depth](WriteQueue::queue_depth) is queued in pieces, each as soon
+    /// as there is room.
+    pub async fn data(&mut self, data: &[u8]) -> Pending<Q::Ticket> {
+        self.queue.set_dcx_data_mode();
+        self.enqueue(data).await
+    }
+
+    /// Queues CASET.
+    pub async fn caset(&mut self, begin: u16, end: u16)
+            -> Pending<Q::Ticket> {
+        let (b, e) = (begin.to_be_bytes(), end.to_be_bytes());
+        self.command(0x2A, &[b[0], b[1], e[0], e[1]]).await
+    }
+
+    /// Queues RASET.
+    pub async fn raset(&mut self, begin: u16, end: u16)
+            -> Pending<Q::Ticket> {
+        let (b, e) = (begin.to_be_bytes(), end.to_be_bytes());
+        self.command(0x2B, &[b[0], b[1], e[0], e[1]]).await
+    }
+
+    /// Queues RAMWR, to be followed by [data()](Self::data).
+    pub async fn ramwr(&mut self) -> Pending<Q::Ticket> {
+        self.command(0x2C, &[]).await
+    }
+
+    /// Waits until the bytes of `pending`, and all bytes queued before them,
+    /// are sent.
+    pub async fn wait(&mut self, pending: Pending<Q::Ticket>) {
+        if let Some(ticket) = pending.ticket {
+            let queue = &mut self.queue;
+            poll_fn(|cx| queue.poll_sent(cx, ticket)).await;
+        }
+    }
+
+    /// Waits until all queued bytes are sent.
+    pub async fn flush(&mut self) {
+        self.wait(Pending{ticket: self.last}).await;
+        self.in_flight = [None; TRACKED_PIECES];
+    }
+
+    async fn enqueue(&mut self, data: &[u8]) -> Pending<Q::Ticket> {
+        let depth = self.queue.queue_depth();
+        assert!(depth > 0, "A WriteQueue must hold at least one byte.");
+        let (limit, piece) = match self.depth {
+            QueueDepth::Backend => (None, depth),
+            QueueDepth::Bytes(bytes) => {
+                let limit = bytes.min(depth);
+                (Some(limit), (limit / 2).max(1))
+            },
+        };
+        for chunk in data.chunks(piece) {
+            let slot = match limit {
+                Some(limit) => Some(self.make_room(limit - chunk.len()).await),
+                None => None,
+            };
+            let queue = &mut self.queue;
+            let ticket = poll_fn(|cx| queue.poll_enqueue(cx, chunk)).await;
+            self.last = Some(ticket);
+            if let Some(slot) = slot {
+                self.in_flight[slot] = Some((ticket, chunk.len()));
+            }
+        }
+        if data.is_empty() { return Pending::sent(); }
+        Pending{ticket: self.last}
+    }
+
+    // Waits for the oldest pieces until at most `bytes` are in flight, with
+    // a slot left to track the next one, which is returned.
+    async fn make_room(&mut self, bytes: usize) -> usize {
+        loop {
+            let tracked = self.in_flight.iter().flatten().count();
+            let in_flight: usize =
+                self.in_flight.iter().flatten().map(|(_, len)| len).sum();
+            if in_flight <= bytes && tracked < TRACKED_PIECES {
+                return tracked;
+            }
+            let oldest = self.in_flight[0].map(|(ticket, _)| ticket);
+            self.wait(Pending{ticket: oldest}).await;
+            self.in_flight.rotate_left(1);
+            self.in_flight[TRACKED_PIECES - 1] = None;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::{Context, Poll};
+    use std::{collections::VecDeque, vec::Vec};
+
+    use crate::fake_device::Wire::{self, *};
+    use crate::spi::DcxPin;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    /// Sends one byte whenever it's polled without room or with the ticket
+    /// unsent, as if the bus were slower than the CPU.
+    struct FakeQueue {
+        depth: usize,
+        queued: VecDeque<Wire>,
+        sent: Vec<Wire>,
+        is_data_mode: bool,
+        max_queued: usize,
+    }
+
+    impl FakeQueue {
+        fn new(depth: usize) -> Self {
+            Self{depth, queued: VecDeque::new(), sent: Vec::new(),
+                 is_data_mode: false, max_queued: 0}
+        }
+
+        fn send_one(&mut self, cx: &mut Context<'_>) {
+            self.sent.push(self.queued.pop_front().unwrap());
+            cx.waker().wake_by_ref();
+        }
+    }
+
+    impl DcxPin for FakeQueue {
+        fn set_dcx_command_mode(&mut self) { self.is_data_mode = false; }
+        fn set_dcx_data_mode(&mut self) { self.is_data_mode = true; }
+    }
+
+    impl WriteQueue for FakeQueue {
+        type Ticket = usize;
+
+        fn queue_depth(&self) -> usize { self.depth }
+
+        fn poll_enqueue(&mut self, cx: &mut Context<'_>, data: &[u8])
+                -> Poll<usize> {
+            assert!(!data.is_empty() && data.len() <= self.depth);
+            if self.queued.len() + data.len() > self.depth {
+                self.send_one(cx);
+                return Poll::Pending;
+            }
+            let is_data_mode = self.is_data_mode;
+            self.queued.extend(data.iter().map(
+                |b| if is_data_mode { Data(*b) } else { Command(*b) }));
+            self.max_queued = self.max_queued.max(self.queued.len());
+            Poll::Ready(self.sent.len() + self.queued.len() - 1)
+        }
+
+        fn poll_sent(&mut self, cx: &mut Context<'_>, ticket: usize)
+                -> Poll<()> {
+            if self.sent.len() > ticket { return Poll::Ready(()); }
+            self.send_one(cx);
+            Poll::Pending
+        }
+    }
+
+    #[test]
+    fn queues_without_sending() {
+        let mut p = Pipeline::new(FakeQueue::new(8));
+        let pending = block_on(p.caset(1, 2));
+        assert_eq!(pending, Pending{ticket: Some(4)});
+        assert!(p.inner().sent.is_empty());
+        assert_eq!(p.inner().queued, [
+            Command(0x2A), Data(0), Data(1), Data(0), Data(2)]);
+
+        let ramwr = block_on(p.ramwr());
+        block_on(p.wait(pending));
+        assert_eq!(p.inner().sent.len(), 5);
+        assert_eq!(p.inner().queued, [Command(0x2C)]);
+        block_on(p.wait(ramwr));
+        assert!(p.inner().queued.is_empty());
+    }
+
+    #[test]
+    fn data_longer_than_queue() {
+        let mut p = Pipeline::new(FakeQueue::new(4));
+        let _ = block_on(p.ramwr());
+        let pending = block_on(p.data(&[1, 2, 3, 4, 5, 6, 7, 8, 9]));
+        assert_eq!(pending, Pending{ticket: Some(9)});
+        // Only what doesn't fit in the queue is sent.
+        assert_eq!(p.inner().queued, [Data(6), Data(7), Data(8), Data(9)]);
+        block_on(p.flush());
+        assert_eq!(p.into_inner().sent, [
+            Command(0x2C), Data(1), Data(2), Data(3), Data(4), Data(5),
+            Data(6), Data(7), Data(8), Data(9)]);
+    }
+
+    #[test]
+    fn paced() {
+        let mut p = Pipeline::new(FakeQueue::new(8));
+        p.set_queue_depth(QueueDepth::Bytes(4));
+        let _ = block_on(p.ramwr());
+        let _ = block_on(p.data(&[1, 2, 3, 4, 5, 6, 7, 8, 9]));
+        assert_eq!(p.inner().max_queued, 4);
+        assert!(p.inner().queued.len() <= 4);
+        block_on(p.flush());
+        assert_eq!(p.into_inner().sent, [
+            Command(0x2C), Data(1), Data(2), Data(3), Data(4), Data(5),
+            Data(6), Data(7), Data(8), Data(9)]);
+
+        // Many small pieces wait for the oldest tracked ones.
+        let mut p = Pipeline::new(FakeQueue::new(8));
+        p.set_queue_depth(QueueDepth::Bytes(64));
+        for _ in 0..TRACKED_PIECES + 2 {
+            let _ = block_on(p.ramwr());
+        }
+        assert_eq!(p.inner().max_queued, TRACKED_PIECES);
+        assert_eq!(p.inner().sent.len(), 2);
+
+        // Unpaced, the backend's queue fills up.
+        let mut p = Pipeline::new(FakeQueue::new(8));
+        let _ = block_on(p.data(&[0; 9]));
+        assert_eq!(p.inner().max_queued, 8);
+    }
+
+    #[test]
+    fn empty() {
+        let mut p = Pipeline::new(FakeQueue::new(4));
+        assert_eq!(block_on(p.data(&[])), Pending::sent());
+        block_on(p.flush());
+        block_on(p.wait(Pending::sent()));
+        assert!(p.inner().sent.is_empty());
+    }
+}  // mod tests
@@ -0,0 +1,43 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Custom gamma corrections of common modules, from their vendors'
+//! initialization code, to pass as [InitProfile::gamma] or to
+//! [Commands::apply_gamma()].
+//!
+//! Each is checked by [GammaConfig::is_valid()] at compile time.
+//!
+//! [InitProfile::gamma]: crate::display::InitProfile::gamma
+//! [Commands::apply_gamma()]: crate::Commands::apply_gamma
+
+use crate::command_structs::GammaConfig;
+
+/// The Adafruit 1.8" and 1.44" TFT breakouts, i.e., the ST7735R modules of
+/// [PanelVariant](crate::display::PanelVariant).
+pub const ADAFRUIT_1_8_TFT: GammaConfig = GammaConfig::Custom{
+    positive: [0x02, 0x1C, 0x07, 0x12, 0x37, 0x32, 0x29, 0x2D,
+               0x29, 0x25, 0x2B, 0x39, 0x00, 0x01, 0x03, 0x10],
+    negative: [0x03, 0x1D, 0x07, 0x06, 0x2E, 0x2C, 0x29, 0x2D,
+               0x2E, 0x2E, 0x37, 0x3F, 0x00, 0x00, 0x02, 0x10],
+};
+const _: () = assert!(ADAFRUIT_1_8_TFT.is_valid());
+
+/// The Waveshare 1.44" LCD HAT, an ST7735S module of 128x128.
+pub const WAVESHARE_1_44: GammaConfig = GammaConfig::Custom{
+    positive: [0x0F, 0x1A, 0x0F, 0x18, 0x2F, 0x28, 0x20, 0x22,
+               0x1F, 0x1B, 0x23, 0x37, 0x00, 0x07, 0x02, 0x10],
+    negative: [0x0F, 0x1B, 0x0F, 0x17, 0x33, 0x2C, 0x29, 0x2E,
+               0x30, 0x30, 0x39, 0x3F, 0x00, 0x07, 0x03, 0x10],
+};
+const _: () = assert!(WAVESHARE_1_44.is_valid());
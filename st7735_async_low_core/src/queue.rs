@@ -0,0 +1,182 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A fixed-capacity queue of commands, filled by non-async code (e.g., an
+//! interrupt handler) and drained later by the task owning [Commands].
+
+use crate::color::Rgb565;
+use crate::command_structs::{Colmod, Madctl};
+use crate::commands::Commands;
+use crate::panel::{Rect, WindowError};
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+
+/// A command that can be queued in a [CommandQueue].
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum QueuedCommand {
+    Dispon,
+    Dispoff,
+    Invon,
+    Invoff,
+    Madctl(Madctl),
+    Colmod(Colmod),
+    /// A horizontal run of `len` pixels of `color`, starting at column `x`
+    /// and row `y`, sent with [Commands::fill()]. Leaves the address window
+    /// at the run. Nothing is sent if it is empty.
+    PixelRun { x: u16, y: u16, len: u16, color: Rgb565 },
+}
+
+impl QueuedCommand {
+    /// Sends the command. Only a [PixelRun](Self::PixelRun) reaching past
+    /// `u16::MAX` fails, see [Rect::last()], then with nothing sent.
+    pub async fn execute<S>(&self, commands: &mut Commands<S>)
+            -> Result<(), WindowError>
+            where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+        match *self {
+            Self::Dispon => commands.dispon().await,
+            Self::Dispoff => commands.dispoff().await,
+            Self::Invon => commands.invon().await,
+            Self::Invoff => commands.invoff().await,
+            Self::Madctl(madctl) => commands.madctl(madctl).await,
+            Self::Colmod(colmod) => commands.colmod(colmod).await,
+            Self::PixelRun{x, y, len, color} => {
+                if len == 0 {
+                    return Ok(());
+                }
+                return commands.fill(Rect::new(x, y, len, 1), color).await;
+            },
+        }
+        Ok(())
+    }
+}
+
+/// A first-in-first-out queue of at most `N` [QueuedCommand]s, without any
+/// allocation.
+///
+/// Pushing is synchronous. To share the queue with an interrupt handler, put
+/// it inside a mutex of the platform, then either [flush()](Self::flush) it
+/// while holding the lock, or [pop()](Self::pop) the commands one by one and
+/// [execute()](QueuedCommand::execute) each of them outside of the lock.
+#[derive(Clone, Debug)]
+pub struct CommandQueue<const N: usize> {
+    items: [QueuedCommand; N],
+    head: usize,
+    len: usize,
+}
+
+impl<const N: usize> CommandQueue<N> {
+    pub const fn new() -> Self {
+        Self{items: [QueuedCommand::Dispon; N], head: 0, len: 0}
+    }
+
+    pub fn len(&self) -> usize { self.len }
+    pub fn is_empty(&self) -> bool { self.len == 0 }
+    pub fn is_full(&self) -> bool { self.len == N }
+
+    /// Appends `command`, or gives it back if the queue is full.
+    pub fn push(&mut self, command: QueuedCommand)
+            -> Result<(), QueuedCommand> {
+        if self.is_full() {
+            return Err(command);
+        }
+        self.items[(self.head + self.len) % N] = command;
+        self.len += 1;
+        Ok(())
+    }
+
+    /// Removes the oldest command.
+    pub fn pop(&mut self) -> Option<QueuedCommand> {
+        if self.is_empty() {
+            return None;
+        }
+        let command = self.items[self.head];
+        self.head = (self.head + 1) % N;
+        self.len -= 1;
+        Some(command)
+    }
+
+    /// Executes and removes all commands, in order, even after one fails.
+    /// Returns the first failure.
+    pub async fn flush<S>(&mut self, commands: &mut Commands<S>)
+            -> Result<(), WindowError>
+            where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+        let mut result = Ok(());
+        while let Some(command) = self.pop() {
+            let executed = command.execute(commands).await;
+            result = result.and(executed);
+        }
+        result
+    }
+}
+
+impl<const N: usize> Default for CommandQueue<N> {
+    fn default() -> Self { Self::new() }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::tests::{Event::*, Log, logging_device};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn fifo() {
+        let mut q = CommandQueue::<2>::new();
+        assert_eq!(q.push(QueuedCommand::Invon), Ok(()));
+        assert_eq!(q.push(QueuedCommand::Dispon), Ok(()));
+        assert!(q.is_full());
+        assert_eq!(q.push(QueuedCommand::Dispoff),
+                   Err(QueuedCommand::Dispoff));
+        assert_eq!(q.pop(), Some(QueuedCommand::Invon));
+        assert_eq!(q.push(QueuedCommand::Invoff), Ok(()));
+        assert_eq!(q.pop(), Some(QueuedCommand::Dispon));
+        assert_eq!(q.pop(), Some(QueuedCommand::Invoff));
+        assert_eq!(q.pop(), None);
+        assert!(q.is_empty());
+    }
+
+    #[test]
+    fn flush() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let mut q = CommandQueue::<4>::new();
+        q.push(QueuedCommand::Colmod(Colmod::R5G6B5)).unwrap();
+        q.push(QueuedCommand::PixelRun{
+            x: 3, y: 4, len: 2, color: Rgb565::from_raw(0xF00F)}).unwrap();
+        q.push(QueuedCommand::Dispon).unwrap();
+        assert_eq!(block_on(q.flush(&mut commands)), Ok(()));
+        assert!(q.is_empty());
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x3A), Data(0b101),
+            Command(0x2A), Data(0), Data(3), Data(0), Data(4),
+            Command(0x2B), Data(0), Data(4), Data(0), Data(4),
+            Command(0x2C), Data(0xF0), Data(0x0F), Data(0xF0), Data(0x0F),
+            Command(0x29)]);
+    }
+
+    #[test]
+    fn flush_past_bad_run() {
+        let log = Log::default();
+        let mut commands = Commands::new(logging_device(&log));
+        let run = |x, len| QueuedCommand::PixelRun{
+            x, y: 0, len, color: Rgb565::from_raw(0)};
+        let mut q = CommandQueue::<4>::new();
+        q.push(run(u16::MAX, 2)).unwrap();
+        q.push(run(0, 0)).unwrap();
+        q.push(QueuedCommand::Dispon).unwrap();
+        assert_eq!(block_on(q.flush(&mut commands)),
+                   Err(WindowError::OutOfRange));
+        assert!(q.is_empty());
+        assert_eq!(*log.lock().unwrap(), [Command(0x29)]);
+    }
+}  // mod tests
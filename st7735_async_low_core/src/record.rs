@@ -0,0 +1,302 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Recording of the traffic to a byte buffer, to be replayed later, e.g.,
+//! from a context that can't run the code issuing the commands, or stored
+//! for factory programming:
+//! ```
+//! # use st7735_async_low_core::{Commands, record::{Recorder, replay}, spi::*};
+//! # async fn f<S: DcxPin>(lcd: &mut Commands<S>)
+//! #         where for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+//! let mut buf = [0u8; 64];
+//! let mut commands = Commands::new(Recorder::new(&mut buf));
+//! commands.caset(0, 127).await;
+//! commands.dispon().await;
+//! let bytes = commands.into_inner().into_recorded().unwrap();
+//! assert_eq!(bytes, [0x00, 0x2A, 0x83, 0, 0, 0, 127, 0x00, 0x29]);
+//! // Later, on the real backend:
+//! replay(lcd, bytes).await;
+//! # }
+//! ```
+//! The recording is a series of runs, each of a header byte and the bytes
+//! written while DCX stayed the same: bit 7 of the header is set for data,
+//! and bits 0-6 are the length of the run minus 1.
+//!
+//! Only the bytes are recorded: waits, e.g., after SLPOUT, are up to the
+//! replaying code. Commands reading the panel are not available.
+
+use core::future::{Ready, ready};
+
+use crate::commands::Commands;
+use crate::spi::{DcxPin, WriteRepeat, WriteU8, WriteU8s, WriteU8sStatic};
+
+const DATA: u8 = 0x80;
+const MAX_RUN: u8 = 0x80;
+
+/// The buffer of a [Recorder] is too small.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Overflow {
+    /// The buffer size needed for the whole recording.
+    pub needed: usize,
+}
+
+impl ::core::fmt::Display for Overflow {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// A backend recording every byte written into a buffer, see the
+/// [module](self) doc. Writes past the end of the buffer are dropped, but
+/// still counted in [Overflow::needed].
+#[derive(Debug)]
+pub struct Recorder<'b> {
+    buf: &'b mut [u8],
+    // The bytes of the recording so far, even those dropped.
+    len: usize,
+    // The bytes actually in `buf`, all of `len` before any overflow.
+    written: usize,
+    // The mode and the length of the last run.
+    run: Option<(bool, u8)>,
+    is_data_mode: bool,
+}
+
+impl<'b> Recorder<'b> {
+    /// Records into `buf`, from its start.
+    pub fn new(buf: &'b mut [u8]) -> Self {
+        Self{buf, len: 0, written: 0, run: None, is_data_mode: false}
+    }
+
+    /// The recording so far.
+    pub fn recorded(&self) -> Result<&[u8], Overflow> {
+        if self.written < self.len {
+            Err(Overflow{needed: self.len})
+        } else {
+            Ok(&self.buf[..self.len])
+        }
+    }
+
+    /// The part of the recording that fitted in the buffer, all of it before
+    /// any [Overflow]. A valid recording by itself, replaying the runs up
+    /// to the first byte dropped.
+    pub fn fitted(&self) -> &[u8] { &self.buf[..self.written] }
+
+    /// The recording, giving the buffer back.
+    pub fn into_recorded(self) -> Result<&'b [u8], Overflow> {
+        if self.written < self.len {
+            Err(Overflow{needed: self.len})
+        } else {
+            Ok(&self.buf[..self.len])
+        }
+    }
+
+    fn push(&mut self, byte: u8) {
+        let is_data = self.is_data_mode;
+        let (cost, run) = match self.run {
+            Some((d, n)) if d == is_data && n < MAX_RUN => (1, n + 1),
+            _ => (2, 1),
+        };
+        if self.written == self.len && self.len + cost <= self.buf.len() {
+            // The header of the run is at the start of a new one, otherwise
+            // it was written before the last byte of the run.
+            let header = self.len + cost - 1 - run as usize;
+            self.buf[header] = if is_data { DATA } else { 0 } | (run - 1);
+            self.buf[self.len + cost - 1] = byte;
+            self.written += cost;
+        }
+        self.len += cost;
+        self.run = Some((is_data, run));
+    }
+
+    fn write(&mut self, data: &[u8]) {
+        for &b in data { self.push(b); }
+    }
+}
+
+impl DcxPin for Recorder<'_> {
+    fn set_dcx_command_mode(&mut self) { self.is_data_mode = false; }
+    fn set_dcx_data_mode(&mut self) { self.is_data_mode = true; }
+}
+
+impl<'a> WriteU8<'a> for Recorder<'_> {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Ready<()> {
+        self.push(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8s<'a> for Recorder<'_> {
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+        self.write(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8sStatic<'a> for Recorder<'_> {
+    type WriteU8sStaticDone = Ready<()>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8]) -> Ready<()> {
+        self.write(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteRepeat<'a> for Recorder<'_> {
+    type WriteRepeatDone = Ready<()>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Ready<()> {
+        for _ in 0..count { self.write(data); }
+        ready(())
+    }
+}
+
+// Iterates over the runs of a recording: whether it is data, and the bytes.
+struct Runs<'r> {
+    rest: &'r [u8],
+}
+
+impl<'r> Iterator for Runs<'r> {
+    type Item = (bool, &'r [u8]);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let (&header, tail) = self.rest.split_first()?;
+        let len = (header & !DATA) as usize + 1;
+        assert!(tail.len() >= len, "Truncated recording.");
+        let (bytes, tail) = tail.split_at(len);
+        self.rest = tail;
+        Some((header & DATA != 0, bytes))
+    }
+}
+
+/// Sends a recording of a [Recorder].
+///
+/// # Panics
+///
+/// If `bytes` is truncated.
+pub async fn replay<S>(commands: &mut Commands<S>, bytes: &[u8])
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    for (is_data, bytes) in (Runs{rest: bytes}) {
+        if is_data {
+            commands.raw_data(bytes).await;
+        } else {
+            for &cmd in bytes { commands.raw_command(cmd).await; }
+        }
+    }
+}
+
+/// Like [replay()], but writes the data through [WriteU8sStatic], e.g., for
+/// DMA to read a recording stored in flash in place.
+pub async fn replay_static<S>(commands: &mut Commands<S>,
+                              bytes: &'static [u8])
+        where S: DcxPin,
+              for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteU8sStatic<'a> {
+    for (is_data, bytes) in (Runs{rest: bytes}) {
+        if is_data {
+            commands.raw_data_static(bytes).await;
+        } else {
+            for &cmd in bytes { commands.raw_command(cmd).await; }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+
+    use crate::fake_device::{FakeDevice, Wire::{Command, Data}};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    async fn draw<S>(commands: &mut Commands<S>)
+            where S: DcxPin,
+                  for<'a> S: WriteU8<'a> + WriteU8s<'a> + WriteRepeat<'a> {
+        commands.dispon().await;
+        commands.caset(0, 99).await;
+        commands.raset(1, 2).await;
+        commands.ramwr().await
+            .write_repeated(&[0x12, 0x34], 200).await;
+        commands.nop().await;
+    }
+
+    #[test]
+    fn record_and_replay() {
+        let mut buf = [0u8; 512];
+        let mut commands = Commands::new(Recorder::new(&mut buf));
+        block_on(draw(&mut commands));
+        let recorded = commands.into_inner().into_recorded().unwrap();
+        // 400 pixel bytes take 4 runs; the commands next to each other share
+        // theirs.
+        assert_eq!(recorded.len(), 3 + 5 + 2 + 5 + 2 + 4 + 400 + 2);
+        assert_eq!(recorded[..13],
+                   [0x01, 0x29, 0x2A, 0x83, 0, 0, 0, 99, 0x00, 0x2B, 0x83,
+                    0, 1]);
+
+        let device = FakeDevice::new();
+        let mut expected = Commands::new(device.clone());
+        block_on(draw(&mut expected));
+        let seq = device.seq();
+        device.clear();
+        let mut commands = Commands::new(device.clone());
+        block_on(replay(&mut commands, recorded));
+        assert_eq!(device.seq(), seq);
+        assert_eq!(seq.len(), 1 + 5 + 5 + 1 + 400 + 1);
+    }
+
+    #[test]
+    fn replay_from_flash() {
+        static RECORDED: [u8; 5] = [0x01, 0x29, 0x2C, 0x80, 0x12];
+        let device = FakeDevice::new();
+        let mut commands = Commands::new(device.clone());
+        block_on(replay_static(&mut commands, &RECORDED));
+        assert_eq!(device.seq(),
+                   vec![Command(0x29), Command(0x2C), Data(0x12)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "Truncated recording")]
+    fn truncated() {
+        let mut commands = Commands::new(FakeDevice::new());
+        block_on(replay(&mut commands, &[0x81, 1]));
+    }
+
+    #[test]
+    fn overflow() {
+        let mut buf = [0u8; 10];
+        let mut commands = Commands::new(Recorder::new(&mut buf));
+        block_on(commands.dispon());
+        let recorder = commands.into_inner();
+        assert_eq!(recorder.recorded(), Ok(&[0x00, 0x29][..]));
+        assert_eq!(recorder.fitted(), [0x00, 0x29]);
+
+        let mut buf = [0u8; 10];
+        let mut commands = Commands::new(Recorder::new(&mut buf));
+        block_on(commands.caset(0, 99));
+        block_on(commands.raset(1, 2));
+        let recorder = commands.into_inner();
+        assert_eq!(recorder.recorded(), Err(Overflow{needed: 14}));
+        assert_eq!(recorder.fitted().len(), 9);
+        let device = FakeDevice::new();
+        block_on(replay(&mut Commands::new(device.clone()),
+                        recorder.fitted()));
+        assert_eq!(device.seq(), vec![
+            Command(0x2A), Data(0), Data(0), Data(0), Data(99),
+            Command(0x2B)]);
+    }
+}  // mod tests
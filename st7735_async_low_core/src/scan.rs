@@ -0,0 +1,441 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Helpers to reorder pixel buffers into the order the LCD consumes them.
+//!
+//! The LCD fills the address window in the order defined by [Madctl]: the
+//! column address is the fast-moving counter, the row address is the
+//! slow-moving one. [RowOrder::BottomToTop] and [ColumnOrder::RightToLeft]
+//! mirror the respective counter; [RowColumnSwap::Swapped] then exchanges them,
+//! so that the column address walks along the physical rows.
+
+use crate::command_structs::{
+    ColumnOrder, Flip, Madctl, RowColumnSwap, RowOrder};
+use crate::panel::Rect;
+
+/// Iterates `buffer`, a row-major image of `width` by `height` pixels in the
+/// unrotated orientation, in the order that an LCD configured with `madctl`
+/// consumes the pixels, so that the image shows up unrotated.
+///
+/// When `madctl` swaps rows and columns, the address window should be set to
+/// `height` columns by `width` rows.
+///
+/// # Panics
+///
+/// If the length of `buffer` is not `width * height`.
+///
+/// # Example
+///
+/// ```
+/// # use st7735_async_low_core::*;
+/// # use st7735_async_low_core::scan::scan_order;
+/// let mut mctl = Madctl::default();
+/// mctl.set_row_address_order(RowOrder::TopToBottom)
+///     .set_column_address_order(ColumnOrder::RightToLeft)
+///     .set_row_column_swap(RowColumnSwap::Unswapped);
+/// let image = [1, 2, 3,
+///              4, 5, 6];
+/// let v: Vec<_> = scan_order(&image, 3, 2, mctl).collect();
+/// assert_eq!(v, [3, 2, 1, 6, 5, 4]);
+/// ```
+pub fn scan_order<T: Copy>(buffer: &[T], width: usize, height: usize,
+                           madctl: Madctl) -> ScanOrder<'_, T> {
+    assert_eq!(buffer.len(), width * height);
+    ScanOrder{
+        buffer, width, height, index: 0,
+        mirror_rows: madctl.row_address_order() == RowOrder::BottomToTop,
+        mirror_columns:
+            madctl.column_address_order() == ColumnOrder::RightToLeft,
+        swap: madctl.row_column_swap() == RowColumnSwap::Swapped,
+    }
+}
+
+/// Iterates `buffer`, a row-major image of `width` by `height` pixels, row by
+/// row but mirrored by `flip`, without copying it.
+///
+/// # Panics
+///
+/// If the length of `buffer` is not `width * height`.
+pub fn flipped<T: Copy>(buffer: &[T], width: usize, height: usize,
+                        flip: Flip) -> ScanOrder<'_, T> {
+    assert_eq!(buffer.len(), width * height);
+    ScanOrder{
+        buffer, width, height, index: 0,
+        mirror_rows: flip.contains(Flip::VERTICAL),
+        mirror_columns: flip.contains(Flip::HORIZONTAL),
+        swap: false,
+    }
+}
+
+/// Same as [transposed()], but the image is mirrored by `flip` first.
+///
+/// # Panics
+///
+/// If the length of `buffer` is not `width * height`.
+pub fn transposed_flipped<T: Copy>(buffer: &[T], width: usize,
+                                   height: usize, flip: Flip)
+        -> ScanOrder<'_, T> {
+    assert_eq!(buffer.len(), width * height);
+    // The fast-moving counter walks along the columns of the image.
+    ScanOrder{
+        buffer, width, height, index: 0,
+        mirror_rows: flip.contains(Flip::HORIZONTAL),
+        mirror_columns: flip.contains(Flip::VERTICAL),
+        swap: true,
+    }
+}
+
+/// The iterator returned by [scan_order()], [flipped()] and
+/// [transposed_flipped()].
+#[derive(Clone, Debug)]
+pub struct ScanOrder<'a, T> {
+    buffer: &'a [T],
+    width: usize,
+    height: usize,
+    index: usize,
+    mirror_rows: bool,
+    mirror_columns: bool,
+    swap: bool,
+}
+
+impl<'a, T: Copy> Iterator for ScanOrder<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index >= self.buffer.len() {
+            return None;
+        }
+        let (x, y) = position(self.index, self.width, self.height,
+                              self.mirror_rows, self.mirror_columns,
+                              self.swap);
+        self.index += 1;
+        Some(self.buffer[y * self.width + x])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.buffer.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: Copy> ExactSizeIterator for ScanOrder<'a, T> {}
+
+// The coordinates in a `width` by `height` image of the `index`-th pixel
+// consumed by the LCD.
+fn position(index: usize, width: usize, height: usize, mirror_rows: bool,
+            mirror_columns: bool, swap: bool) -> (usize, usize) {
+    // Sizes of the fast-moving (column) and slow-moving (row) counters.
+    let (columns, rows) = if swap { (height, width) } else { (width, height) };
+    let mut column = index % columns;
+    let mut row = index / columns;
+    if mirror_columns { column = columns - 1 - column; }
+    if mirror_rows { row = rows - 1 - row; }
+    if swap { (row, column) } else { (column, row) }
+}
+
+/// Iterates the coordinates of `rect`, in the unrotated orientation, in the
+/// order that an LCD configured with `madctl` consumes the pixels, as
+/// [scan_order()] does for a buffer. So a procedural image, e.g., a shader
+/// `|x, y| color`, can be written without a buffer.
+///
+/// # Example
+///
+/// ```
+/// # use st7735_async_low_core::*;
+/// # use st7735_async_low_core::color::Rgb565;
+/// # use st7735_async_low_core::panel::Rect;
+/// # use st7735_async_low_core::scan::WindowIter;
+/// # use st7735_async_low_core::spi::{DcxPin, WriteU8, WriteU8s};
+/// async fn gradient<S>(commands: &mut Commands<S>, madctl: Madctl)
+///         where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+///     let rect = Rect::new(0, 0, 32, 64);
+///     let pixels = WindowIter::new(rect, madctl)
+///         .map(|(x, y)| Rgb565::new(x as u8, y as u8, 0));
+///     commands.ramwr().await.write_pixels(pixels).await;
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct WindowIter {
+    rect: Rect,
+    index: usize,
+    mirror_rows: bool,
+    mirror_columns: bool,
+    swap: bool,
+}
+
+impl WindowIter {
+    /// When `madctl` swaps rows and columns, the address window should be set
+    /// to `rect.height` columns by `rect.width` rows.
+    pub fn new(rect: Rect, madctl: Madctl) -> Self {
+        Self{
+            rect, index: 0,
+            mirror_rows: madctl.row_address_order() == RowOrder::BottomToTop,
+            mirror_columns:
+                madctl.column_address_order() == ColumnOrder::RightToLeft,
+            swap: madctl.row_column_swap() == RowColumnSwap::Swapped,
+        }
+    }
+}
+
+impl Iterator for WindowIter {
+    type Item = (u16, u16);
+
+    fn next(&mut self) -> Option<(u16, u16)> {
+        if self.index >= self.rect.area() {
+            return None;
+        }
+        let (x, y) = position(self.index, self.rect.width as usize,
+                              self.rect.height as usize, self.mirror_rows,
+                              self.mirror_columns, self.swap);
+        self.index += 1;
+        Some((self.rect.x + x as u16, self.rect.y + y as u16))
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.rect.area() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl ExactSizeIterator for WindowIter {}
+
+/// Iterates `buffer`, a row-major image of `width` by `height` pixels, column
+/// by column, i.e., as a row-major image of `height` by `width` pixels. This
+/// is what an LCD needs when the rows and columns are swapped by the MV bit
+/// of [Madctl] but the image isn't rotated.
+///
+/// Each step strides a whole row through `buffer`. When the image is
+/// transposed repeatedly and memory allows, [transpose_into()] does it with
+/// better locality.
+///
+/// # Panics
+///
+/// If the length of `buffer` is not `width * height`.
+pub fn transposed<T: Copy>(buffer: &[T], width: usize, height: usize)
+        -> Transposed<'_, T> {
+    assert_eq!(buffer.len(), width * height);
+    Transposed{buffer, width, height, index: 0}
+}
+
+/// The iterator returned by [transposed()].
+#[derive(Clone, Debug)]
+pub struct Transposed<'a, T> {
+    buffer: &'a [T],
+    width: usize,
+    height: usize,
+    index: usize,
+}
+
+impl<'a, T: Copy> Iterator for Transposed<'a, T> {
+    type Item = T;
+
+    fn next(&mut self) -> Option<T> {
+        if self.index >= self.buffer.len() {
+            return None;
+        }
+        let x = self.index / self.height;
+        let y = self.index % self.height;
+        self.index += 1;
+        Some(self.buffer[y * self.width + x])
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.buffer.len() - self.index;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<'a, T: Copy> ExactSizeIterator for Transposed<'a, T> {}
+
+// The side of the square tiles of `transpose_into()`, so that the rows of a
+// tile of both the source and the destination stay in the cache.
+const TILE: usize = 8;
+
+/// Writes the transpose of `src`, a row-major image of `width` by `height`
+/// pixels, into `dst`, i.e., the same image as `height` by `width`. Works
+/// tile by tile, which is friendlier to caches than [transposed()].
+///
+/// # Panics
+///
+/// If the length of `src` or `dst` is not `width * height`.
+pub fn transpose_into<T: Copy>(src: &[T], width: usize, height: usize,
+                               dst: &mut [T]) {
+    assert_eq!(src.len(), width * height);
+    assert_eq!(dst.len(), width * height);
+    for y0 in (0..height).step_by(TILE) {
+        for x0 in (0..width).step_by(TILE) {
+            for y in y0..(y0 + TILE).min(height) {
+                for x in x0..(x0 + TILE).min(width) {
+                    dst[x * height + y] = src[y * width + x];
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+    use super::*;
+
+    fn madctl(rows: RowOrder, columns: ColumnOrder, swap: RowColumnSwap)
+            -> Madctl {
+        let mut mctl = Madctl::default();
+        mctl.set_row_address_order(rows)
+            .set_column_address_order(columns)
+            .set_row_column_swap(swap);
+        mctl
+    }
+
+    const IMAGE: [u8; 6] = [1, 2, 3,
+                            4, 5, 6];
+
+    fn scan(mctl: Madctl) -> Vec<u8> {
+        scan_order(&IMAGE, 3, 2, mctl).collect()
+    }
+
+    #[test]
+    fn identity() {
+        let mctl = madctl(RowOrder::TopToBottom, ColumnOrder::LeftToRight,
+                          RowColumnSwap::Unswapped);
+        assert_eq!(scan(mctl), [1, 2, 3, 4, 5, 6]);
+    }
+
+    #[test]
+    fn mirrored_rows() {
+        let mctl = madctl(RowOrder::BottomToTop, ColumnOrder::LeftToRight,
+                          RowColumnSwap::Unswapped);
+        assert_eq!(scan(mctl), [4, 5, 6, 1, 2, 3]);
+    }
+
+    #[test]
+    fn mirrored_both() {
+        let mctl = madctl(RowOrder::BottomToTop, ColumnOrder::RightToLeft,
+                          RowColumnSwap::Unswapped);
+        assert_eq!(scan(mctl), [6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn swapped() {
+        let mctl = madctl(RowOrder::TopToBottom, ColumnOrder::LeftToRight,
+                          RowColumnSwap::Swapped);
+        assert_eq!(scan(mctl), [1, 4, 2, 5, 3, 6]);
+    }
+
+    #[test]
+    fn swapped_and_mirrored_columns() {
+        let mctl = madctl(RowOrder::TopToBottom, ColumnOrder::RightToLeft,
+                          RowColumnSwap::Swapped);
+        assert_eq!(scan(mctl), [4, 1, 5, 2, 6, 3]);
+    }
+
+    #[test]
+    fn exact_size() {
+        let mctl = Madctl::default();
+        let mut it = scan_order(&IMAGE, 2, 3, mctl);
+        assert_eq!(it.len(), 6);
+        it.next();
+        assert_eq!(it.len(), 5);
+    }
+
+    #[test]
+    fn transpose() {
+        let v: Vec<_> = transposed(&IMAGE, 3, 2).collect();
+        assert_eq!(v, [1, 4, 2, 5, 3, 6]);
+        assert_eq!(transposed(&IMAGE, 3, 2).len(), 6);
+        assert_eq!(transposed::<u8>(&[], 0, 5).next(), None);
+    }
+
+    #[test]
+    fn flip() {
+        let flip = |f| flipped(&IMAGE, 3, 2, f).collect::<Vec<_>>();
+        assert_eq!(flip(Flip::empty()), IMAGE);
+        assert_eq!(flip(Flip::HORIZONTAL), [3, 2, 1, 6, 5, 4]);
+        assert_eq!(flip(Flip::VERTICAL), [4, 5, 6, 1, 2, 3]);
+        assert_eq!(flip(Flip::HORIZONTAL | Flip::VERTICAL),
+                   [6, 5, 4, 3, 2, 1]);
+    }
+
+    #[test]
+    fn transpose_flipped() {
+        let flip = |f| transposed_flipped(&IMAGE, 3, 2, f).collect::<Vec<_>>();
+        assert_eq!(flip(Flip::empty()), [1, 4, 2, 5, 3, 6]);
+        assert_eq!(flip(Flip::HORIZONTAL), [3, 6, 2, 5, 1, 4]);
+        assert_eq!(flip(Flip::VERTICAL), [4, 1, 5, 2, 6, 3]);
+        assert_eq!(flip(Flip::HORIZONTAL | Flip::VERTICAL),
+                   [6, 3, 5, 2, 4, 1]);
+    }
+
+    #[test]
+    fn transpose_tiled() {
+        // Spanning partial tiles in both directions.
+        let (width, height) = (19, 11);
+        let image: Vec<usize> = (0..width * height).collect();
+        let mut dst = std::vec![0; width * height];
+        transpose_into(&image, width, height, &mut dst);
+        let expected: Vec<_> = transposed(&image, width, height).collect();
+        assert_eq!(dst, expected);
+    }
+
+    #[test]
+    fn window_iter() {
+        let mctl = madctl(RowOrder::TopToBottom, ColumnOrder::RightToLeft,
+                          RowColumnSwap::Swapped);
+        let v: Vec<_> = WindowIter::new(Rect::new(10, 20, 3, 2), mctl)
+            .collect();
+        assert_eq!(v, [(10, 21), (10, 20), (11, 21), (11, 20), (12, 21),
+                       (12, 20)]);
+    }
+
+    #[test]
+    fn window_iter_properties() {
+        use RowOrder::*;
+        use ColumnOrder::*;
+        use RowColumnSwap::*;
+        for &rows in &[TopToBottom, BottomToTop] {
+            for &columns in &[LeftToRight, RightToLeft] {
+                for &swap in &[Unswapped, Swapped] {
+                    let mctl = madctl(rows, columns, swap);
+                    for (width, height) in (0..5).flat_map(
+                            |w| (0..5).map(move |h| (w, h))) {
+                        let rect = Rect::new(2, 3, width, height);
+                        let it = WindowIter::new(rect, mctl);
+                        assert_eq!(it.len(), rect.area());
+                        let mut v: Vec<_> = it.collect();
+                        assert_eq!(v.len(), rect.area());
+                        // The same order as a buffer of the coordinates.
+                        let image: Vec<_> = (0..height)
+                            .flat_map(|y| (0..width).map(
+                                move |x| (2 + x, 3 + y)))
+                            .collect();
+                        let scanned: Vec<_> = scan_order(
+                            &image, width as usize, height as usize, mctl)
+                            .collect();
+                        assert_eq!(v, scanned);
+                        // Each pixel once.
+                        v.sort_unstable();
+                        v.dedup();
+                        assert_eq!(v.len(), rect.area());
+                    }
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic]
+    fn wrong_size() {
+        scan_order(&IMAGE, 2, 2, Madctl::default());
+    }
+}  // mod tests
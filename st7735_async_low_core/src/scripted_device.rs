@@ -0,0 +1,229 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A backend checking the bytes written against a script of expected
+//! commands, with canned answers to the reads, for tests of code built on
+//! [Commands](crate::Commands) without a mocking framework:
+//! ```
+//! # use st7735_async_low_core::{Colmod, Commands, task::block_on};
+//! # use st7735_async_low_core::scripted_device::ScriptedDevice;
+//! let device = ScriptedDevice::new();
+//! device.expect_command(0x2A, &[0, 1, 0, 2]);
+//! device.expect_read(0x0C, 0b101, 8);
+//! let mut commands = Commands::new(device.clone());
+//! block_on(commands.caset(1, 2));
+//! assert_eq!(block_on(commands.rddcolmod()), Colmod::R5G6B5);
+//! device.assert_done();
+//! ```
+//! Unlike [FakeDevice](crate::fake_device::FakeDevice), an unexpected byte
+//! panics right away, pointing at the write.
+//!
+//! The impls aren't plain `async fn`s yet: the [spi](crate::spi) traits name
+//! their futures until the crate migrates to async functions in traits. As
+//! every step completes at once, each future is a [Ready], which needs
+//! neither boxing nor `unsafe` meanwhile.
+//!
+//! Needs the `test-utils` feature.
+
+use core::future::{Ready, ready};
+use std::{cell::RefCell, collections::VecDeque, rc::Rc};
+
+use crate::fake_device::Wire;
+use crate::spi::{
+    DcxPin, Read, ReadBits, WriteRepeat, WriteU8, WriteU8s,
+    WriteU8sStatic};
+
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+enum Step {
+    Write(Wire),
+    // The lowest `len` bits, the highest first.
+    Read{bits: u32, len: usize},
+}
+
+/// See the [module](self) doc. Clones share the script, so that one can be
+/// kept by the test while the other is moved into the
+/// [Commands](crate::Commands).
+#[derive(Clone, Debug, Default)]
+pub struct ScriptedDevice {
+    script: Rc<RefCell<VecDeque<Step>>>,
+    is_data_mode: bool,
+}
+
+impl ScriptedDevice {
+    /// A device expecting nothing yet.
+    pub fn new() -> Self { Self::default() }
+
+    /// Expects `cmd` written as a command, then `data` as its data.
+    pub fn expect_command(&self, cmd: u8, data: &[u8]) {
+        let mut script = self.script.borrow_mut();
+        script.push_back(Step::Write(Wire::Command(cmd)));
+        script.extend(data.iter().map(|&b| Step::Write(Wire::Data(b))));
+    }
+
+    /// Expects the read command `cmd`, answering the read that follows with
+    /// the lowest `len` bits of `bits`, the highest first. As on the wire,
+    /// the dummy bit, if any, is included, e.g., as the highest of 25 bits
+    /// for [RDDID](crate::Commands::rddid). Bits read beyond are zeros.
+    pub fn expect_read(&self, cmd: u8, bits: u32, len: usize) {
+        assert!(len <= 32);
+        let mut script = self.script.borrow_mut();
+        script.push_back(Step::Write(Wire::Command(cmd)));
+        script.push_back(Step::Read{bits, len});
+    }
+
+    /// Panics unless the whole script was played.
+    pub fn assert_done(&self) {
+        let script = self.script.borrow();
+        assert!(script.is_empty(), "Expected still: {:?}.", script);
+    }
+
+    fn next(&self, what: &dyn core::fmt::Debug) -> Step {
+        self.script.borrow_mut().pop_front()
+            .unwrap_or_else(|| panic!("Unexpected {:?}: the script is done.",
+                                      what))
+    }
+
+    fn write(&self, data: &[u8]) {
+        for &b in data {
+            let wire = if self.is_data_mode {
+                Wire::Data(b)
+            } else {
+                Wire::Command(b)
+            };
+            let step = self.next(&wire);
+            assert_eq!(step, Step::Write(wire), "Unexpected write.");
+        }
+    }
+}
+
+impl DcxPin for ScriptedDevice {
+    fn set_dcx_command_mode(&mut self) { self.is_data_mode = false; }
+    fn set_dcx_data_mode(&mut self) { self.is_data_mode = true; }
+}
+
+impl<'a> WriteU8<'a> for ScriptedDevice {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Ready<()> {
+        self.write(&[data]);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8s<'a> for ScriptedDevice {
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+        self.write(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteU8sStatic<'a> for ScriptedDevice {
+    type WriteU8sStaticDone = Ready<()>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8]) -> Ready<()> {
+        self.write(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteRepeat<'a> for ScriptedDevice {
+    type WriteRepeatDone = Ready<()>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Ready<()> {
+        for _ in 0..count { self.write(data); }
+        ready(())
+    }
+}
+
+impl<'a> Read<'a> for ScriptedDevice {
+    type ReadBitsType = ScriptedReader;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType {
+        match self.next(&"read") {
+            Step::Read{bits, len} => ScriptedReader{bits, len},
+            step => panic!("Unexpected read, expected {:?}.", step),
+        }
+    }
+}
+
+/// Reads the answer given to [ScriptedDevice::expect_read()].
+#[derive(Debug)]
+pub struct ScriptedReader {
+    bits: u32,
+    /// How many of the lowest `bits` are still unread.
+    len: usize,
+}
+
+impl<'a> ReadBits<'a> for ScriptedReader {
+    type ReadBitsDone = Ready<u32>;
+
+    fn read_bits(&'a mut self, num_bits: usize) -> Ready<u32> {
+        assert!(num_bits <= 32);
+        let mut v = 0u32;
+        for _ in 0..num_bits {
+            let bit = if self.len == 0 { 0 } else {
+                self.len -= 1;
+                self.bits >> self.len & 1
+            };
+            v = v << 1 | bit;
+        }
+        ready(v)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::command_structs::Colmod;
+    use crate::commands::Commands;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn scripted() {
+        let device = ScriptedDevice::new();
+        device.expect_command(0x2A, &[0, 1, 0, 2]);
+        device.expect_read(0x0C, 0b0101, 8);
+        device.expect_command(0x29, &[]);
+        let mut commands = Commands::new(device.clone());
+        block_on(commands.caset(1, 2));
+        assert_eq!(block_on(commands.rddcolmod()), Colmod::R5G6B5);
+        block_on(commands.dispon());
+        device.assert_done();
+    }
+
+    #[test]
+    #[should_panic(expected = "Unexpected write")]
+    fn unexpected_write() {
+        let device = ScriptedDevice::new();
+        device.expect_command(0x2A, &[0, 1, 0, 2]);
+        block_on(Commands::new(device).caset(1, 3));
+    }
+
+    #[test]
+    #[should_panic(expected = "the script is done")]
+    fn past_the_end() {
+        block_on(Commands::new(ScriptedDevice::new()).dispon());
+    }
+
+    #[test]
+    #[should_panic(expected = "Expected still")]
+    fn not_done() {
+        let device = ScriptedDevice::new();
+        device.expect_command(0x29, &[]);
+        device.assert_done();
+    }
+}  // mod tests
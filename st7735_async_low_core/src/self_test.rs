@@ -0,0 +1,165 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! A self test for production lines: draws a known pattern and reads a sample
+//! of it back.
+
+use crate::color::Rgb666;
+use crate::command_structs::Colmod;
+use crate::commands::Commands;
+use crate::panel::Panel;
+use crate::spi::{DcxPin, Read, WriteU8, WriteU8s};
+
+/// The result of [Commands::self_test()].
+///
+/// The stuck bits are in the 18-bit layout `R << 12 | G << 6 | B`, each
+/// component being 6-bit.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq)]
+pub struct SelfTestReport {
+    /// The number of pixels read back.
+    pub sampled: usize,
+    /// The number of pixels read back differently from the pattern.
+    pub mismatched: usize,
+    /// Bits read as 1 in every sampled pixel, despite being 0 in some of the
+    /// pattern.
+    pub stuck_high: u32,
+    /// Bits read as 0 in every sampled pixel, despite being 1 in some of the
+    /// pattern.
+    pub stuck_low: u32,
+}
+
+impl SelfTestReport {
+    pub fn passed(&self) -> bool { self.mismatched == 0 }
+}
+
+/// The test pattern: gradients in red and green along the columns, plus a
+/// checkerboard in blue. Panels at least 64 pixels wide see every bit both
+/// as 0 and 1 in each row.
+pub fn test_pattern(x: u16, y: u16) -> Rgb666 {
+    let v = (x & 0x3F) as u8;
+    Rgb666::new(v, !v, if (x ^ y) & 1 == 0 { 0 } else { 0x3F })
+}
+
+fn pack(p: Rgb666) -> u32 {
+    (p.r() as u32) << 12 | (p.g() as u32) << 6 | p.b() as u32
+}
+
+impl<S> Commands<S> where S: DcxPin,
+                          for<'a> S: WriteU8<'a> + WriteU8s<'a> + Read<'a> {
+    /// Draws [test_pattern()] over the whole `panel` and reads back its
+    /// first, middle and last rows.
+    ///
+    /// Notice that this leaves the LCD in [Colmod::R6G6B6], so that the
+    /// pixels are read back exactly as written, and the address window at the
+    /// last row read.
+    pub async fn self_test(&mut self, panel: &Panel) -> SelfTestReport {
+        let mut report = SelfTestReport::default();
+        let (width, height) = (panel.width(), panel.height());
+        if width == 0 || height == 0 {
+            return report;
+        }
+        let (x0, y0) = (panel.column_offset(), panel.row_offset());
+        self.colmod(Colmod::R6G6B6).await;
+        self.caset(x0, x0 + width - 1).await;
+        self.raset(y0, y0 + height - 1).await;
+        let pixels = (0..height).flat_map(|y| {
+            (0..width).map(move |x| test_pattern(x, y))
+        });
+        self.ramwr().await.write_pixels(pixels).await;
+
+        let (mut read_and, mut read_or) = (0x3FFFF, 0);
+        let (mut expected_and, mut expected_or) = (0x3FFFF, 0);
+        let mut rows = [0, height / 2, height - 1];
+        let rows = match height {
+            1 => &rows[..1],
+            2 => { rows[1] = 1; &rows[..2] },
+            _ => &rows[..],
+        };
+        for &y in rows {
+            self.raset(y0 + y, y0 + y).await;
+            self.ramrd_with(width as usize, |x, p| {
+                let expected = test_pattern(x as u16, y);
+                report.sampled += 1;
+                if p != expected {
+                    report.mismatched += 1;
+                }
+                let (p, expected) = (pack(p), pack(expected));
+                read_and &= p;
+                read_or |= p;
+                expected_and &= expected;
+                expected_or |= expected;
+            }).await;
+        }
+        report.stuck_high = read_and & !expected_and;
+        report.stuck_low = !read_or & expected_or;
+        report
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::testing_device::{block_on, MockDevice};
+    use super::*;
+
+    // A device accepting any write, and reading back `pixels` with `or_mask`
+    // set, for each RAMRD of `per_read` pixels.
+    fn device_reading(pixels: &[Rgb666], per_read: usize, or_mask: u32)
+            -> MockDevice {
+        let mut bits = Vec::new();
+        for chunk in pixels.chunks(per_read) {
+            bits.push(false);  // The dummy bit.
+            for &p in chunk {
+                let v = pack(p) | or_mask;
+                for shift in [12, 6, 0] {
+                    let byte = ((v >> shift & 0x3F) << 2) as u8;
+                    bits.extend((0..8).rev().map(|i| byte >> i & 1 != 0));
+                }
+            }
+        }
+        let mut bits = bits.into_iter();
+        let mut d = MockDevice::new();
+        d.mock().expect_write_command().return_const(());
+        d.mock().expect_write_data().return_const(());
+        d.mock().expect_start_reading().return_const(());
+        d.mock().expect_finish_reading().return_const(());
+        d.mock().expect_read_bit().returning(move || bits.next().unwrap());
+        d
+    }
+
+    #[test]
+    fn passes() {
+        let panel = Panel::new(3, 1);
+        let pattern: Vec<_> = (0..3).map(|x| test_pattern(x, 0)).collect();
+        let mut cmds = Commands::new(device_reading(&pattern, 3, 0));
+        let report = block_on(cmds.self_test(&panel));
+        assert_eq!(report, SelfTestReport{sampled: 3, ..Default::default()});
+        assert!(report.passed());
+    }
+
+    #[test]
+    fn stuck_high() {
+        let panel = Panel::new(2, 3).with_offsets(1, 1);
+        let pattern: Vec<_> = [0, 1, 2].iter()
+            .flat_map(|&y| (0..2).map(move |x| test_pattern(x, y)))
+            .collect();
+        // Bit 0 of green.
+        let mut cmds = Commands::new(device_reading(&pattern, 2, 1 << 6));
+        let report = block_on(cmds.self_test(&panel));
+        assert_eq!(report, SelfTestReport{
+            sampled: 6, mismatched: 3, stuck_high: 1 << 6, stuck_low: 0});
+        assert!(!report.passed());
+    }
+}  // mod tests
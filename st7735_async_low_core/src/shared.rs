@@ -0,0 +1,233 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Commands] shared between interrupt handlers and the main task (feature
+//! `critical-section`).
+
+use core::cell::RefCell;
+use core::future::Future;
+use core::ops::{Deref, DerefMut};
+use core::pin::pin;
+use core::task::{Context, Poll, Waker};
+
+use critical_section::Mutex;
+
+use crate::commands::{Commands, SimpleCommandFuture};
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+use crate::task::yield_now;
+
+/// The [Commands] were in use, e.g., locked by the main task in the middle of
+/// a transfer, which must not be interrupted.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Busy;
+
+impl ::core::fmt::Display for Busy {
+    fn fmt(&self, f: &mut ::core::fmt::Formatter)
+            -> ::core::fmt::Result {
+        <Self as core::fmt::Debug>::fmt(self, f)
+    }
+}
+
+/// [Commands] that can live in a `static`, used by the main task through
+/// [lock()](Self::lock) and by interrupt (or panic) handlers through the
+/// blocking commands, e.g., [dispoff()](Self::dispoff).
+///
+/// As [Commands::new()] isn't `const`, a `static` starts
+/// [empty()](Self::empty) and is given the commands at runtime by
+/// [init()](Self::init):
+/// ```ignore
+/// static SHARED: SharedCommands<Spi> = SharedCommands::empty();
+///
+/// SHARED.init(Commands::new(spi));
+/// ```
+///
+/// The blocking commands busy-poll the backend outside of any critical
+/// section. With a backend completing its transfers in an interrupt, e.g.,
+/// of a DMA, they must not be called from a handler that this interrupt
+/// can't preempt, or they never return.
+pub struct SharedCommands<S> {
+    commands: Mutex<RefCell<Option<Commands<S>>>>,
+}
+
+impl<S> SharedCommands<S> {
+    /// Shares `commands`, e.g., owned by the main task and lent to the
+    /// handlers.
+    pub const fn new(commands: Commands<S>) -> Self {
+        Self{commands: Mutex::new(RefCell::new(Some(commands)))}
+    }
+
+    /// Without commands until [init()](Self::init), e.g., as the initializer
+    /// of a `static`. Meanwhile the blocking commands fail with [Busy] and
+    /// [lock()](Self::lock) waits.
+    pub const fn empty() -> Self {
+        Self{commands: Mutex::new(RefCell::new(None))}
+    }
+
+    /// Shares `commands`, once, before any use. Replaces those already
+    /// shared, if any.
+    pub fn init(&self, commands: Commands<S>) {
+        self.put_back(commands);
+    }
+
+    fn take(&self) -> Option<Commands<S>> {
+        critical_section::with(|cs| self.commands.borrow_ref_mut(cs).take())
+    }
+
+    fn put_back(&self, commands: Commands<S>) {
+        critical_section::with(|cs| {
+            *self.commands.borrow_ref_mut(cs) = Some(commands);
+        });
+    }
+
+    /// Waits until no one else uses the commands, then keeps them until the
+    /// returned guard is dropped. Meanwhile the blocking commands fail with
+    /// [Busy].
+    pub async fn lock(&self) -> SharedCommandsGuard<'_, S> {
+        loop {
+            if let Some(commands) = self.take() {
+                return SharedCommandsGuard{shared: self,
+                                           commands: Some(commands)};
+            }
+            yield_now().await;
+        }
+    }
+}
+
+impl<S> SharedCommands<S> where S: DcxPin,
+                                for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    // Runs the command to completion by polling it until ready. The
+    // commands are taken out in a critical section, but polled outside, so
+    // that the interrupts completing the transfers still run.
+    fn blocking<F>(&self, command: F) -> Result<(), Busy>
+            where F: for<'c> FnOnce(&'c mut Commands<S>)
+                                    -> SimpleCommandFuture<'c, S> {
+        let commands = self.take().ok_or(Busy)?;
+        // Puts the commands back, even if the backend panics.
+        let mut guard = SharedCommandsGuard{shared: self,
+                                            commands: Some(commands)};
+        let mut done = pin!(command(&mut guard));
+        let mut cx = Context::from_waker(Waker::noop());
+        while done.as_mut().poll(&mut cx) == Poll::Pending {}
+        Ok(())
+    }
+
+    /// Turns the display on, blocking.
+    pub fn dispon(&self) -> Result<(), Busy> { self.blocking(|c| c.dispon()) }
+    /// Turns the display off, blocking.
+    pub fn dispoff(&self) -> Result<(), Busy> { self.blocking(|c| c.dispoff()) }
+    /// Enables the inversion mode, blocking.
+    pub fn invon(&self) -> Result<(), Busy> { self.blocking(|c| c.invon()) }
+    /// Disables the inversion mode, blocking.
+    pub fn invoff(&self) -> Result<(), Busy> { self.blocking(|c| c.invoff()) }
+}
+
+/// The guard returned by [SharedCommands::lock()].
+pub struct SharedCommandsGuard<'s, S> {
+    shared: &'s SharedCommands<S>,
+    // Only `None` while being dropped.
+    commands: Option<Commands<S>>,
+}
+
+impl<'s, S> Deref for SharedCommandsGuard<'s, S> {
+    type Target = Commands<S>;
+
+    fn deref(&self) -> &Commands<S> { self.commands.as_ref().unwrap() }
+}
+
+impl<'s, S> DerefMut for SharedCommandsGuard<'s, S> {
+    fn deref_mut(&mut self) -> &mut Commands<S> {
+        self.commands.as_mut().unwrap()
+    }
+}
+
+impl<'s, S> Drop for SharedCommandsGuard<'s, S> {
+    fn drop(&mut self) {
+        if let Some(commands) = self.commands.take() {
+            self.shared.put_back(commands);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::pin::Pin;
+    use core::sync::atomic::{AtomicBool, Ordering};
+
+    use crate::display::tests::{Event::*, Log, logging_device};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn blocking_and_locked() {
+        let log = Log::default();
+        let shared = SharedCommands::new(Commands::new(logging_device(&log)));
+        assert_eq!(shared.dispoff(), Ok(()));
+        block_on(async {
+            let mut commands = shared.lock().await;
+            commands.invon().await;
+            assert_eq!(shared.dispon(), Err(Busy));
+        });
+        assert_eq!(shared.dispon(), Ok(()));
+        assert_eq!(*log.lock().unwrap(),
+                   [Command(0x28), Command(0x21), Command(0x29)]);
+    }
+
+    // Its writes are completed by another thread, entering a critical
+    // section as an interrupt handler would.
+    struct Interrupted;
+    struct Completion(Option<std::thread::JoinHandle<()>>);
+    static COMPLETED: AtomicBool = AtomicBool::new(false);
+
+    impl Future for Completion {
+        type Output = ();
+        fn poll(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<()> {
+            let handle = self.0.get_or_insert_with(|| std::thread::spawn(|| {
+                critical_section::with(|_| {
+                    COMPLETED.store(true, Ordering::Release);
+                });
+            }));
+            if handle.is_finished() && COMPLETED.load(Ordering::Acquire) {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }
+    }
+    impl DcxPin for Interrupted {
+        fn set_dcx_command_mode(&mut self) {}
+        fn set_dcx_data_mode(&mut self) {}
+    }
+    impl<'a> WriteU8<'a> for Interrupted {
+        type WriteU8Done = Completion;
+        fn write_u8(&'a mut self, _: u8) -> Completion { Completion(None) }
+    }
+    impl<'a> WriteU8s<'a> for Interrupted {
+        type WriteU8sDone = Completion;
+        fn write_u8s(&'a mut self, _: &'a [u8]) -> Completion {
+            Completion(None)
+        }
+    }
+
+    #[test]
+    fn blocking_outside_critical_section() {
+        static SHARED: SharedCommands<Interrupted> = SharedCommands::empty();
+        assert_eq!(SHARED.dispon(), Err(Busy));
+        SHARED.init(Commands::new(Interrupted));
+        for command in [SharedCommands::dispon, SharedCommands::dispoff] {
+            COMPLETED.store(false, Ordering::Release);
+            assert_eq!(command(&SHARED), Ok(()));
+            assert!(COMPLETED.load(Ordering::Acquire));
+        }
+    }
+}  // mod tests
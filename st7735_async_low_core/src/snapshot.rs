@@ -0,0 +1,160 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Golden-file tests at the wire level: [to_text()] renders a
+//! [FakeDevice](crate::fake_device::FakeDevice) recording in a canonical text
+//! form, and [assert_snapshot()] compares it against a checked-in file.
+//!
+//! The text has one line per command: the command byte, a colon, then its
+//! data bytes, all in hex and 16 data bytes per line at most, e.g.,
+//! ```text
+//! 2A: 00 00 00 7F
+//! 29:
+//! ```
+//! Data written before any command goes after `--:`.
+//!
+//! Run the tests with `ST7735_UPDATE_SNAPSHOTS=1` in the environment to
+//! (re)write the golden files instead of comparing against them.
+//!
+//! Needs the `test-utils` feature.
+
+use core::fmt::Write;
+use std::{format, fs, path::Path, string::String, vec::Vec};
+
+use crate::fake_device::Wire;
+
+/// The environment variable making [assert_snapshot()] write golden files.
+pub const UPDATE_ENV: &str = "ST7735_UPDATE_SNAPSHOTS";
+
+const BYTES_PER_LINE: usize = 16;
+// Lines of context around the first mismatch of a diff.
+const CONTEXT: usize = 3;
+
+/// Renders `seq` in the canonical text form.
+pub fn to_text(seq: &[Wire]) -> String {
+    let mut text = String::new();
+    let mut data_on_line = None;
+    for wire in seq {
+        match *wire {
+            Wire::Command(c) => {
+                if data_on_line.is_some() { text.push('\n'); }
+                write!(text, "{:02X}:", c).unwrap();
+                data_on_line = Some(0);
+            },
+            Wire::Data(d) => {
+                let n = match data_on_line {
+                    None => { text.push_str("--:"); 0 },
+                    Some(n) if n == BYTES_PER_LINE => {
+                        text.push_str("\n   ");
+                        0
+                    },
+                    Some(n) => n,
+                };
+                write!(text, " {:02X}", d).unwrap();
+                data_on_line = Some(n + 1);
+            },
+        }
+    }
+    if data_on_line.is_some() { text.push('\n'); }
+    text
+}
+
+/// Compares `seq` against the golden file at `path`, panicking with a diff
+/// on mismatches. Writes the file instead if [UPDATE_ENV] is set.
+pub fn assert_snapshot(path: impl AsRef<Path>, seq: &[Wire]) {
+    let path = path.as_ref();
+    let actual = to_text(seq);
+    if std::env::var_os(UPDATE_ENV).is_some() {
+        fs::write(path, &actual).unwrap_or_else(
+            |e| panic!("cannot write {}: {}", path.display(), e));
+        return;
+    }
+    let expected = fs::read_to_string(path).unwrap_or_else(|e| panic!(
+        "cannot read {}: {}; set {}=1 to create it", path.display(), e,
+        UPDATE_ENV));
+    if let Some(diff) = diff(&expected, &actual) {
+        panic!("{} mismatches; set {}=1 to update it\n{}",
+               path.display(), UPDATE_ENV, diff);
+    }
+}
+
+/// Describes how `actual` differs from `expected`, around the first
+/// mismatching line; `None` if they are equal.
+pub fn diff(expected: &str, actual: &str) -> Option<String> {
+    let expected: Vec<&str> = expected.lines().collect();
+    let actual: Vec<&str> = actual.lines().collect();
+    let first = (0..expected.len().max(actual.len()))
+        .find(|i| expected.get(*i) != actual.get(*i))?;
+    let mut out = format!("first mismatch at line {} (expected {} lines, got \
+                           {})\n", first + 1, expected.len(), actual.len());
+    for line in &expected[first.saturating_sub(CONTEXT)..first] {
+        writeln!(out, "  {}", line).unwrap();
+    }
+    let end = |lines: &[&str]| (first + CONTEXT).min(lines.len());
+    for line in expected.get(first..end(&expected)).unwrap_or_default() {
+        writeln!(out, "- {}", line).unwrap();
+    }
+    for line in actual.get(first..end(&actual)).unwrap_or_default() {
+        writeln!(out, "+ {}", line).unwrap();
+    }
+    Some(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec;
+    use crate::display::{Display, PanelVariant};
+    use crate::display::tests::{FakeBacklight, FakeDelay, FakeReset, Log};
+    use crate::fake_device::FakeDevice;
+    use crate::panel::Panel;
+    use crate::testing_device::block_on;
+    use super::*;
+    use Wire::*;
+
+    #[test]
+    fn text() {
+        let mut seq = vec![Data(1), Command(0x2C)];
+        seq.extend((0..17).map(Data));
+        seq.push(Command(0x29));
+        assert_eq!(to_text(&seq), "--: 01\n\
+            2C: 00 01 02 03 04 05 06 07 08 09 0A 0B 0C 0D 0E 0F\n    10\n\
+            29:\n");
+        assert_eq!(to_text(&[]), "");
+    }
+
+    #[test]
+    fn diffs() {
+        assert_eq!(diff("a\nb\n", "a\nb\n"), None);
+        assert_eq!(diff("a\nb\nc\n", "a\nx\nc\n").unwrap(),
+                   "first mismatch at line 2 (expected 3 lines, got 3)\n\
+                    \x20 a\n- b\n- c\n+ x\n+ c\n");
+        assert_eq!(diff("a\n", "a\nb\n").unwrap(),
+                   "first mismatch at line 2 (expected 1 lines, got 2)\n\
+                    \x20 a\n+ b\n");
+    }
+
+    #[test]
+    fn init_red_tab() {
+        let log = Log::default();
+        let device = FakeDevice::new();
+        let mut display = Display::new(
+            device.clone(), FakeReset(log.clone()), FakeDelay(log.clone()),
+            FakeBacklight(log), Panel::new(0, 0));
+        block_on(display.init_variant(PanelVariant::RedTab));
+        assert_snapshot(
+            concat!(env!("CARGO_MANIFEST_DIR"),
+                    "/snapshots/init_red_tab.txt"),
+            &device.seq());
+    }
+}  // mod tests
@@ -0,0 +1,538 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Some traits needed to implement, in order to use [`Commands`].
+//!
+//! The minimum would be to implement [DcxPin] and one of [WriteU8] and
+//! [WriteU8s], then use an [`adapter`] to complete the missing
+//! one of [WriteU8] and [WriteU8s]. With these, the write parts of [`Commands`]
+//! are already usable.
+//!
+//! Note that the SPI protocol of ST7735's write commands
+//! are actually compatible with command SPI implementations of
+//! microcontrollers, eg., STM32 SPI with `CPOL=1` (clock idles at high) and
+//! `CPHA=1` (data sampled at the second edge) without an SS pin. An example
+//! can be found at [examples/stm32f3348_disco](https://github.com/jeru/st7735-async-low/tree/main/st7735_async_low/examples/stm32f3348_disco).
+//!
+//! If the user also needs to read data from the ST7735, then [Read] and
+//! [ReadBits] should also be implemented. Presumably **no** high performance is
+//! needed because reading is mostly for debugging purposes; the dummy bit
+//! when reading 24- or 32-bit data is quite annoying (not totally impossible
+//! to implement with hardware SPI but quite challenging); and when reading,
+//! the clock must toggles slower than when writing (so the user needs to
+//! reconfigure the SPI anyway). Therefore, it is
+//! recommended that the user simply implements [ReadBits::read_bits()] with
+//! bit-bangs.
+//!
+//! # Performance Consideration
+//!
+//! The reason to allow the user to implement [WriteU8] and [WriteU8s]
+//! separately is for better performance. While it is natural to think
+//! [WriteU8s] as a looped version of [WriteU8], there can be quite some
+//! latency and throughput differences. Eg., in a STM32 microcontroller,
+//! A loop-based [WriteU8s] is suboptimal for the following reasons:
+//! * As soon as a byte is started to be sent, the user can already write
+//!   the next byte to SPI's TX FIFO buffer. But [WriteU8::write_u8()] finishes
+//!   only after the previous byte is fully sent to the device.
+//! * DMA (direct memory access) is also very beneficial for [WriteU8s],
+//!   especially when sending many bytes.
+//!
+//! So the user should only use an [`AdapterU8`] if they doesn't care
+//! about the performance difference here.
+//!
+//! # Why Not `async fn` in Traits
+//!
+//! The traits here are plain Rust, with no attribute macro: each names its
+//! future as an associated type, and the lifetime parameter of the trait
+//! ties the future to the borrow of `self`. That parameter is how such a
+//! borrow was spelled before generic associated types (GATs), hence the
+//! bounds like `for<'a> S: WriteU8<'a>`. Implementers write the future by
+//! hand or reuse one, e.g., [core::future::Ready] for a blocking bus, or the
+//! futures in [`adapters`].
+//!
+//! [`Commands`] keeps the futures of the backend inside its own named ones
+//! (see [`CommandFuture`]), so that callers can store them in `static`s and
+//! hand-written state machines without allocating. The futures of a native
+//! `async fn` in a trait can't be named on stable Rust, which would break
+//! that.
+//!
+//! The migration thus goes through GATs first, with the traits of [`gat`],
+//! e.g., `type WriteU8Done<'a>` with
+//! `fn write_u8(&mut self, data: u8) -> Self::WriteU8Done<'_>`, which keep
+//! the futures nameable and drop the trait lifetime:
+//! 1. Now, a backend implements either family. Wrapped in a [gat::Compat],
+//!    an implementation of [gat::WriteU8], [gat::WriteU8s] or [gat::Read]
+//!    implements [WriteU8], [WriteU8s] or [Read] and [ReadBits]
+//!    respectively, so it works with [`Commands`] and every adapter
+//!    unchanged.
+//! 2. In the next breaking release, the bounds of [`Commands`] and of the
+//!    adapters move to the [`gat`] traits, which take the names here, and
+//!    a wrapper goes the other way for the remaining implementations of the
+//!    lifetime-parameterized traits.
+//! 3. `async fn` follows once its futures can be named.
+//!
+//! [`Commands`]: ../struct.Commands.html
+//! [`adapter`]: ../adapters/index.html
+//! [`AdapterU8`]: ../adapters/struct.AdapterU8.html
+//! [`adapters`]: ../adapters/index.html
+//! [`CommandFuture`]: ../struct.CommandFuture.html
+//! [`gat`]: gat
+
+use core::future::Future;
+use core::marker::PhantomData;
+use core::pin::Pin;
+use core::task::{Context, Poll};
+
+pub mod gat;
+
+/// Defines how the `DCX` pin operates.
+///
+/// The implementation should own the pin exclusively, i.e., not share it with
+/// any other handle. Then only [Commands](crate::Commands) and the
+/// [RamWriter](crate::RamWriter) borrowing it can toggle the pin, and no data
+/// phase can be interrupted by a stray command mode.
+pub trait DcxPin {
+    /// Toggles the DCX pin to the `command mode` (LOW value).
+    fn set_dcx_command_mode(&mut self);
+    /// Toggles the DCX pin to the `data mode` (HIGH value).
+    fn set_dcx_data_mode(&mut self);
+}
+
+/// Defines how a single [u8] is written with the `SCK` and `SDA` pins.
+///
+/// Common MCUs' SPI peripheral can be used, with
+/// CPOL=1, CPHA=1 and MSB-first. Notice the timing requirement from ST7735's
+/// datasheet. Most important ones:
+/// * `SCK` low duration and high durations are at least 15ns long.
+/// * `SCK` period is at least 66ns long.
+pub trait WriteU8<'a> {
+    type WriteU8Done : 'a + Future<Output=()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done;
+}
+
+/// Defines how a sequence of `u8` or `u16` is written with the `SCK` and `SDA`
+/// pins.
+///
+/// Writing no data must write nothing, leave DCX alone and resolve on the
+/// first poll; the same goes for [WriteU8sStatic], [WriteU16s] and
+/// [WriteRepeat]. See [`conformance`] to check an implementation.
+///
+/// [`conformance`]: ../conformance/index.html
+pub trait WriteU8s<'a> {
+    type WriteU8sDone : 'a + Future<Output=()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone;
+
+    /// How many bytes `write_u8s()` prefers at most, e.g., the free room of a
+    /// small TX FIFO of a bit-bang implementation, which may change between
+    /// writes. Writers splitting long data into chunks, e.g.,
+    /// [RamWriter::write_chunked()](crate::RamWriter::write_chunked), ask it
+    /// before each chunk. Longer data must still be written correctly.
+    ///
+    /// No limit by default.
+    fn preferred_chunk(&self) -> usize { usize::MAX }
+}
+
+/// Defines how a sequence of `u8` living for the whole program, e.g., in flash,
+/// is written.
+///
+/// As `data` outlives any transfer, implementations can hand it straight to
+/// DMA without copying it into a buffer of their own. Otherwise, wrap a
+/// [WriteU8s] implementation with an [`AdapterStatic`].
+///
+/// [`AdapterStatic`]: ../adapters/struct.AdapterStatic.html
+pub trait WriteU8sStatic<'a> {
+    type WriteU8sStaticDone : 'a + Future<Output=()>;
+
+    fn write_u8s_static(&'a mut self, data: &'static [u8])
+        -> Self::WriteU8sStaticDone;
+}
+
+/// Defines how a sequence of `u16` is written, each high byte first, e.g., by
+/// DMA with 16-bit frames, so that RGB565 pixels held as `u16` go out without
+/// being copied; see
+/// [RamWriter::write_u16_slice()](crate::RamWriter::write_u16_slice).
+///
+/// Otherwise, wrap a [WriteU8s] implementation with an [`AdapterU16`].
+///
+/// [`AdapterU16`]: ../adapters/struct.AdapterU16.html
+pub trait WriteU16s<'a> {
+    type WriteU16sDone : 'a + Future<Output=()>;
+
+    fn write_u16s(&'a mut self, data: &'a [u16]) -> Self::WriteU16sDone;
+}
+
+/// Defines how the same sequence of `u8` is written `count` times in a row,
+/// e.g., to fill an area with a single color.
+///
+/// Hardware with circular DMA or reloadable TX FIFOs can implement it to
+/// write at the full bus speed. Otherwise, wrap a [WriteU8s] implementation
+/// with an [`AdapterRepeat`] for a loop-based implementation.
+///
+/// [`AdapterRepeat`]: ../adapters/struct.AdapterRepeat.html
+pub trait WriteRepeat<'a> {
+    type WriteRepeatDone : 'a + Future<Output=()>;
+
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+        -> Self::WriteRepeatDone;
+}
+
+/// Defines how to wait until every byte written has left the MCU, for an
+/// implementation whose write futures finish as soon as the bytes are handed
+/// over, e.g., to a TX FIFO or to a DMA. Then toggling DCX right away would
+/// apply to the bytes still in flight.
+///
+/// Optional: wrap such an implementation with a [`Barrier`], which flushes
+/// before each DCX mode change.
+///
+/// [`Barrier`]: ../adapters/struct.Barrier.html
+pub trait WriteBarrier<'a> {
+    type FlushDone : 'a + Future<Output=()>;
+
+    fn flush(&'a mut self) -> Self::FlushDone;
+}
+
+/// Defines a transmit queue, which accepts bytes without waiting for them to
+/// be sent, e.g., a ring buffer drained by DMA or by the TX interrupt.
+///
+/// Needed by the [`pipeline`] module only. Each queued byte keeps the DCX mode
+/// set when it was queued, i.e., the implementation applies [DcxPin] to the
+/// pin when the byte is actually sent.
+///
+/// [`pipeline`]: ../pipeline/index.html
+pub trait WriteQueue: DcxPin {
+    /// Identifies a queued byte, e.g., its position in the whole stream.
+    type Ticket: Copy;
+
+    /// How many bytes the queue holds at most.
+    fn queue_depth(&self) -> usize;
+
+    /// Copies the whole `data` into the queue once there is room for it, and
+    /// returns the ticket of its last byte. `data` is non-empty and no longer
+    /// than [queue_depth()](Self::queue_depth).
+    fn poll_enqueue(&mut self, cx: &mut Context<'_>, data: &[u8])
+        -> Poll<Self::Ticket>;
+
+    /// Ready once the byte of `ticket`, and so every byte before it, is sent.
+    fn poll_sent(&mut self, cx: &mut Context<'_>, ticket: Self::Ticket)
+        -> Poll<()>;
+}
+
+/// Defines how to switch the bus speed around reads, as reading requires a
+/// slower `SCK` than writing (see [ReadBits]).
+///
+/// Optional: wrap an implementation of both this and [Read] with
+/// [SpeedSwitching](crate::adapters::SpeedSwitching), which calls
+/// `enter_read_speed()` right after the command byte of a read command is
+/// written, and `enter_write_speed()` after the reading is done. With these,
+/// reads with hardware SPI become feasible. Both do nothing by default.
+pub trait BusSpeedControl {
+    fn enter_read_speed(&mut self) {}
+    fn enter_write_speed(&mut self) {}
+}
+
+/// Defines how the MCU should use the `SCK` and `SDA` pins to read data.
+///
+/// It is assumed the reading isn't super important (mostly for debugging
+/// purposes); and the implementation is actually quite hard because some
+/// commands read without a dummy bit and some read with a dummy bit.
+/// So the user can choose to simply **not** implement it. The write commands
+/// of [Commands](crate::Commands) will still work in that case.
+///
+/// Calling `start_reading()` should switch the device into reading mode,
+/// which should be switched back into writing mode when the returned object
+/// of `start_reading()` is dropped.
+///
+/// The bus speed can be adjusted around reads with [BusSpeedControl].
+pub trait Read<'a> {
+    type ReadBitsType : 'a + for<'b> ReadBits<'b>;
+
+    fn start_reading(&'a mut self) -> Self::ReadBitsType;
+}
+
+/// Defines how the helper RAII variable returned by [Read::start_reading()]
+/// should behave.
+///
+/// Notice the timing requirement from ST7735's datasheet. Most important ones:
+/// * `SCK` low duration and high durations are at least 60ns long.
+/// * `SCK` period is at least 150ns long.
+pub trait ReadBits<'a> {
+    type ReadBitsDone : 'a + Future<Output=u32>;
+
+    fn read_bits(&'a mut self, num_bits: usize) -> Self::ReadBitsDone;
+}
+
+/// Bit-bangs a read of `num_bits` (at most 32), the first bit read ending up
+/// as the highest one, e.g., to implement [ReadBits::read_bits()] with a few
+/// closures over the pins of a board:
+/// * `set_sck` drives `SCK`, `true` being high;
+/// * `get_sda` samples `SDA`, done at the end of the low half period;
+/// * `low_delay` and `high_delay` wait for the low and the high half periods,
+///   each at least 60ns and together at least 150ns.
+///
+/// `SCK` is expected high before and is left high after. The dummy clock
+/// cycle of the 24- and 32-bit reads is simply a 1-bit read, see
+/// [ReadBytes::read_bytes()].
+pub fn bitbang_read(num_bits: usize, mut set_sck: impl FnMut(bool),
+                    mut get_sda: impl FnMut() -> bool,
+                    mut low_delay: impl FnMut(), mut high_delay: impl FnMut())
+        -> u32 {
+    let mut v = 0u32;
+    for _ in 0..num_bits {
+        set_sck(false);
+        low_delay();
+        let bit = get_sda();
+        set_sck(true);
+        high_delay();
+        v = v << 1 | bit as u32;
+    }
+    v
+}
+
+/// Reads whole bytes, for reads longer than the 32 bits of
+/// [ReadBits::read_bits()], e.g., several pixels of RAMRD. Implemented for
+/// every [ReadBits], reading up to 4 bytes per
+/// [read_bits()](ReadBits::read_bits).
+pub trait ReadBytes<'a> {
+    type ReadBytesDone : 'a + Future<Output=()>;
+
+    /// Fills `out`, the first bit read being the most significant one of
+    /// `out[0]`. If `skip_dummy`, the dummy clock cycle of the 24- and 32-bit
+    /// reads is skipped first. Nothing is read if `out` is empty.
+    fn read_bytes(&'a mut self, out: &'a mut [u8], skip_dummy: bool)
+        -> Self::ReadBytesDone;
+}
+
+impl<'a, R: 'a + for<'b> ReadBits<'b>> ReadBytes<'a> for R {
+    type ReadBytesDone = ReadBytesFuture<'a, R>;
+
+    fn read_bytes(&'a mut self, out: &'a mut [u8], skip_dummy: bool)
+            -> Self::ReadBytesDone {
+        ReadBytesFuture{r: self, out, pos: 0, dummy: skip_dummy,
+                        current: None, _r: PhantomData}
+    }
+}
+
+/// Internal details of [ReadBytes::read_bytes()].
+pub struct ReadBytesFuture<'a, R: for<'b> ReadBits<'b>> {
+    // Borrowed by `current` while it is `Some`.
+    r: *mut R,
+    out: &'a mut [u8],
+    pos: usize,
+    dummy: bool,
+    // The pending read and how many bytes it reads.
+    current: Option<(<R as ReadBits<'a>>::ReadBitsDone, usize)>,
+    _r: PhantomData<&'a mut R>,
+}
+
+impl<'a, R: 'a + for<'b> ReadBits<'b>> Future for ReadBytesFuture<'a, R> {
+    type Output = ();
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        // Safety: Only `Self::current` needs pinning. The implementation
+        // below indeed never moves it, only creates and drops.
+        let rb = unsafe {self.get_unchecked_mut()};
+        loop {
+            if rb.current.is_none() {
+                let len = (rb.out.len() - rb.pos).min(4);
+                if len == 0 { return Poll::Ready(()); }
+                let (num_bits, len) =
+                    if rb.dummy {(1, 0)} else {(len * 8, len)};
+                // Safety: `current` is `None`.
+                let r: &'a mut R = unsafe {&mut *rb.r};
+                rb.current = Some((r.read_bits(num_bits), len));
+            }
+            let (v, len) = match &mut rb.current {
+                Some((done, len)) => {
+                    // Safety: Pinning a field of a pinned.
+                    let done = unsafe {Pin::new_unchecked(done)};
+                    match done.poll(cx) {
+                        Poll::Ready(v) => (v, *len),
+                        Poll::Pending => return Poll::Pending,
+                    }
+                },
+                None => unsafe {core::hint::unreachable_unchecked()},
+            };
+            rb.current = None;
+            rb.dummy = false;
+            for (i, b) in rb.out[rb.pos..rb.pos + len].iter_mut().enumerate() {
+                *b = (v >> (8 * (len - 1 - i))) as u8;
+            }
+            rb.pos += len;
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use core::marker::PhantomData;
+    use core::pin::Pin;
+    use core::task::{Context, Poll};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    struct FutureDummy1<'a, T, R = ()> {
+        _t: &'a T,
+        _r: PhantomData<R>,
+    }
+    impl<'a, T, R> FutureDummy1<'a, T, R> {
+        pub fn new(t: &'a T) -> Self { Self{_t: t, _r: Default::default()} }
+    }
+    impl<'a, T, R: Default> Future for FutureDummy1<'a, T, R> {
+        type Output = R;
+
+        fn poll(self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<R> {
+            Poll::Ready(Default::default())
+        }
+    }
+
+    #[derive(Default)]
+    struct Dummy1 { u: usize, i: isize }
+
+    impl<'a> WriteU8<'a> for Dummy1 {
+        type WriteU8Done = FutureDummy1<'a, usize>;
+
+        fn write_u8(&'a mut self, _data: u8) -> Self::WriteU8Done {
+            FutureDummy1::new(&self.u)
+        }
+    }
+
+    impl<'a> WriteU8s<'a> for Dummy1 {
+        type WriteU8sDone = FutureDummy1<'a, isize>;
+
+        fn write_u8s(&'a mut self, _data: &'a [u8]) -> Self::WriteU8sDone {
+            FutureDummy1::new(&self.i)
+        }
+    }
+
+    #[test]
+    fn write_u8() {
+        let mut dummy: Dummy1 = Default::default();
+        drop(async { dummy.write_u8(10).await; });
+    }
+
+    #[test]
+    fn write_u8_slice() {
+        let mut dummy: Dummy1 = Default::default();
+        let items: [u8; 3] = [0, 1, 2];
+        drop(async { dummy.write_u8s(&items).await; });
+    }
+
+    #[derive(Default)]
+    struct Dummy2 { i: i64 }
+    struct Dummy2Reader<'a> { d: &'a mut Dummy2 }
+
+    impl<'a> Read<'a> for Dummy2 {
+        type ReadBitsType = Dummy2Reader<'a>;
+
+        fn start_reading(&'a mut self) -> Self::ReadBitsType {
+            Dummy2Reader{d: self}
+        }
+    }
+
+    impl<'a, 'b> ReadBits<'b> for Dummy2Reader<'a> {
+        type ReadBitsDone = FutureDummy1<'b, i64, u32>;
+
+        fn read_bits(&'b mut self, _num_bits: usize) -> Self::ReadBitsDone {
+            FutureDummy1::new(&self.d.i)
+        }
+    }
+
+    #[test]
+    fn read_bytes() {
+        use std::vec::Vec;
+        // Reads the bits of `stream`, from the most significant one.
+        struct Stream { stream: u64, left: usize, reads: Vec<usize> }
+        impl<'a> ReadBits<'a> for Stream {
+            type ReadBitsDone = core::future::Ready<u32>;
+            fn read_bits(&'a mut self, num_bits: usize) -> Self::ReadBitsDone {
+                self.reads.push(num_bits);
+                self.left -= num_bits;
+                let v = self.stream >> self.left & ((1 << num_bits) - 1);
+                core::future::ready(v as u32)
+            }
+        }
+
+        // The dummy bit, then the bytes.
+        let mut r = Stream{stream: 1 << 56 | 0x12_3456_789A_BCDE, left: 57,
+                           reads: Vec::new()};
+        let mut out = [0; 7];
+        block_on(r.read_bytes(&mut out, true));
+        assert_eq!(out, [0x12, 0x34, 0x56, 0x78, 0x9A, 0xBC, 0xDE]);
+        assert_eq!(r.reads, [1, 32, 24]);
+
+        block_on(r.read_bytes(&mut [], true));
+        assert_eq!(r.reads, [1, 32, 24]);
+    }
+
+    #[test]
+    fn bitbang() {
+        use std::{cell::RefCell, vec::Vec};
+        // The LCD shifts out the next bit of `script` on each falling edge.
+        #[derive(Default)]
+        struct Pins { script: Vec<bool>, pos: usize, sck: bool,
+                      log: Vec<&'static str> }
+        struct BitBang<'p>(&'p RefCell<Pins>);
+        impl<'a, 'p> ReadBits<'a> for BitBang<'p> {
+            type ReadBitsDone = core::future::Ready<u32>;
+            fn read_bits(&'a mut self, num_bits: usize) -> Self::ReadBitsDone {
+                let pins = self.0;
+                core::future::ready(bitbang_read(
+                    num_bits,
+                    |high| {
+                        let mut p = pins.borrow_mut();
+                        if p.sck && !high { p.pos += 1; }
+                        p.sck = high;
+                        p.log.push(if high { "H" } else { "L" });
+                    },
+                    || {
+                        let mut p = pins.borrow_mut();
+                        assert!(!p.sck, "Sampled while SCK is high.");
+                        p.log.push("S");
+                        p.script[p.pos - 1]
+                    },
+                    || pins.borrow_mut().log.push("l"),
+                    || pins.borrow_mut().log.push("h")))
+            }
+        }
+
+        let bits =
+            |v: u32, n: usize| (0..n).rev().map(move |i| v >> i & 1 != 0);
+        let pins = RefCell::new(Pins{sck: true, ..Default::default()});
+        let mut r = BitBang(&pins);
+        pins.borrow_mut().script.extend(bits(0b1011, 4));
+        assert_eq!(block_on(r.read_bits(4)), 0b1011);
+        assert_eq!(pins.borrow().log.concat(), "LlSHh".repeat(4));
+        assert_eq!(block_on(r.read_bits(0)), 0);
+        assert!(pins.borrow().sck);
+
+        // A set dummy bit, then 3 bytes.
+        pins.borrow_mut().script.push(true);
+        pins.borrow_mut().script.extend(bits(0x7C_89F0, 24));
+        let mut out = [0; 3];
+        block_on(r.read_bytes(&mut out, true));
+        assert_eq!(out, [0x7C, 0x89, 0xF0]);
+        assert_eq!(pins.borrow().pos, pins.borrow().script.len());
+    }
+
+    #[test]
+    fn read_bits() {
+        let mut dummy: Dummy2 = Default::default();
+        drop(async {
+            let mut r = dummy.start_reading();
+            r.read_bits(12).await
+        });
+    }
+}
@@ -0,0 +1,220 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! The SPI traits with generic associated types (GATs): the futures still
+//! have names, but borrow `self` through the lifetime parameter of their
+//! type instead of the one of the trait, so implementations need neither
+//! the trait lifetime nor the higher-ranked bounds.
+//!
+//! They are the first step of the migration described in the [parent
+//! module](super): wrapped in a [Compat], an implementation of them
+//! implements the lifetime-parameterized counterparts, e.g., [WriteU8]
+//! becomes [super::WriteU8] for every lifetime, so that it works with
+//! [Commands] and every adapter as they are. The implementing type has to
+//! outlive the bounds like `for<'a> S: super::WriteU8<'a>`, i.e., be
+//! `'static`, e.g., own its peripherals.
+//!
+//! ```
+//! use core::future::{Ready, ready};
+//! use st7735_async_low_core::spi::{DcxPin, gat};
+//! use st7735_async_low_core::Commands;
+//!
+//! struct Spi;  // Owns the peripheral.
+//!
+//! impl DcxPin for Spi {
+//!     fn set_dcx_command_mode(&mut self) {}
+//!     fn set_dcx_data_mode(&mut self) {}
+//! }
+//! impl gat::WriteU8 for Spi {
+//!     type WriteU8Done<'a> = Ready<()>;
+//!     fn write_u8(&mut self, _data: u8) -> Ready<()> { ready(()) }
+//! }
+//! impl gat::WriteU8s for Spi {
+//!     type WriteU8sDone<'a> = Ready<()>;
+//!     fn write_u8s(&mut self, _data: &[u8]) -> Ready<()> { ready(()) }
+//! }
+//!
+//! let mut commands = Commands::new(gat::Compat::new(Spi));
+//! drop(commands.dispon());
+//! ```
+//!
+//! [Commands]: crate::Commands
+
+use core::future::Future;
+
+use super::{BusSpeedControl, DcxPin};
+
+/// Same as [super::WriteU8], with the future borrowing `self` for the
+/// lifetime of its type.
+pub trait WriteU8 {
+    type WriteU8Done<'a> : 'a + Future<Output=()> where Self: 'a;
+
+    fn write_u8(&mut self, data: u8) -> Self::WriteU8Done<'_>;
+}
+
+/// Same as [super::WriteU8s], with the future borrowing `self` and `data` for
+/// the lifetime of its type.
+pub trait WriteU8s {
+    type WriteU8sDone<'a> : 'a + Future<Output=()> where Self: 'a;
+
+    fn write_u8s<'a>(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone<'a>;
+
+    /// See [super::WriteU8s::preferred_chunk()].
+    fn preferred_chunk(&self) -> usize { usize::MAX }
+}
+
+/// Same as [super::Read] and [super::ReadBits] in one: the reading mode is
+/// entered by [start_reading()](Self::start_reading) and left by
+/// [finish_reading()](Self::finish_reading), called when the reader of
+/// [Compat] is dropped.
+pub trait Read {
+    type ReadBitsDone<'a> : 'a + Future<Output=u32> where Self: 'a;
+
+    fn start_reading(&mut self);
+    /// See [super::ReadBits::read_bits()].
+    fn read_bits(&mut self, num_bits: usize) -> Self::ReadBitsDone<'_>;
+    fn finish_reading(&mut self);
+}
+
+/// Implements the lifetime-parameterized traits of the [parent
+/// module](super) for an implementation `T` of those here, passing
+/// [DcxPin] and [BusSpeedControl] through.
+#[derive(Debug, Default)]
+pub struct Compat<T> {
+    t: T,
+}
+
+impl<T> Compat<T> {
+    pub fn new(t: T) -> Self { Self{t} }
+
+    pub fn inner(&mut self) -> &mut T { &mut self.t }
+    pub fn into_inner(self) -> T { self.t }
+}
+
+impl<T: DcxPin> DcxPin for Compat<T> {
+    fn set_dcx_command_mode(&mut self) { self.t.set_dcx_command_mode(); }
+    fn set_dcx_data_mode(&mut self) { self.t.set_dcx_data_mode(); }
+}
+
+impl<T: BusSpeedControl> BusSpeedControl for Compat<T> {
+    fn enter_read_speed(&mut self) { self.t.enter_read_speed(); }
+    fn enter_write_speed(&mut self) { self.t.enter_write_speed(); }
+}
+
+impl<'a, T: 'a + WriteU8> super::WriteU8<'a> for Compat<T> {
+    type WriteU8Done = <T as WriteU8>::WriteU8Done<'a>;
+
+    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
+        self.t.write_u8(data)
+    }
+}
+
+impl<'a, T: 'a + WriteU8s> super::WriteU8s<'a> for Compat<T> {
+    type WriteU8sDone = <T as WriteU8s>::WriteU8sDone<'a>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
+        self.t.write_u8s(data)
+    }
+    fn preferred_chunk(&self) -> usize { self.t.preferred_chunk() }
+}
+
+// `'static`, as the reader has to read bits for every lifetime.
+impl<'a, T: 'static + Read> super::Read<'a> for Compat<T> {
+    type ReadBitsType = Reader<'a, T>;
+
+    fn start_reading(&'a mut self) -> Reader<'a, T> {
+        self.t.start_reading();
+        Reader{r: &mut self.t}
+    }
+}
+
+/// The reader of [Compat] for a [Read], leaving the reading mode when
+/// dropped.
+pub struct Reader<'a, T: Read> {
+    r: &'a mut T,
+}
+
+impl<'b, T: 'b + Read> super::ReadBits<'b> for Reader<'_, T> {
+    type ReadBitsDone = <T as Read>::ReadBitsDone<'b>;
+
+    fn read_bits(&'b mut self, num_bits: usize) -> Self::ReadBitsDone {
+        self.r.read_bits(num_bits)
+    }
+}
+
+impl<T: Read> Drop for Reader<'_, T> {
+    fn drop(&mut self) { self.r.finish_reading(); }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::future::{Ready, ready};
+    use std::vec::Vec;
+
+    use crate::commands::Commands;
+    use crate::testing_device::block_on;
+    use super::*;
+
+    // Records the bytes written, `true` for the data mode, and answers
+    // every read with 0x7C.
+    #[derive(Default)]
+    struct Log {
+        data_mode: bool,
+        written: Vec<(bool, u8)>,
+        reading: bool,
+    }
+
+    impl DcxPin for Log {
+        fn set_dcx_command_mode(&mut self) { self.data_mode = false; }
+        fn set_dcx_data_mode(&mut self) { self.data_mode = true; }
+    }
+    impl WriteU8 for Log {
+        type WriteU8Done<'a> = Ready<()>;
+        fn write_u8(&mut self, data: u8) -> Ready<()> {
+            self.written.push((self.data_mode, data));
+            ready(())
+        }
+    }
+    impl WriteU8s for Log {
+        type WriteU8sDone<'a> = Ready<()>;
+        fn write_u8s(&mut self, data: &[u8]) -> Ready<()> {
+            let mode = self.data_mode;
+            self.written.extend(data.iter().map(|&b| (mode, b)));
+            ready(())
+        }
+    }
+    impl Read for Log {
+        type ReadBitsDone<'a> = Ready<u32>;
+        fn start_reading(&mut self) { self.reading = true; }
+        fn read_bits(&mut self, num_bits: usize) -> Ready<u32> {
+            assert!(self.reading && num_bits == 8);
+            ready(0x7C)
+        }
+        fn finish_reading(&mut self) { self.reading = false; }
+    }
+
+    #[test]
+    fn drives_commands() {
+        let mut commands = Commands::new(Compat::new(Log::default()));
+        block_on(async {
+            commands.caset(1, 2).await;
+            assert_eq!(commands.rdid1().await, 0x7C);
+        });
+        let log = commands.into_inner().into_inner();
+        assert!(!log.reading);
+        assert_eq!(log.written, [
+            (false, 0x2A), (true, 0), (true, 1), (true, 0), (true, 2),
+            (false, 0xDA)]);
+    }
+}  // mod tests
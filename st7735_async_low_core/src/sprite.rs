@@ -0,0 +1,146 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Small images drawn over what is already on the screen, e.g., cursors and
+//! icons, see [Display::draw_sprite()].
+//!
+//! The LCD has no alpha channel, so a transparent color is implemented by
+//! not writing those pixels: each row is split into its [opaque runs], each
+//! written into its own 1-pixel-high window.
+//!
+//! [Display::draw_sprite()]: crate::display::Display::draw_sprite
+//! [opaque runs]: opaque_runs
+
+use core::ops::Range;
+
+use crate::panel::{Panel, Rect};
+
+/// An image of `P` pixels, row-major from the top-left corner.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub struct Sprite<'a, P> {
+    width: u16,
+    height: u16,
+    pixels: &'a [P],
+}
+
+impl<'a, P> Sprite<'a, P> {
+    /// # Panics
+    ///
+    /// If the length of `pixels` is not `width * height`.
+    pub fn new(width: u16, height: u16, pixels: &'a [P]) -> Self {
+        assert_eq!(pixels.len(), width as usize * height as usize);
+        Self{width, height, pixels}
+    }
+
+    pub fn width(&self) -> u16 { self.width }
+    pub fn height(&self) -> u16 { self.height }
+
+    /// The pixels of row `y`.
+    pub fn row(&self, y: u16) -> &'a [P] {
+        let w = self.width as usize;
+        &self.pixels[y as usize * w..][..w]
+    }
+
+    /// The part visible when drawn at `(x, y)` of `panel`, possibly outside:
+    /// the rectangle on the panel, and the one in the sprite. `None` if
+    /// nothing is visible.
+    pub fn clip(&self, x: i32, y: i32, panel: &Panel) -> Option<(Rect, Rect)> {
+        let columns = clip_1d(x, self.width, panel.width())?;
+        let rows = clip_1d(y, self.height, panel.height())?;
+        let len = |r: &Range<u16>| r.end - r.start;
+        let on_panel = Rect::new((x + columns.start as i32) as u16,
+                                 (y + rows.start as i32) as u16,
+                                 len(&columns), len(&rows));
+        let in_sprite = Rect::new(columns.start, rows.start, len(&columns),
+                                  len(&rows));
+        Some((on_panel, in_sprite))
+    }
+}
+
+// The part of `0..len`, at `at` of `0..limit`, inside the latter. In `i64`
+// so that no `at` overflows.
+fn clip_1d(at: i32, len: u16, limit: u16) -> Option<Range<u16>> {
+    let at = at as i64;
+    let start = (-at).clamp(0, len as i64);
+    let end = (limit as i64 - at).clamp(0, len as i64);
+    (start < end).then_some(start as u16..end as u16)
+}
+
+/// The ranges of the pixels of `row` other than `transparent`, in order.
+/// The whole row if `transparent` is `None`.
+pub fn opaque_runs<P: PartialEq>(row: &[P], transparent: Option<P>)
+        -> OpaqueRuns<'_, P> {
+    OpaqueRuns{row, transparent, pos: 0}
+}
+
+/// The iterator of [opaque_runs()].
+#[derive(Clone, Debug)]
+pub struct OpaqueRuns<'a, P> {
+    row: &'a [P],
+    transparent: Option<P>,
+    pos: usize,
+}
+
+impl<P: PartialEq> Iterator for OpaqueRuns<'_, P> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        let is_opaque = |p: &P| Some(p) != self.transparent.as_ref();
+        let rest = &self.row[self.pos..];
+        let start = self.pos + rest.iter().position(is_opaque)?;
+        let len = self.row[start..].iter().position(|p| !is_opaque(p))
+            .unwrap_or(self.row.len() - start);
+        self.pos = start + len;
+        Some(start..start + len)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use super::*;
+
+    #[test]
+    fn runs() {
+        let runs = |row: &[u8], t| opaque_runs(row, t).collect::<Vec<_>>();
+        assert_eq!(runs(&[0, 1, 1, 0, 0, 2, 0, 3], Some(0)),
+                   [1..3, 5..6, 7..8]);
+        assert_eq!(runs(&[1, 0, 1], Some(0)), [0..1, 2..3]);
+        assert!(runs(&[0, 0], Some(0)).is_empty());
+        let mut whole = opaque_runs(&[0, 0], None);
+        assert_eq!((whole.next(), whole.next()), (Some(0..2), None));
+        assert!(runs(&[], None).is_empty());
+    }
+
+    #[test]
+    fn clip() {
+        let pixels = [0u8; 12];
+        let s = Sprite::new(4, 3, &pixels);
+        let panel = Panel::new(10, 8);
+        assert_eq!(s.clip(2, 1, &panel),
+                   Some((Rect::new(2, 1, 4, 3), Rect::new(0, 0, 4, 3))));
+        assert_eq!(s.clip(-1, -2, &panel),
+                   Some((Rect::new(0, 0, 3, 1), Rect::new(1, 2, 3, 1))));
+        assert_eq!(s.clip(8, 6, &panel),
+                   Some((Rect::new(8, 6, 2, 2), Rect::new(0, 0, 2, 2))));
+        assert_eq!(s.clip(10, 0, &panel), None);
+        assert_eq!(s.clip(0, -3, &panel), None);
+        assert_eq!(s.clip(i32::MIN, 0, &panel), None);
+        assert_eq!(s.clip(0, i32::MIN, &panel), None);
+        assert_eq!(s.clip(i32::MAX, 0, &panel), None);
+        assert_eq!(s.clip(i32::MIN, i32::MAX, &panel), None);
+        assert_eq!(s.row(1).len(), 4);
+    }
+}  // mod tests
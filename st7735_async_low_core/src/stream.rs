@@ -0,0 +1,245 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Pixel bytes produced chunk by chunk, whatever they come from: a buffer,
+//! an iterator of pixels, runs of a color or a closure. See
+//! [RamWriter::write_stream()](crate::RamWriter::write_stream).
+//!
+//! Unlike [PixelSource](crate::color::PixelSource), which is addressed by
+//! position, a [PixelStream] is read once, in the order of the memory
+//! writes.
+//!
+//! The typed `write_*` methods of [RamWriter](crate::RamWriter) stream their
+//! pixels this way. Those taking bytes already in memory, e.g.,
+//! `write_chunked()` or `write_static()`, hand them to the backend as they
+//! are instead, without copying them through a chunk.
+
+use crate::color::{Pixel, Rgb444};
+
+/// The pixel bytes to be written, in the sending order.
+pub trait PixelStream {
+    /// Fills the beginning of `out` with the next bytes, returning how many.
+    /// Returns `0` only once exhausted, or if `out` is empty.
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize;
+}
+
+impl PixelStream for &[u8] {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize {
+        let n = out.len().min(self.len());
+        let (chunk, rest) = self.split_at(n);
+        out[..n].copy_from_slice(chunk);
+        *self = rest;
+        n
+    }
+}
+
+/// The bytes of the pixels of an iterator. See [pixels()].
+#[derive(Clone, Debug)]
+pub struct Pixels<P, I> {
+    pixels: I,
+    // The pixel that didn't fit in the previous chunk, and how many of its
+    // bytes did.
+    pending: Option<(P, usize)>,
+}
+
+/// Streams `pixels`, e.g., a slice of [Rgb565](crate::color::Rgb565) with
+/// `.iter().copied()`.
+pub fn pixels<P, I>(pixels: I) -> Pixels<P, I::IntoIter>
+        where P: Pixel, I: IntoIterator<Item=P> {
+    Pixels{pixels: pixels.into_iter(), pending: None}
+}
+
+impl<P: Pixel, I: Iterator<Item=P>> PixelStream for Pixels<P, I> {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < out.len() {
+            let (pixel, start) = match self.pending.take() {
+                Some(pending) => pending,
+                None => match self.pixels.next() {
+                    Some(pixel) => (pixel, 0),
+                    None => break,
+                },
+            };
+            let bytes = pixel.to_bytes();
+            let src = &bytes.as_ref()[start..];
+            let m = src.len().min(out.len() - n);
+            out[n..n + m].copy_from_slice(&src[..m]);
+            n += m;
+            if m < src.len() {
+                self.pending = Some((pixel, start + m));
+            }
+        }
+        n
+    }
+}
+
+/// Runs of a pixel repeated, e.g., a run-length encoded image. See [runs()].
+#[derive(Clone, Debug)]
+pub struct Runs<P, I> {
+    runs: Pixels<P, RunPixels<P, I>>,
+}
+
+/// Streams `runs`, each being a count and the pixel repeated that many
+/// times.
+pub fn runs<P, I>(runs: I) -> Runs<P, I::IntoIter>
+        where P: Pixel, I: IntoIterator<Item=(usize, P)> {
+    Runs{runs: pixels(RunPixels{runs: runs.into_iter(), current: None})}
+}
+
+impl<P: Pixel, I: Iterator<Item=(usize, P)>> PixelStream for Runs<P, I> {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize {
+        self.runs.next_chunk(out)
+    }
+}
+
+/// The pixels of [Runs], one by one.
+#[derive(Clone, Debug)]
+struct RunPixels<P, I> {
+    runs: I,
+    current: Option<(usize, P)>,
+}
+
+impl<P: Pixel, I: Iterator<Item=(usize, P)>> Iterator for RunPixels<P, I> {
+    type Item = P;
+
+    fn next(&mut self) -> Option<P> {
+        loop {
+            match self.current {
+                Some((n, pixel)) if n > 0 => {
+                    self.current = Some((n - 1, pixel));
+                    return Some(pixel);
+                },
+                _ => self.current = Some(self.runs.next()?),
+            }
+        }
+    }
+}
+
+/// Colors in the [R4G4B4](crate::Colmod::R4G4B4) format, every two of
+/// them packed into 3 bytes. See [rgb444()].
+#[derive(Clone, Debug)]
+pub struct Rgb444s<I> {
+    pixels: I,
+    // The packed bytes of the last pair, and the next of them to write.
+    packed: [u8; 3],
+    next: usize,
+    len: usize,
+}
+
+/// Streams `pixels` packed in pairs, the last one alone into 2 bytes.
+pub fn rgb444<I: IntoIterator<Item=Rgb444>>(pixels: I) -> Rgb444s<I::IntoIter> {
+    Rgb444s{pixels: pixels.into_iter(), packed: [0; 3], next: 0, len: 0}
+}
+
+impl<I: Iterator<Item=Rgb444>> PixelStream for Rgb444s<I> {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize {
+        let mut n = 0;
+        while n < out.len() {
+            if self.next == self.len {
+                let first = match self.pixels.next() {
+                    Some(first) => first,
+                    None => break,
+                };
+                let head = first.r() << 4 | first.g();
+                (self.packed, self.len) = match self.pixels.next() {
+                    Some(second) => ([head, first.b() << 4 | second.r(),
+                                      second.g() << 4 | second.b()], 3),
+                    None => ([head, first.b() << 4, 0], 2),
+                };
+                self.next = 0;
+            }
+            let m = (self.len - self.next).min(out.len() - n);
+            out[n..n + m]
+                .copy_from_slice(&self.packed[self.next..self.next + m]);
+            n += m;
+            self.next += m;
+        }
+        n
+    }
+}
+
+/// The bytes written by a closure. See [from_fn()].
+#[derive(Clone, Debug)]
+pub struct FromFn<F>(F);
+
+/// Streams what `f` writes into its argument, `f` returning how many bytes
+/// it wrote, as [PixelStream::next_chunk()] does.
+pub fn from_fn<F: FnMut(&mut [u8]) -> usize>(f: F) -> FromFn<F> { FromFn(f) }
+
+impl<F: FnMut(&mut [u8]) -> usize> PixelStream for FromFn<F> {
+    fn next_chunk(&mut self, out: &mut [u8]) -> usize { (self.0)(out) }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::vec::Vec;
+
+    use crate::color::{Rgb565, Rgb666};
+    use super::*;
+
+    // Reads `s` in chunks of `chunk` bytes.
+    fn collect<T: PixelStream>(mut s: T, chunk: usize) -> Vec<u8> {
+        let mut all = Vec::new();
+        let mut buffer = [0; 16];
+        loop {
+            let n = s.next_chunk(&mut buffer[..chunk]);
+            if n == 0 { return all; }
+            all.extend_from_slice(&buffer[..n]);
+        }
+    }
+
+    #[test]
+    fn slice() {
+        let data: &[u8] = &[1, 2, 3, 4, 5];
+        assert_eq!(collect(data, 2), [1, 2, 3, 4, 5]);
+    }
+
+    #[test]
+    fn pixels_split_across_chunks() {
+        let p = [Rgb666::new(1, 2, 3), Rgb666::new(4, 5, 6)];
+        let expected = [4, 8, 12, 16, 20, 24];
+        for chunk in 1..8 {
+            assert_eq!(collect(pixels(p.iter().copied()), chunk), expected);
+        }
+    }
+
+    #[test]
+    fn runs_of_pixels() {
+        let (a, b) = (Rgb565::from_raw(0x1234), Rgb565::from_raw(0xABCD));
+        assert_eq!(collect(runs([(2, a), (0, b), (1, b)]), 3),
+                   [0x12, 0x34, 0x12, 0x34, 0xAB, 0xCD]);
+    }
+
+    #[test]
+    fn rgb444_pairs() {
+        let p = [Rgb444::new(1, 2, 3), Rgb444::new(4, 5, 6),
+                 Rgb444::new(7, 8, 9)];
+        for chunk in 1..6 {
+            assert_eq!(collect(rgb444(p), chunk),
+                       [0x12, 0x34, 0x56, 0x78, 0x90]);
+        }
+        assert!(collect(rgb444([]), 4).is_empty());
+    }
+
+    #[test]
+    fn closure() {
+        let mut left = 5u8;
+        let s = from_fn(|out: &mut [u8]| {
+            let n = out.len().min(left as usize);
+            for b in &mut out[..n] { *b = left; left -= 1; }
+            n
+        });
+        assert_eq!(collect(s, 2), [5, 4, 3, 2, 1]);
+    }
+}  // mod tests
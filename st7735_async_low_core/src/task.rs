@@ -0,0 +1,234 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Executor-independent helpers for cooperative scheduling.
+
+use core::future::Future;
+use core::pin::{Pin, pin};
+use core::sync::atomic::{AtomicBool, Ordering};
+use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+/// Returns a future that is pending exactly once, so that a single-threaded
+/// executor gets the chance to run other tasks.
+pub fn yield_now() -> YieldNow { YieldNow{yielded: false} }
+
+/// The future returned by [yield_now()].
+#[derive(Debug)]
+pub struct YieldNow { yielded: bool }
+
+impl Future for YieldNow {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.yielded {
+            return Poll::Ready(());
+        }
+        self.yielded = true;
+        cx.waker().wake_by_ref();
+        Poll::Pending
+    }
+}
+
+/// How [block_on_with()] waits while its future is pending and not woken.
+///
+/// Closures work, e.g., `|| cortex_m::asm::wfe()` to sleep the core until
+/// the next interrupt (which is also what calls the waker of a driver).
+pub trait WaitStrategy {
+    /// Returns once the future may have been woken.
+    fn wait(&mut self);
+}
+
+impl<F: FnMut()> WaitStrategy for F {
+    fn wait(&mut self) { self() }
+}
+
+/// A [WaitStrategy] checking again right away.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct BusySpin;
+
+impl WaitStrategy for BusySpin {
+    fn wait(&mut self) { core::hint::spin_loop(); }
+}
+
+/// How many calls of [block_on_with()] can wait for their own wake-ups at
+/// the same time, nested or on other threads. The calls beyond poll their
+/// futures again without waiting.
+pub const MAX_BLOCKING_CALLS: usize = 8;
+
+// The wake-up flag of a call of `block_on_with()`, pointed to by the data
+// pointer of its wakers. Static, so that a waker outliving the call is
+// harmless: it merely wakes the next call using the slot spuriously.
+struct Slot {
+    taken: AtomicBool,
+    woken: AtomicBool,
+}
+
+impl Slot {
+    const fn free() -> Self {
+        Self{taken: AtomicBool::new(false), woken: AtomicBool::new(false)}
+    }
+}
+
+static SLOTS: [Slot; MAX_BLOCKING_CALLS] =
+    [const { Slot::free() }; MAX_BLOCKING_CALLS];
+
+static VTABLE: RawWakerVTable = RawWakerVTable::new(
+    |data| RawWaker::new(data, &VTABLE),
+    wake, wake, |_| {});
+
+// Safety: `data` points to the `woken` flag of a slot.
+unsafe fn wake(data: *const ()) {
+    (*data.cast::<AtomicBool>()).store(true, Ordering::Release);
+}
+
+// Frees its slot, even if the future panics.
+struct Taken(&'static Slot);
+
+impl Drop for Taken {
+    fn drop(&mut self) { self.0.taken.store(false, Ordering::Release); }
+}
+
+fn take_slot() -> Option<Taken> {
+    SLOTS.iter()
+        .find(|slot| slot.taken.compare_exchange(
+            false, true, Ordering::Acquire, Ordering::Relaxed).is_ok())
+        .map(Taken)
+}
+
+/// Runs `future` to completion on the current thread, polling it again only
+/// after it has been woken and calling `strategy` in between.
+///
+/// Each call has its own wake-up flag, so calls may be nested or run on
+/// several threads at once. Beyond [MAX_BLOCKING_CALLS] of them, the future
+/// is polled again right away instead.
+pub fn block_on_with<F: Future, W: WaitStrategy>(future: F, strategy: &mut W)
+        -> F::Output {
+    let mut future = pin!(future);
+    let slot = match take_slot() {
+        Some(slot) => slot,
+        None => {
+            let mut cx = Context::from_waker(Waker::noop());
+            loop {
+                if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                    return output;
+                }
+            }
+        },
+    };
+    let woken = &slot.0.woken;
+    // Safety: The data pointer is the static flag that `wake()` expects.
+    let waker = unsafe {Waker::from_raw(RawWaker::new(
+        woken as *const AtomicBool as *const (), &VTABLE))};
+    let mut cx = Context::from_waker(&waker);
+    woken.store(true, Ordering::Release);
+    loop {
+        // Cleared before polling, so that a wake-up during the poll is not
+        // lost.
+        if woken.swap(false, Ordering::AcqRel) {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        } else {
+            strategy.wait();
+        }
+    }
+}
+
+/// [block_on_with()] using [BusySpin].
+pub fn block_on<F: Future>(future: F) -> F::Output {
+    block_on_with(future, &mut BusySpin)
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::Waker;
+    use super::*;
+
+    #[test]
+    fn pending_once() {
+        let mut cx = Context::from_waker(Waker::noop());
+        let mut y = yield_now();
+        assert!(Pin::new(&mut y).poll(&mut cx).is_pending());
+        assert!(Pin::new(&mut y).poll(&mut cx).is_ready());
+    }
+
+    #[test]
+    fn block_on_yield() {
+        let mut waits = 0;
+        block_on_with(yield_now(), &mut || waits += 1);
+        assert_eq!(waits, 0);
+    }
+
+    #[test]
+    fn block_on_waits_until_woken() {
+        use std::sync::{Arc, Mutex};
+
+        // Pending 3 times, leaving its waker for the strategy to wake.
+        struct Parked { polls: usize, waker: Arc<Mutex<Option<Waker>>> }
+        impl Future for Parked {
+            type Output = usize;
+            fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>)
+                    -> Poll<usize> {
+                self.polls += 1;
+                if self.polls > 3 {
+                    return Poll::Ready(self.polls);
+                }
+                *self.waker.lock().unwrap() = Some(cx.waker().clone());
+                Poll::Pending
+            }
+        }
+
+        let waker = Arc::new(Mutex::new(None::<Waker>));
+        let parked = Parked{polls: 0, waker: waker.clone()};
+        let mut waits = 0;
+        let polls = block_on_with(parked, &mut || {
+            waits += 1;
+            // Only every other wait finds a wake-up.
+            if waits % 2 == 0 {
+                waker.lock().unwrap().take().unwrap().wake();
+            }
+        });
+        assert_eq!(polls, 4);
+        assert_eq!(waits, 6);
+    }
+
+    #[test]
+    fn block_on_busy_spin() {
+        assert_eq!(block_on(async { yield_now().await; 7 }), 7);
+    }
+
+    #[test]
+    fn block_on_nested() {
+        let mut waits = 0;
+        let v = block_on_with(async {
+            yield_now().await;
+            block_on_with(async { yield_now().await; 3 }, &mut || panic!())
+        }, &mut || waits += 1);
+        assert_eq!(v, 3);
+        assert_eq!(waits, 0);
+    }
+
+    #[test]
+    fn block_on_threads() {
+        let threads: std::vec::Vec<_> = (0..3).map(|i| {
+            std::thread::spawn(move || block_on_with(async move {
+                for _ in 0..1000 { yield_now().await; }
+                i
+            }, &mut || panic!("A wake-up was lost.")))
+        }).collect();
+        for (i, t) in threads.into_iter().enumerate() {
+            assert_eq!(t.join().unwrap(), i);
+        }
+    }
+}  // mod tests
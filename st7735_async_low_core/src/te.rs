@@ -0,0 +1,28 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Traits for synchronizing with the refresh of the LCD through its tearing
+//! effect (TE) output.
+
+use core::future::Future;
+
+/// Defines how to wait for the TE pin, enabled with
+/// [Commands::teon_mode()](crate::Commands::teon_mode).
+pub trait TePin<'a> {
+    type VblankDone : 'a + Future<Output=()>;
+
+    /// Waits until the LCD enters the next vertical blanking period, i.e., the
+    /// next rising edge of TE in mode 0.
+    fn wait_for_vblank(&'a mut self) -> Self::VblankDone;
+}
@@ -13,10 +13,10 @@
 // limitations under the License.
 
 use std::{boxed::Box, format, vec::Vec};  // TODO: Remove after mockall 0.9.2+.
-use std::pin::Pin;
-use std::future::Future;
+use std::future::{Future, Ready, ready};
 
-use crate::spi::{DcxPin, Read, ReadBits, WriteU8, WriteU8s};
+use crate::spi::{
+    BusSpeedControl, DcxPin, Read, ReadBits, WriteRepeat, WriteU8, WriteU8s};
 
 pub fn block_on<F: Future>(f: F) -> F::Output {
     let rt = tokio::runtime::Builder::new_current_thread().build().unwrap();
@@ -39,6 +39,8 @@ pub trait PlainIO {
 pub struct MockDevice {
     mock: MockPlainIO,
     is_data_mode: bool,
+    is_read_speed: bool,
+    speed_switches: usize,
 }
 
 impl MockDevice {
@@ -46,6 +48,9 @@ impl MockDevice {
     pub fn mock(&mut self) -> &mut MockPlainIO { &mut self.mock }
 
     pub fn is_data_mode(&self) -> bool { self.is_data_mode }
+    pub fn is_read_speed(&self) -> bool { self.is_read_speed }
+    /// How many times [BusSpeedControl] has been called.
+    pub fn speed_switches(&self) -> usize { self.speed_switches }
 
     pub fn expect_standard_write_command(&mut self, command: u8, data: &[u8]) {
         let mut seq = mockall::Sequence::new();
@@ -68,27 +73,55 @@ impl DcxPin for MockDevice {
     fn set_dcx_data_mode(&mut self) { self.is_data_mode = true; }
 }
 
-impl<'a> WriteU8<'a> for MockDevice {
-    type WriteU8Done = Pin<Box<dyn Future<Output=()> + 'a>>;
+impl BusSpeedControl for MockDevice {
+    fn enter_read_speed(&mut self) {
+        self.is_read_speed = true;
+        self.speed_switches += 1;
+    }
+    fn enter_write_speed(&mut self) {
+        self.is_read_speed = false;
+        self.speed_switches += 1;
+    }
+}
 
-    fn write_u8(&'a mut self, data: u8) -> Self::WriteU8Done {
-        Box::pin(async move {
+// The futures are ready, the mock being called when they are created.
+impl MockDevice {
+    fn write(&mut self, data: &[u8]) {
+        for &one in data {
             if self.is_data_mode {
-                self.mock.write_data(data);
+                self.mock.write_data(one);
             } else {
-                self.mock.write_command(data);
+                self.mock.write_command(one);
             }
-        })
+        }
+    }
+}
+
+impl<'a> WriteU8<'a> for MockDevice {
+    type WriteU8Done = Ready<()>;
+
+    fn write_u8(&'a mut self, data: u8) -> Ready<()> {
+        self.write(&[data]);
+        ready(())
     }
 }
 
 impl<'a> WriteU8s<'a> for MockDevice {
-    type WriteU8sDone = Pin<Box<dyn Future<Output=()> + 'a>>;
+    type WriteU8sDone = Ready<()>;
+
+    fn write_u8s(&'a mut self, data: &'a [u8]) -> Ready<()> {
+        self.write(data);
+        ready(())
+    }
+}
+
+impl<'a> WriteRepeat<'a> for MockDevice {
+    type WriteRepeatDone = Ready<()>;
 
-    fn write_u8s(&'a mut self, data: &'a [u8]) -> Self::WriteU8sDone {
-        Box::pin(async move {
-            for one in data { self.write_u8(*one).await; }
-        })
+    fn write_repeated(&'a mut self, data: &'a [u8], count: usize)
+            -> Ready<()> {
+        for _ in 0..count { self.write(data); }
+        ready(())
     }
 }
 
@@ -110,16 +143,14 @@ impl<'d> Drop for MockDeviceReader<'d> {
 }
 
 impl<'a, 'd> ReadBits<'a> for MockDeviceReader<'d> {
-    type ReadBitsDone = Pin<Box<dyn Future<Output=u32> + 'a>>;
+    type ReadBitsDone = Ready<u32>;
 
-    fn read_bits(&'a mut self, num_bits: usize) -> Self::ReadBitsDone {
-        Box::pin(async move {
-            let mut r: u32 = 0;
-            for _ in 0..num_bits {
-                r = r.wrapping_shl(1) | (self.d.mock.read_bit() as u32);
-            }
-            r
-        })
+    fn read_bits(&'a mut self, num_bits: usize) -> Ready<u32> {
+        let mut r: u32 = 0;
+        for _ in 0..num_bits {
+            r = r.wrapping_shl(1) | (self.d.mock.read_bit() as u32);
+        }
+        ready(r)
     }
 }
 
@@ -0,0 +1,148 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! Estimates of how long frames take on the bus, to check at startup or in
+//! host tests that the SPI clock can sustain the intended frame rate:
+//! ```
+//! # use st7735_async_low_core::{Colmod, panel::Rect, throughput};
+//! let frame = Rect::new(0, 0, 128, 160);
+//! assert!(throughput::max_fps(frame, Colmod::R5G6B5, 16_000_000) >= 30);
+//! ```
+//! Only the bits on the wire are counted: the time spent rendering the
+//! pixels, and gaps the SPI leaves between bytes, come on top.
+
+use core::convert::TryFrom;
+use core::time::Duration;
+
+use crate::command_structs::Colmod;
+use crate::panel::Rect;
+
+/// The bytes setting the window before the pixels: CASET and RASET with 4
+/// bytes of data each, then RAMWR.
+pub const WINDOW_SETUP_BYTES: usize = 11;
+
+/// The bytes sent to draw `frame` in `colmod`, including the window setup.
+///
+/// # Panics
+///
+/// If `colmod` is [Colmod::Unknown].
+pub const fn frame_bytes(frame: Rect, colmod: Colmod) -> usize {
+    let pixels = frame.area();
+    WINDOW_SETUP_BYTES + match colmod {
+        // Two pixels packed into 3 bytes, and a last odd one into 2.
+        Colmod::R4G4B4 => (pixels * 3).div_ceil(2),
+        Colmod::R5G6B5 => pixels * 2,
+        Colmod::R6G6B6 => pixels * 3,
+        Colmod::Unknown => panic!("Unknown color mode."),
+    }
+}
+
+/// How long drawing `frame` in `colmod` takes with an SPI clock of `spi_hz`,
+/// rounded up to the next nanosecond.
+///
+/// # Panics
+///
+/// If `colmod` is [Colmod::Unknown], or `spi_hz` is 0.
+pub fn estimate(frame: Rect, colmod: Colmod, spi_hz: u32) -> Duration {
+    assert!(spi_hz > 0, "Zero SPI clock.");
+    let bits = frame_bytes(frame, colmod) as u64 * 8;
+    let hz = spi_hz as u64;
+    // The remainder is below `hz`, so its nanoseconds fit in a u64 while
+    // those of the whole frame may not.
+    let nanos = (bits % hz * 1_000_000_000).div_ceil(hz);
+    Duration::new(bits / hz, 0) + Duration::from_nanos(nanos)
+}
+
+/// The most frames per second that can be drawn like `frame` in `colmod`
+/// with an SPI clock of `spi_hz`, rounded down.
+///
+/// # Panics
+///
+/// If `colmod` is [Colmod::Unknown].
+pub fn max_fps(frame: Rect, colmod: Colmod, spi_hz: u32) -> u32 {
+    let bits = frame_bytes(frame, colmod) as u64 * 8;
+    (spi_hz as u64 / bits) as u32
+}
+
+/// Whether `fps` frames per second drawn like `frame` in `colmod` fit into
+/// an SPI clock of `spi_hz`.
+///
+/// # Panics
+///
+/// If `colmod` is [Colmod::Unknown].
+pub fn sustains(frame: Rect, colmod: Colmod, spi_hz: u32, fps: u32) -> bool {
+    max_fps(frame, colmod, spi_hz) >= fps
+}
+
+/// The lowest SPI clock in Hz sustaining `fps` frames per second drawn like
+/// `frame` in `colmod`, or `None` if more than `u32::MAX` Hz, the clocks
+/// the other functions take.
+///
+/// # Panics
+///
+/// If `colmod` is [Colmod::Unknown].
+pub fn min_spi_hz(frame: Rect, colmod: Colmod, fps: u32) -> Option<u32> {
+    let hz = frame_bytes(frame, colmod) as u64 * 8 * fps as u64;
+    u32::try_from(hz).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const FULL: Rect = Rect::new(0, 0, 128, 160);
+
+    #[test]
+    fn bytes() {
+        assert_eq!(frame_bytes(FULL, Colmod::R5G6B5), 11 + 128 * 160 * 2);
+        assert_eq!(frame_bytes(FULL, Colmod::R6G6B6), 11 + 128 * 160 * 3);
+        assert_eq!(frame_bytes(FULL, Colmod::R4G4B4), 11 + 128 * 160 * 3 / 2);
+        assert_eq!(frame_bytes(Rect::new(5, 5, 3, 1), Colmod::R4G4B4),
+                   11 + 5);
+        assert_eq!(frame_bytes(Rect::default(), Colmod::R5G6B5), 11);
+    }
+
+    #[test]
+    fn timing() {
+        // 40971 bytes, 327768 bits.
+        assert_eq!(estimate(FULL, Colmod::R5G6B5, 8_000_000),
+                   Duration::from_nanos(40_971_000));
+        assert_eq!(estimate(FULL, Colmod::R5G6B5, 3),
+                   Duration::from_secs(109_256));
+        assert_eq!(max_fps(FULL, Colmod::R5G6B5, 8_000_000), 24);
+        assert!(sustains(FULL, Colmod::R5G6B5, 8_000_000, 24));
+        assert!(!sustains(FULL, Colmod::R5G6B5, 8_000_000, 25));
+        assert_eq!(min_spi_hz(FULL, Colmod::R5G6B5, 30), Some(9_833_040));
+        assert_eq!(min_spi_hz(FULL, Colmod::R5G6B5, 20_000), None);
+        assert!(sustains(FULL, Colmod::R5G6B5, 9_833_040, 30));
+        assert!(!sustains(FULL, Colmod::R5G6B5, 9_833_039, 30));
+    }
+
+    #[test]
+    fn large_frames() {
+        // 65535 * 65535 * 3 + 11 bytes: their bits in nanoseconds at 1 Hz
+        // don't fit in a u64.
+        let huge = Rect::new(0, 0, u16::MAX, u16::MAX);
+        assert_eq!(estimate(huge, Colmod::R6G6B6, 1),
+                   Duration::from_secs(103_076_069_488));
+        assert_eq!(estimate(huge, Colmod::R6G6B6, u32::MAX),
+                   Duration::from_nanos(23_999_267_610));
+    }
+
+    #[test]
+    #[should_panic(expected = "Unknown color mode")]
+    fn unknown() {
+        max_fps(FULL, Colmod::Unknown, 8_000_000);
+    }
+}  // mod tests
@@ -0,0 +1,192 @@
+// Copyright 2021 Google LLC
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//     https://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+//! [Commands](crate::Commands) tracking the sleep mode in their type, so that
+//! writing the memory before `SLPOUT` and its delay, the most common mistake
+//! of an init sequence, doesn't compile. The dynamic API, e.g.,
+//! [check_ram_access()](crate::Commands::check_ram_access), is unchanged;
+//! this is an optional layer over it.
+//!
+//! ```
+//! # use st7735_async_low_core::{spi::*, delay::DelayMs, typestate::*};
+//! # async fn f<S: DcxPin, D>(spi: S, delay: &mut D)
+//! #         where for<'a> S: WriteU8<'a> + WriteU8s<'a>,
+//! #               for<'a> D: DelayMs<'a> {
+//! let mut lcd = Commands::new(spi).slpout_and_wait(delay).await;
+//! lcd.ramwr().await;
+//! # }
+//! ```
+//! While asleep, the memory can't be written:
+//! ```compile_fail
+//! # use st7735_async_low_core::{spi::*, typestate::*};
+//! # async fn f<S: DcxPin>(spi: S)
+//! #         where for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+//! let mut lcd = Commands::new(spi);
+//! lcd.ramwr().await;
+//! # }
+//! ```
+
+use core::marker::PhantomData;
+use core::ops::{Deref, DerefMut};
+
+use crate::command_structs::{Colmod, GammaCurve, Madctl, TeMode};
+use crate::commands::{
+    CommandWithU8Future, SimpleCommandFuture, WindowFuture};
+use crate::delay::DelayMs;
+use crate::spi::{DcxPin, WriteU8, WriteU8s};
+
+/// The sleep mode is on, e.g., after a reset: the memory can't be written.
+#[derive(Debug)]
+pub struct Asleep;
+
+/// `SLPOUT` was sent and its delay waited for.
+#[derive(Debug)]
+pub struct Awake;
+
+/// [Commands](crate::Commands) in the sleep mode `M`, [Asleep] or [Awake].
+/// When awake, they dereference to the full dynamic API. When asleep, only
+/// the configuration commands are available.
+#[derive(Debug)]
+pub struct Commands<S, M> {
+    commands: crate::Commands<S>,
+    _mode: PhantomData<M>,
+}
+
+impl<S, M> Commands<S, M> {
+    fn with_mode<N>(self) -> Commands<S, N> {
+        Commands{commands: self.commands, _mode: PhantomData}
+    }
+
+    /// Leaves the type state, back to the dynamic API.
+    pub fn into_inner(self) -> crate::Commands<S> { self.commands }
+
+    /// Tells that the LCD was reset with its `RESX` pin, hence is asleep.
+    pub fn after_hardware_reset(mut self) -> Commands<S, Asleep> {
+        self.commands.reset_state();
+        self.with_mode()
+    }
+}
+
+impl<S: DcxPin> Commands<S, Asleep> {
+    /// Wraps [Commands::new()](crate::Commands::new): the panel is assumed
+    /// to be freshly reset.
+    pub fn new(spi: S) -> Self {
+        Self{commands: crate::Commands::new(spi), _mode: PhantomData}
+    }
+}
+
+impl<S, M> Commands<S, M>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Sends `SWRESET` and waits the 120ms before `SLPOUT` is allowed.
+    pub async fn swreset_and_wait<D>(mut self, delay: &mut D)
+            -> Commands<S, Asleep>
+            where for<'a> D: DelayMs<'a> {
+        self.commands.swreset().await;
+        delay.delay_ms(120).await;
+        self.with_mode()
+    }
+}
+
+macro_rules! forward {
+    ($($name:ident($($arg:ident: $type:ty),*) -> $future:ident;)*) => {$(
+        #[doc = concat!("See [Commands::", stringify!($name), "()](",
+                        "crate::Commands::", stringify!($name), ").")]
+        pub fn $name(&mut self, $($arg: $type),*) -> $future<'_, S> {
+            self.commands.$name($($arg),*)
+        }
+    )*};
+}
+
+impl<S> Commands<S, Asleep>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Sends `SLPOUT` and waits until the LCD is ready, as set by the
+    /// [profile](crate::Commands::profile).
+    pub async fn slpout_and_wait<D>(mut self, delay: &mut D)
+            -> Commands<S, Awake>
+            where for<'a> D: DelayMs<'a> {
+        self.commands.slpout().await;
+        let ms = self.commands.profile().slpout_delay_ms;
+        delay.delay_ms(ms).await;
+        self.commands.mark_awake();
+        self.with_mode()
+    }
+
+    forward! {
+        caset(begin: u16, end: u16) -> WindowFuture;
+        raset(begin: u16, end: u16) -> WindowFuture;
+        colmod(data: Colmod) -> CommandWithU8Future;
+        madctl(data: Madctl) -> CommandWithU8Future;
+        gamset(curve: GammaCurve) -> CommandWithU8Future;
+        teon_mode(mode: TeMode) -> CommandWithU8Future;
+        invon() -> SimpleCommandFuture;
+        invoff() -> SimpleCommandFuture;
+        noron() -> SimpleCommandFuture;
+        dispon() -> SimpleCommandFuture;
+        dispoff() -> SimpleCommandFuture;
+    }
+}
+
+impl<S> Commands<S, Awake>
+        where S: DcxPin, for<'a> S: WriteU8<'a> + WriteU8s<'a> {
+    /// Sends `SLPIN` and waits until `SLPOUT` is allowed again, as set by the
+    /// [profile](crate::Commands::profile).
+    pub async fn slpin_and_wait<D>(mut self, delay: &mut D)
+            -> Commands<S, Asleep>
+            where for<'a> D: DelayMs<'a> {
+        self.commands.slpin().await;
+        let ms = self.commands.profile().slpin_delay_ms;
+        delay.delay_ms(ms).await;
+        self.with_mode()
+    }
+}
+
+// `SLPIN` sent through these isn't reflected in the type; the dynamic
+// checks still catch the memory writes then.
+impl<S> Deref for Commands<S, Awake> {
+    type Target = crate::Commands<S>;
+
+    fn deref(&self) -> &crate::Commands<S> { &self.commands }
+}
+
+impl<S> DerefMut for Commands<S, Awake> {
+    fn deref_mut(&mut self) -> &mut crate::Commands<S> { &mut self.commands }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::display::tests::{Event::*, FakeDelay, Log, logging_device};
+    use crate::testing_device::block_on;
+    use super::*;
+
+    #[test]
+    fn transitions() {
+        let log = Log::default();
+        let mut delay = FakeDelay(log.clone());
+        let mut lcd = Commands::new(logging_device(&log));
+        block_on(lcd.colmod(Colmod::R5G6B5));
+        let mut lcd = block_on(lcd.slpout_and_wait(&mut delay));
+        assert_eq!(lcd.check_ram_access(), Ok(()));
+        block_on(lcd.ramwr());
+        let lcd = block_on(lcd.slpin_and_wait(&mut delay));
+        let lcd = block_on(lcd.swreset_and_wait(&mut delay));
+        let lcd = lcd.after_hardware_reset();
+        assert!(lcd.into_inner().check_ram_access().is_err());
+        assert_eq!(*log.lock().unwrap(), [
+            Command(0x3A), Data(0x05),
+            Command(0x11), Delay(120),
+            Command(0x2C),
+            Command(0x10), Delay(120),
+            Command(0x01), Delay(120)]);
+    }
+}  // mod tests